@@ -0,0 +1,1012 @@
+use core::cell::RefCell;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, OutputPin, PinState};
+use embedded_io::{ErrorType, Read, ReadReady, Write, WriteReady};
+use heapless::Deque;
+
+use crate::time::{Clock, Deadline, IntoMillis};
+
+/// Adapter that emulates [`ReadReady`] for serial devices that only implement [`Read`].
+///
+/// Readiness is checked by reading a single byte into an internal one-byte buffer, which
+/// is handed out first by the next [`Read::read`]. The underlying device's `read` must
+/// return `Ok(0)` when no data is available for this to be non-blocking; if it blocks
+/// until data arrives instead, so will `read_ready`.
+///
+/// # Example
+/// ```ignore
+/// let hc12 = HC12::factor_settings(PollRead::new(uart), programming_pin, &mut delay)
+///     .unwrap()
+///     .program(&mut delay)
+///     .unwrap();
+/// ```
+pub struct PollRead<T> {
+    inner: T,
+    peeked: Option<u8>,
+}
+
+impl<T> PollRead<T> {
+    /// Wrap a serial device
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            peeked: None,
+        }
+    }
+
+    /// Return the underlying device. A byte consumed by a readiness check and not yet
+    /// read is returned alongside it.
+    pub fn into_inner(self) -> (T, Option<u8>) {
+        (self.inner, self.peeked)
+    }
+}
+
+impl<T: ErrorType> ErrorType for PollRead<T> {
+    type Error = T::Error;
+}
+
+impl<T: Read> Read for PollRead<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match self.peeked.take() {
+            Some(byte) => {
+                buf[0] = byte;
+                Ok(1)
+            }
+            None => self.inner.read(buf),
+        }
+    }
+}
+
+impl<T: Read> ReadReady for PollRead<T> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        if self.peeked.is_some() {
+            return Ok(true);
+        }
+
+        let mut byte = [0u8];
+        if self.inner.read(&mut byte)? == 1 {
+            self.peeked = Some(byte[0]);
+        }
+
+        Ok(self.peeked.is_some())
+    }
+}
+
+impl<T: Write> Write for PollRead<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush()
+    }
+}
+
+impl<T: WriteReady> WriteReady for PollRead<T> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        self.inner.write_ready()
+    }
+}
+
+/// Adapter for serial devices that only implement [`Read`], reading AT responses without
+/// polling for readiness.
+///
+/// AT exchanges normally poll [`ReadReady`] before each read. Through this adapter, bytes
+/// are instead read one at a time for as long as reads return them, and the response
+/// timeout is counted while they return nothing. This is slightly slower, and unable to
+/// exit early: the device's `read` must return `Ok(0)` when no data is available, as a
+/// read that blocks cannot be interrupted. [`PollRead`] is an alternative for devices
+/// whose reads do return `Ok(0)`.
+///
+/// # Example
+/// ```ignore
+/// let hc12 = HC12::factor_settings(Unpolled::new(uart), programming_pin, &mut delay)
+///     .unwrap()
+///     .program(&mut delay)
+///     .unwrap();
+/// ```
+pub struct Unpolled<T> {
+    inner: T,
+}
+
+impl<T> Unpolled<T> {
+    /// Wrap a serial device
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Return the underlying device
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ErrorType> ErrorType for Unpolled<T> {
+    type Error = T::Error;
+}
+
+impl<T: Read> Read for Unpolled<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(feature = "programming")]
+impl<T: Read> crate::AtRead for Unpolled<T> {
+    fn response_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+}
+
+impl<T: Write> Write for Unpolled<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush()
+    }
+}
+
+impl<T: WriteReady> WriteReady for Unpolled<T> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        self.inner.write_ready()
+    }
+}
+
+/// Delay adapter that splits long waits into chunks and calls a hook between them.
+///
+/// The programming delays in this crate are tens of milliseconds long, which is enough to
+/// starve a watchdog or a peripheral that needs polling. Wrapping the delay provider runs
+/// the hook after every `chunk_ms` of waiting (and after any shorter remainder), so the
+/// application can kick the watchdog from it. Without the wrapper there is no overhead.
+///
+/// # Example
+/// ```ignore
+/// let mut delay = HookedDelay::new(timer, 10, || watchdog.feed());
+/// hc12.program(&mut delay).unwrap();
+/// ```
+pub struct HookedDelay<D, F> {
+    delay: D,
+    chunk_ns: u32,
+    hook: F,
+}
+
+impl<D, F> HookedDelay<D, F>
+where
+    D: DelayNs,
+    F: FnMut(),
+{
+    /// Wrap a delay provider, calling `hook` at least every `chunk`
+    pub fn new(delay: D, chunk: impl IntoMillis, hook: F) -> Self {
+        Self {
+            delay,
+            chunk_ns: chunk.into_ms().saturating_mul(1_000_000).max(1),
+            hook,
+        }
+    }
+
+    /// Return the wrapped delay provider
+    pub fn inner(self) -> D {
+        self.delay
+    }
+}
+
+impl<D, F> DelayNs for HookedDelay<D, F>
+where
+    D: DelayNs,
+    F: FnMut(),
+{
+    fn delay_ns(&mut self, mut ns: u32) {
+        while ns > 0 {
+            let chunk = ns.min(self.chunk_ns);
+            self.delay.delay_ns(chunk);
+            (self.hook)();
+            ns -= chunk;
+        }
+    }
+}
+
+/// Programming pin adapter for boards that invert the SET line, for example through a
+/// level-shifting transistor, so that the module enters AT mode when the pin is driven
+/// high.
+///
+/// Every mode switch in this crate drives the pin through [`OutputPin`], so wrapping the
+/// pin flips all of them.
+///
+/// # Example
+/// ```ignore
+/// let hc12 = HC12::factor_settings(uart, InvertedPin::new(set_pin), &mut delay).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct InvertedPin<P> {
+    inner: P,
+}
+
+impl<P> InvertedPin<P> {
+    /// Wrap a pin
+    pub fn new(inner: P) -> Self {
+        Self { inner }
+    }
+
+    /// Return the underlying pin
+    pub fn inner(self) -> P {
+        self.inner
+    }
+}
+
+impl<P: digital::ErrorType> digital::ErrorType for InvertedPin<P> {
+    type Error = P::Error;
+}
+
+impl<P: OutputPin> OutputPin for InvertedPin<P> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.inner.set_high()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.inner.set_low()
+    }
+}
+
+/// Direction of traffic through a [`TapUart`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Dir {
+    /// Bytes read from the device
+    Rx,
+    /// Bytes written to the device
+    Tx,
+}
+
+/// A receiver of the traffic seen by a [`TapUart`]. Implemented for closures taking the
+/// direction and the bytes, and for [`Capture`].
+pub trait Tap {
+    /// Called with the bytes that crossed the wrapper, in order
+    fn tap(&mut self, dir: Dir, bytes: &[u8]);
+}
+
+impl<F> Tap for F
+where
+    F: FnMut(Dir, &[u8]),
+{
+    fn tap(&mut self, dir: Dir, bytes: &[u8]) {
+        self(dir, bytes)
+    }
+}
+
+/// Wrapper around a serial device that reports every byte read or written, for debugging
+/// framing and AT exchanges. Compose it with `map_device` to tap a device that is already
+/// built.
+///
+/// # Example
+/// ```ignore
+/// let hc12 = hc12.map_device(|uart| TapUart::new(uart, |dir, bytes: &[u8]| {
+///     defmt::trace!("{} {=[u8]:a}", dir, bytes);
+/// }));
+/// ```
+pub struct TapUart<U, F> {
+    inner: U,
+    tap: F,
+}
+
+impl<U, F: Tap> TapUart<U, F> {
+    /// Wrap a serial device
+    pub fn new(inner: U, tap: F) -> Self {
+        Self { inner, tap }
+    }
+
+    /// The receiver of the traffic
+    pub fn tap(&self) -> &F {
+        &self.tap
+    }
+
+    /// Decompose into the device and the receiver of the traffic
+    pub fn inner(self) -> (U, F) {
+        (self.inner, self.tap)
+    }
+}
+
+impl<U: ErrorType, F> ErrorType for TapUart<U, F> {
+    type Error = U::Error;
+}
+
+impl<U: Read, F: Tap> Read for TapUart<U, F> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let count = self.inner.read(buf)?;
+        if count > 0 {
+            self.tap.tap(Dir::Rx, &buf[..count]);
+        }
+        Ok(count)
+    }
+}
+
+impl<U: ReadReady, F> ReadReady for TapUart<U, F> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.inner.read_ready()
+    }
+}
+
+impl<U: Write, F: Tap> Write for TapUart<U, F> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let count = self.inner.write(buf)?;
+        if count > 0 {
+            self.tap.tap(Dir::Tx, &buf[..count]);
+        }
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush()
+    }
+}
+
+impl<U: WriteReady, F> WriteReady for TapUart<U, F> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        self.inner.write_ready()
+    }
+}
+
+/// A [`Tap`] keeping the last `N` bytes of traffic in memory, for post-mortem dumps
+#[derive(Debug, Default)]
+pub struct Capture<const N: usize> {
+    bytes: Deque<(Dir, u8), N>,
+}
+
+impl<const N: usize> Capture<N> {
+    /// An empty capture
+    pub const fn new() -> Self {
+        Self {
+            bytes: Deque::new(),
+        }
+    }
+
+    /// The captured bytes, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = (Dir, u8)> + '_ {
+        self.bytes.iter().copied()
+    }
+
+    /// The captured bytes in one direction, oldest first
+    pub fn bytes(&self, dir: Dir) -> impl Iterator<Item = u8> + '_ {
+        self.iter()
+            .filter(move |(direction, _)| *direction == dir)
+            .map(|(_, byte)| byte)
+    }
+
+    /// Forget all captured bytes
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+}
+
+impl<const N: usize> Tap for Capture<N> {
+    fn tap(&mut self, dir: Dir, bytes: &[u8]) {
+        for byte in bytes {
+            if self.bytes.is_full() {
+                self.bytes.pop_front();
+            }
+            self.bytes.push_back((dir, *byte)).ok();
+        }
+    }
+}
+
+/// One serial port shared by two modules through an analog multiplexer, whose selector
+/// is driven by a pin.
+///
+/// Each [`port`](Self::port) is a device of its own, which any API in this crate accepts
+/// like a UART. Using a port drives the selector to its level first; by default the
+/// serial port is flushed before switching, so bytes written to one module are not sent
+/// to the other. Bytes the unselected module sends are lost.
+///
+/// A port that writes holds the multiplexer until it flushes or reads, which ends an
+/// AT command. Until then, the other port fails with [`MuxError::Busy`] instead of
+/// switching in the middle of a frame.
+///
+/// # Example
+/// ```ignore
+/// let mux = MuxedUart::new(uart, select_pin);
+/// let hc12 = HC12::factor_settings(mux.port(PinState::Low), set_a, &mut delay)?;
+/// let other = TransparentHC12::assume_factory(mux.port(PinState::High), set_b);
+/// ```
+pub struct MuxedUart<U, S> {
+    shared: RefCell<Mux<U, S>>,
+}
+
+struct Mux<U, S> {
+    uart: U,
+    select: S,
+    selected: Option<PinState>,
+    holder: Option<PinState>,
+    flush_on_switch: bool,
+}
+
+impl<U, S> MuxedUart<U, S> {
+    /// Share `uart` through a multiplexer switched by `select`
+    pub fn new(uart: U, select: S) -> Self {
+        Self {
+            shared: RefCell::new(Mux {
+                uart,
+                select,
+                selected: None,
+                holder: None,
+                flush_on_switch: true,
+            }),
+        }
+    }
+
+    /// Whether to flush the serial port before switching to the other module. On by
+    /// default.
+    pub fn flush_on_switch(self, flush: bool) -> Self {
+        self.shared.borrow_mut().flush_on_switch = flush;
+        self
+    }
+
+    /// The device reaching the module selected by driving the selector to `level`
+    pub fn port(&self, level: PinState) -> MuxPort<'_, U, S> {
+        MuxPort { mux: self, level }
+    }
+
+    /// Return the serial port and selector pin
+    pub fn inner(self) -> (U, S) {
+        let mux = self.shared.into_inner();
+        (mux.uart, mux.select)
+    }
+}
+
+impl<U: Write, S: OutputPin> Mux<U, S> {
+    /// Switch to `level`, unless the other port holds the multiplexer
+    fn acquire(&mut self, level: PinState) -> Result<&mut U, MuxError<U::Error, S::Error>> {
+        if self.holder.is_some_and(|holder| holder != level) {
+            return Err(MuxError::Busy);
+        }
+        if self.selected != Some(level) {
+            if self.flush_on_switch && self.selected.is_some() {
+                self.uart.flush().map_err(MuxError::Uart)?;
+            }
+            self.select.set_state(level).map_err(MuxError::Select)?;
+            self.selected = Some(level);
+        }
+        Ok(&mut self.uart)
+    }
+}
+
+/// A failure of a [`MuxPort`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum MuxError<U, S> {
+    /// The serial port failed
+    Uart(U),
+    /// The selector pin could not be switched
+    Select(S),
+    /// The other port is part way through a write
+    Busy,
+}
+
+impl<U: embedded_io::Error, S: core::fmt::Debug> embedded_io::Error for MuxError<U, S> {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Self::Uart(error) => error.kind(),
+            Self::Select(_) | Self::Busy => embedded_io::ErrorKind::Other,
+        }
+    }
+}
+
+/// One module's side of a [`MuxedUart`]
+pub struct MuxPort<'a, U, S> {
+    mux: &'a MuxedUart<U, S>,
+    level: PinState,
+}
+
+impl<U: ErrorType, S: digital::ErrorType> ErrorType for MuxPort<'_, U, S> {
+    type Error = MuxError<U::Error, S::Error>;
+}
+
+impl<U: Read + Write, S: OutputPin> Read for MuxPort<'_, U, S> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut mux = self.mux.shared.borrow_mut();
+        mux.acquire(self.level)?;
+        mux.holder = None;
+        mux.uart.read(buf).map_err(MuxError::Uart)
+    }
+}
+
+impl<U: ReadReady + Write, S: OutputPin> ReadReady for MuxPort<'_, U, S> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        let mut mux = self.mux.shared.borrow_mut();
+        mux.acquire(self.level)?
+            .read_ready()
+            .map_err(MuxError::Uart)
+    }
+}
+
+impl<U: Write, S: OutputPin> Write for MuxPort<'_, U, S> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let mut mux = self.mux.shared.borrow_mut();
+        let count = mux
+            .acquire(self.level)?
+            .write(buf)
+            .map_err(MuxError::Uart)?;
+        mux.holder = Some(self.level);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let mut mux = self.mux.shared.borrow_mut();
+        mux.acquire(self.level)?.flush().map_err(MuxError::Uart)?;
+        mux.holder = None;
+        Ok(())
+    }
+}
+
+impl<U: WriteReady + Write, S: OutputPin> WriteReady for MuxPort<'_, U, S> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        let mut mux = self.mux.shared.borrow_mut();
+        mux.acquire(self.level)?
+            .write_ready()
+            .map_err(MuxError::Uart)
+    }
+}
+
+/// How long the module takes to enter transparent mode once the programming pin is
+/// released. Bytes written sooner are lost.
+pub const SETTLE_MS: u32 = 80;
+
+/// What a [`SettleGate`] does with a write before the module has settled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum SettlePolicy {
+    /// Wait until the module has settled, then write
+    #[default]
+    Wait,
+    /// Fail with [`SettleError::NotSettled`], writing nothing
+    Refuse,
+}
+
+/// A failure of a [`SettleGate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum SettleError<E> {
+    /// The serial device failed
+    Link(E),
+    /// The module has not settled, and the policy is [`SettlePolicy::Refuse`]
+    NotSettled,
+}
+
+impl<E: embedded_io::Error> embedded_io::Error for SettleError<E> {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Self::Link(error) => error.kind(),
+            Self::NotSettled => embedded_io::ErrorKind::Other,
+        }
+    }
+}
+
+/// Adapter that holds writes back while the module settles into transparent mode.
+///
+/// The transitions in this crate wait out the settle themselves, except
+/// [`into_transparent_mode_unsettled`](crate::HC12::into_transparent_mode_unsettled),
+/// which returns as soon as the pin is released. Wrap its device in a gate
+/// [`armed`](Self::armed) for [`SETTLE_MS`], and until the window has passed
+/// [`write_ready`](WriteReady::write_ready) is false, and writes wait or fail according
+/// to the [`SettlePolicy`]. Once settled, or if never armed, the gate passes everything
+/// through.
+///
+/// # Example
+/// ```ignore
+/// let hc12 = hc12.into_transparent_mode_unsettled()?;
+/// let mut link = SettleGate::armed(hc12, clock, delay, SETTLE_MS).policy(SettlePolicy::Refuse);
+/// ```
+pub struct SettleGate<T, C, D> {
+    inner: T,
+    clock: C,
+    delay: D,
+    policy: SettlePolicy,
+    settles: Option<Deadline>,
+}
+
+impl<T, C: Clock, D: DelayNs> SettleGate<T, C, D> {
+    /// Wrap a device that has already settled
+    pub fn new(inner: T, clock: C, delay: D) -> Self {
+        Self {
+            inner,
+            clock,
+            delay,
+            policy: SettlePolicy::default(),
+            settles: None,
+        }
+    }
+
+    /// Wrap a device whose module settles `window` from now
+    pub fn armed(inner: T, clock: C, delay: D, window: impl IntoMillis) -> Self {
+        let mut gate = Self::new(inner, clock, delay);
+        gate.arm(window);
+        gate
+    }
+
+    /// Set what happens to writes before the module has settled
+    pub fn policy(mut self, policy: SettlePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Start a settle window of `window` from now, after releasing the programming pin
+    pub fn arm(&mut self, window: impl IntoMillis) {
+        self.settles = Some(Deadline::after(&self.clock, window));
+    }
+
+    /// Milliseconds until the module has settled, zero once it has
+    pub fn remaining_ms(&self) -> u32 {
+        self.settles
+            .map_or(0, |deadline| deadline.remaining_ms(&self.clock))
+    }
+
+    /// Return the wrapped device
+    pub fn inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ErrorType, C, D> ErrorType for SettleGate<T, C, D> {
+    type Error = SettleError<T::Error>;
+}
+
+impl<T: Read, C: Clock, D: DelayNs> Read for SettleGate<T, C, D> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.inner.read(buf).map_err(SettleError::Link)
+    }
+}
+
+impl<T: ReadReady, C: Clock, D: DelayNs> ReadReady for SettleGate<T, C, D> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.inner.read_ready().map_err(SettleError::Link)
+    }
+}
+
+impl<T: Write, C: Clock, D: DelayNs> Write for SettleGate<T, C, D> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let remaining_ms = self.remaining_ms();
+        if remaining_ms > 0 {
+            match self.policy {
+                SettlePolicy::Wait => self.delay.delay_ms(remaining_ms),
+                SettlePolicy::Refuse => return Err(SettleError::NotSettled),
+            }
+        }
+        self.settles = None;
+        self.inner.write(buf).map_err(SettleError::Link)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush().map_err(SettleError::Link)
+    }
+}
+
+impl<T: WriteReady, C: Clock, D: DelayNs> WriteReady for SettleGate<T, C, D> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        if self.remaining_ms() > 0 {
+            return Ok(false);
+        }
+        self.inner.write_ready().map_err(SettleError::Link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{CountingDelay, Source};
+
+    #[test]
+    fn read_ready_peeks_one_byte() {
+        let mut reader = PollRead::new(Source::new().data(b"OK"));
+        assert!(reader.read_ready().unwrap());
+        // asking again must not consume another byte
+        assert!(reader.read_ready().unwrap());
+
+        let mut buffer = [0u8; 4];
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1);
+        assert_eq!(reader.read(&mut buffer[1..]).unwrap(), 1);
+        assert_eq!(&buffer[..2], b"OK");
+        assert!(!reader.read_ready().unwrap());
+    }
+
+    #[test]
+    fn settle_gate_holds_writes_for_the_window() {
+        use crate::test_utils::Sink;
+        use core::cell::Cell;
+
+        let now = Cell::new(1000);
+        let clock = || now.get();
+        let mut gate = SettleGate::armed(Sink::new(), clock, CountingDelay::new(), SETTLE_MS)
+            .policy(SettlePolicy::Refuse);
+
+        assert!(!gate.write_ready().unwrap());
+        assert_eq!(gate.write(b"early"), Err(SettleError::NotSettled));
+        now.set(1000 + SETTLE_MS - 1);
+        assert_eq!(gate.remaining_ms(), 1);
+        assert!(!gate.write_ready().unwrap());
+        now.set(1000 + SETTLE_MS);
+        assert!(gate.write_ready().unwrap());
+        gate.write_all(b"on time").unwrap();
+        assert_eq!(gate.inner().data(), b"on time");
+
+        // never armed, nothing changes
+        let mut gate = SettleGate::new(Sink::new(), clock, CountingDelay::new());
+        assert!(gate.write_ready().unwrap());
+        gate.write_all(b"steady").unwrap();
+        assert_eq!(gate.inner().data(), b"steady");
+    }
+
+    #[test]
+    #[cfg(feature = "programming")]
+    fn settle_gate_waits_after_an_unsettled_transition() {
+        use crate::mock::MockHc12;
+        use crate::HC12;
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .into_transparent_mode_unsettled()
+            .unwrap();
+        let released_ms = module.now_ms();
+        let mut gate = SettleGate::armed(hc12, || module.now_ms(), module.delay(), SETTLE_MS);
+
+        assert!(!gate.write_ready().unwrap());
+        gate.write_all(b"first").unwrap();
+        assert_eq!(module.now_ms() - released_ms, SETTLE_MS);
+        assert_eq!(module.transmitted(), b"first");
+        assert!(gate.write_ready().unwrap());
+    }
+
+    #[test]
+    fn hooked_delay_handles_remainders() {
+        let mut calls = 0;
+        let mut delay = HookedDelay::new(CountingDelay::new(), 10, || calls += 1);
+        delay.delay_ms(25);
+        delay.delay_us(1);
+
+        assert_eq!(calls, 4);
+    }
+
+    #[test]
+    fn capture_keeps_latest_bytes() {
+        let mut capture: Capture<4> = Capture::new();
+        capture.tap(Dir::Tx, b"AT+V");
+        capture.tap(Dir::Rx, b"OK");
+
+        let kept: heapless::Vec<(Dir, u8), 4> = capture.iter().collect();
+        assert_eq!(
+            kept.as_slice(),
+            &[
+                (Dir::Tx, b'+'),
+                (Dir::Tx, b'V'),
+                (Dir::Rx, b'O'),
+                (Dir::Rx, b'K')
+            ]
+        );
+        assert!(capture.bytes(Dir::Rx).eq(*b"OK"));
+
+        capture.clear();
+        assert_eq!(capture.iter().count(), 0);
+    }
+
+    #[test]
+    fn mux_port_holds_until_flushed_or_read() {
+        use crate::test_utils::{Duo, PinLog, Sink};
+
+        let select = PinLog::new();
+        let duo = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(b"xy"),
+        };
+        let mux = MuxedUart::new(duo, select.pin());
+        let (mut a, mut b) = (mux.port(PinState::Low), mux.port(PinState::High));
+
+        a.write_all(b"abc").unwrap();
+        assert_eq!(b.read(&mut [0; 2]), Err(MuxError::Busy));
+        assert_eq!(b.write(b"z"), Err(MuxError::Busy));
+        a.flush().unwrap();
+        assert_eq!(b.read(&mut [0; 2]), Ok(2));
+
+        // reading the answer also ends a write
+        b.write_all(b"z").unwrap();
+        assert_eq!(a.write(b"w"), Err(MuxError::Busy));
+        assert_eq!(b.read(&mut [0; 2]), Ok(0));
+        a.write_all(b"w").unwrap();
+
+        let (duo, _) = mux.inner();
+        assert_eq!(duo.sink.data(), b"abczw");
+        let (low, high) = (PinState::Low, PinState::High);
+        assert_eq!(select.states().as_slice(), [low, high, low]);
+    }
+
+    /// Programming through the adapters
+    #[cfg(feature = "programming")]
+    mod at {
+        use super::*;
+        use crate::commands::test::run_command;
+        use crate::paramaters::Power;
+        use crate::speeds::B9600;
+        use crate::test_utils::{Duo, PinLog, Sink, TestError};
+        use crate::HC12;
+        use embedded_hal::digital::PinState;
+        use heapless::Deque;
+
+        /// Answers every command with an `OK` echo, and implements only `Read` and `Write`
+        #[derive(Default)]
+        struct Module {
+            command: heapless::Vec<u8, 16>,
+            rx: Deque<u8, 64>,
+        }
+
+        impl ErrorType for Module {
+            type Error = TestError;
+        }
+
+        impl Write for Module {
+            fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+                if buf.ends_with(b"\n") {
+                    let echo = self.command.get(2..).unwrap_or_default();
+                    for byte in b"OK".iter().chain(echo).chain(b"\r\n") {
+                        self.rx.push_back(*byte).ok();
+                    }
+                    self.command.clear();
+                } else {
+                    // transparent traffic is not part of the command
+                    if buf.starts_with(b"AT") {
+                        self.command.clear();
+                    }
+                    self.command.extend_from_slice(buf).ok();
+                }
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> Result<(), Self::Error> {
+                Ok(())
+            }
+        }
+
+        impl Read for Module {
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+                let mut count = 0;
+                while count < buf.len() {
+                    match self.rx.pop_front() {
+                        Some(byte) => buf[count] = byte,
+                        None => break,
+                    }
+                    count += 1;
+                }
+                Ok(count)
+            }
+        }
+
+        #[test]
+        fn programs_over_read_only_device() {
+            let pins = PinLog::new();
+            let mut delay = CountingDelay::new();
+            let device = PollRead::new(Module::default());
+
+            HC12::factor_settings(device, pins.pin(), &mut delay)
+                .unwrap()
+                .power(Power::MAX)
+                .program(&mut delay)
+                .unwrap();
+
+            assert_eq!(pins.states().as_slice(), [PinState::Low]);
+        }
+
+        #[test]
+        fn hooked_delay_runs_hook_per_chunk() {
+            let pins = PinLog::new();
+            let mut calls = 0;
+            let mut delay = HookedDelay::new(CountingDelay::new(), 10, || calls += 1);
+
+            // entering programming mode waits 40ms, then four commands wait 40ms each
+            HC12::factor_settings(Unpolled::new(Module::default()), pins.pin(), &mut delay)
+                .unwrap()
+                .power(Power::MAX)
+                .program(&mut delay)
+                .unwrap();
+            assert_eq!(pins.states().as_slice(), [PinState::Low]);
+
+            assert_eq!(calls, 20);
+        }
+
+        #[test]
+        fn inverted_pin_flips_every_mode_switch() {
+            let plain = PinLog::new();
+            let inverted = PinLog::new();
+            let mut delay = CountingDelay::new();
+
+            let device = Unpolled::new(Module::default());
+            let hc12 = HC12::factor_settings(device, plain.pin(), &mut delay)
+                .unwrap()
+                .into_transparent_mode(&mut delay)
+                .unwrap()
+                .into_programming_mode(&mut delay)
+                .unwrap();
+            let device = hc12.into_transparent_mode(&mut delay).unwrap().inner().0;
+            HC12::factor_settings(device, InvertedPin::new(inverted.pin()), &mut delay)
+                .unwrap()
+                .into_transparent_mode(&mut delay)
+                .unwrap()
+                .into_programming_mode(&mut delay)
+                .unwrap()
+                .into_transparent_mode(&mut delay)
+                .unwrap();
+
+            let (low, high) = (PinState::Low, PinState::High);
+            assert_eq!(plain.states().as_slice(), [low, high, low, high]);
+            assert_eq!(inverted.states().as_slice(), [high, low, high, low]);
+        }
+
+        #[test]
+        fn mux_switches_only_between_at_exchanges() {
+            let select = PinLog::new();
+            let (set_a, set_b) = (PinLog::new(), PinLog::new());
+            let mut delay = CountingDelay::new();
+            let mux = MuxedUart::new(PollRead::new(Module::default()), select.pin());
+
+            let radio_a = HC12::factor_settings(mux.port(PinState::Low), set_a.pin(), &mut delay)
+                .unwrap()
+                .power(Power::MAX)
+                .program(&mut delay)
+                .unwrap()
+                .into_transparent_mode(&mut delay)
+                .unwrap();
+            let mut radio_b: crate::TransparentHC12<_, _, crate::modes::Fu3, B9600> =
+                crate::TransparentHC12::assume_factory(mux.port(PinState::High), set_b.pin());
+
+            // module B is part way through a frame, so A cannot start an AT exchange
+            radio_b.write_all(b"frame").unwrap();
+            let programming_a = radio_a.into_programming_mode(&mut delay).unwrap();
+            assert!(matches!(
+                programming_a.program(&mut delay),
+                Err(crate::Error::DeviceError(MuxError::Busy))
+            ));
+
+            let radio_a =
+                HC12::factor_settings(mux.port(PinState::Low), set_a.pin(), &mut delay).unwrap();
+            radio_b.flush().unwrap();
+            radio_a.power(Power::MAX).program(&mut delay).unwrap();
+
+            let (low, high) = (PinState::Low, PinState::High);
+            assert_eq!(select.states().as_slice(), [low, high, low]);
+        }
+
+        #[test]
+        fn tap_sees_at_exchange() {
+            use heapless::Vec;
+
+            let mut seen: Vec<(Dir, Vec<u8, 16>), 16> = Vec::new();
+            let duo = Duo {
+                sink: Sink::new().accept_data(10),
+                src: Source::new().data(b"OK+B9600\r\n"),
+            };
+            let mut tapped = TapUart::new(duo, |dir, bytes: &[u8]| {
+                seen.push((dir, Vec::from_slice(bytes).unwrap())).unwrap();
+            });
+            let mut delay = CountingDelay::new();
+            run_command(&mut tapped, B9600::default(), &mut delay, None).unwrap();
+
+            let tx: Vec<u8, 32> = seen
+                .iter()
+                .filter(|(dir, _)| *dir == Dir::Tx)
+                .flat_map(|(_, bytes)| bytes.iter().copied())
+                .collect();
+            let rx: Vec<u8, 32> = seen
+                .iter()
+                .filter(|(dir, _)| *dir == Dir::Rx)
+                .flat_map(|(_, bytes)| bytes.iter().copied())
+                .collect();
+            assert_eq!(tx.as_slice(), b"AT+B9600\r\n");
+            assert_eq!(rx.as_slice(), b"OK+B9600\r\n");
+            // everything is written before anything is read
+            assert_eq!(seen.iter().position(|(dir, _)| *dir == Dir::Rx), Some(2));
+        }
+    }
+}
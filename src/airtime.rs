@@ -0,0 +1,156 @@
+//! How long a transmission occupies the channel.
+//!
+//! The module sends over the air at its own rate, which depends on the mode and, in FU3,
+//! on the serial speed. Each packet on the air also carries the radio's own preamble, sync
+//! word, length and CRC. In FU2 and FU4 data is sent in packets of at most
+//! [`MAX_PACKET_PAYLOAD`] bytes. The figures below are from the HC-12 datasheet and the
+//! packet format of the Si4463 transceiver it is built on. They do not include the pauses
+//! between packets that the slow modes require, see
+//! [`PACKET_INTERVAL_MS`](crate::modes::ValidMode::PACKET_INTERVAL_MS).
+
+use crate::modes::ValidMode;
+use crate::speeds::ValidSpeed;
+
+/// Air rate in FU1 and FU2, in bits per second
+pub const FU1_FU2_AIR_BPS: u32 = 250_000;
+
+/// Air rate in FU4, in bits per second
+pub const FU4_AIR_BPS: u32 = 500;
+
+/// Air rates in FU3, as pairs of the highest serial speed and the air rate it uses, both
+/// in bits per second
+pub const FU3_AIR_BPS: [(u32, u32); 4] = [
+    (2400, 5_000),
+    (9600, 15_000),
+    (38400, 58_000),
+    (u32::MAX, 236_000),
+];
+
+/// Bytes the radio adds to each packet: a 4 byte preamble, 2 byte sync word, length byte
+/// and 2 byte CRC
+pub const PACKET_OVERHEAD_BYTES: usize = 9;
+
+/// The longest packet payload in FU2 and FU4. Longer writes are split into several
+/// packets.
+pub const MAX_PACKET_PAYLOAD: usize = 60;
+
+/// Bits on the serial line for each byte, with a start and a stop bit
+const SERIAL_BITS_PER_BYTE: u32 = 10;
+
+/// The air rate, in bits per second, of mode number `mode` at serial speed `baud_bps`
+pub fn air_bps(mode: u8, baud_bps: u32) -> u32 {
+    match mode {
+        3 => FU3_AIR_BPS
+            .iter()
+            .find(|(serial_bps, _)| baud_bps <= *serial_bps)
+            .map_or(FU3_AIR_BPS[3].1, |(_, air_bps)| *air_bps),
+        4 => FU4_AIR_BPS,
+        _ => FU1_FU2_AIR_BPS,
+    }
+}
+
+/// Microseconds on the air for `payload_len` bytes written to a module in mode number
+/// `mode`, at serial speed `baud_bps`. Saturates at `u32::MAX`.
+pub fn time_on_air_us(mode: u8, baud_bps: u32, payload_len: usize) -> u32 {
+    let packets = match mode {
+        2 | 4 => payload_len.div_ceil(MAX_PACKET_PAYLOAD),
+        _ => usize::from(payload_len > 0),
+    };
+    let bits = (payload_len + packets * PACKET_OVERHEAD_BYTES) as u64 * 8;
+    let air_bps = air_bps(mode, baud_bps) as u64;
+    u32::try_from((bits * 1_000_000).div_ceil(air_bps)).unwrap_or(u32::MAX)
+}
+
+/// The sustained payload rate, in bits per second, of a module in mode number `mode` at
+/// serial speed `baud_bps`, sending packets of [`MAX_PACKET_PAYLOAD`] bytes at most every
+/// `packet_interval_ms`. This is the slowest of the serial line, the time on the air of
+/// each packet, and the pacing between packets.
+pub fn effective_throughput_bps(mode: u8, baud_bps: u32, packet_interval_ms: u32) -> u32 {
+    let serial_bps = baud_bps / SERIAL_BITS_PER_BYTE * 8;
+    let packet_us = time_on_air_us(mode, baud_bps, MAX_PACKET_PAYLOAD)
+        .max(packet_interval_ms.saturating_mul(1000)) as u64;
+    let air_bps = (MAX_PACKET_PAYLOAD as u64 * 8 * 1_000_000 / packet_us) as u32;
+    serial_bps.min(air_bps)
+}
+
+/// [`effective_throughput_bps`] for a mode and speed known at compile time, paced as
+/// [`PACKET_INTERVAL_MS`](ValidMode::PACKET_INTERVAL_MS) recommends
+pub fn effective_throughput_bps_for<Mode: ValidMode, Speed: ValidSpeed>() -> u32 {
+    effective_throughput_bps(Mode::NUMBER, Speed::bps(), Mode::PACKET_INTERVAL_MS)
+}
+
+/// [`time_on_air_us`] for a mode and speed known at compile time
+pub fn time_on_air_us_for<Mode: ValidMode, Speed: ValidSpeed>(payload_len: usize) -> u32 {
+    time_on_air_us(Mode::NUMBER, Speed::bps(), payload_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::{Fu1, Fu3, Fu4};
+    use crate::speeds::{B115200, B1200, B9600};
+
+    // These pin the model above, and have not been checked against a module on the air
+
+    #[test]
+    fn fu3_depends_on_serial_speed() {
+        assert_eq!(air_bps(3, 1200), 5_000);
+        assert_eq!(air_bps(3, 9600), 15_000);
+        assert_eq!(air_bps(3, 19200), 58_000);
+        assert_eq!(air_bps(3, 115200), 236_000);
+
+        // (10 + 9) bytes at 15 kbps
+        assert_eq!(time_on_air_us_for::<Fu3, B9600>(10), 10_134);
+        // (1 + 9) bytes at 236 kbps
+        assert_eq!(time_on_air_us_for::<Fu3, B115200>(1), 339);
+    }
+
+    #[test]
+    fn slow_modes_split_into_packets() {
+        // packets of 60 and 40 bytes, with 9 bytes of overhead each: 118 bytes at 500 bps
+        assert_eq!(time_on_air_us_for::<Fu4, B1200>(100), 1_888_000);
+        // one byte over a packet costs a second packet's overhead
+        assert_eq!(time_on_air_us(2, 2400, 60), 2_208);
+        assert_eq!(time_on_air_us(2, 2400, 61), 2_528);
+        // FU1 sends them as a single packet
+        assert_eq!(time_on_air_us_for::<Fu1, B9600>(61), 2_240);
+    }
+
+    #[test]
+    fn throughput_rises_with_serial_speed() {
+        let speeds = [1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200];
+        for (mode, interval_ms) in [(1, 0), (3, 0)] {
+            for pair in speeds.windows(2) {
+                assert!(
+                    effective_throughput_bps(mode, pair[0], interval_ms)
+                        <= effective_throughput_bps(mode, pair[1], interval_ms),
+                    "FU{mode} from {} to {} bps",
+                    pair[0],
+                    pair[1]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn throughput_agrees_with_time_on_air() {
+        let frame_bits = MAX_PACKET_PAYLOAD as u64 * 8;
+        for (mode, baud_bps) in [(1, 9600), (2, 4800), (3, 2400), (3, 115200), (4, 1200)] {
+            let air_bps = frame_bits * 1_000_000 / time_on_air_us(mode, baud_bps, 60) as u64;
+            assert!(effective_throughput_bps(mode, baud_bps, 0) as u64 <= air_bps);
+        }
+
+        // serial limited: 8 of every 10 bits on the line are payload
+        assert_eq!(effective_throughput_bps_for::<Fu3, B9600>(), 7_680);
+        // paced: one 60 byte frame every 2 s
+        assert_eq!(effective_throughput_bps_for::<Fu4, B1200>(), 240);
+        // air limited, once the pacing is dropped: 480 bits in 1.104 s
+        assert_eq!(effective_throughput_bps(4, 1200, 0), 434);
+    }
+
+    #[test]
+    fn nothing_to_send() {
+        assert_eq!(time_on_air_us(4, 1200, 0), 0);
+        assert_eq!(time_on_air_us(3, 9600, 0), 0);
+    }
+}
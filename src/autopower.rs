@@ -0,0 +1,252 @@
+//! Settling on the lowest transmit power that sustains the link.
+//!
+//! [`auto_power`] steps the power down from [`Power::MAX`], running a short packet error
+//! rate measurement at each level against a peer running
+//! [`echo_forever`](crate::linktest::echo_forever), and settles on the last level that
+//! met the [`PowerCriteria`].
+
+use embedded_hal::{delay::DelayNs, digital::OutputPin};
+use embedded_io::{Read, ReadReady, Write};
+
+use crate::linktest::{measure_per, PerReport};
+use crate::paramaters::Power;
+use crate::{RoundTripError, TransparentHC12};
+
+/// What a power level must achieve to be kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct PowerCriteria {
+    /// Probes sent at each level
+    pub probes: u16,
+    /// Bytes in each probe, see [`measure_per`]
+    pub payload_len: usize,
+    /// How long to wait for each probe to return
+    pub timeout_ms: u32,
+    /// The highest acceptable packet error rate, in parts per million
+    pub max_per_ppm: u32,
+}
+
+impl Default for PowerCriteria {
+    /// 20 probes of 16 bytes, each given 200ms, with at most 5% lost
+    fn default() -> Self {
+        Self {
+            probes: 20,
+            payload_len: 16,
+            timeout_ms: 200,
+            max_per_ppm: 50_000,
+        }
+    }
+}
+
+/// The outcome of [`auto_power`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct PowerSearch {
+    /// The lowest level that met the criteria, or `None` if even [`Power::MAX`] did not,
+    /// in which case the module is left there
+    pub power: Option<Power>,
+    /// The measurement at each level tried, indexed from [`Power::P1`]
+    pub reports: [Option<PerReport>; 8],
+}
+
+/// The power search could not be completed: a level was not accepted, or the link failed
+/// during a measurement
+pub type AutoPowerError<D, P> = RoundTripError<D, P>;
+
+/// Find the lowest power at which the link still meets `criteria`, and leave the module
+/// there. If the search fails part way, the module is left at the power reported by
+/// [`power`](TransparentHC12::power).
+pub fn auto_power<Device, Pin, Mode, Speed>(
+    hc12: &mut TransparentHC12<Device, Pin, Mode, Speed>,
+    delay: &mut impl DelayNs,
+    criteria: &PowerCriteria,
+) -> Result<PowerSearch, AutoPowerError<Device::Error, Pin::Error>>
+where
+    Device: Read + ReadReady + Write,
+    Pin: OutputPin,
+{
+    let mut search = PowerSearch::default();
+
+    for level in Power::iter().rev() {
+        hc12.set_power(level, delay)?;
+        let report = measure_per(
+            hc12,
+            criteria.probes,
+            criteria.payload_len,
+            criteria.timeout_ms,
+            delay,
+        )
+        .map_err(AutoPowerError::Link)?;
+        search.reports[level as usize - 1] = Some(report);
+
+        if report.per_ppm() > criteria.max_per_ppm {
+            break;
+        }
+        search.power = Some(level);
+    }
+
+    let settled = search.power.unwrap_or(Power::MAX);
+    if settled != hc12.power {
+        hc12.set_power(settled, delay)?;
+    }
+    Ok(search)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::Fu3;
+    use crate::paramaters::Channel;
+    use crate::speeds::B9600;
+    use crate::test_utils::CountingDelay;
+    use core::cell::Cell;
+    use core::convert::Infallible;
+    use embedded_hal::digital::ErrorType as PinErrorType;
+    use embedded_io::ErrorType;
+    use heapless::{Deque, Vec};
+
+    /// The programming pin, low while the module is in AT mode
+    struct SetPin<'a>(&'a Cell<bool>);
+
+    impl PinErrorType for SetPin<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for SetPin<'_> {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.0.set(true);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.0.set(false);
+            Ok(())
+        }
+    }
+
+    /// A module and an echoing peer, losing one in `loss[power - 1]` probes (never if zero)
+    struct Air<'a> {
+        at: &'a Cell<bool>,
+        loss: [u32; 8],
+        power: u8,
+        frames: u32,
+        pending: Vec<u8, 64>,
+        rx: Deque<u8, 128>,
+    }
+
+    impl ErrorType for Air<'_> {
+        type Error = Infallible;
+    }
+
+    impl Write for Air<'_> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            for byte in buf {
+                self.pending.push(*byte).unwrap();
+                if self.at.get() && *byte == b'\n' {
+                    // AT+Pn\r\n
+                    self.power = self.pending[4] - b'0';
+                    for byte in [b'O', b'K', b'+', b'P', self.pending[4], b'\r', b'\n'] {
+                        self.rx.push_back(byte).unwrap();
+                    }
+                    self.pending.clear();
+                } else if !self.at.get() && *byte == 0 {
+                    self.frames += 1;
+                    let every = self.loss[self.power as usize - 1];
+                    if every == 0 || !self.frames.is_multiple_of(every) {
+                        for byte in &self.pending {
+                            self.rx.push_back(*byte).unwrap();
+                        }
+                    }
+                    self.pending.clear();
+                }
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl ReadReady for Air<'_> {
+        fn read_ready(&mut self) -> Result<bool, Self::Error> {
+            Ok(!self.rx.is_empty())
+        }
+    }
+
+    impl Read for Air<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let mut count = 0;
+            for slot in buf.iter_mut() {
+                let Some(byte) = self.rx.pop_front() else {
+                    break;
+                };
+                *slot = byte;
+                count += 1;
+            }
+            Ok(count)
+        }
+    }
+
+    fn hc12(at: &Cell<bool>, loss: [u32; 8]) -> TransparentHC12<Air<'_>, SetPin<'_>, Fu3, B9600> {
+        let air = Air {
+            at,
+            loss,
+            power: 8,
+            frames: 0,
+            pending: Vec::new(),
+            rx: Deque::new(),
+        };
+        TransparentHC12::assume_programmed(air, SetPin(at), Channel::default(), Power::P8)
+    }
+
+    #[test]
+    // the search climbs to P5, so only without a cap
+    #[cfg(not(any(
+        feature = "max-power-p1",
+        feature = "max-power-p2",
+        feature = "max-power-p3",
+        feature = "max-power-p4",
+        feature = "max-power-p5",
+        feature = "max-power-p6",
+        feature = "max-power-p7"
+    )))]
+    fn settles_on_lowest_passing_level() {
+        let at = Cell::new(false);
+        // P4 loses one probe in ten, P3 and below one in two
+        let mut hc12 = hc12(&at, [2, 2, 2, 10, 0, 0, 0, 0]);
+
+        let search = auto_power(
+            &mut hc12,
+            &mut CountingDelay::new(),
+            &PowerCriteria::default(),
+        )
+        .unwrap();
+        assert_eq!(search.power, Some(Power::P5));
+        assert_eq!(*hc12.power(), Power::P5);
+        assert_eq!(hc12.device.power, 5);
+        assert!(!at.get());
+
+        let p4 = search.reports[3].unwrap();
+        assert_eq!((p4.sent, p4.received), (20, 18));
+        assert!(search.reports[2].is_none());
+    }
+
+    #[test]
+    fn full_power_failing_stays_at_full_power() {
+        let at = Cell::new(false);
+        let mut hc12 = hc12(&at, [2; 8]);
+
+        let search = auto_power(
+            &mut hc12,
+            &mut CountingDelay::new(),
+            &PowerCriteria::default(),
+        )
+        .unwrap();
+        assert_eq!(search.power, None);
+        assert_eq!(hc12.device.power, Power::MAX as u8);
+        assert!(search.reports[..Power::MAX as usize - 1]
+            .iter()
+            .all(Option::is_none));
+    }
+}
@@ -0,0 +1,240 @@
+//! Sleeping whenever the link goes quiet.
+//!
+//! An [`AutoSleep`] wraps a transparent device and a [`Clock`], and remembers when bytes
+//! were last written or read. Once nothing has happened for the idle timeout,
+//! [`poll`](AutoSleep::poll) puts the module to sleep with `AT+SLEEP`. The next
+//! [`write`](AutoSleep::write) wakes it first, which blocks for the pin and mode change
+//! settle times of the [`AtTimings`](crate::AtTimings), and says so with
+//! [`WriteOutcome::WokeFirst`].
+//!
+//! A sleeping module receives nothing, so while asleep the device is never ready to
+//! read, and reads return no bytes rather than blocking.
+
+use core::fmt::Debug;
+
+use embedded_hal::{delay::DelayNs, digital::OutputPin};
+use embedded_io::{ErrorType, Read, ReadReady, Write};
+
+use crate::commands::Sleep;
+use crate::time::{Clock, IntoMillis};
+use crate::{AtRead, RoundTripError, TransparentHC12};
+
+/// What a [`write`](AutoSleep::write) did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum WriteOutcome {
+    /// This many bytes were written to an awake module
+    Written(usize),
+    /// The module was woken first, then this many bytes were written
+    WokeFirst(usize),
+}
+
+impl WriteOutcome {
+    /// The number of bytes written
+    pub fn written(&self) -> usize {
+        match self {
+            Self::Written(count) | Self::WokeFirst(count) => *count,
+        }
+    }
+}
+
+/// The module could not be put to sleep, woken or written to
+pub type AutoSleepError<D, P> = RoundTripError<D, P>;
+
+/// Puts the module to sleep after an idle timeout, see the [module documentation](self)
+pub struct AutoSleep<D, C> {
+    device: D,
+    clock: C,
+    idle_ms: u32,
+    last_activity_ms: u32,
+    asleep: bool,
+}
+
+impl<Device, Pin, Mode, Speed, C: Clock> AutoSleep<TransparentHC12<Device, Pin, Mode, Speed>, C> {
+    /// Sleep once nothing has been written or read for `idle`. The module must be awake;
+    /// the timeout starts now.
+    pub fn new(
+        hc12: TransparentHC12<Device, Pin, Mode, Speed>,
+        clock: C,
+        idle: impl IntoMillis,
+    ) -> Self {
+        Self {
+            last_activity_ms: clock.now_ms(),
+            device: hc12,
+            clock,
+            idle_ms: idle.into_ms(),
+            asleep: false,
+        }
+    }
+}
+
+impl<D, C> AutoSleep<D, C> {
+    /// Whether the module is asleep
+    pub fn is_asleep(&self) -> bool {
+        self.asleep
+    }
+
+    /// Return the device and the clock. The module may be asleep, see
+    /// [`is_asleep`](Self::is_asleep).
+    pub fn inner(self) -> (D, C) {
+        (self.device, self.clock)
+    }
+}
+
+impl<Device, Pin, Mode, Speed, C> AutoSleep<TransparentHC12<Device, Pin, Mode, Speed>, C>
+where
+    Device: AtRead + Write,
+    Pin: OutputPin,
+    C: Clock,
+{
+    /// Put the module to sleep if it has been idle for the timeout. Returns whether it
+    /// went to sleep on this call.
+    pub fn poll(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<bool, AutoSleepError<Device::Error, Pin::Error>> {
+        let idle_ms = self.clock.now_ms().wrapping_sub(self.last_activity_ms);
+        if self.asleep || idle_ms < self.idle_ms {
+            return Ok(false);
+        }
+        self.device.flush().map_err(AutoSleepError::Link)?;
+        self.device
+            .round_trip(Sleep, delay)
+            .map_err(AutoSleepError::Pin)?
+            .map_err(AutoSleepError::At)?;
+        self.asleep = true;
+        Ok(true)
+    }
+
+    /// Write `buf`, waking the module first if it is asleep
+    pub fn write(
+        &mut self,
+        buf: &[u8],
+        delay: &mut impl DelayNs,
+    ) -> Result<WriteOutcome, AutoSleepError<Device::Error, Pin::Error>> {
+        let woke = self.asleep;
+        if woke {
+            self.device.wake(delay).map_err(AutoSleepError::Pin)?;
+            self.asleep = false;
+        }
+        let written = self.device.write(buf).map_err(AutoSleepError::Link)?;
+        self.last_activity_ms = self.clock.now_ms();
+        Ok(if woke {
+            WriteOutcome::WokeFirst(written)
+        } else {
+            WriteOutcome::Written(written)
+        })
+    }
+
+    /// Flush the device
+    pub fn flush(&mut self) -> Result<(), Device::Error> {
+        self.device.flush()
+    }
+}
+
+impl<D: ErrorType, C> ErrorType for AutoSleep<D, C> {
+    type Error = D::Error;
+}
+
+impl<D: Read, C: Clock> Read for AutoSleep<D, C> {
+    /// Read as usual while awake. While asleep, returns 0 rather than blocking.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.asleep {
+            return Ok(0);
+        }
+        let count = self.device.read(buf)?;
+        if count > 0 {
+            self.last_activity_ms = self.clock.now_ms();
+        }
+        Ok(count)
+    }
+}
+
+impl<D: ReadReady, C: Clock> ReadReady for AutoSleep<D, C> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        if self.asleep {
+            return Ok(false);
+        }
+        self.device.read_ready()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beacon::WAKE_MS;
+    use crate::mock::{MockHc12, MockSerial, MockSetPin};
+    use crate::modes::Fu3;
+    use crate::speeds::B9600;
+
+    fn auto_sleep<'a>(
+        module: &'a MockHc12,
+        offset_ms: u32,
+    ) -> AutoSleep<TransparentHC12<MockSerial<'a>, MockSetPin<'a>, Fu3, B9600>, impl Clock + 'a>
+    {
+        let hc12 = TransparentHC12::assume_factory(module.serial(), module.set_pin());
+        AutoSleep::new(hc12, move || module.now_ms().wrapping_add(offset_ms), 5000)
+    }
+
+    #[test]
+    fn sleeps_once_idle_and_activity_resets_the_timeout() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut sleepy = auto_sleep(&module, 0);
+
+        module.advance_ms(4000);
+        assert_eq!(
+            sleepy.write(b"hi", &mut delay),
+            Ok(WriteOutcome::Written(2))
+        );
+        module.advance_ms(3000);
+        module.receive(b"yo");
+        let mut buf = [0; 4];
+        assert_eq!(sleepy.read(&mut buf), Ok(2));
+
+        module.advance_ms(4999);
+        assert_eq!(sleepy.poll(&mut delay), Ok(false));
+        module.advance_ms(1);
+        assert_eq!(sleepy.poll(&mut delay), Ok(true));
+        assert!(sleepy.is_asleep() && module.is_asleep());
+        assert_eq!(sleepy.poll(&mut delay), Ok(false));
+
+        // nothing arrives at a sleeping module, and reads do not block
+        module.receive(b"lost");
+        assert_eq!(sleepy.read_ready(), Ok(false));
+        assert_eq!(sleepy.read(&mut buf), Ok(0));
+    }
+
+    #[test]
+    fn writing_wakes_the_module_first() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut sleepy = auto_sleep(&module, 0);
+        module.advance_ms(5000);
+        assert_eq!(sleepy.poll(&mut delay), Ok(true));
+        assert_eq!(module.transmitted().as_slice(), b"");
+
+        let before_ms = module.now_ms();
+        assert_eq!(
+            sleepy.write(b"wake", &mut delay),
+            Ok(WriteOutcome::WokeFirst(4))
+        );
+        assert_eq!(module.now_ms() - before_ms, WAKE_MS);
+        assert!(!sleepy.is_asleep() && !module.is_asleep());
+        assert_eq!(module.transmitted().as_slice(), b"wake");
+        assert_eq!(sleepy.write(b"!", &mut delay).map(|o| o.written()), Ok(1));
+    }
+
+    #[test]
+    fn timeout_survives_clock_wraparound() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        // the clock wraps 1s into the timeout
+        let mut sleepy = auto_sleep(&module, u32::MAX - 999);
+
+        module.advance_ms(4999);
+        assert_eq!(sleepy.poll(&mut delay), Ok(false));
+        module.advance_ms(1);
+        assert_eq!(sleepy.poll(&mut delay), Ok(true));
+    }
+}
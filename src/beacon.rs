@@ -0,0 +1,377 @@
+//! Periodic unattended transmissions.
+//!
+//! A [`Beacon`] owns a transparent device and sends a frame from a payload closure every
+//! interval. [`poll`](Beacon::poll) is called from the main loop with the current time: it
+//! starts a frame when one is due, and only writes while the device is ready, so it never
+//! blocks. Frames are started no closer together than the mode's
+//! [`PACKET_INTERVAL_MS`](crate::modes::ValidMode::PACKET_INTERVAL_MS).
+//!
+//! Nodes powered up together would keep sending at the same moments, so each slot can be
+//! moved by a random amount with [`jitter`](Beacon::jitter). Slots stay centred on the
+//! interval, so the jitter does not accumulate.
+//!
+//! With the `programming` feature, [`poll_sleeping`](Beacon::poll_sleeping) also puts the
+//! module to sleep with `AT+SLEEP` after each frame, and wakes it just before the next slot.
+//!
+//! Times are milliseconds from any free-running clock, and may wrap around.
+
+use embedded_io::{Write, WriteReady};
+
+use crate::modes::ValidMode;
+use crate::time::IntoMillis;
+use crate::TransparentHC12;
+
+/// How long waking a sleeping module takes with [`AtTimings::DATASHEET`], and so how early
+/// before a slot it is woken
+///
+/// [`AtTimings::DATASHEET`]: crate::AtTimings::DATASHEET
+pub const WAKE_MS: u32 = 120;
+
+/// The largest jitter, as a percentage of the interval
+pub const MAX_JITTER_PERCENT: u8 = 50;
+
+/// Sends a frame every interval, see the [module documentation](self)
+pub struct Beacon<D, F, const N: usize> {
+    device: D,
+    payload: F,
+    interval_ms: u32,
+    min_spacing_ms: u32,
+    jitter_percent: u8,
+    rng: u32,
+    /// The next slot before jitter, or `None` before the first poll
+    slot_ms: Option<u32>,
+    due_ms: u32,
+    frame: [u8; N],
+    len: usize,
+    written: usize,
+    #[cfg(feature = "programming")]
+    asleep: bool,
+}
+
+impl<Device, Pin, Mode, Speed, F, const N: usize>
+    Beacon<TransparentHC12<Device, Pin, Mode, Speed>, F, N>
+where
+    Mode: ValidMode,
+    F: FnMut(&mut [u8]) -> usize,
+{
+    /// Send a frame every `interval`, the first on the first poll. `payload` fills
+    /// the buffer of `N` bytes and returns the frame's length; a slot with an empty frame
+    /// is skipped.
+    pub fn new(
+        hc12: TransparentHC12<Device, Pin, Mode, Speed>,
+        interval: impl IntoMillis,
+        payload: F,
+    ) -> Self {
+        Self {
+            device: hc12,
+            payload,
+            interval_ms: interval.into_ms(),
+            min_spacing_ms: Mode::PACKET_INTERVAL_MS,
+            jitter_percent: 0,
+            rng: 1,
+            slot_ms: None,
+            due_ms: 0,
+            frame: [0; N],
+            len: 0,
+            written: 0,
+            #[cfg(feature = "programming")]
+            asleep: false,
+        }
+    }
+}
+
+impl<D, F, const N: usize> Beacon<D, F, N> {
+    /// Move each slot by up to `percent` of the interval either way, at most
+    /// [`MAX_JITTER_PERCENT`]. Give each node a different `seed`, such as its address.
+    pub fn jitter(mut self, percent: u8, seed: u32) -> Self {
+        self.jitter_percent = percent.min(MAX_JITTER_PERCENT);
+        // xorshift never leaves zero
+        self.rng = seed.max(1);
+        self
+    }
+
+    /// When the next frame is due, or `None` before the first poll
+    pub fn next_due_ms(&self) -> Option<u32> {
+        self.slot_ms.map(|_| self.due_ms)
+    }
+
+    /// Whether a frame is part way through being written
+    pub fn is_sending(&self) -> bool {
+        self.written < self.len
+    }
+
+    /// Return the device. A frame part way through being written is dropped.
+    pub fn inner(self) -> D {
+        self.device
+    }
+
+    /// Schedule the slot after the one starting at `now_ms`
+    fn advance(&mut self, now_ms: u32) {
+        let mut slot_ms = self
+            .slot_ms
+            .unwrap_or(now_ms)
+            .wrapping_add(self.interval_ms);
+        if is_due(now_ms, slot_ms) {
+            // the caller fell behind by a whole interval, so skip the missed slots
+            slot_ms = now_ms.wrapping_add(self.interval_ms);
+        }
+        self.slot_ms = Some(slot_ms);
+
+        let span = (self.interval_ms as u64 * self.jitter_percent as u64 / 100) as u32;
+        let offset = if span == 0 {
+            0
+        } else {
+            (self.next_random() % (2 * span + 1)) as i64 - span as i64
+        };
+        let due_ms = slot_ms.wrapping_add(offset as u32);
+        self.due_ms = if due_ms.wrapping_sub(now_ms) < self.min_spacing_ms {
+            now_ms.wrapping_add(self.min_spacing_ms)
+        } else {
+            due_ms
+        };
+    }
+
+    /// xorshift32
+    fn next_random(&mut self) -> u32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng
+    }
+}
+
+impl<D, F, const N: usize> Beacon<D, F, N>
+where
+    D: Write + WriteReady,
+    F: FnMut(&mut [u8]) -> usize,
+{
+    /// Start a frame if one is due, and write as much of it as the device accepts.
+    /// Returns whether a frame was completed.
+    pub fn poll(&mut self, now_ms: u32) -> Result<bool, D::Error> {
+        if !self.is_sending() {
+            if self.slot_ms.is_some() && !is_due(now_ms, self.due_ms) {
+                return Ok(false);
+            }
+            self.len = (self.payload)(&mut self.frame).min(N);
+            self.written = 0;
+            self.advance(now_ms);
+            if self.len == 0 {
+                return Ok(false);
+            }
+        }
+
+        while self.is_sending() {
+            if !self.device.write_ready()? {
+                return Ok(false);
+            }
+            let written = self.device.write(&self.frame[self.written..self.len])?;
+            if written == 0 {
+                return Ok(false);
+            }
+            self.written += written;
+        }
+        Ok(true)
+    }
+}
+
+/// Whether `at_ms` has been reached at `now_ms`
+fn is_due(now_ms: u32, at_ms: u32) -> bool {
+    (now_ms.wrapping_sub(at_ms) as i32) >= 0
+}
+
+#[cfg(feature = "programming")]
+pub use sleeping::BeaconError;
+
+#[cfg(feature = "programming")]
+mod sleeping {
+    use embedded_hal::{delay::DelayNs, digital::OutputPin};
+    use embedded_io::{Write, WriteReady};
+
+    use super::{is_due, Beacon};
+    use crate::commands::Sleep;
+    use crate::modes::ValidMode;
+    use crate::speeds::ValidSpeed;
+    use crate::{AtRead, RoundTripError, TransparentHC12};
+
+    /// A sleeping beacon could not send, sleep or wake
+    pub type BeaconError<D, P> = RoundTripError<D, P>;
+
+    impl<Device, Pin, Mode, Speed, F, const N: usize>
+        Beacon<TransparentHC12<Device, Pin, Mode, Speed>, F, N>
+    where
+        Device: AtRead + Write + WriteReady,
+        Pin: OutputPin,
+        Mode: ValidMode,
+        Speed: ValidSpeed,
+        F: FnMut(&mut [u8]) -> usize,
+    {
+        /// Like [`poll`](Self::poll), but put the module to sleep once a frame has been
+        /// sent over the air, and wake it just before the next slot, as long before as
+        /// waking takes with the device's [`AtTimings`](crate::AtTimings), by default
+        /// [`WAKE_MS`](super::WAKE_MS). Sleeping and waking block, for the frame's time
+        /// on air and the AT exchange, or for the wake.
+        pub fn poll_sleeping(
+            &mut self,
+            now_ms: u32,
+            delay: &mut impl DelayNs,
+        ) -> Result<bool, BeaconError<Device::Error, Pin::Error>> {
+            if self.asleep {
+                if is_due(now_ms.wrapping_add(self.device.wake_ms()), self.due_ms) {
+                    self.device.wake(delay).map_err(BeaconError::Pin)?;
+                    self.asleep = false;
+                }
+                // the time has moved on while waking, so the frame waits for the next poll
+                return Ok(false);
+            }
+
+            let sent = self.poll(now_ms).map_err(BeaconError::Link)?;
+            if sent {
+                self.device.flush().map_err(BeaconError::Link)?;
+                delay.delay_us(self.device.time_on_air_us(self.len));
+                self.device
+                    .round_trip(Sleep, delay)
+                    .map_err(BeaconError::Pin)?
+                    .map_err(BeaconError::At)?;
+                self.asleep = true;
+            }
+            Ok(sent)
+        }
+
+        /// Whether the module is asleep between slots
+        pub fn is_asleep(&self) -> bool {
+            self.asleep
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::{Fu3, Fu4};
+    use crate::speeds::{B1200, B9600};
+    use crate::test_utils::{PinLog, RecordingPin, Sink};
+    use heapless::Vec;
+
+    type Radio<'a, Mode, Speed> = TransparentHC12<Sink, RecordingPin<'a>, Mode, Speed>;
+
+    fn radio<Mode, Speed>(pins: &PinLog, sink: Sink) -> Radio<'_, Mode, Speed> {
+        TransparentHC12::assume_configured(sink, pins.pin(), Default::default())
+    }
+
+    /// Poll every millisecond up to `until_ms`, returning when each frame completed
+    fn run<D, F, const N: usize>(beacon: &mut Beacon<D, F, N>, until_ms: u32) -> Vec<u32, 64>
+    where
+        D: Write + WriteReady,
+        F: FnMut(&mut [u8]) -> usize,
+    {
+        let mut sent = Vec::new();
+        for now_ms in 0..until_ms {
+            if beacon.poll(now_ms).ok().unwrap() {
+                sent.push(now_ms).unwrap();
+            }
+        }
+        sent
+    }
+
+    #[test]
+    fn sends_every_interval() {
+        let pins = PinLog::new();
+        let mut count = 0u8;
+        let mut beacon: Beacon<_, _, 4> =
+            Beacon::new(radio::<Fu3, B9600>(&pins, Sink::new()), 1000, |frame| {
+                count += 1;
+                frame[..2].copy_from_slice(&[0xbe, count]);
+                2
+            });
+
+        assert_eq!(run(&mut beacon, 3500), [0, 1000, 2000, 3000]);
+        assert_eq!(beacon.next_due_ms(), Some(4000));
+        let (sink, _) = beacon.inner().inner();
+        assert_eq!(sink.data(), [0xbe, 1, 0xbe, 2, 0xbe, 3, 0xbe, 4]);
+    }
+
+    #[test]
+    fn jitter_moves_slots_without_drifting() {
+        let pins = PinLog::new();
+        let payload = |frame: &mut [u8]| {
+            frame[0] = 1;
+            1
+        };
+        let mut a: Beacon<_, _, 1> =
+            Beacon::new(radio::<Fu3, B9600>(&pins, Sink::new()), 1000, payload).jitter(20, 1);
+        let mut b: Beacon<_, _, 1> =
+            Beacon::new(radio::<Fu3, B9600>(&pins, Sink::new()), 1000, payload).jitter(20, 2);
+
+        let (sent_a, sent_b) = (run(&mut a, 19_500), run(&mut b, 19_500));
+        assert_ne!(sent_a, sent_b);
+        for sent in [sent_a, sent_b] {
+            assert_eq!(sent.len(), 20);
+            for (index, at_ms) in sent.iter().enumerate().skip(1) {
+                // each frame is within 20% of its slot
+                assert!(at_ms.abs_diff(index as u32 * 1000) <= 200, "{sent:?}");
+            }
+            assert!(sent.iter().any(|at_ms| at_ms % 1000 != 0));
+        }
+    }
+
+    #[test]
+    fn waits_for_the_device_and_the_mode_spacing() {
+        let pins = PinLog::new();
+        let mut calls = 0;
+        // FU4 starts frames at least two seconds apart
+        let mut beacon: Beacon<_, _, 8> = Beacon::new(
+            radio::<Fu4, B1200>(&pins, Sink::new().accept_data(6)),
+            500,
+            |frame| {
+                calls += 1;
+                frame[..4].copy_from_slice(b"ping");
+                4
+            },
+        );
+
+        assert_eq!(beacon.poll(0), Ok(true));
+        assert_eq!(beacon.next_due_ms(), Some(2000));
+        assert_eq!(beacon.poll(1999), Ok(false));
+        // the device only takes two more bytes, so the frame stays part way written
+        assert_eq!(beacon.poll(2000), Ok(false));
+        assert!(beacon.is_sending());
+        assert_eq!(beacon.poll(2001), Ok(false));
+        drop(beacon);
+        assert_eq!(calls, 2);
+    }
+
+    #[cfg(feature = "programming")]
+    #[test]
+    fn sleeps_between_slots() {
+        use crate::mock::MockHc12;
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let hc12: TransparentHC12<_, _, Fu3, B9600> =
+            TransparentHC12::assume_factory(module.serial(), module.set_pin());
+        let mut beacon: Beacon<_, _, 4> = Beacon::new(hc12, 5000, |frame| {
+            frame[..2].copy_from_slice(b"hi");
+            2
+        });
+
+        assert_eq!(beacon.poll_sleeping(module.now_ms(), &mut delay), Ok(true));
+        assert!(module.is_asleep() && beacon.is_asleep());
+        assert_eq!(module.transmitted().as_slice(), b"hi");
+        // sending, then AT+SLEEP, took well under a second
+        assert!(module.now_ms() < 1000);
+
+        module.advance_ms(4000 - module.now_ms());
+        assert_eq!(beacon.poll_sleeping(module.now_ms(), &mut delay), Ok(false));
+        assert!(module.is_asleep());
+
+        // woken just before the slot
+        module.advance_ms(4900 - module.now_ms());
+        assert_eq!(beacon.poll_sleeping(module.now_ms(), &mut delay), Ok(false));
+        assert!(!module.is_asleep());
+        assert_eq!(module.now_ms(), 4900 + WAKE_MS);
+
+        assert_eq!(beacon.poll_sleeping(module.now_ms(), &mut delay), Ok(true));
+        assert_eq!(module.transmitted().as_slice(), b"hi");
+        assert!(module.is_asleep());
+    }
+}
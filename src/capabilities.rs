@@ -0,0 +1,261 @@
+//! What the module's firmware can be asked to do.
+//!
+//! HC-12 firmware differs between versions and between clones, and a command the firmware
+//! does not know is not always refused: some versions answer with garbage, or take the
+//! wrong setting. [`query_version`](crate::HC12::query_version) reads the version line and
+//! looks it up in a [`CapabilityTable`], and from then on the device refuses commands the
+//! firmware does not support with
+//! [`UnsupportedByFirmware`](crate::Error::UnsupportedByFirmware), before anything is sent.
+//! Until the version is queried, nothing is refused.
+//!
+//! The table knows the HC-12 versions below. A version it does not know gets
+//! [`Capabilities::CONSERVATIVE`], so a new clone should be added with
+//! [`insert`](CapabilityTable::insert) once it has been tried.
+//!
+//! | Version | Serial format | Factory reset | Sleep |
+//! |---------|---------------|---------------|-------|
+//! | V2.3    | no            | yes           | yes   |
+//! | V2.4    | yes           | yes           | yes   |
+//! | V2.6    | yes           | yes           | yes   |
+//!
+//! ```
+//! use hc12_rs::capabilities::{Capabilities, Capability, CapabilityTable};
+//! use hc12_rs::mock::MockHc12;
+//! use hc12_rs::HC12;
+//!
+//! let module = MockHc12::new();
+//! module.set_firmware("SI4463 RF MODULE V1.1");
+//! let mut delay = module.delay();
+//! let mut hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay).unwrap();
+//!
+//! let mut table = CapabilityTable::<8>::new();
+//! hc12.query_version(&table, &mut delay).unwrap();
+//! assert!(!hc12.capabilities().unwrap().supports(Capability::Sleep));
+//!
+//! // this clone was tried, and sleeps like the original
+//! table
+//!     .insert(
+//!         "RF MODULE V1.1",
+//!         Capabilities {
+//!             sleep: true,
+//!             ..Capabilities::CONSERVATIVE
+//!         },
+//!     )
+//!     .unwrap();
+//! hc12.query_version(&table, &mut delay).unwrap();
+//! assert!(hc12.capabilities().unwrap().supports(Capability::Sleep));
+//! ```
+
+use heapless::Vec;
+
+use crate::{response, Response, TransparentHC12};
+
+/// A command that not every firmware supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Capability {
+    /// Setting the serial data, parity and stop bits, `AT+Ux`
+    SerialFormat,
+    /// Restoring the factory settings, `AT+DEFAULT`
+    FactoryReset,
+    /// Sleeping once AT mode is left, `AT+SLEEP`
+    Sleep,
+}
+
+/// The commands a firmware version supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Capabilities {
+    /// `AT+Ux` is understood
+    pub serial_format: bool,
+    /// `AT+DEFAULT` is understood
+    pub factory_reset: bool,
+    /// `AT+SLEEP` is understood
+    pub sleep: bool,
+}
+
+impl Capabilities {
+    /// Every optional command
+    pub const ALL: Self = Self {
+        serial_format: true,
+        factory_reset: true,
+        sleep: true,
+    };
+
+    /// No optional command, for firmware that is not known
+    pub const CONSERVATIVE: Self = Self {
+        serial_format: false,
+        factory_reset: false,
+        sleep: false,
+    };
+
+    /// Whether `capability` is supported
+    pub const fn supports(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::SerialFormat => self.serial_format,
+            Capability::FactoryReset => self.factory_reset,
+            Capability::Sleep => self.sleep,
+        }
+    }
+}
+
+/// The version line of the module's firmware, read with
+/// [`firmware_version`](crate::HC12::firmware_version)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct FirmwareVersion {
+    /// The line as the module sent it, such as `www.hc01.com HC-12_V2.6`
+    pub banner: Response<32>,
+    /// The major and minor version, if they could be read from the line
+    pub version: Option<(u8, u8)>,
+}
+
+impl FirmwareVersion {
+    /// Read the version from a version line
+    pub fn parse(banner: Response<32>) -> Self {
+        let version = response::firmware_version(banner.as_bytes());
+        Self { banner, version }
+    }
+
+    /// Whether the version is known to be at least `major.minor`. An unknown version is not.
+    pub fn at_least(&self, major: u8, minor: u8) -> bool {
+        self.version
+            .is_some_and(|version| version >= (major, minor))
+    }
+}
+
+/// The firmware versions known to the crate, by the end of their version line
+const KNOWN: [(&str, Capabilities); 3] = [
+    (
+        "HC-12_V2.3",
+        Capabilities {
+            serial_format: false,
+            ..Capabilities::ALL
+        },
+    ),
+    ("HC-12_V2.4", Capabilities::ALL),
+    ("HC-12_V2.6", Capabilities::ALL),
+];
+
+/// Firmware versions and what they support, holding up to `N` versions
+#[derive(Debug, Clone)]
+pub struct CapabilityTable<const N: usize> {
+    entries: Vec<(&'static str, Capabilities), N>,
+}
+
+impl<const N: usize> Default for CapabilityTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> CapabilityTable<N> {
+    /// The versions known to the crate, as many as fit
+    pub fn new() -> Self {
+        let mut table = Self::empty();
+        for (version, capabilities) in KNOWN {
+            table.insert(version, capabilities).ok();
+        }
+        table
+    }
+
+    /// A table without any versions, so every firmware is treated as unknown
+    pub const fn empty() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add a firmware version, or replace what is known about it. `version` is matched
+    /// against the end of the version line, so `HC-12_V2.7` matches
+    /// `www.hc01.com HC-12_V2.7`. Versions added later are matched first. If the table is
+    /// full, the entry is handed back.
+    pub fn insert(
+        &mut self,
+        version: &'static str,
+        capabilities: Capabilities,
+    ) -> Result<(), (&'static str, Capabilities)> {
+        if let Some(index) = self.entries.iter().position(|(known, _)| *known == version) {
+            self.entries.remove(index);
+        }
+        self.entries.push((version, capabilities))
+    }
+
+    /// What the firmware that answered `AT+V` with `line` supports
+    pub fn lookup(&self, line: &str) -> Capabilities {
+        let line = line.trim_end();
+        self.entries
+            .iter()
+            .rev()
+            .find(|(version, _)| line.ends_with(version))
+            .map_or(Capabilities::CONSERVATIVE, |(_, capabilities)| {
+                *capabilities
+            })
+    }
+}
+
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed> {
+    /// What the firmware supports, if its version was queried in programming mode
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        self.session.capabilities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autosleep::AutoSleepError;
+    use crate::mock::MockHc12;
+    use crate::{Error, HC12};
+
+    #[test]
+    fn versions_are_looked_up() {
+        let mut table = CapabilityTable::<4>::new();
+        let v23 = table.lookup("www.hc01.com HC-12_V2.3\r\n");
+        assert!(!v23.supports(Capability::SerialFormat));
+        assert!(v23.supports(Capability::Sleep));
+        let v26 = table.lookup("www.hc01.com HC-12_V2.6");
+        assert!(v26.supports(Capability::SerialFormat));
+        assert_eq!(table.lookup("HC-12_V2.61"), Capabilities::CONSERVATIVE);
+
+        // later entries win, and a full table hands the entry back
+        table
+            .insert("HC-12_V2.6", Capabilities::CONSERVATIVE)
+            .unwrap();
+        assert_eq!(
+            table.lookup("www.hc01.com HC-12_V2.6"),
+            Capabilities::CONSERVATIVE
+        );
+        table.insert("V9", Capabilities::ALL).unwrap();
+        assert!(table.insert("V10", Capabilities::ALL).is_err());
+
+        // a table too small for the known versions keeps the first
+        let small = CapabilityTable::<1>::new();
+        assert_eq!(small.lookup("HC-12_V2.3"), KNOWN[0].1);
+        assert_eq!(small.lookup("HC-12_V2.6"), Capabilities::CONSERVATIVE);
+    }
+
+    #[test]
+    fn unsupported_commands_are_not_sent() {
+        let module = MockHc12::new();
+        module.set_firmware("CLONE V0.9");
+        let mut delay = module.delay();
+        let mut hc12 =
+            HC12::factor_settings(module.serial(), module.set_pin(), &mut delay).unwrap();
+        assert_eq!(hc12.capabilities(), None);
+        hc12.query_version(&CapabilityTable::<4>::new(), &mut delay)
+            .unwrap();
+        assert_eq!(hc12.capabilities(), Some(Capabilities::CONSERVATIVE));
+
+        let mut hc12 = hc12.into_transparent_mode(&mut delay).unwrap();
+        let result = hc12.park(&mut delay);
+        assert!(matches!(
+            result,
+            Err(AutoSleepError::At(Error::UnsupportedByFirmware(
+                Capability::Sleep
+            )))
+        ));
+        assert!(!module.is_asleep());
+        assert!(!module.in_at_mode());
+    }
+}
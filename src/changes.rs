@@ -0,0 +1,72 @@
+//! What a reconfiguration changed, for logging or display.
+
+use core::fmt;
+
+use crate::paramaters::{Channel, Power};
+use crate::RoundTripError;
+
+/// One setting before and after a reconfiguration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct FieldChange<T> {
+    /// The value before
+    pub old: T,
+    /// The value after
+    pub new: T,
+    /// Whether a command was sent to change it. Unchanged values are skipped.
+    pub sent: bool,
+}
+
+impl<T: PartialEq> FieldChange<T> {
+    /// Whether the value changed
+    pub fn changed(&self) -> bool {
+        self.old != self.new
+    }
+}
+
+/// The settings touched by
+/// [`TransparentHC12::apply_diff`](crate::TransparentHC12::apply_diff)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct ChangeSummary {
+    /// The channel
+    pub channel: FieldChange<Channel>,
+    /// The transmit power
+    pub power: FieldChange<Power>,
+}
+
+impl ChangeSummary {
+    /// Whether no command was sent
+    pub fn is_empty(&self) -> bool {
+        !self.channel.sent && !self.power.sent
+    }
+}
+
+impl fmt::Display for ChangeSummary {
+    /// One field per line, such as `channel: 1 -> 5` or `power: P8 (unchanged)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let channel = self.channel;
+        if channel.sent {
+            writeln!(
+                f,
+                "channel: {} -> {}",
+                u8::from(channel.old),
+                u8::from(channel.new)
+            )?;
+        } else {
+            writeln!(f, "channel: {} (unchanged)", u8::from(channel.new))?;
+        }
+
+        let power = self.power;
+        if power.sent {
+            writeln!(f, "power: {:?} -> {:?}", power.old, power.new)
+        } else {
+            writeln!(f, "power: {:?} (unchanged)", power.new)
+        }
+    }
+}
+
+/// A reconfiguration did not complete. The settings that were applied before the failure
+/// are kept, see [`TransparentHC12::configuration`](crate::TransparentHC12::configuration).
+/// Nothing is sent in transparent mode, so this is never `Link`.
+pub type ApplyError<D, P> = RoundTripError<D, P>;
@@ -1,88 +1,644 @@
-use core::str::from_utf8;
+use core::convert::Infallible;
 
 use embedded_hal::delay::DelayNs;
-use embedded_io::{Read, Write};
-use heapless::String;
+use embedded_io::{Read, ReadReady, Write};
+use heapless::{String, Vec};
 
-use crate::Error;
+use crate::capabilities::Capability;
+use crate::events::{notify, AtEvent, Observer};
+use crate::modes::{Fu1, Fu2, Fu3, Fu4};
+use crate::paramaters::{Channel, Parity, Power, SerialFormat, StopBits};
+use crate::response;
+use crate::speeds::ValidSpeed;
+use crate::{Error, Response};
 
 pub trait Command {
     fn command(&self) -> String<16>;
+
+    /// The capability the firmware needs to understand the command, if it is optional
+    fn requires(&self) -> Option<Capability> {
+        None
+    }
+
+    /// The setting the OK line should echo, as its prefix and value, such as `("C", 21)`
+    /// for `OK+C021`, for commands that set one
+    fn echo(&self) -> Option<(&'static str, u32)> {
+        None
+    }
+
+    /// Whether the command changes the serial speed or format, which some modules take
+    /// longer to answer
+    fn changes_serial(&self) -> bool {
+        false
+    }
+}
+
+/// Check that an OK line echoes the value `command` set. A module that echoes another
+/// value did not apply it.
+pub(crate) fn verify_echo<E: embedded_io::Error, const N: usize>(
+    command: &impl Command,
+    line: Response<N>,
+) -> Result<Response<N>, Error<E, Infallible, N>> {
+    let Some((prefix, expected)) = command.echo() else {
+        return Ok(line);
+    };
+    match response::echoed(line.as_bytes(), prefix.as_bytes()) {
+        Some(got) if got == expected => Ok(line),
+        Some(got) => Err(Error::Mismatch { expected, got }),
+        None => Err(Error::InvalidResponse(line)),
+    }
+}
+
+/// Build a command from a prefix and a decimal argument, zero-padded to at least `width`
+/// digits. This avoids pulling `core::fmt` into the AT path for the parameterized commands.
+fn with_decimal(prefix: &str, value: u8, width: usize) -> String<16> {
+    let digits = [
+        b'0' + value / 100,
+        b'0' + value / 10 % 10,
+        b'0' + value % 10,
+    ];
+    let significant = if value >= 100 {
+        3
+    } else if value >= 10 {
+        2
+    } else {
+        1
+    };
+
+    let mut command: String<16> = prefix.try_into().unwrap();
+    for &digit in &digits[3 - significant.max(width)..] {
+        command.push(digit as char).unwrap();
+    }
+    command
+}
+
+impl<T> Command for T
+where
+    T: ValidSpeed,
+{
+    fn command(&self) -> heapless::String<16> {
+        // every speed command is shorter than the string, so this cannot fail
+        T::COMMAND.try_into().unwrap()
+    }
+
+    fn echo(&self) -> Option<(&'static str, u32)> {
+        Some(("B", T::bps()))
+    }
+
+    fn changes_serial(&self) -> bool {
+        true
+    }
+}
+
+impl Command for Fu1 {
+    fn command(&self) -> heapless::String<16> {
+        "AT+FU1".try_into().unwrap()
+    }
+
+    fn echo(&self) -> Option<(&'static str, u32)> {
+        Some(("FU", 1))
+    }
+}
+
+impl Command for Fu2 {
+    fn command(&self) -> heapless::String<16> {
+        "AT+FU2".try_into().unwrap()
+    }
+
+    fn echo(&self) -> Option<(&'static str, u32)> {
+        Some(("FU", 2))
+    }
+}
+
+impl Command for Fu3 {
+    fn command(&self) -> heapless::String<16> {
+        "AT+FU3".try_into().unwrap()
+    }
+
+    fn echo(&self) -> Option<(&'static str, u32)> {
+        Some(("FU", 3))
+    }
+}
+
+impl Command for Fu4 {
+    fn command(&self) -> heapless::String<16> {
+        "AT+FU4".try_into().unwrap()
+    }
+
+    fn echo(&self) -> Option<(&'static str, u32)> {
+        Some(("FU", 4))
+    }
+}
+
+/// Check the module is listening, answered with `OK`
+pub(crate) struct Ping;
+
+impl Command for Ping {
+    fn command(&self) -> heapless::String<16> {
+        "AT".try_into().unwrap()
+    }
+}
+
+/// A command given as text, for commands the crate does not model
+pub(crate) struct Raw(pub(crate) String<16>);
+
+impl Command for Raw {
+    fn command(&self) -> heapless::String<16> {
+        self.0.clone()
+    }
+}
+
+/// Query the firmware version
+pub(crate) struct Version;
+
+impl Command for Version {
+    fn command(&self) -> heapless::String<16> {
+        "AT+V".try_into().unwrap()
+    }
+}
+
+/// Put the module to sleep once it leaves AT mode
+pub(crate) struct Sleep;
+
+impl Command for Sleep {
+    fn command(&self) -> heapless::String<16> {
+        "AT+SLEEP".try_into().unwrap()
+    }
+
+    fn requires(&self) -> Option<Capability> {
+        Some(Capability::Sleep)
+    }
+}
+
+/// Ask the module for one of its settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Query {
+    /// `AT+RB`, answered with `OK+B9600`
+    Baudrate,
+    /// `AT+RC`, answered with `OK+RC001`
+    Channel,
+    /// `AT+RP`, answered with `OK+RP:+20dBm`
+    Power,
+    /// `AT+RF`, answered with `OK+FU3`
+    Mode,
+    /// `AT+RX`, answered with each of the above, a line each
+    All,
+}
+
+impl Command for Query {
+    fn command(&self) -> heapless::String<16> {
+        let command = match self {
+            Query::Baudrate => "AT+RB",
+            Query::Channel => "AT+RC",
+            Query::Power => "AT+RP",
+            Query::Mode => "AT+RF",
+            Query::All => "AT+RX",
+        };
+        command.try_into().unwrap()
+    }
+}
+
+impl Command for Channel {
+    fn command(&self) -> heapless::String<16> {
+        with_decimal("AT+C", (*self).into(), 3)
+    }
+
+    fn echo(&self) -> Option<(&'static str, u32)> {
+        Some(("C", u8::from(*self).into()))
+    }
 }
 
-pub(crate) fn run_command<D: Read + Write>(
-    device: &mut D,
-    command: impl Command,
-    delay: &mut impl DelayNs,
-) -> Result<(), Error<D::Error>> {
-    send_command(device, command, delay)?;
-    recieve_command(device)?;
-    Ok(())
+impl Command for Power {
+    fn command(&self) -> String<16> {
+        with_decimal("AT+P", self.into(), 1)
+    }
+
+    fn echo(&self) -> Option<(&'static str, u32)> {
+        Some(("P", u8::from(self).into()))
+    }
+}
+
+impl Command for SerialFormat {
+    /// `AT+Uxyz`, such as `AT+U8E1`. The datasheet writes 1.5 stop bits as `3`.
+    fn command(&self) -> String<16> {
+        let parity = match self.parity() {
+            Parity::None => 'N',
+            Parity::Odd => 'O',
+            Parity::Even => 'E',
+        };
+        let stop_bits = match self.stop_bits() {
+            StopBits::One => '1',
+            StopBits::Two => '2',
+            StopBits::OneAndHalf => '3',
+        };
+        let mut command = with_decimal("AT+U", self.data_bits() as u8, 1);
+        for c in [parity, stop_bits] {
+            command.push(c).unwrap();
+        }
+        command
+    }
+
+    fn requires(&self) -> Option<Capability> {
+        Some(Capability::SerialFormat)
+    }
+
+    fn changes_serial(&self) -> bool {
+        true
+    }
 }
 
-fn send_command<D: Write>(
-    device: &mut D,
-    command: impl Command,
-    delay: &mut impl DelayNs,
-) -> Result<(), D::Error> {
-    device.write_all(command.command().as_bytes())?;
+/// A serial device AT responses can be read from. Devices implementing [`ReadReady`] are
+/// polled, so a read is only issued once a byte has arrived and the response timeout is
+/// enforced even if the device's reads block. A device that only implements [`Read`] can
+/// be wrapped in [`Unpolled`](crate::adapters::Unpolled) instead.
+pub trait AtRead: Read {
+    /// Whether a read would return a byte without blocking
+    fn response_ready(&mut self) -> Result<bool, Self::Error>;
+}
+
+impl<T: Read + ReadReady + ?Sized> AtRead for T {
+    fn response_ready(&mut self) -> Result<bool, Self::Error> {
+        self.read_ready()
+    }
+}
+
+/// A serial device usable for AT exchanges, as a single object-safe trait
+pub(crate) trait Port: AtRead + Write {}
+
+impl<T: AtRead + Write> Port for T {}
+
+/// Run a command, waiting `settle_ms` before reading a response of up to `N` bytes. It is
+/// only generic over the error type and capacity, so the command/response loop is compiled
+/// once per serial error type, rather than once per device, delay and command combination.
+pub(crate) fn exchange<E: embedded_io::Error, const N: usize>(
+    device: &mut dyn Port<Error = E>,
+    command: String<16>,
+    delay: &mut dyn DelayNs,
+    observer: Option<Observer>,
+    settle_ms: u32,
+    timeout_ms: u32,
+) -> Result<Response<N>, Error<E, Infallible, N>> {
+    let sent = send_command(device, command, delay, settle_ms)?;
+    notify(observer, || AtEvent::CommandSent(sent));
+
+    let response = recieve_command(device, delay, timeout_ms);
+    notify_response(observer, &response);
+    response
+}
+
+/// Run a command answered with `L` lines of up to `N` bytes each, such as `AT+RX`. Each
+/// line gets the full response timeout, so lines arriving in fragments, or some time
+/// apart, are accumulated until all have arrived. Up to `L` blank lines are skipped, after
+/// which the next fails the exchange with `Error::InvalidResponse`, as does the first line
+/// that is not OK.
+pub(crate) fn exchange_lines<E: embedded_io::Error, const N: usize, const L: usize>(
+    device: &mut dyn Port<Error = E>,
+    command: String<16>,
+    delay: &mut dyn DelayNs,
+    observer: Option<Observer>,
+    settle_ms: u32,
+    timeout_ms: u32,
+) -> Result<Vec<Response<N>, L>, Error<E, Infallible, N>> {
+    let sent = send_command(device, command, delay, settle_ms)?;
+    notify(observer, || AtEvent::CommandSent(sent));
+
+    let mut lines = Vec::new();
+    let mut blank = 0;
+    while !lines.is_full() {
+        let response = recieve_command(device, delay, timeout_ms);
+        if let Err(Error::NoOK(line)) = &response {
+            if line.trim().is_empty() {
+                // a module repeating blank lines would otherwise never run out of them
+                if blank == L {
+                    return Err(Error::InvalidResponse(line.clone()));
+                }
+                blank += 1;
+                continue;
+            }
+        }
+        notify_response(observer, &response);
+        // there is room, as the loop checks
+        lines.push(response?).ok();
+    }
+    Ok(lines)
+}
+
+fn notify_response<E: embedded_io::Error, const N: usize>(
+    observer: Option<Observer>,
+    response: &Result<Response<N>, Error<E, Infallible, N>>,
+) {
+    match response {
+        Ok(line) | Err(Error::NoOK(line)) => {
+            notify(observer, || AtEvent::ResponseReceived(clip(line)))
+        }
+        Err(Error::NoResponse) => notify(observer, || AtEvent::Timeout),
+        Err(_) => {}
+    }
+}
+
+/// The first `N` bytes of a response, for reporting a response of any capacity
+pub(crate) fn clip<const N: usize, const M: usize>(line: &Response<M>) -> Response<N> {
+    let mut end = line.len().min(N);
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    // `end` is at most N, so this cannot fail
+    line[..end].try_into().unwrap()
+}
+
+/// Write a command and give the module `settle_ms` to act on it, returning the text that
+/// was sent
+fn send_command<E: embedded_io::Error>(
+    device: &mut dyn Write<Error = E>,
+    command: String<16>,
+    delay: &mut dyn DelayNs,
+    settle_ms: u32,
+) -> Result<String<16>, E> {
+    device.write_all(command.as_bytes())?;
     device.write_all("\r\n".as_bytes())?;
-    delay.delay_ms(40);
-    Ok(())
+    trace_at!(trace, "AT command sent: {}", command.as_str());
+    delay.delay_ms(settle_ms);
+    Ok(command)
 }
 
-fn recieve_command<D: Read>(device: &mut D) -> Result<(), Error<D::Error>> {
-    let mut buffer = [0u8; 16];
+/// Read a single response line. Bytes are read one at a time until the line terminator,
+/// until the device has had nothing more to give for `timeout_ms`, or until the buffer of
+/// `N` bytes is full, so it never reads into the next response. The timeout is counted in
+/// 1ms waits while the device is not ready, or its reads return no bytes. Returns the OK
+/// line without any leading noise, or `Error::Truncated` if the line does not fit.
+fn recieve_command<E: embedded_io::Error, const N: usize>(
+    device: &mut dyn AtRead<Error = E>,
+    delay: &mut dyn DelayNs,
+    timeout_ms: u32,
+) -> Result<Response<N>, Error<E, Infallible, N>> {
+    let mut buffer = [0u8; N];
     let mut pointer = 0;
+    let mut waited_ms = 0;
+
+    while pointer < buffer.len() {
+        if !device.response_ready()? || device.read(&mut buffer[pointer..pointer + 1])? == 0 {
+            if waited_ms >= timeout_ms {
+                break;
+            }
+            delay.delay_ms(1);
+            waited_ms += 1;
+            continue;
+        }
+        pointer += 1;
 
-    while let Ok(bytes) = device.read(&mut buffer[pointer..]) {
-        if bytes == 0 {
+        if buffer[pointer - 1] == b'\n' {
             break;
         }
-        pointer += bytes;
+    }
 
-        if buffer.contains(&b'\n') {}
+    if pointer == 0 {
+        return Err(Error::NoResponse);
+    }
+    if pointer == N && buffer[N - 1] != b'\n' {
+        return Err(Error::Truncated);
     }
 
-    device.read(&mut buffer)?;
-    let s = from_utf8(&buffer).unwrap();
-    if s.contains("OK") {
-        Ok(())
+    let s = response::text(response::skip_noise(&buffer[..pointer]));
+    trace_at!(
+        trace,
+        "AT response received: {}",
+        s.trim_end_matches(['\r', '\n'])
+    );
+    // the buffer is the same size as the string, so this cannot fail
+    let line: Response<N> = s.try_into().unwrap();
+    if buffer[pointer - 1] != b'\n' {
+        return Err(Error::Unterminated(line));
+    }
+    if response::is_ok(s.as_bytes()) {
+        Ok(line)
     } else {
-        Err(Error::NoOK(s.try_into().unwrap()))
+        Err(Error::NoOK(line))
     }
 }
 
 #[cfg(test)]
-mod test {
-    use crate::speeds::B9600;
+pub(crate) mod test {
+    use crate::speeds::*;
+    use crate::{AtTimings, RESPONSE_CAPACITY, RESPONSE_TIMEOUT_MS};
+    use core::fmt::Write as _;
 
     use super::*;
-    use embedded_hal_mock::eh1 as hal;
-    use embedded_io::ErrorType;
-    use mock_embedded_io as io;
+    use crate::paramaters::DataBits;
+    use crate::test_utils::{CountingDelay, Duo, Sink, Source};
+
+    /// Hands out scripted fragments, each after a few reads, or readiness checks, that
+    /// return nothing
+    pub(crate) struct Fragments {
+        fragments: &'static [&'static [u8]],
+        offset: usize,
+        gap: u32,
+        idle: u32,
+    }
+
+    impl Fragments {
+        pub(crate) fn new(fragments: &'static [&'static [u8]], gap: u32) -> Self {
+            Self {
+                fragments,
+                offset: 0,
+                gap,
+                idle: 0,
+            }
+        }
+    }
+
+    impl embedded_io::ErrorType for Fragments {
+        type Error = Infallible;
+    }
+
+    impl Read for Fragments {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let Some((fragment, rest)) = self.fragments.split_first() else {
+                return Ok(0);
+            };
+            if self.offset == 0 && self.idle < self.gap {
+                self.idle += 1;
+                return Ok(0);
+            }
+            let left = &fragment[self.offset..];
+            let count = buf.len().min(left.len());
+            buf[..count].copy_from_slice(&left[..count]);
+            self.offset += count;
+            if self.offset == fragment.len() {
+                (self.fragments, self.offset, self.idle) = (rest, 0, 0);
+            }
+            Ok(count)
+        }
+    }
+
+    impl ReadReady for Fragments {
+        fn read_ready(&mut self) -> Result<bool, Self::Error> {
+            if self.fragments.is_empty() {
+                return Ok(false);
+            }
+            if self.offset == 0 && self.idle < self.gap {
+                self.idle += 1;
+                return Ok(false);
+            }
+            Ok(true)
+        }
+    }
+
+    impl Write for Fragments {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    /// Run a command, reading a response of up to `RESPONSE_CAPACITY` bytes
+    pub(crate) fn run_command<D: AtRead + embedded_io::Write>(
+        device: &mut D,
+        command: impl Command,
+        delay: &mut impl DelayNs,
+        observer: Option<Observer>,
+    ) -> Result<Response, Error<D::Error>> {
+        exchange::<_, RESPONSE_CAPACITY>(
+            device,
+            command.command(),
+            delay,
+            observer,
+            AtTimings::DATASHEET.command_settle_ms,
+            RESPONSE_TIMEOUT_MS,
+        )
+    }
+
+    #[test]
+    fn mode_commands_are_correct() {
+        assert_eq!(Fu1::default().command().as_str(), "AT+FU1");
+        assert_eq!(Fu2::default().command().as_str(), "AT+FU2");
+        assert_eq!(Fu3::default().command().as_str(), "AT+FU3");
+        assert_eq!(Fu4::default().command().as_str(), "AT+FU4");
+    }
+
+    #[test]
+    fn serial_format_commands_are_correct() {
+        let cases = [
+            (Parity::None, StopBits::One, "AT+U8N1"),
+            (Parity::None, StopBits::OneAndHalf, "AT+U8N3"),
+            (Parity::None, StopBits::Two, "AT+U8N2"),
+            (Parity::Odd, StopBits::One, "AT+U8O1"),
+            (Parity::Odd, StopBits::OneAndHalf, "AT+U8O3"),
+            (Parity::Odd, StopBits::Two, "AT+U8O2"),
+            (Parity::Even, StopBits::One, "AT+U8E1"),
+            (Parity::Even, StopBits::OneAndHalf, "AT+U8E3"),
+            (Parity::Even, StopBits::Two, "AT+U8E2"),
+        ];
+        for (parity, stop_bits, expected) in cases {
+            let format = SerialFormat::new(DataBits::Eight, parity, stop_bits).unwrap();
+            assert_eq!(format.command().as_str(), expected);
+        }
+
+        let seven = SerialFormat::new(DataBits::Seven, Parity::Odd, StopBits::Two).unwrap();
+        assert_eq!(seven.command().as_str(), "AT+U7O2");
+        let nine = SerialFormat::new(DataBits::Nine, Parity::None, StopBits::One).unwrap();
+        assert_eq!(nine.command().as_str(), "AT+U9N1");
+    }
+
+    fn check_speed<T: ValidSpeed>() {
+        let mut expected: String<16> = String::new();
+        write!(&mut expected, "AT+B{}", T::bps()).unwrap();
+        assert_eq!(T::default().command(), expected);
+    }
+
+    #[test]
+    fn speed_commands_match_bps() {
+        check_speed::<B1200>();
+        check_speed::<B2400>();
+        check_speed::<B4800>();
+        check_speed::<B9600>();
+        check_speed::<B19200>();
+        check_speed::<B38400>();
+        check_speed::<B57600>();
+        check_speed::<B115200>();
+    }
+
+    #[test]
+    fn channel_command_format() {
+        let ch = Channel::new(5).unwrap();
+        // zero-padded three-digit decimal
+        assert_eq!(ch.command().as_str(), "AT+C005");
+    }
+
+    #[test]
+    fn channel_commands_match_formatting() {
+        for n in 1..=127 {
+            let mut expected: String<16> = String::new();
+            write!(&mut expected, "AT+C{:03}", n).unwrap();
+            assert_eq!(Channel::new(n).unwrap().command(), expected);
+        }
+    }
+
+    #[test]
+    fn power_variants_and_default() {
+        // Explicit variant
+        assert_eq!(Power::P3.command().as_str(), "AT+P3");
+        // Default is P8
+        assert_eq!(Power::default().command().as_str(), "AT+P8");
+    }
+
+    #[test]
+    fn power_commands_match_formatting() {
+        for power in [
+            Power::P1,
+            Power::P2,
+            Power::P3,
+            Power::P4,
+            Power::P5,
+            Power::P6,
+            Power::P7,
+            Power::P8,
+        ] {
+            let mut expected: String<16> = String::new();
+            write!(&mut expected, "AT+P{}", power as u8).unwrap();
+            assert_eq!(power.command(), expected);
+        }
+    }
 
     #[test]
     fn send_b9600() {
         let expected_command = "AT+B9600\r\n".as_bytes();
-        let mut writer = io::Sink::new().accept_data(expected_command.len());
-        let mut delay = hal::delay::NoopDelay::new();
-        send_command(&mut writer, B9600::default(), &mut delay).unwrap();
+        let mut writer = Sink::new().accept_data(expected_command.len());
+        let mut delay = CountingDelay::new();
+        send_command(
+            &mut writer,
+            B9600::default().command(),
+            &mut delay,
+            AtTimings::DATASHEET.command_settle_ms,
+        )
+        .unwrap();
         assert_eq!(expected_command, writer.into_inner_data());
     }
 
     #[test]
     fn recieve_b9600() {
         let response = "OK+B9600\r\n".as_bytes();
-        let mut reader = io::Source::new().data(response);
-        recieve_command(&mut reader).unwrap()
+        let mut reader = Source::new().data(response);
+        recieve_command::<_, RESPONSE_CAPACITY>(
+            &mut reader,
+            &mut CountingDelay::new(),
+            RESPONSE_TIMEOUT_MS,
+        )
+        .unwrap();
     }
 
     #[test]
     fn receive_non_ok_response() {
         let response = b"ERR+CMD\r\n";
-        let mut reader = io::Source::new().data(response);
-        let err = recieve_command(&mut reader).unwrap_err();
+        let mut reader = Source::new().data(response);
+        let err = recieve_command::<_, RESPONSE_CAPACITY>(
+            &mut reader,
+            &mut CountingDelay::new(),
+            RESPONSE_TIMEOUT_MS,
+        )
+        .unwrap_err();
         // We get a NoOK variant
         if let Error::NoOK(s) = err {
             assert!(s.as_str().starts_with("ERR+CMD"));
@@ -91,39 +647,196 @@ mod test {
         }
     }
 
+    #[test]
+    fn receive_skips_noise() {
+        let mut reader = Source::new().data(b"\x00\xffOK+B9600\r\n");
+        let line = recieve_command::<_, RESPONSE_CAPACITY>(
+            &mut reader,
+            &mut CountingDelay::new(),
+            RESPONSE_TIMEOUT_MS,
+        )
+        .unwrap();
+        assert_eq!(line.as_str(), "OK+B9600\r\n");
+    }
+
+    #[test]
+    fn receive_accumulates_a_trickling_line() {
+        let mut delay = CountingDelay::new();
+        let mut one_byte_at_a_time = Fragments::new(
+            &[b"O", b"K", b"+", b"B", b"9", b"6", b"0", b"0", b"\r", b"\n"],
+            2,
+        );
+        let line = recieve_command::<_, RESPONSE_CAPACITY>(
+            &mut one_byte_at_a_time,
+            &mut delay,
+            RESPONSE_TIMEOUT_MS,
+        );
+        assert_eq!(line.unwrap().as_str(), "OK+B9600\r\n");
+
+        // split between the O and the K, so neither half is OK on its own
+        let mut split = Fragments::new(&[b"O", b"K+C021\r\n"], 50);
+        let line =
+            recieve_command::<_, RESPONSE_CAPACITY>(&mut split, &mut delay, RESPONSE_TIMEOUT_MS);
+        assert_eq!(line.unwrap().as_str(), "OK+C021\r\n");
+    }
+
+    #[test]
+    fn receive_gives_up_on_an_unfinished_line() {
+        let mut delay = CountingDelay::new();
+        let mut unfinished = Fragments::new(&[b"OK+B96"], 1);
+        let line = recieve_command::<_, RESPONSE_CAPACITY>(&mut unfinished, &mut delay, 20);
+        assert_eq!(line, Err(Error::Unterminated("OK+B96".try_into().unwrap())));
+        // the timeout counts every wait, including the one before the fragment
+        assert_eq!(delay.elapsed_ms(), 20);
+    }
+
+    #[test]
+    fn tiny_buffer_reports_truncation() {
+        let mut reader = Source::new().data(b"OK+B9600\r\n");
+        assert!(matches!(
+            recieve_command::<_, 4>(&mut reader, &mut CountingDelay::new(), RESPONSE_TIMEOUT_MS),
+            Err(Error::Truncated)
+        ));
+
+        // a line that exactly fills the buffer is not truncated
+        let mut reader = Source::new().data(b"OK\r\n");
+        assert_eq!(
+            recieve_command::<_, 4>(&mut reader, &mut CountingDelay::new(), RESPONSE_TIMEOUT_MS)
+                .unwrap()
+                .as_str(),
+            "OK\r\n"
+        );
+    }
+
+    #[test]
+    fn wide_buffer_holds_long_lines() {
+        let mut dev = Duo {
+            sink: Sink::new().accept_data(4 + 2),
+            src: Source::new().data(b"OK+B9600,RF:FU3,P8\r\n"),
+        };
+        let mut delay = CountingDelay::new();
+        let line = exchange::<_, 32>(
+            &mut dev,
+            "AT+V".try_into().unwrap(),
+            &mut delay,
+            None,
+            AtTimings::DATASHEET.command_settle_ms,
+            RESPONSE_TIMEOUT_MS,
+        );
+        assert_eq!(line.unwrap().as_str(), "OK+B9600,RF:FU3,P8\r\n");
+
+        let mut dev = Duo {
+            sink: Sink::new().accept_data(4 + 2),
+            src: Source::new().data(b"OK+B9600,RF:FU3,P8\r\n"),
+        };
+        let line = exchange::<_, 8>(
+            &mut dev,
+            "AT+V".try_into().unwrap(),
+            &mut delay,
+            None,
+            AtTimings::DATASHEET.command_settle_ms,
+            RESPONSE_TIMEOUT_MS,
+        );
+        assert!(matches!(line, Err(Error::Truncated)));
+    }
+
+    #[test]
+    fn default_buffer_holds_30_byte_lines() {
+        let answer = "OK+RP:+20dBm OK+RC001 OK+FU3\r\n";
+        let banner = "www.hc01.com HC-12_V2.6 beta\r\n";
+        assert_eq!((answer.len(), banner.len()), (30, 30));
+
+        let mut dev = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(answer.as_bytes()),
+        };
+        let mut delay = CountingDelay::new();
+        let line = run_command(&mut dev, Query::All, &mut delay, None).unwrap();
+        assert_eq!(line.as_str(), answer);
+
+        // a line without OK is handed back whole as well
+        let mut dev = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(banner.as_bytes()),
+        };
+        let line = run_command(&mut dev, Version, &mut delay, None);
+        assert_eq!(line, Err(Error::NoOK(banner.try_into().unwrap())));
+    }
+
     #[test]
     fn run_command_happy_path() {
-        // Combine a Sink and Source into a single device...
-        struct Duo {
-            sink: io::Sink,
-            src: io::Source,
-        }
+        // Prepare a device that will accept a B9600 command and then return OK
+        let mut dev = Duo {
+            sink: Sink::new().accept_data(8 + 2), // "AT+B9600" + "\r\n"
+            src: Source::new().data(b"OK+B9600\r\n"),
+        };
+        let mut delay = CountingDelay::new();
+        // Should succeed without error
+        run_command(&mut dev, B9600::default(), &mut delay, None).unwrap();
+    }
 
-        impl ErrorType for Duo {
-            type Error = mock_embedded_io::MockError;
+    #[test]
+    fn run_commands_back_to_back() {
+        // Both responses are already buffered; each command must only consume its own line
+        let mut dev = Duo {
+            sink: Sink::new().accept_data(7 + 2 + 5 + 2),
+            src: Source::new().data(b"OK+C005\r\nERROR\r\n"),
+        };
+        let mut delay = CountingDelay::new();
+        run_command(&mut dev, Channel::new(5).unwrap(), &mut delay, None).unwrap();
+        let err = run_command(&mut dev, Power::P8, &mut delay, None).unwrap_err();
+        if let Error::NoOK(s) = err {
+            assert_eq!(s.as_str(), "ERROR\r\n");
+        } else {
+            panic!("Expected Error::NoOK, got {:?}", err);
         }
+    }
 
-        impl embedded_io::Write for Duo {
-            fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
-                self.sink.write(buf)
-            }
-            fn flush(&mut self) -> Result<(), Self::Error> {
-                Ok(())
+    #[cfg(feature = "log")]
+    #[test]
+    fn logs_at_exchange() {
+        extern crate std;
+        use std::{string::String, string::ToString, sync::Mutex, vec::Vec};
+
+        static MESSAGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        struct Capture;
+        impl log::Log for Capture {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
             }
-        }
-        impl embedded_io::Read for Duo {
-            fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
-                self.src.read(buf)
+            fn log(&self, record: &log::Record) {
+                MESSAGES.lock().unwrap().push(record.args().to_string());
             }
+            fn flush(&self) {}
         }
 
-        // Prepare a device that will accept a B9600 command and then return OK
-        let mut dev = Duo {
-            sink: io::Sink::new().accept_data(8 + 2), // "AT+B9600" + "\r\n"
-            src: io::Source::new().data(b"OK+B9600\r\n"),
-        };
-        let mut delay = hal::delay::NoopDelay::new();
-        // Should succeed without error
-        run_command(&mut dev, B9600::default(), &mut delay).unwrap();
+        log::set_logger(&Capture).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let mut writer = Sink::new().accept_data(10);
+        let mut delay = CountingDelay::new();
+        send_command(
+            &mut writer,
+            B9600::default().command(),
+            &mut delay,
+            AtTimings::DATASHEET.command_settle_ms,
+        )
+        .unwrap();
+        let mut reader = Source::new().data(b"OK+B9600\r\n");
+        recieve_command::<_, RESPONSE_CAPACITY>(
+            &mut reader,
+            &mut CountingDelay::new(),
+            RESPONSE_TIMEOUT_MS,
+        )
+        .unwrap();
+
+        assert_eq!(
+            *MESSAGES.lock().unwrap(),
+            [
+                "AT command sent: AT+B9600",
+                "AT response received: OK+B9600"
+            ]
+        );
     }
 }
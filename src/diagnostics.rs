@@ -0,0 +1,91 @@
+//! A one-call snapshot of driver and module state, for support reports
+
+use core::fmt;
+
+use crate::linktest::PerReport;
+use crate::modes::FuMode;
+use crate::paramaters::{Channel, Configuration, FullConfiguration, Power};
+#[cfg(feature = "transaction-log")]
+use crate::transactions::{Transaction, DEVICE_LOG_DEPTH};
+use crate::Response;
+
+/// A diagnostic snapshot, taken with `HC12::dump`. Each part is `None` if it could not be
+/// found out. The `Display` output is one field per line, ready to paste into a support
+/// ticket.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Diagnostics {
+    /// The settings held by the driver
+    pub configuration: Option<FullConfiguration>,
+    /// The settings the module reports with `AT+RX`, if it answered
+    pub reported: Option<DeviceStatus>,
+    /// The firmware version reported by the module, if it answered
+    pub firmware: Option<Response<32>>,
+    /// The result of an earlier [`measure_per`](crate::linktest::measure_per). The link
+    /// cannot be measured in AT mode, so `dump` leaves this for the application to fill in.
+    pub link: Option<PerReport>,
+    /// The most recent AT transactions, oldest first
+    #[cfg(feature = "transaction-log")]
+    pub transactions: heapless::Vec<Transaction, DEVICE_LOG_DEPTH>,
+}
+
+/// The settings the module reports it is running with, read with `HC12::query_all`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct DeviceStatus {
+    /// The serial speed, in bits per second
+    pub baudrate_bps: u32,
+    /// The channel
+    pub channel: Channel,
+    /// The power level. It may be above the build's
+    /// [`MAX_POWER`](crate::paramaters::MAX_POWER), if the module was set up elsewhere.
+    pub power: Power,
+    /// The mode
+    pub mode: FuMode,
+}
+
+impl From<DeviceStatus> for FullConfiguration {
+    fn from(status: DeviceStatus) -> Self {
+        FullConfiguration {
+            configuration: Configuration::new(status.channel, status.power),
+            mode: status.mode.number(),
+            baudrate_bps: status.baudrate_bps,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.configuration {
+            Some(configuration) => writeln!(f, "driver: {configuration}")?,
+            None => writeln!(f, "driver: unknown")?,
+        }
+        match self.reported {
+            Some(status) => writeln!(f, "module: {}", FullConfiguration::from(status))?,
+            None => writeln!(f, "module: unknown")?,
+        }
+        match &self.firmware {
+            Some(version) => writeln!(f, "firmware: {}", version.trim_end())?,
+            None => writeln!(f, "firmware: unknown")?,
+        }
+        match self.link {
+            Some(link) => writeln!(
+                f,
+                "link: {} of {} probes returned, {} corrupted",
+                link.received, link.sent, link.corrupted
+            )?,
+            None => writeln!(f, "link: not measured")?,
+        }
+
+        #[cfg(feature = "transaction-log")]
+        for transaction in &self.transactions {
+            write!(f, "{} -> ", transaction.command)?;
+            match &transaction.response {
+                Some(response) => write!(f, "{}", response.trim_end())?,
+                None => write!(f, "-")?,
+            }
+            writeln!(f, " ({:?})", transaction.status)?;
+        }
+        Ok(())
+    }
+}
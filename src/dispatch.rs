@@ -0,0 +1,207 @@
+//! Handing received frames to callbacks from a poll loop.
+//!
+//! [`Hc12Events`] owns a device and a [`FrameReader`]. Handlers are registered with
+//! [`on_frame`](Hc12Events::on_frame), [`on_error`](Hc12Events::on_error) and
+//! [`on_idle`](Hc12Events::on_idle), and [`poll`](Hc12Events::poll), called from the main
+//! loop, reads whatever has arrived and calls them in the order the frames completed.
+//! Handlers are stored by type, so any closure can be used without allocating.
+//!
+//! Handlers cannot send: the device is borrowed by [`poll`](Hc12Events::poll) while they
+//! run, so they have no way to reach it. To reply to a frame, a handler records what to
+//! send, and the main loop writes it through [`device_mut`](Hc12Events::device_mut) once
+//! the poll has returned.
+
+use embedded_io::{Read, ReadReady};
+
+use crate::framing::{Frame, FrameError, FrameReader, MAX_ENCODED};
+
+/// Dispatches received frames to handlers, see the [module documentation](self)
+pub struct Hc12Events<D, F, E, I, const N: usize = MAX_ENCODED> {
+    device: D,
+    reader: FrameReader<N>,
+    on_frame: F,
+    on_error: E,
+    on_idle: Option<I>,
+    last_rx_ms: Option<u32>,
+}
+
+impl<D> Hc12Events<D, fn(Frame<'_>), fn(FrameError), fn(u32)> {
+    /// Read frames from `device`, ignoring them until handlers are registered
+    pub fn new(device: D) -> Self {
+        Self {
+            device,
+            reader: FrameReader::new(),
+            on_frame: |_| {},
+            on_error: |_| {},
+            on_idle: None,
+            last_rx_ms: None,
+        }
+    }
+}
+
+impl<D, F, E, I, const N: usize> Hc12Events<D, F, E, I, N> {
+    /// Call `handler` with each frame received intact
+    pub fn on_frame<G>(self, handler: G) -> Hc12Events<D, G, E, I, N>
+    where
+        G: FnMut(Frame<'_>),
+    {
+        Hc12Events {
+            device: self.device,
+            reader: self.reader,
+            on_frame: handler,
+            on_error: self.on_error,
+            on_idle: self.on_idle,
+            last_rx_ms: self.last_rx_ms,
+        }
+    }
+
+    /// Call `handler` with the reason each corrupt or overlong frame was dropped
+    pub fn on_error<G>(self, handler: G) -> Hc12Events<D, F, G, I, N>
+    where
+        G: FnMut(FrameError),
+    {
+        Hc12Events {
+            device: self.device,
+            reader: self.reader,
+            on_frame: self.on_frame,
+            on_error: handler,
+            on_idle: self.on_idle,
+            last_rx_ms: self.last_rx_ms,
+        }
+    }
+
+    /// Call `handler` on each poll that reads nothing, with the milliseconds since a byte
+    /// last arrived, or since the first poll
+    pub fn on_idle<G>(self, handler: G) -> Hc12Events<D, F, E, G, N>
+    where
+        G: FnMut(u32),
+    {
+        Hc12Events {
+            device: self.device,
+            reader: self.reader,
+            on_frame: self.on_frame,
+            on_error: self.on_error,
+            on_idle: Some(handler),
+            last_rx_ms: self.last_rx_ms,
+        }
+    }
+
+    /// The device, for sending between polls
+    pub fn device_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    /// Return the device. A partly received frame is dropped.
+    pub fn inner(self) -> D {
+        self.device
+    }
+}
+
+impl<D, F, E, I, const N: usize> Hc12Events<D, F, E, I, N>
+where
+    D: Read + ReadReady,
+    F: FnMut(Frame<'_>),
+    E: FnMut(FrameError),
+    I: FnMut(u32),
+{
+    /// Read everything that has arrived and dispatch each completed frame. Returns the
+    /// number of frames handed to the frame handler.
+    pub fn poll(&mut self, now_ms: u32) -> Result<usize, D::Error> {
+        let since_ms = *self.last_rx_ms.get_or_insert(now_ms);
+        let mut frames = 0;
+        let mut received = false;
+        let mut buf = [0u8; 32];
+
+        while self.device.read_ready()? {
+            let count = self.device.read(&mut buf)?;
+            if count == 0 {
+                break;
+            }
+            received = true;
+            for byte in &buf[..count] {
+                match self.reader.push(*byte) {
+                    Some(Ok(frame)) => {
+                        (self.on_frame)(frame);
+                        frames += 1;
+                    }
+                    Some(Err(error)) => (self.on_error)(error),
+                    None => {}
+                }
+            }
+        }
+
+        if received {
+            self.last_rx_ms = Some(now_ms);
+        } else if let Some(on_idle) = &mut self.on_idle {
+            on_idle(now_ms.wrapping_sub(since_ms));
+        }
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::encode;
+    use crate::test_utils::Source;
+    use core::cell::RefCell;
+    use heapless::Vec;
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Event {
+        Frame(u8, Vec<u8, 8>),
+        Error(FrameError),
+        Idle(u32),
+    }
+
+    fn wire(frames: &[(u8, &[u8])]) -> Vec<u8, 64> {
+        let mut wire = Vec::new();
+        for (kind, payload) in frames {
+            let mut buf = [0; 16];
+            let len = encode(*kind, payload, &mut buf).unwrap();
+            wire.extend_from_slice(&buf[..len]).unwrap();
+        }
+        wire
+    }
+
+    #[test]
+    fn dispatches_back_to_back_frames_in_order() {
+        let log: RefCell<Vec<Event, 8>> = RefCell::new(Vec::new());
+        let mut bytes = wire(&[(0x80, b"one"), (0x81, b"two")]);
+        // corrupt a third frame's CRC
+        let mut corrupt = wire(&[(0x82, b"x")]);
+        corrupt[2] ^= 0x40;
+        bytes.extend_from_slice(&corrupt).unwrap();
+
+        let mut events = Hc12Events::new(Source::new().data(&bytes))
+            .on_frame(|frame| {
+                let payload = Vec::from_slice(frame.payload).unwrap();
+                log.borrow_mut()
+                    .push(Event::Frame(frame.kind, payload))
+                    .unwrap();
+            })
+            .on_error(|error| log.borrow_mut().push(Event::Error(error)).unwrap())
+            .on_idle(|idle_ms| log.borrow_mut().push(Event::Idle(idle_ms)).unwrap());
+
+        assert_eq!(events.poll(100), Ok(2));
+        assert_eq!(events.poll(350), Ok(0));
+        drop(events);
+        assert_eq!(
+            log.into_inner(),
+            [
+                Event::Frame(0x80, Vec::from_slice(b"one").unwrap()),
+                Event::Frame(0x81, Vec::from_slice(b"two").unwrap()),
+                Event::Error(FrameError::Checksum),
+                Event::Idle(250),
+            ]
+        );
+    }
+
+    #[test]
+    fn frames_are_ignored_without_handlers() {
+        let bytes = wire(&[(0x80, b"one")]);
+        let mut events: Hc12Events<_, _, _, _> = Hc12Events::new(Source::new().data(&bytes));
+        assert_eq!(events.poll(0), Ok(1));
+        assert_eq!(events.poll(10), Ok(0));
+    }
+}
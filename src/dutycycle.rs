@@ -0,0 +1,371 @@
+//! Listening in short windows, with the module asleep in between.
+//!
+//! A [`DutyCycle`] owns a transparent device and, every period, wakes the module, listens
+//! for a window, and puts it back to sleep with `AT+SLEEP`. Bytes received during the
+//! window are handed to a [`ReceiveCallback`] as they arrive, and a frame queued with
+//! [`send`](DutyCycle::send) goes out at the start of the next window.
+//!
+//! [`poll`](DutyCycle::poll) is called from the main loop with a [`Clock`]. Waking never
+//! blocks: the programming pin is pulsed over several polls, while the cycle is
+//! [`WakingUp`](DutyState::WakingUp). Going to sleep blocks for the AT exchange.
+//!
+//! The time the module spends awake, from the start of each wake up to the end of the
+//! `AT+SLEEP` exchange, is added up for power budgeting, see
+//! [`awake_ms`](DutyCycle::awake_ms).
+
+use core::fmt::Debug;
+
+use embedded_hal::{delay::DelayNs, digital::OutputPin};
+use embedded_io::{Read, ReadReady, Write, WriteReady};
+
+use crate::commands::Sleep;
+use crate::modes::ValidMode;
+use crate::speeds::ValidSpeed;
+use crate::time::{Clock, IntoMillis};
+use crate::{RoundTripError, TransparentHC12};
+
+/// Called with the bytes received during a listening window, as they arrive
+pub type ReceiveCallback = fn(&[u8]);
+
+/// Where a [`DutyCycle`] is in its period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum DutyState {
+    /// The module is asleep until the next period
+    Sleeping,
+    /// The module is being woken, which takes the pin and mode change settle times of the
+    /// [`AtTimings`](crate::AtTimings)
+    WakingUp,
+    /// The module is awake and received bytes are being delivered
+    Listening,
+    /// A queued frame is being sent, as part of the listening window
+    Transmitting,
+}
+
+/// A duty cycle could not send, receive, sleep or wake
+pub type DutyCycleError<D, P> = RoundTripError<D, P>;
+
+/// Wakes the module to listen for a window every period, see the
+/// [module documentation](self)
+pub struct DutyCycle<D, const N: usize> {
+    device: D,
+    period_ms: u32,
+    listen_ms: u32,
+    on_receive: Option<ReceiveCallback>,
+    state: DutyState,
+    /// When the state was entered, or `None` before the first poll
+    since_ms: Option<u32>,
+    /// Whether the programming pin has been released while waking up
+    released: bool,
+    cycle_ms: u32,
+    window_ms: u32,
+    awake_since_ms: u32,
+    awake_total_ms: u64,
+    frame: [u8; N],
+    len: usize,
+    written: usize,
+    on_air_until_ms: Option<u32>,
+}
+
+impl<Device, Pin, Mode, Speed, const N: usize>
+    DutyCycle<TransparentHC12<Device, Pin, Mode, Speed>, N>
+{
+    /// Wake every `period` and listen for `listen`. The module must be awake; the first
+    /// window starts on the first poll.
+    pub fn new(
+        hc12: TransparentHC12<Device, Pin, Mode, Speed>,
+        period: impl IntoMillis,
+        listen: impl IntoMillis,
+    ) -> Self {
+        Self {
+            device: hc12,
+            period_ms: period.into_ms(),
+            listen_ms: listen.into_ms(),
+            on_receive: None,
+            state: DutyState::Listening,
+            since_ms: None,
+            released: false,
+            cycle_ms: 0,
+            window_ms: 0,
+            awake_since_ms: 0,
+            awake_total_ms: 0,
+            frame: [0; N],
+            len: 0,
+            written: 0,
+            on_air_until_ms: None,
+        }
+    }
+}
+
+impl<D, const N: usize> DutyCycle<D, N> {
+    /// Call `callback` with the bytes received during each window
+    pub fn on_receive(mut self, callback: ReceiveCallback) -> Self {
+        self.on_receive = Some(callback);
+        self
+    }
+
+    /// Queue `frame` for the start of the next window, or the current one if the module
+    /// is listening. Returns `false` if a frame is already queued or it does not fit.
+    pub fn send(&mut self, frame: &[u8]) -> bool {
+        if self.len != 0 || frame.len() > N {
+            return false;
+        }
+        self.frame[..frame.len()].copy_from_slice(frame);
+        self.len = frame.len();
+        self.written = 0;
+        true
+    }
+
+    /// The current state
+    pub fn state(&self) -> DutyState {
+        self.state
+    }
+
+    /// The milliseconds the module has been awake up to `now_ms`, since the first poll
+    pub fn awake_ms(&self, now_ms: u32) -> u64 {
+        match (self.state, self.since_ms) {
+            (DutyState::Sleeping, _) | (_, None) => self.awake_total_ms,
+            _ => self.awake_total_ms + now_ms.wrapping_sub(self.awake_since_ms) as u64,
+        }
+    }
+
+    /// Return the device. A queued frame is dropped.
+    pub fn inner(self) -> D {
+        self.device
+    }
+
+    fn enter(&mut self, state: DutyState, now_ms: u32) {
+        self.state = state;
+        self.since_ms = Some(now_ms);
+    }
+
+    /// Listen, or send first if a frame is queued
+    fn awake_state(&self) -> DutyState {
+        if self.len == 0 {
+            DutyState::Listening
+        } else {
+            DutyState::Transmitting
+        }
+    }
+}
+
+impl<Device, Pin, Mode, Speed, const N: usize>
+    DutyCycle<TransparentHC12<Device, Pin, Mode, Speed>, N>
+where
+    Device: Read + ReadReady + Write + WriteReady,
+    Pin: OutputPin,
+    Mode: ValidMode,
+    Speed: ValidSpeed,
+{
+    /// Move the cycle on as far as the time allows, and return the state it is left in
+    pub fn poll(
+        &mut self,
+        clock: &impl Clock,
+        delay: &mut impl DelayNs,
+    ) -> Result<DutyState, DutyCycleError<Device::Error, Pin::Error>> {
+        let now_ms = clock.now_ms();
+        let Some(since_ms) = self.since_ms else {
+            self.cycle_ms = now_ms;
+            self.window_ms = now_ms;
+            self.awake_since_ms = now_ms;
+            self.enter(self.awake_state(), now_ms);
+            return Ok(self.state);
+        };
+        let elapsed_ms = now_ms.wrapping_sub(since_ms);
+
+        match self.state {
+            DutyState::Sleeping => {
+                let mut next_ms = self.cycle_ms.wrapping_add(self.period_ms);
+                if !is_due(now_ms, next_ms) {
+                    return Ok(self.state);
+                }
+                if is_due(now_ms, next_ms.wrapping_add(self.period_ms)) {
+                    // the caller fell behind by a whole period, so skip the missed windows
+                    next_ms = now_ms;
+                }
+                self.cycle_ms = next_ms;
+                self.device.begin_wake().map_err(DutyCycleError::Pin)?;
+                self.released = false;
+                self.awake_since_ms = now_ms;
+                self.enter(DutyState::WakingUp, now_ms);
+            }
+            DutyState::WakingUp => {
+                if !self.released && elapsed_ms >= self.device.session.timings.pin_settle_ms {
+                    self.device.end_wake().map_err(DutyCycleError::Pin)?;
+                    self.released = true;
+                }
+                if self.released && elapsed_ms >= self.device.wake_ms() {
+                    self.window_ms = now_ms;
+                    self.enter(self.awake_state(), now_ms);
+                }
+            }
+            DutyState::Transmitting => self.transmit(now_ms)?,
+            DutyState::Listening => {
+                self.deliver()?;
+                if self.len != 0 {
+                    self.enter(DutyState::Transmitting, now_ms);
+                    self.transmit(now_ms)?;
+                } else if now_ms.wrapping_sub(self.window_ms) >= self.listen_ms {
+                    self.device
+                        .round_trip(Sleep, delay)
+                        .map_err(DutyCycleError::Pin)?
+                        .map_err(DutyCycleError::At)?;
+                    let asleep_ms = clock.now_ms();
+                    self.awake_total_ms += asleep_ms.wrapping_sub(self.awake_since_ms) as u64;
+                    self.enter(DutyState::Sleeping, asleep_ms);
+                }
+            }
+        }
+        Ok(self.state)
+    }
+
+    /// Write as much of the queued frame as the device accepts, then wait out its time
+    /// on air before listening again
+    fn transmit(&mut self, now_ms: u32) -> Result<(), DutyCycleError<Device::Error, Pin::Error>> {
+        while self.written < self.len {
+            if !self.device.write_ready().map_err(DutyCycleError::Link)? {
+                return Ok(());
+            }
+            let written = self
+                .device
+                .write(&self.frame[self.written..self.len])
+                .map_err(DutyCycleError::Link)?;
+            if written == 0 {
+                return Ok(());
+            }
+            self.written += written;
+        }
+
+        let until_ms = match self.on_air_until_ms {
+            Some(until_ms) => until_ms,
+            None => {
+                self.device.flush().map_err(DutyCycleError::Link)?;
+                let on_air_ms = self.device.time_on_air_us(self.len).div_ceil(1000);
+                let until_ms = now_ms.wrapping_add(on_air_ms);
+                self.on_air_until_ms = Some(until_ms);
+                until_ms
+            }
+        };
+        if is_due(now_ms, until_ms) {
+            self.len = 0;
+            self.written = 0;
+            self.on_air_until_ms = None;
+            self.enter(DutyState::Listening, now_ms);
+        }
+        Ok(())
+    }
+
+    /// Hand everything received so far to the callback
+    fn deliver(&mut self) -> Result<(), DutyCycleError<Device::Error, Pin::Error>> {
+        let mut buf = [0u8; 32];
+        while self.device.read_ready().map_err(DutyCycleError::Link)? {
+            let count = self.device.read(&mut buf).map_err(DutyCycleError::Link)?;
+            if count == 0 {
+                break;
+            }
+            if let Some(callback) = self.on_receive {
+                callback(&buf[..count]);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `at_ms` has been reached at `now_ms`
+fn is_due(now_ms: u32, at_ms: u32) -> bool {
+    (now_ms.wrapping_sub(at_ms) as i32) >= 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockHc12, MockSerial, MockSetPin};
+    use crate::modes::Fu3;
+    use crate::speeds::B9600;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use heapless::Vec;
+
+    type Cycle<'a> = DutyCycle<TransparentHC12<MockSerial<'a>, MockSetPin<'a>, Fu3, B9600>, 8>;
+
+    fn duty_cycle(module: &MockHc12) -> Cycle<'_> {
+        let hc12 = TransparentHC12::assume_factory(module.serial(), module.set_pin());
+        DutyCycle::new(hc12, 1000, 200)
+    }
+
+    /// Poll every 10ms up to `until_ms`, recording each state change and when it happened
+    fn run(
+        module: &MockHc12,
+        cycle: &mut Cycle,
+        until_ms: u32,
+        log: &mut Vec<(u32, DutyState), 32>,
+    ) {
+        let mut delay = module.delay();
+        while module.now_ms() < until_ms {
+            let state = cycle.poll(&|| module.now_ms(), &mut delay).unwrap();
+            if log.last().is_none_or(|(_, last)| *last != state) {
+                log.push((module.now_ms(), state)).unwrap();
+            }
+            assert_eq!(module.is_asleep(), state == DutyState::Sleeping);
+            module.advance_ms(10);
+        }
+    }
+
+    #[test]
+    fn wakes_listens_and_sleeps_every_period() {
+        let module = MockHc12::new();
+        let mut cycle = duty_cycle(&module);
+        let mut log = Vec::new();
+        run(&module, &mut cycle, 3000, &mut log);
+
+        // AT+SLEEP takes 160ms after each 200ms window
+        assert_eq!(
+            log,
+            [
+                (0, DutyState::Listening),
+                (360, DutyState::Sleeping),
+                (1000, DutyState::WakingUp),
+                (1120, DutyState::Listening),
+                (1480, DutyState::Sleeping),
+                (2000, DutyState::WakingUp),
+                (2120, DutyState::Listening),
+                (2480, DutyState::Sleeping),
+            ]
+        );
+        assert_eq!(cycle.awake_ms(module.now_ms()), 360 + 480 + 480);
+        assert!(!module.in_at_mode());
+    }
+
+    static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+    fn count(bytes: &[u8]) {
+        RECEIVED.fetch_add(bytes.len(), Ordering::Relaxed);
+    }
+
+    #[test]
+    fn sends_queued_frames_and_delivers_only_while_awake() {
+        let module = MockHc12::new();
+        let mut cycle = duty_cycle(&module).on_receive(count);
+        let mut log = Vec::new();
+        run(&module, &mut cycle, 500, &mut log);
+
+        // dropped by the sleeping module
+        module.receive(b"lost");
+        assert!(cycle.send(b"ping"));
+        assert!(!cycle.send(b"pong"));
+        run(&module, &mut cycle, 1200, &mut log);
+        assert_eq!(module.transmitted().as_slice(), b"ping");
+        // sent at the start of the window, then on the air for about 20ms
+        assert_eq!(
+            &log[2..],
+            [
+                (1000, DutyState::WakingUp),
+                (1120, DutyState::Transmitting),
+                (1140, DutyState::Listening),
+            ]
+        );
+
+        module.receive(b"hello");
+        run(&module, &mut cycle, 1300, &mut log);
+        assert_eq!(RECEIVED.load(Ordering::Relaxed), 5);
+    }
+}
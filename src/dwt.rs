@@ -0,0 +1,181 @@
+//! A [`DelayNs`] provider for bare Cortex-M projects with no timer to spare.
+//!
+//! [`CycleDelay`] busy-waits on the DWT cycle counter, `CYCCNT`, given the core clock
+//! frequency. It handles the counter wrapping around, and splits waits too long for one
+//! pass of the counter. Cores without a cycle counter, or with the trace unit switched
+//! off, fall back to a calibrated busy loop, which waits at least as long.
+//!
+//! The DWT registers are not available on Cortex-M0 and M0+ (`thumbv6m` targets), so
+//! this module does not build for them.
+//!
+//! # Example
+//! ```ignore
+//! let mut core = cortex_m::Peripherals::take().unwrap();
+//! let mut delay = CycleDelay::enable(&mut core.DCB, &mut core.DWT, 64_000_000);
+//! let hc12 = HC12::factor_settings(uart, set_pin, &mut delay).unwrap();
+//! ```
+
+use cortex_m::peripheral::{DCB, DWT};
+use embedded_hal::delay::DelayNs;
+
+/// The longest single wait on the counter, well inside one pass so a wrap is never
+/// mistaken for a short wait
+const MAX_PASS_CYCLES: u64 = 1 << 30;
+
+/// A free-running 32 bit cycle counter, which may wrap around
+pub trait CycleCounter {
+    /// The current count
+    fn cycles(&mut self) -> u32;
+}
+
+/// The DWT cycle counter, `CYCCNT`
+#[derive(Debug, Clone, Copy)]
+pub struct Dwt;
+
+impl CycleCounter for Dwt {
+    fn cycles(&mut self) -> u32 {
+        DWT::cycle_count()
+    }
+}
+
+/// Busy-wait delays counted in core clock cycles, see the
+/// [module documentation](crate::dwt)
+#[derive(Debug, Clone, Copy)]
+pub struct CycleDelay<C = Dwt> {
+    /// `None` when there is no running counter, and the busy loop is used
+    counter: Option<C>,
+    core_hz: u32,
+}
+
+impl CycleDelay<Dwt> {
+    /// Enable tracing and the cycle counter, if the core has one, and delay with it
+    pub fn enable(dcb: &mut DCB, dwt: &mut DWT, core_hz: u32) -> Self {
+        if DWT::has_cycle_counter() {
+            dcb.enable_trace();
+            DWT::unlock();
+            dwt.enable_cycle_counter();
+        }
+        Self::new(core_hz)
+    }
+
+    /// Delay with the cycle counter, which must already be enabled. If it is not, the
+    /// busy loop is used instead.
+    pub fn new(core_hz: u32) -> Self {
+        Self {
+            counter: DWT::cycle_counter_enabled().then_some(Dwt),
+            core_hz,
+        }
+    }
+}
+
+impl<C: CycleCounter> CycleDelay<C> {
+    /// Delay with any running cycle counter clocked at `core_hz`
+    pub fn with_counter(counter: C, core_hz: u32) -> Self {
+        Self {
+            counter: Some(counter),
+            core_hz,
+        }
+    }
+
+    /// The cycles in `count` units of `1 / per_second` seconds, rounded up
+    fn cycles(&self, count: u32, per_second: u64) -> u64 {
+        (u64::from(count) * u64::from(self.core_hz)).div_ceil(per_second)
+    }
+
+    fn wait(&mut self, mut cycles: u64) {
+        while cycles > 0 {
+            let pass = cycles.min(MAX_PASS_CYCLES) as u32;
+            match &mut self.counter {
+                Some(counter) => {
+                    let start = counter.cycles();
+                    while counter.cycles().wrapping_sub(start) < pass {}
+                }
+                None => cortex_m::asm::delay(pass),
+            }
+            cycles -= u64::from(pass);
+        }
+    }
+}
+
+impl<C: CycleCounter> DelayNs for CycleDelay<C> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.wait(self.cycles(ns, 1_000_000_000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.wait(self.cycles(us, 1_000_000));
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.wait(self.cycles(ms, 1_000));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// A counter that advances `step` cycles each time it is read, tracking the total
+    struct FakeCounter<'a> {
+        now: u32,
+        step: u32,
+        total: &'a Cell<u64>,
+    }
+
+    impl CycleCounter for FakeCounter<'_> {
+        fn cycles(&mut self) -> u32 {
+            self.now = self.now.wrapping_add(self.step);
+            self.total.set(self.total.get() + u64::from(self.step));
+            self.now
+        }
+    }
+
+    fn delay(start: u32, step: u32, total: &Cell<u64>) -> CycleDelay<FakeCounter<'_>> {
+        let counter = FakeCounter {
+            now: start,
+            step,
+            total,
+        };
+        CycleDelay::with_counter(counter, 64_000_000)
+    }
+
+    #[test]
+    fn waits_the_cycles_asked_for() {
+        let total = Cell::new(0);
+        let mut delay = delay(0, 100, &total);
+
+        delay.delay_us(1000);
+        // the first read sets the start, and the wait ends on the first read past it
+        assert_eq!(total.get(), 64_000 + 100);
+
+        total.set(0);
+        delay.delay_ns(1);
+        assert_eq!(total.get(), 200, "rounded up to one cycle");
+
+        total.set(0);
+        delay.delay_ms(80);
+        assert_eq!(total.get(), 5_120_000 + 100);
+    }
+
+    #[test]
+    fn survives_the_counter_wrapping() {
+        let total = Cell::new(0);
+        let mut delay = delay(u32::MAX - 1000, 7, &total);
+
+        delay.delay_us(10);
+        assert!((640..640 + 14).contains(&total.get()));
+    }
+
+    #[test]
+    fn long_waits_take_several_passes() {
+        let total = Cell::new(0);
+        let mut delay = delay(0, 1 << 20, &total);
+
+        // 100s at 64MHz is longer than the counter's range
+        delay.delay_ms(100_000);
+        let cycles = 6_400_000_000;
+        assert!(total.get() >= cycles);
+        assert!(total.get() < cycles + 8 * (1 << 20));
+    }
+}
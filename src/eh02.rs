@@ -0,0 +1,315 @@
+//! Adapters for HALs still on embedded-hal 0.2.
+//!
+//! [`Eh02Pin`], [`Eh02Serial`] and [`Eh02Delay`] wrap a 0.2 `OutputPin`, `nb` serial port
+//! and `DelayUs` so they can be handed to [`HC12`](crate::HC12) and
+//! [`TransparentHC12`](crate::TransparentHC12) unchanged. The 0.2 error types carry no
+//! trait, so they are wrapped in an [`Eh02Error`], which reports
+//! [`ErrorKind::Other`](embedded_io::ErrorKind::Other) to generic code and keeps the
+//! original error for the application.
+//!
+//! # Example
+//! ```ignore
+//! let serial = Eh02Serial::new(uart);
+//! let pin = Eh02Pin::new(set_pin);
+//! let mut delay = Eh02Delay::new(timer);
+//! let hc12 = HC12::factor_settings(serial, pin, &mut delay).unwrap();
+//! ```
+
+use core::fmt::Debug;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, OutputPin};
+use embedded_hal_02::blocking::delay::DelayUs;
+use embedded_hal_02::digital::v2::OutputPin as OutputPin02;
+use embedded_hal_02::serial;
+use embedded_io::{ErrorType, Read, ReadReady, Write};
+
+/// An error from an embedded-hal 0.2 peripheral
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Eh02Error<E>(pub E);
+
+impl<E: Debug> digital::Error for Eh02Error<E> {
+    fn kind(&self) -> digital::ErrorKind {
+        digital::ErrorKind::Other
+    }
+}
+
+impl<E: Debug> embedded_io::Error for Eh02Error<E> {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        embedded_io::ErrorKind::Other
+    }
+}
+
+/// An embedded-hal 0.2 output pin as a 1.0 [`OutputPin`]
+#[derive(Debug)]
+pub struct Eh02Pin<P>(P);
+
+impl<P> Eh02Pin<P> {
+    /// Wrap a 0.2 output pin
+    pub fn new(pin: P) -> Self {
+        Self(pin)
+    }
+
+    /// Return the pin
+    pub fn into_inner(self) -> P {
+        self.0
+    }
+}
+
+impl<P: OutputPin02> digital::ErrorType for Eh02Pin<P>
+where
+    P::Error: Debug,
+{
+    type Error = Eh02Error<P::Error>;
+}
+
+impl<P: OutputPin02> OutputPin for Eh02Pin<P>
+where
+    P::Error: Debug,
+{
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low().map_err(Eh02Error)
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high().map_err(Eh02Error)
+    }
+}
+
+/// An embedded-hal 0.2 `nb` serial port as an embedded-io device.
+///
+/// Reads take whatever bytes are waiting and return `Ok(0)` if there are none, like the
+/// devices this crate polls, so response timeouts still apply. Readiness is checked by
+/// reading a byte ahead, which is handed out first by the next read. Bytes read before an
+/// error are lost with it. Writes block for the first byte and send as many more as the
+/// port takes without blocking.
+#[derive(Debug)]
+pub struct Eh02Serial<S> {
+    inner: S,
+    peeked: Option<u8>,
+}
+
+impl<S> Eh02Serial<S> {
+    /// Wrap a 0.2 serial port
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            peeked: None,
+        }
+    }
+
+    /// Return the serial port. A byte consumed by a readiness check and not yet read is
+    /// returned alongside it.
+    pub fn into_inner(self) -> (S, Option<u8>) {
+        (self.inner, self.peeked)
+    }
+}
+
+impl<S, E: Debug> ErrorType for Eh02Serial<S>
+where
+    S: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+{
+    type Error = Eh02Error<E>;
+}
+
+impl<S, E: Debug> Read for Eh02Serial<S>
+where
+    S: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut read = 0;
+        while read < buf.len() {
+            let byte = match self.peeked.take() {
+                Some(byte) => byte,
+                None => match self.inner.read() {
+                    Ok(byte) => byte,
+                    Err(nb::Error::WouldBlock) => break,
+                    Err(nb::Error::Other(error)) => return Err(Eh02Error(error)),
+                },
+            };
+            buf[read] = byte;
+            read += 1;
+        }
+        Ok(read)
+    }
+}
+
+impl<S, E: Debug> ReadReady for Eh02Serial<S>
+where
+    S: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+{
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        if self.peeked.is_none() {
+            match self.inner.read() {
+                Ok(byte) => self.peeked = Some(byte),
+                Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(error)) => return Err(Eh02Error(error)),
+            }
+        }
+        Ok(self.peeked.is_some())
+    }
+}
+
+impl<S, E: Debug> Write for Eh02Serial<S>
+where
+    S: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let Some((&first, rest)) = buf.split_first() else {
+            return Ok(0);
+        };
+        nb::block!(self.inner.write(first)).map_err(Eh02Error)?;
+
+        let mut written = 1;
+        for &byte in rest {
+            match self.inner.write(byte) {
+                Ok(()) => written += 1,
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(error)) => return Err(Eh02Error(error)),
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        nb::block!(self.inner.flush()).map_err(Eh02Error)
+    }
+}
+
+/// An embedded-hal 0.2 microsecond delay as a 1.0 [`DelayNs`]. Nanoseconds are rounded
+/// up to whole microseconds.
+#[derive(Debug)]
+pub struct Eh02Delay<D>(D);
+
+impl<D> Eh02Delay<D> {
+    /// Wrap a 0.2 delay provider
+    pub fn new(delay: D) -> Self {
+        Self(delay)
+    }
+
+    /// Return the delay provider
+    pub fn into_inner(self) -> D {
+        self.0
+    }
+}
+
+impl<D: DelayUs<u32>> DelayNs for Eh02Delay<D> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.delay_us(ns.div_ceil(1000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.0.delay_us(us);
+    }
+
+    fn delay_ms(&mut self, mut ms: u32) {
+        // in chunks, so the microseconds cannot overflow
+        while ms > 0 {
+            let chunk = ms.min(1000);
+            self.0.delay_us(chunk * 1000);
+            ms -= chunk;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockHc12;
+    use core::convert::Infallible;
+
+    /// A 0.2 peripheral built from a 1.0 one, as an old HAL would provide
+    struct Legacy<T>(T);
+
+    impl<T: Read + Write> serial::Read<u8> for Legacy<T> {
+        type Error = T::Error;
+
+        fn read(&mut self) -> nb::Result<u8, Self::Error> {
+            let mut byte = [0];
+            match self.0.read(&mut byte)? {
+                0 => Err(nb::Error::WouldBlock),
+                _ => Ok(byte[0]),
+            }
+        }
+    }
+
+    impl<T: Read + Write> serial::Write<u8> for Legacy<T> {
+        type Error = T::Error;
+
+        fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+            self.0.write_all(&[word])?;
+            Ok(())
+        }
+
+        fn flush(&mut self) -> nb::Result<(), Self::Error> {
+            Ok(self.0.flush()?)
+        }
+    }
+
+    impl<T: OutputPin<Error = Infallible>> OutputPin02 for Legacy<T> {
+        type Error = Infallible;
+
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.0.set_low()
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.0.set_high()
+        }
+    }
+
+    impl<T: DelayNs> DelayUs<u32> for Legacy<T> {
+        fn delay_us(&mut self, us: u32) {
+            self.0.delay_us(us);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "programming")]
+    fn programs_through_the_shims() {
+        let module = MockHc12::new();
+        let serial = Eh02Serial::new(Legacy(module.serial()));
+        let pin = Eh02Pin::new(Legacy(module.set_pin()));
+        let mut delay = Eh02Delay::new(Legacy(module.delay()));
+
+        let mut hc12 = crate::HC12::factor_settings(serial, pin, &mut delay)
+            .unwrap()
+            .channel(crate::paramaters::Channel::new(21).unwrap())
+            .power(crate::paramaters::Power::MAX)
+            .program(&mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+        assert_eq!(module.settings().channel, 21);
+        assert!(!module.in_at_mode());
+
+        hc12.write_all(b"hello").unwrap();
+        assert_eq!(module.transmitted(), b"hello");
+    }
+
+    #[test]
+    fn reads_do_not_block() {
+        let module = MockHc12::new();
+        let mut serial = Eh02Serial::new(Legacy(module.serial()));
+        let mut buf = [0; 8];
+
+        assert!(!serial.read_ready().unwrap());
+        assert_eq!(serial.read(&mut buf), Ok(0));
+
+        module.receive(b"abc");
+        assert!(serial.read_ready().unwrap());
+        assert_eq!(serial.read(&mut buf), Ok(3));
+        assert_eq!(&buf[..3], b"abc");
+    }
+
+    #[test]
+    fn long_delays_are_chunked() {
+        let module = MockHc12::new();
+        let mut delay = Eh02Delay::new(Legacy(module.delay()));
+        let start_ms = module.now_ms();
+        delay.delay_ms(5_000_000);
+        delay.delay_ns(1);
+        assert_eq!(module.now_ms() - start_ms, 5_000_000);
+    }
+}
@@ -1,31 +1,125 @@
+use core::convert::Infallible;
 use core::fmt::Debug;
 
 use heapless::String;
 
-use crate::paramaters::BadChannel;
+use crate::capabilities::Capability;
+use crate::paramaters::{BadChannel, ChannelNotAllowed, Power};
+use crate::validation::ConfigWarning;
 
-/// An error in creating a device, for some internal or an underlying issue
-#[derive(Debug)]
+/// The default capacity, in bytes, of a buffered AT response line, enough for the longest
+/// lines the module sends, such as the version banner
+pub const RESPONSE_CAPACITY: usize = 48;
+
+/// The default time, in milliseconds, to wait for an AT response to start or continue
+/// once the device has nothing to read. See `HC12::response_timeout_ms`.
+pub const RESPONSE_TIMEOUT_MS: u32 = 100;
+
+/// An AT response line, holding up to `N` bytes
+pub type Response<const N: usize = RESPONSE_CAPACITY> = String<N>;
+
+/// An error in creating a device, for some internal or an underlying issue. `P` is the
+/// error of the programming pin, for operations that switch it, and `N` is the capacity
+/// of the response buffer.
+#[derive(Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
-pub enum Error<D: Debug> {
+pub enum Error<D: Debug, P = Infallible, const N: usize = RESPONSE_CAPACITY> {
     /// Underlying device error
     DeviceError(D),
+    /// The programming pin could not be switched
+    PinError(P),
     /// An invalid channel was selected
     BadChannel(u8),
-    /// No response was recieved
+    /// The channel is not in the device's allowed channels
+    ChannelNotAllowed(u8),
+    /// The power is above the build's [`MAX_POWER`](crate::paramaters::MAX_POWER)
+    ExceedsBuildCap(Power),
+    /// Strict programming found a problem with the configuration, see
+    /// [`validation`](crate::validation)
+    Validation(ConfigWarning),
+    /// No response was recieved before the response timeout
     NoResponse,
     /// A non-ok response was recieved
-    NoOK(String<16>),
+    NoOK(Response<N>),
+    /// The response did not fit in the response buffer. The rest of the line is left
+    /// unread.
+    Truncated,
+    /// The response stopped before the end of its line, and nothing more arrived before
+    /// the response timeout. Holds what did arrive.
+    Unterminated(Response<N>),
+    /// A raw command did not fit in the 16 bytes of a command. Nothing was sent.
+    CommandTooLong,
+    /// An `OK` response to a query or setting did not hold a value that could be understood
+    InvalidResponse(Response<N>),
+    /// The module echoed another value than the one sent, so it did not apply it. Values
+    /// are as sent: bits per second, the channel, the power level or the mode number.
+    Mismatch {
+        /// The value sent
+        expected: u32,
+        /// The value echoed
+        got: u32,
+    },
+    /// The module reported a serial speed, in bits per second, that is not one of the
+    /// eight it supports
+    UnknownBaudrate(u32),
+    /// The module reported a transmit power, in dBm, that is not one of its levels
+    UnknownPower(i8),
+    /// The module's firmware does not support the command, see
+    /// [`capabilities`](crate::capabilities). Nothing was sent.
+    UnsupportedByFirmware(Capability),
+}
+
+impl<D: Debug, const N: usize> Error<D, Infallible, N> {
+    /// The same error, from an operation that also switches the programming pin
+    pub(crate) fn with_pin<P>(self) -> Error<D, P, N> {
+        match self {
+            Self::DeviceError(error) => Error::DeviceError(error),
+            Self::PinError(never) => match never {},
+            Self::BadChannel(channel) => Error::BadChannel(channel),
+            Self::ChannelNotAllowed(channel) => Error::ChannelNotAllowed(channel),
+            Self::ExceedsBuildCap(power) => Error::ExceedsBuildCap(power),
+            Self::Validation(warning) => Error::Validation(warning),
+            Self::NoResponse => Error::NoResponse,
+            Self::NoOK(line) => Error::NoOK(line),
+            Self::Truncated => Error::Truncated,
+            Self::Unterminated(line) => Error::Unterminated(line),
+            Self::CommandTooLong => Error::CommandTooLong,
+            Self::InvalidResponse(line) => Error::InvalidResponse(line),
+            Self::Mismatch { expected, got } => Error::Mismatch { expected, got },
+            Self::UnknownBaudrate(bps) => Error::UnknownBaudrate(bps),
+            Self::UnknownPower(dbm) => Error::UnknownPower(dbm),
+            Self::UnsupportedByFirmware(capability) => Error::UnsupportedByFirmware(capability),
+        }
+    }
 }
 
-impl<D: embedded_io::Error> From<D> for Error<D> {
+impl<D: embedded_io::Error, P, const N: usize> From<D> for Error<D, P, N> {
     fn from(value: D) -> Self {
         Error::DeviceError(value)
     }
 }
 
-impl<D: core::fmt::Debug> From<BadChannel> for Error<D> {
+impl<D: Debug, P, const N: usize> From<ChannelNotAllowed> for Error<D, P, N> {
+    fn from(value: ChannelNotAllowed) -> Self {
+        Self::ChannelNotAllowed(value.0.into())
+    }
+}
+
+impl<D: Debug, P, const N: usize> From<BadChannel> for Error<D, P, N> {
     fn from(value: BadChannel) -> Self {
         Self::BadChannel(value.into())
     }
 }
+
+/// A transparent device could not complete an operation that briefly returns the module to
+/// AT mode, such as changing the power or going to sleep
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum RoundTripError<D: Debug, P> {
+    /// A command was refused, or not accepted by the module
+    At(Error<D>),
+    /// The programming pin could not be switched
+    Pin(P),
+    /// The serial device failed in transparent mode
+    Link(D),
+}
@@ -0,0 +1,40 @@
+use heapless::String;
+
+use crate::Response;
+
+/// Something that happened while talking to, or switching the mode of, the module.
+/// Delivered synchronously to the observer installed with `HC12::observer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum AtEvent {
+    /// An AT command was written to the module
+    CommandSent(String<16>),
+    /// A response line was read from the module, whether or not it was OK. Lines longer
+    /// than [`RESPONSE_CAPACITY`](crate::RESPONSE_CAPACITY) are clipped.
+    ResponseReceived(Response),
+    /// The module did not respond to a command
+    Timeout,
+    /// The programming pin was switched, and the module is now in a new mode
+    TransitionPerformed(Transition),
+}
+
+/// A change between programming and transparent mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Transition {
+    /// The module entered AT (programming) mode
+    IntoProgramming,
+    /// The module entered transparent mode
+    IntoTransparent,
+}
+
+/// A function receiving [`AtEvent`]s. Being a plain function pointer, it is free when
+/// unset, and can be copied between device states.
+pub type Observer = fn(AtEvent);
+
+/// Deliver an event, only building it if an observer is installed
+pub(crate) fn notify(observer: Option<Observer>, event: impl FnOnce() -> AtEvent) {
+    if let Some(observer) = observer {
+        observer(event())
+    }
+}
@@ -0,0 +1,19 @@
+//! Internal logging glue. Every diagnostic is written once through
+//! [`trace_at!`], which forwards to `defmt` and/or `log` depending on the
+//! enabled features, and compiles to nothing when neither is enabled.
+
+/// Emit a diagnostic at the given level (`trace`, `debug`, ...). Arguments
+/// must be formattable by both `defmt` and `core::fmt`, so pass `&str`
+/// rather than heapless strings.
+macro_rules! trace_at {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "defmt-03")]
+        defmt::$level!($($arg)+);
+        #[cfg(feature = "log")]
+        log::$level!($($arg)+);
+        #[cfg(not(any(feature = "defmt-03", feature = "log")))]
+        {
+            let _ = ($($arg)+,);
+        }
+    }};
+}
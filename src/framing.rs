@@ -0,0 +1,328 @@
+//! A minimal framing layer for traffic over the radio.
+//!
+//! The module itself is a transparent byte pipe, so frames must carry their own
+//! boundaries and integrity check. Each frame is a kind byte, the payload, and a CRC-16
+//! of both, COBS-encoded so that the only zero byte on the wire is the delimiter at the
+//! end of the frame. A receiver that starts mid-frame, or sees a corrupted frame, is back
+//! in step at the next delimiter.
+
+use crate::storage::Buffer;
+
+/// The byte ending every frame on the wire
+pub const DELIMITER: u8 = 0;
+
+/// The longest payload a frame can carry. This keeps every encoded frame within a single
+/// COBS block.
+pub const MAX_PAYLOAD: usize = 250;
+
+/// Bytes added to the payload by encoding: the kind, the CRC, the COBS code byte and the
+/// delimiter
+pub const OVERHEAD: usize = 5;
+
+/// The longest frame on the wire
+pub const MAX_ENCODED: usize = MAX_PAYLOAD + OVERHEAD;
+
+/// Frame kinds used by the helpers in this crate. Applications sharing the framing should
+/// use kinds from `0x80` upwards.
+pub mod kinds {
+    /// A link test probe, see [`linktest`](crate::linktest)
+    pub const PROBE: u8 = 0x01;
+    /// A throughput test frame, see [`linktest`](crate::linktest)
+    pub const STREAM: u8 = 0x02;
+    /// A keepalive beacon, see [`heartbeat`](crate::heartbeat)
+    pub const BEACON: u8 = 0x03;
+    /// A serialized message, see `message`
+    pub const MESSAGE: u8 = 0x04;
+}
+
+/// A frame could not be encoded or decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum FrameError {
+    /// The frame is malformed
+    Corrupt,
+    /// The frame is well formed, but its CRC does not match
+    Checksum,
+    /// The frame does not fit in the buffer, or the payload is longer than [`MAX_PAYLOAD`]
+    TooLong,
+}
+
+/// A decoded frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    /// The kind byte
+    pub kind: u8,
+    /// The payload
+    pub payload: &'a [u8],
+}
+
+/// CRC-16/CCITT-FALSE
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Encode a frame into `out`, returning the number of bytes to send, delimiter included.
+/// `out` must hold at least `payload.len() + OVERHEAD` bytes.
+pub fn encode(kind: u8, payload: &[u8], out: &mut [u8]) -> Result<usize, FrameError> {
+    if payload.len() > MAX_PAYLOAD || out.len() < payload.len() + OVERHEAD {
+        return Err(FrameError::TooLong);
+    }
+
+    let mut crc_input = [0u8; MAX_PAYLOAD + 1];
+    crc_input[0] = kind;
+    crc_input[1..=payload.len()].copy_from_slice(payload);
+    let crc = crc16(&crc_input[..=payload.len()]).to_le_bytes();
+
+    // COBS: each code byte gives the distance to the next zero, which is dropped
+    let mut code_index = 0;
+    let mut write = 1;
+    let mut code = 1u8;
+    for byte in crc_input[..=payload.len()].iter().chain(&crc) {
+        if *byte == 0 {
+            out[code_index] = code;
+            code_index = write;
+            code = 1;
+        } else {
+            out[write] = *byte;
+            code += 1;
+        }
+        write += 1;
+    }
+    out[code_index] = code;
+    out[write] = DELIMITER;
+    Ok(write + 1)
+}
+
+/// Reassembles frames from received bytes, holding up to `N` encoded bytes in `S`, see
+/// [`storage`](crate::storage)
+pub struct FrameReader<const N: usize = MAX_ENCODED, S = heapless::Vec<u8, N>> {
+    buffer: S,
+    overflowed: bool,
+    complete: bool,
+}
+
+impl<const N: usize> Default for FrameReader<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FrameReader<N> {
+    /// An empty reader
+    pub const fn new() -> Self {
+        Self {
+            buffer: heapless::Vec::new(),
+            overflowed: false,
+            complete: false,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> FrameReader<N, alloc::vec::Vec<u8>> {
+    /// An empty reader that allocates its buffer as frames arrive
+    pub const fn new_alloc() -> Self {
+        Self {
+            buffer: alloc::vec::Vec::new(),
+            overflowed: false,
+            complete: false,
+        }
+    }
+}
+
+impl<const N: usize, S: Buffer<u8>> FrameReader<N, S> {
+    /// Discard any partially received frame
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.overflowed = false;
+        self.complete = false;
+    }
+
+    /// Feed one received byte. At the end of each frame this returns the frame, or the
+    /// reason it was rejected. Empty frames, such as repeated delimiters, are skipped.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Frame<'_>, FrameError>> {
+        if self.complete {
+            self.reset();
+        }
+
+        if byte != DELIMITER {
+            if self.buffer.len() >= N || self.buffer.push(byte).is_err() {
+                self.overflowed = true;
+            }
+            return None;
+        }
+
+        self.complete = true;
+        if self.overflowed {
+            return Some(Err(FrameError::TooLong));
+        }
+        if self.buffer.is_empty() {
+            return None;
+        }
+
+        Some(self.decode())
+    }
+
+    fn decode(&mut self) -> Result<Frame<'_>, FrameError> {
+        let buffer = self.buffer.as_mut_slice();
+        let encoded = buffer.len();
+        let (mut read, mut write) = (0, 0);
+        while read < encoded {
+            let code = buffer[read] as usize;
+            read += 1;
+            if code == 0 || read + code - 1 > encoded {
+                return Err(FrameError::Corrupt);
+            }
+            // decoding never writes ahead of reading, so this can be done in place
+            buffer.copy_within(read..read + code - 1, write);
+            read += code - 1;
+            write += code - 1;
+            if code != 0xFF && read < encoded {
+                buffer[write] = 0;
+                write += 1;
+            }
+        }
+
+        if write < 3 {
+            return Err(FrameError::Corrupt);
+        }
+        let (body, crc) = buffer[..write].split_at(write - 2);
+        if crc16(body).to_le_bytes() != crc {
+            return Err(FrameError::Checksum);
+        }
+
+        Ok(Frame {
+            kind: body[0],
+            payload: &body[1..],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed<const N: usize, S: Buffer<u8>>(
+        reader: &mut FrameReader<N, S>,
+        bytes: &[u8],
+        mut each: impl FnMut(Result<Frame<'_>, FrameError>),
+    ) {
+        for byte in bytes {
+            if let Some(result) = reader.push(*byte) {
+                each(result);
+            }
+        }
+    }
+
+    #[test]
+    fn crc_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    /// Run a reassembly check against each storage backend
+    macro_rules! each_backend {
+        ($check:ident) => {{
+            $check(FrameReader::new());
+            #[cfg(feature = "alloc")]
+            $check(FrameReader::new_alloc());
+        }};
+        ($check:ident, $n:literal) => {{
+            $check(FrameReader::<$n>::new());
+            #[cfg(feature = "alloc")]
+            $check(FrameReader::<$n, alloc::vec::Vec<u8>>::new_alloc());
+        }};
+    }
+
+    #[test]
+    fn round_trips_with_zeros() {
+        each_backend!(round_trips_with_zeros_in);
+    }
+
+    fn round_trips_with_zeros_in<S: Buffer<u8>>(mut reader: FrameReader<MAX_ENCODED, S>) {
+        let payload = [0, 1, 0, 0, 2, 0xFF, 0];
+        let mut wire = [0u8; MAX_ENCODED];
+        let len = encode(0x81, &payload, &mut wire).unwrap();
+        assert_eq!(len, payload.len() + OVERHEAD);
+        assert!(!wire[..len - 1].contains(&DELIMITER));
+
+        let mut frames = 0;
+        feed(&mut reader, &wire[..len], |frame| {
+            let frame = frame.unwrap();
+            assert_eq!(frame.kind, 0x81);
+            assert_eq!(frame.payload, payload);
+            frames += 1;
+        });
+        assert_eq!(frames, 1);
+    }
+
+    #[test]
+    fn longest_payload_round_trips() {
+        each_backend!(longest_payload_round_trips_in);
+    }
+
+    fn longest_payload_round_trips_in<S: Buffer<u8>>(mut reader: FrameReader<MAX_ENCODED, S>) {
+        let payload = [0x55; MAX_PAYLOAD];
+        let mut wire = [0u8; MAX_ENCODED];
+        let len = encode(0x80, &payload, &mut wire).unwrap();
+        assert_eq!(len, MAX_ENCODED);
+
+        feed(&mut reader, &wire[..len], |frame| {
+            assert_eq!(frame.unwrap().payload, payload)
+        });
+        assert_eq!(
+            encode(0x80, &[0; MAX_PAYLOAD + 1], &mut [0; 300]),
+            Err(FrameError::TooLong)
+        );
+    }
+
+    #[test]
+    fn corruption_is_detected_and_resynchronised() {
+        each_backend!(corruption_is_detected_and_resynchronised_in);
+    }
+
+    fn corruption_is_detected_and_resynchronised_in<S: Buffer<u8>>(
+        mut reader: FrameReader<MAX_ENCODED, S>,
+    ) {
+        let mut wire = [0u8; 2 * MAX_ENCODED];
+        let first = encode(0x80, b"hello", &mut wire).unwrap();
+        let second = encode(0x80, b"world", &mut wire[first..]).unwrap();
+        wire[3] ^= 0x04;
+
+        let mut results: heapless::Vec<Result<u8, FrameError>, 4> = heapless::Vec::new();
+        feed(&mut reader, &wire[..first + second], |frame| {
+            results.push(frame.map(|frame| frame.payload[0])).unwrap();
+        });
+        assert_eq!(results.as_slice(), [Err(FrameError::Checksum), Ok(b'w')]);
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        each_backend!(oversized_frames_are_rejected_in, 8);
+    }
+
+    fn oversized_frames_are_rejected_in<S: Buffer<u8>>(mut reader: FrameReader<8, S>) {
+        let mut wire = [0u8; MAX_ENCODED];
+        let len = encode(0x80, b"too long for eight", &mut wire).unwrap();
+
+        let mut results: heapless::Vec<Result<(), FrameError>, 4> = heapless::Vec::new();
+        feed(&mut reader, &wire[..len], |frame| {
+            results.push(frame.map(|_| ())).unwrap();
+        });
+        let len = encode(0x80, b"ok", &mut wire).unwrap();
+        feed(&mut reader, &wire[..len], |frame| {
+            results.push(frame.map(|_| ())).unwrap();
+        });
+        assert_eq!(results.as_slice(), [Err(FrameError::TooLong), Ok(())]);
+    }
+}
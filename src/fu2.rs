@@ -0,0 +1,227 @@
+//! Sending to and receiving on modules in FU2, which only listen now and then.
+//!
+//! A module in FU2 saves power by sleeping between short listening periods, so a single
+//! packet sent to it is easily missed. The datasheet does not give the length of these
+//! periods, so the wake interval of the receiver is a parameter here.
+//!
+//! [`fu2_send_with_wake`] repeats a frame, as closely as FU2 allows, for long enough that
+//! one copy lands in the receiver's next listening period. [`fu2_receive_window`] polls a
+//! receiver for one window and returns the first frame that arrives. A window of at
+//! least [`PACKET_INTERVAL_MS`](crate::modes::ValidMode::PACKET_INTERVAL_MS) always
+//! overlaps one of the repeats. The receiver may see several copies of a frame, so frames
+//! should carry something to tell repeats apart, such as a sequence number.
+//!
+//! Frames end with [`DELIMITER`], as written by [`framing::encode`](crate::framing::encode).
+
+use embedded_hal::delay::DelayNs;
+use embedded_io::{Read, ReadReady, Write};
+
+use crate::framing::DELIMITER;
+use crate::modes::{Fu2, ValidMode};
+use crate::time::{Clock, IntoMillis};
+use crate::TransparentHC12;
+
+/// How many times [`fu2_send_with_wake`] sends a frame for a receiver waking every
+/// `wake_interval`
+pub fn fu2_repeats(wake_interval: impl IntoMillis) -> u32 {
+    wake_interval.into_ms() / Fu2::PACKET_INTERVAL_MS + 1
+}
+
+/// Send `frame` [`fu2_repeats`] times, [`PACKET_INTERVAL_MS`](ValidMode::PACKET_INTERVAL_MS)
+/// apart, so that a receiver waking every `wake_interval` catches one copy. Blocks for
+/// roughly `wake_interval`, and returns the number of copies sent.
+pub fn fu2_send_with_wake<Device, Pin, Speed>(
+    hc12: &mut TransparentHC12<Device, Pin, Fu2, Speed>,
+    frame: &[u8],
+    wake_interval: impl IntoMillis,
+    delay: &mut impl DelayNs,
+) -> Result<u32, Device::Error>
+where
+    Device: Write,
+{
+    let repeats = fu2_repeats(wake_interval);
+    for repeat in 0..repeats {
+        if repeat > 0 {
+            delay.delay_ms(Fu2::PACKET_INTERVAL_MS);
+        }
+        hc12.write_all(frame)?;
+        hc12.flush()?;
+    }
+    Ok(repeats)
+}
+
+/// Listen for up to `window` and copy the first frame to arrive into `buf`, without
+/// its delimiter. Returns its length, or `None` if no frame arrived in time. Bytes after
+/// the frame are left unread. A frame longer than `buf` is cut short.
+pub fn fu2_receive_window<Device, Pin, Speed>(
+    hc12: &mut TransparentHC12<Device, Pin, Fu2, Speed>,
+    clock: &impl Clock,
+    window: impl IntoMillis,
+    buf: &mut [u8],
+) -> Result<Option<usize>, Device::Error>
+where
+    Device: Read + ReadReady,
+{
+    let window_ms = window.into_ms();
+    let start_ms = clock.now_ms();
+    let mut len = 0;
+    while clock.now_ms().wrapping_sub(start_ms) < window_ms {
+        while hc12.read_ready()? {
+            let mut byte = [0];
+            if hc12.read(&mut byte)? == 0 {
+                break;
+            }
+            match byte[0] {
+                // an empty frame is just a stray delimiter
+                DELIMITER if len == 0 => {}
+                DELIMITER => return Ok(Some(len)),
+                byte => {
+                    if let Some(slot) = buf.get_mut(len) {
+                        *slot = byte;
+                        len += 1;
+                    }
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speeds::B2400;
+    use crate::test_utils::{PinLog, RecordingPin};
+    use core::cell::{Cell, RefCell};
+    use core::convert::Infallible;
+    use embedded_io::ErrorType;
+    use heapless::Vec;
+
+    /// Packets on the air, and when each was sent
+    #[derive(Default)]
+    struct Air {
+        now_ms: Cell<u32>,
+        packets: RefCell<Vec<(u32, Vec<u8, 16>), 16>>,
+    }
+
+    impl DelayNs for &Air {
+        fn delay_ns(&mut self, ns: u32) {
+            self.now_ms.set(self.now_ms.get() + ns / 1_000_000);
+        }
+    }
+
+    /// A module sending whole packets, or receiving those sent since it woke
+    struct Radio<'a> {
+        air: &'a Air,
+        awake_from_ms: u32,
+        delivered: usize,
+        rx: Vec<u8, 64>,
+    }
+
+    impl ErrorType for Radio<'_> {
+        type Error = Infallible;
+    }
+
+    impl Write for Radio<'_> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let packet = Vec::from_slice(buf).unwrap();
+            let now_ms = self.air.now_ms.get();
+            self.air
+                .packets
+                .borrow_mut()
+                .push((now_ms, packet))
+                .unwrap();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl ReadReady for Radio<'_> {
+        fn read_ready(&mut self) -> Result<bool, Self::Error> {
+            let now_ms = self.air.now_ms.get();
+            for (sent_ms, packet) in &self.air.packets.borrow()[self.delivered..] {
+                if *sent_ms > now_ms {
+                    break;
+                }
+                if *sent_ms >= self.awake_from_ms {
+                    self.rx.extend_from_slice(packet).unwrap();
+                }
+                self.delivered += 1;
+            }
+            Ok(!self.rx.is_empty())
+        }
+    }
+
+    impl Read for Radio<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            buf[0] = self.rx.remove(0);
+            Ok(1)
+        }
+    }
+
+    fn radio<'a>(
+        air: &'a Air,
+        pins: &'a PinLog,
+        awake_from_ms: u32,
+    ) -> TransparentHC12<Radio<'a>, RecordingPin<'a>, Fu2, B2400> {
+        let radio = Radio {
+            air,
+            awake_from_ms,
+            delivered: 0,
+            rx: Vec::new(),
+        };
+        TransparentHC12::assume_configured(radio, pins.pin(), Default::default())
+    }
+
+    /// Send a frame for a receiver waking every `wake_interval_ms`, then listen from
+    /// 2500ms for `window_ms`
+    fn exchange(wake_interval_ms: u32, window_ms: u32) -> Option<Vec<u8, 16>> {
+        let (air, pins) = (Air::default(), PinLog::new());
+        let mut sender = radio(&air, &pins, 0);
+        fu2_send_with_wake(&mut sender, b"hi\0", wake_interval_ms, &mut &air).unwrap();
+
+        air.now_ms.set(2500);
+        let mut receiver = radio(&air, &pins, 2500);
+        // time moves on by a millisecond each time the receiver looks at the clock
+        let clock = || {
+            air.now_ms.set(air.now_ms.get() + 1);
+            air.now_ms.get()
+        };
+        let mut buf = [0; 16];
+        let len = fu2_receive_window(&mut receiver, &clock, window_ms, &mut buf).unwrap()?;
+        Some(Vec::from_slice(&buf[..len]).unwrap())
+    }
+
+    #[test]
+    fn repeats_cover_the_wake_interval() {
+        assert_eq!(fu2_repeats(0), 1);
+        assert_eq!(fu2_repeats(3000), 4);
+
+        let (air, pins) = (Air::default(), PinLog::new());
+        let mut sender = radio(&air, &pins, 0);
+        assert_eq!(
+            fu2_send_with_wake(&mut sender, b"x\0", 2500, &mut &air),
+            Ok(3)
+        );
+        let sent: Vec<u32, 4> = air.packets.borrow().iter().map(|(at, _)| *at).collect();
+        assert_eq!(sent, [0, 1000, 2000]);
+    }
+
+    #[test]
+    fn single_packet_is_missed_by_a_sleeping_receiver() {
+        assert_eq!(exchange(0, 1100), None);
+    }
+
+    #[test]
+    fn repeated_packet_is_caught() {
+        assert_eq!(exchange(3000, 1100).unwrap(), b"hi");
+    }
+
+    #[test]
+    fn window_shorter_than_the_spacing_can_miss() {
+        assert_eq!(exchange(3000, 400), None);
+    }
+}
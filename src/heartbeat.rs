@@ -0,0 +1,261 @@
+//! Link up/down detection with periodic beacons.
+//!
+//! Both sides run a [`Heartbeat`], which hands out a small beacon frame every interval and
+//! listens for the peer's. After a number of intervals without hearing a beacon the link
+//! is declared down, and it is only declared up again after several consecutive beacons,
+//! so a marginal link does not flap. Beacons use their own frame kind, so they share the
+//! [framing layer](crate::framing) with application traffic.
+//!
+//! The helper does no IO itself: [`poll`](Heartbeat::poll) returns the beacon to send, and
+//! received frames are offered to [`receive`](Heartbeat::receive).
+
+use crate::framing::{encode, kinds, Frame, OVERHEAD};
+use crate::modes::ValidMode;
+use crate::time::IntoMillis;
+
+/// Bytes of a beacon on the wire
+pub const BEACON_LEN: usize = 1 + OVERHEAD;
+
+/// Whether the peer is being heard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum LinkState {
+    /// Beacons are arriving from the peer
+    Up,
+    /// The peer has not been heard for too long, or has not been heard yet
+    Down,
+}
+
+/// A function told about each change of [`LinkState`]
+pub type LinkCallback = fn(LinkState);
+
+/// An encoded beacon, ready to be written to the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Beacon {
+    bytes: [u8; BEACON_LEN],
+}
+
+impl Beacon {
+    /// The bytes to send
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Sends beacons and tracks whether the peer's are arriving
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    interval_ms: u32,
+    down_after: u8,
+    up_after: u8,
+    next_beacon_ms: Option<u32>,
+    last_heard_ms: Option<u32>,
+    streak: u8,
+    sequence: u8,
+    state: LinkState,
+    on_change: Option<LinkCallback>,
+}
+
+impl Heartbeat {
+    /// Beacon every `interval`, and declare the link down after `down_after` intervals
+    /// without hearing the peer, which is expected to use the same interval. The link
+    /// starts down, and comes up after a single beacon unless
+    /// [`up_after`](Heartbeat::up_after) says otherwise.
+    pub fn new(interval: impl IntoMillis, down_after: u8) -> Self {
+        Self {
+            interval_ms: interval.into_ms(),
+            down_after: down_after.max(1),
+            up_after: 1,
+            next_beacon_ms: None,
+            last_heard_ms: None,
+            streak: 0,
+            sequence: 0,
+            state: LinkState::Down,
+            on_change: None,
+        }
+    }
+
+    /// As [`new`](Heartbeat::new), but never beaconing more often than the mode's
+    /// [`PACKET_INTERVAL_MS`](ValidMode::PACKET_INTERVAL_MS) allows
+    pub fn for_mode<Mode: ValidMode>(interval: impl IntoMillis, down_after: u8) -> Self {
+        Self::new(interval.into_ms().max(Mode::PACKET_INTERVAL_MS), down_after)
+    }
+
+    /// Only declare the link up again after `count` consecutive beacons
+    pub fn up_after(mut self, count: u8) -> Self {
+        self.up_after = count.max(1);
+        self
+    }
+
+    /// Call `callback` whenever the link goes up or down
+    pub fn on_change(mut self, callback: LinkCallback) -> Self {
+        self.on_change = Some(callback);
+        self
+    }
+
+    /// The current state of the link
+    pub fn link_state(&self) -> LinkState {
+        self.state
+    }
+
+    /// When the peer was last heard, if it has been since the link last went down
+    pub fn last_heard_ms(&self) -> Option<u32> {
+        self.last_heard_ms
+    }
+
+    /// Check for a silent peer, and return a beacon if one is due. Call this regularly
+    /// from the main loop.
+    pub fn poll(&mut self, now_ms: u32) -> Option<Beacon> {
+        if self.is_silent(now_ms) {
+            self.last_heard_ms = None;
+            self.streak = 0;
+            self.set_state(LinkState::Down);
+        }
+
+        if let Some(next_ms) = self.next_beacon_ms {
+            if (now_ms.wrapping_sub(next_ms) as i32) < 0 {
+                return None;
+            }
+        }
+        self.next_beacon_ms = Some(now_ms.wrapping_add(self.interval_ms));
+
+        let mut bytes = [0u8; BEACON_LEN];
+        // a one byte payload always fits
+        encode(kinds::BEACON, &[self.sequence], &mut bytes).unwrap();
+        self.sequence = self.sequence.wrapping_add(1);
+        Some(Beacon { bytes })
+    }
+
+    /// Offer a received frame. Returns whether it was a beacon, so that anything else can
+    /// be handed to the application.
+    pub fn receive(&mut self, frame: &Frame<'_>, now_ms: u32) -> bool {
+        if frame.kind != kinds::BEACON {
+            return false;
+        }
+
+        if self.is_silent(now_ms) {
+            self.streak = 0;
+        }
+        self.streak = self.streak.saturating_add(1);
+        self.last_heard_ms = Some(now_ms);
+        if self.streak >= self.up_after {
+            self.set_state(LinkState::Up);
+        }
+        true
+    }
+
+    fn is_silent(&self, now_ms: u32) -> bool {
+        let timeout_ms = self.interval_ms.saturating_mul(self.down_after as u32);
+        self.last_heard_ms
+            .is_some_and(|heard_ms| now_ms.wrapping_sub(heard_ms) >= timeout_ms)
+    }
+
+    fn set_state(&mut self, state: LinkState) {
+        if self.state != state {
+            self.state = state;
+            if let Some(callback) = self.on_change {
+                callback(state);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::FrameReader;
+    use crate::modes::Fu4;
+
+    extern crate std;
+    use std::{sync::Mutex, vec::Vec};
+
+    /// Decode a beacon, and offer it to `heartbeat`
+    fn deliver(heartbeat: &mut Heartbeat, beacon: Beacon, now_ms: u32) -> bool {
+        let mut reader: FrameReader = FrameReader::new();
+        let mut consumed = false;
+        for byte in beacon.as_bytes() {
+            if let Some(frame) = reader.push(*byte) {
+                consumed = heartbeat.receive(&frame.unwrap(), now_ms);
+            }
+        }
+        consumed
+    }
+
+    #[test]
+    fn beacons_are_paced() {
+        let mut heartbeat = Heartbeat::new(1000, 3);
+        assert!(heartbeat.poll(0).is_some());
+        assert!(heartbeat.poll(999).is_none());
+        assert!(heartbeat.poll(1000).is_some());
+        assert!(heartbeat.poll(1500).is_none());
+
+        let mut slow = Heartbeat::for_mode::<Fu4>(500, 3);
+        assert!(slow.poll(0).is_some());
+        assert!(slow.poll(1999).is_none());
+        assert!(slow.poll(2000).is_some());
+    }
+
+    #[test]
+    fn link_goes_down_after_missed_beacons() {
+        static EDGES: Mutex<Vec<LinkState>> = Mutex::new(Vec::new());
+        fn record(state: LinkState) {
+            EDGES.lock().unwrap().push(state);
+        }
+
+        let mut peer = Heartbeat::new(1000, 3);
+        let mut heartbeat = Heartbeat::new(1000, 3).on_change(record);
+        assert_eq!(heartbeat.link_state(), LinkState::Down);
+
+        let beacon = peer.poll(0).unwrap();
+        assert!(deliver(&mut heartbeat, beacon, 0));
+        assert_eq!(heartbeat.link_state(), LinkState::Up);
+
+        // the next three beacons go missing
+        heartbeat.poll(2999);
+        assert_eq!(heartbeat.link_state(), LinkState::Up);
+        heartbeat.poll(3000);
+        assert_eq!(heartbeat.link_state(), LinkState::Down);
+        assert_eq!(heartbeat.last_heard_ms(), None);
+
+        let beacon = peer.poll(4000).unwrap();
+        deliver(&mut heartbeat, beacon, 4000);
+        assert_eq!(
+            EDGES.lock().unwrap().as_slice(),
+            [LinkState::Up, LinkState::Down, LinkState::Up]
+        );
+    }
+
+    #[test]
+    fn recovery_needs_consecutive_beacons() {
+        let mut peer = Heartbeat::new(1000, 2);
+        let mut heartbeat = Heartbeat::new(1000, 2).up_after(3);
+
+        let beacon = peer.poll(0).unwrap();
+        deliver(&mut heartbeat, beacon, 0);
+        let beacon = peer.poll(1000).unwrap();
+        deliver(&mut heartbeat, beacon, 1000);
+        assert_eq!(heartbeat.link_state(), LinkState::Down);
+
+        // a gap restarts the count, even without a poll in between
+        let beacon = peer.poll(3500).unwrap();
+        deliver(&mut heartbeat, beacon, 3500);
+        let beacon = peer.poll(4500).unwrap();
+        deliver(&mut heartbeat, beacon, 4500);
+        assert_eq!(heartbeat.link_state(), LinkState::Down);
+
+        let beacon = peer.poll(5500).unwrap();
+        deliver(&mut heartbeat, beacon, 5500);
+        assert_eq!(heartbeat.link_state(), LinkState::Up);
+    }
+
+    #[test]
+    fn application_frames_are_left_alone() {
+        let mut heartbeat = Heartbeat::new(1000, 3);
+        let frame = Frame {
+            kind: 0x80,
+            payload: b"data",
+        };
+        assert!(!heartbeat.receive(&frame, 0));
+        assert_eq!(heartbeat.link_state(), LinkState::Down);
+    }
+}
@@ -0,0 +1,361 @@
+//! Hardware-in-the-loop testing against real modules on USB serial adapters.
+//!
+//! Each module's SET pin is wired to the adapter's RTS (or DTR) line, so the harness can
+//! switch it between AT and transparent mode. The adapters are chosen with environment
+//! variables:
+//!
+//! - `HC12_PORT_A` and `HC12_PORT_B`: the serial ports of the two modules
+//! - `HC12_SET_LINE`: `rts` (the default) or `dtr`, the line wired to SET
+//!
+//! The tests in this module are ignored by default, and run with
+//! `cargo test --features hil -- --ignored`. Each one holds a [`Restore`] guard for the
+//! stations it uses, which returns the modules to FU3, 9600 bps and channel 1 even when
+//! an assertion fails.
+
+extern crate std;
+
+use std::io::{self, Read as _, Write as _};
+use std::string::String;
+use std::time::Duration;
+use std::{boxed::Box, thread};
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital;
+use embedded_io::{ErrorKind, ErrorType, Read, ReadReady, Write};
+use serialport::SerialPort;
+
+use crate::commands::exchange;
+use crate::linktest::{self, PerReport, PingReport};
+use crate::modes::Fu3;
+use crate::paramaters::{Channel, Power};
+use crate::speeds::B9600;
+use crate::time::{Deadline, IntoMillis, StdClock};
+use crate::{AtTimings, TransparentHC12, HC12, RESPONSE_CAPACITY, RESPONSE_TIMEOUT_MS};
+
+/// Serial speeds tried when looking for a module in an unknown state
+const SPEEDS: [u32; 8] = [9600, 1200, 2400, 4800, 19200, 38400, 57600, 115200];
+
+/// How long a read waits for the first byte
+const READ_TIMEOUT_MS: u64 = 50;
+
+/// The harness could not reach a module
+#[derive(Debug)]
+pub enum HilError {
+    /// The environment variable naming the port is not set
+    MissingPort(&'static str),
+    /// The serial port could not be opened or configured
+    Serial(serialport::Error),
+    /// The module did not answer at any serial speed
+    NotResponding,
+}
+
+impl From<serialport::Error> for HilError {
+    fn from(value: serialport::Error) -> Self {
+        Self::Serial(value)
+    }
+}
+
+/// An I/O error from a serial port
+#[derive(Debug)]
+pub struct IoError(pub io::Error);
+
+impl embedded_io::Error for IoError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// The control line wired to the module's SET pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetLine {
+    /// Request to send
+    Rts,
+    /// Data terminal ready
+    Dtr,
+}
+
+/// A serial port as an `embedded-io` device. Reads return zero bytes when nothing
+/// arrives within a short timeout, as the AT exchange expects.
+pub struct HilSerial {
+    port: Box<dyn SerialPort>,
+}
+
+impl ErrorType for HilSerial {
+    type Error = IoError;
+}
+
+impl Read for HilSerial {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match self.port.read(buf) {
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => Ok(0),
+            result => result.map_err(IoError),
+        }
+    }
+}
+
+impl ReadReady for HilSerial {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        let waiting = self.port.bytes_to_read();
+        Ok(waiting.map_err(|error| IoError(error.into()))? > 0)
+    }
+}
+
+impl Write for HilSerial {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.port.write(buf).map_err(IoError)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.port.flush().map_err(IoError)
+    }
+}
+
+/// The SET pin, driven through a control line of the adapter. Control lines are active
+/// low, so asserting the line pulls SET low.
+pub struct SetPin {
+    port: Box<dyn SerialPort>,
+    line: SetLine,
+}
+
+impl digital::ErrorType for SetPin {
+    type Error = core::convert::Infallible;
+}
+
+impl digital::OutputPin for SetPin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.assert(true);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.assert(false);
+        Ok(())
+    }
+}
+
+impl SetPin {
+    fn assert(&mut self, level: bool) {
+        // a failure here shows up as a module that does not answer
+        let _ = match self.line {
+            SetLine::Rts => self.port.write_request_to_send(level),
+            SetLine::Dtr => self.port.write_data_terminal_ready(level),
+        };
+    }
+}
+
+/// A delay backed by `std::thread::sleep`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdDelay;
+
+impl DelayNs for StdDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        thread::sleep(Duration::from_nanos(ns as u64));
+    }
+}
+
+/// One module on the bench
+#[derive(Debug, Clone)]
+pub struct Station {
+    path: String,
+    line: SetLine,
+}
+
+impl Station {
+    /// The station whose port is named by the environment variable `var`
+    pub fn from_env(var: &'static str) -> Result<Self, HilError> {
+        let path = std::env::var(var).map_err(|_| HilError::MissingPort(var))?;
+        let line = match std::env::var("HC12_SET_LINE").as_deref() {
+            Ok("dtr") => SetLine::Dtr,
+            _ => SetLine::Rts,
+        };
+        Ok(Self { path, line })
+    }
+
+    /// Open the port at `bps`, leaving the module in transparent mode
+    pub fn open(&self, bps: u32) -> Result<(HilSerial, SetPin), HilError> {
+        let port = serialport::new(&self.path, bps)
+            .timeout(Duration::from_millis(READ_TIMEOUT_MS))
+            .open()?;
+        let mut set = SetPin {
+            port: port.try_clone()?,
+            line: self.line,
+        };
+        // opening the port may assert the control lines
+        set.assert(false);
+        Ok((HilSerial { port }, set))
+    }
+
+    /// A programmer for a module at its factory speed
+    pub fn programmer(&self) -> Result<HC12<HilSerial, SetPin, Fu3, B9600>, HilError> {
+        let (serial, set) = self.open(9600)?;
+        HC12::factor_settings(serial, set, &mut StdDelay).map_err(|_| HilError::NotResponding)
+    }
+
+    /// The module in transparent mode, assuming the factory settings
+    pub fn transparent(&self) -> Result<TransparentHC12<HilSerial, SetPin, Fu3, B9600>, HilError> {
+        let (serial, set) = self.open(9600)?;
+        Ok(TransparentHC12::assume_factory(serial, set))
+    }
+
+    /// Return the module to its factory settings, whatever serial speed it was left at
+    pub fn restore(&self) -> Result<(), HilError> {
+        use digital::OutputPin;
+
+        for bps in SPEEDS {
+            let (mut serial, mut set) = self.open(bps)?;
+            set.set_low().ok();
+            StdDelay.delay_ms(AtTimings::DATASHEET.pin_settle_ms);
+            serial.port.clear(serialport::ClearBuffer::All)?;
+
+            let at = exchange::<_, RESPONSE_CAPACITY>(
+                &mut serial,
+                "AT".try_into().unwrap(),
+                &mut StdDelay,
+                None,
+                AtTimings::DATASHEET.command_settle_ms,
+                RESPONSE_TIMEOUT_MS,
+            );
+            if at.is_ok() {
+                let reset = exchange::<_, RESPONSE_CAPACITY>(
+                    &mut serial,
+                    "AT+DEFAULT".try_into().unwrap(),
+                    &mut StdDelay,
+                    None,
+                    AtTimings::DATASHEET.command_settle_ms,
+                    RESPONSE_TIMEOUT_MS,
+                );
+                set.set_high().ok();
+                StdDelay.delay_ms(AtTimings::DATASHEET.mode_change_settle_ms);
+                return reset.map(|_| ()).map_err(|_| HilError::NotResponding);
+            }
+            set.set_high().ok();
+        }
+        Err(HilError::NotResponding)
+    }
+
+    /// A guard restoring the module when dropped
+    pub fn guard(&self) -> Restore<'_> {
+        Restore { station: self }
+    }
+}
+
+/// Restores a [`Station`] to its factory settings when dropped, including while
+/// unwinding from a failed assertion
+pub struct Restore<'a> {
+    station: &'a Station,
+}
+
+impl Drop for Restore<'_> {
+    fn drop(&mut self) {
+        if let Err(error) = self.station.restore() {
+            std::eprintln!("failed to restore {}: {error:?}", self.station.path);
+        }
+    }
+}
+
+/// Program `station` to `channel` and `power`, keeping FU3 and 9600 bps
+pub fn program(station: &Station, channel: Channel, power: Power) -> Result<(), HilError> {
+    station
+        .programmer()?
+        .channel(channel)
+        .power(power)
+        .program(&mut StdDelay)
+        .map(|_| ())
+        .map_err(|_| HilError::NotResponding)
+}
+
+/// Run `measure` on `a`, while `b` echoes probes for up to `window_ms`
+fn with_echo<T>(
+    a: &Station,
+    b: &Station,
+    window_ms: u32,
+    measure: impl FnOnce(&mut TransparentHC12<HilSerial, SetPin, Fu3, B9600>) -> Result<T, IoError>,
+) -> Result<T, HilError> {
+    let mut near = a.transparent()?;
+    let mut far = b.transparent()?;
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            let clock = StdClock::new();
+            let deadline = Deadline::after(&clock, window_ms);
+            linktest::echo_forever(&mut far, &clock, deadline)
+        });
+        measure(&mut near).map_err(|error| HilError::Serial(error.0.into()))
+    })
+}
+
+/// Ping `b` from `a`, both at factory settings
+pub fn ping(
+    a: &Station,
+    b: &Station,
+    count: u16,
+    timeout: impl IntoMillis,
+) -> Result<PingReport, HilError> {
+    let timeout_ms = timeout.into_ms();
+    let window_ms = (count as u32 + 1) * timeout_ms;
+    with_echo(a, b, window_ms, |near| {
+        linktest::ping(near, &StdClock::new(), count, timeout_ms)
+    })
+}
+
+/// Measure the packet error rate from `a` to `b` and back, both at factory settings
+pub fn per(
+    a: &Station,
+    b: &Station,
+    count: u16,
+    payload_len: usize,
+    timeout: impl IntoMillis,
+) -> Result<PerReport, HilError> {
+    let timeout_ms = timeout.into_ms();
+    let window_ms = (count as u32 + 1) * timeout_ms;
+    with_echo(a, b, window_ms, |near| {
+        linktest::measure_per(near, count, payload_len, timeout_ms, &mut StdDelay)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stations() -> (Station, Station) {
+        (
+            Station::from_env("HC12_PORT_A").unwrap(),
+            Station::from_env("HC12_PORT_B").unwrap(),
+        )
+    }
+
+    #[test]
+    #[ignore = "needs two modules on the bench"]
+    fn programs_and_restores() {
+        let (a, _) = stations();
+        let _restore = a.guard();
+
+        program(&a, Channel::new(21).unwrap(), Power::P4).unwrap();
+        a.restore().unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs two modules on the bench"]
+    fn ping_between_stations() {
+        let (a, b) = stations();
+        let _restore = (a.guard(), b.guard());
+        a.restore().unwrap();
+        b.restore().unwrap();
+
+        let report = ping(&a, &b, 20, 250).unwrap();
+        assert!(report.lost < 20, "{report:?}");
+        assert!(report.max_ms < 250);
+    }
+
+    #[test]
+    #[ignore = "needs two modules on the bench"]
+    fn per_between_stations() {
+        let (a, b) = stations();
+        let _restore = (a.guard(), b.guard());
+        a.restore().unwrap();
+        b.restore().unwrap();
+
+        let report = per(&a, &b, 50, 32, 250).unwrap();
+        assert!(report.per_ppm() < 100_000, "{report:?}");
+    }
+}
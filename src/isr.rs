@@ -0,0 +1,262 @@
+use core::convert::Infallible;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use embedded_io::{ErrorType, Read, ReadReady, Write, WriteReady};
+use heapless::spsc::{Consumer, Producer, Queue};
+
+/// A receive buffer filled from a UART interrupt and drained from the main loop.
+///
+/// At high speeds the UART FIFO overruns unless bytes are drained as soon as they arrive.
+/// Split the buffer into an [`IsrProducer`], which is moved into the interrupt handler,
+/// and an [`IsrConsumer`], which implements [`Read`] and [`ReadReady`]. Combined with
+/// the UART's transmit half in an [`IsrBuffered`], it can be used as the device of an
+/// HC-12. The queue holds at most `N - 1` bytes.
+///
+/// # Example
+/// ```ignore
+/// static mut RX: IsrBuffer<64> = IsrBuffer::new();
+///
+/// let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(RX)).split() };
+/// // move `producer` to the interrupt, and call `producer.push_rx_byte(byte)` there
+/// let device = IsrBuffered::new(consumer, uart_tx);
+/// let hc12 = HC12::factor_settings(device, programming_pin, &mut delay);
+/// ```
+pub struct IsrBuffer<const N: usize> {
+    queue: Queue<u8, N>,
+    overruns: AtomicUsize,
+    high_water: AtomicUsize,
+}
+
+impl<const N: usize> IsrBuffer<N> {
+    /// Create an empty buffer, usable in a `static`
+    pub const fn new() -> Self {
+        Self {
+            queue: Queue::new(),
+            overruns: AtomicUsize::new(0),
+            high_water: AtomicUsize::new(0),
+        }
+    }
+
+    /// Split the buffer into its interrupt and main loop halves
+    pub fn split(&mut self) -> (IsrProducer<'_, N>, IsrConsumer<'_, N>) {
+        let (producer, consumer) = self.queue.split();
+
+        (
+            IsrProducer {
+                producer,
+                overruns: &self.overruns,
+                high_water: &self.high_water,
+            },
+            IsrConsumer {
+                consumer,
+                overruns: &self.overruns,
+                high_water: &self.high_water,
+            },
+        )
+    }
+}
+
+impl<const N: usize> Default for IsrBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The interrupt half of an [`IsrBuffer`]
+pub struct IsrProducer<'a, const N: usize> {
+    producer: Producer<'a, u8, N>,
+    overruns: &'a AtomicUsize,
+    high_water: &'a AtomicUsize,
+}
+
+impl<const N: usize> IsrProducer<'_, N> {
+    /// Queue a received byte. If the buffer is full the byte is dropped, the overrun is
+    /// counted, and `false` is returned. Never blocks.
+    pub fn push_rx_byte(&mut self, byte: u8) -> bool {
+        // the producer is the only writer of both counters, so plain loads and stores
+        // suffice, which keeps this usable on cores without atomic read-modify-write
+        if self.producer.enqueue(byte).is_err() {
+            let overruns = self.overruns.load(Ordering::Relaxed);
+            self.overruns.store(overruns + 1, Ordering::Relaxed);
+            return false;
+        }
+
+        let len = self.producer.len();
+        if len > self.high_water.load(Ordering::Relaxed) {
+            self.high_water.store(len, Ordering::Relaxed);
+        }
+        true
+    }
+}
+
+/// The main loop half of an [`IsrBuffer`]
+pub struct IsrConsumer<'a, const N: usize> {
+    consumer: Consumer<'a, u8, N>,
+    overruns: &'a AtomicUsize,
+    high_water: &'a AtomicUsize,
+}
+
+impl<const N: usize> IsrConsumer<'_, N> {
+    /// Number of bytes dropped because the buffer was full
+    pub fn overruns(&self) -> usize {
+        self.overruns.load(Ordering::Relaxed)
+    }
+
+    /// The most bytes that have been waiting in the buffer at once
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water.load(Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> ErrorType for IsrConsumer<'_, N> {
+    type Error = Infallible;
+}
+
+impl<const N: usize> Read for IsrConsumer<'_, N> {
+    /// Blocks until at least one byte has been pushed by the interrupt
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while !self.consumer.ready() {
+            core::hint::spin_loop();
+        }
+
+        let mut count = 0;
+        for slot in buf.iter_mut() {
+            let Some(byte) = self.consumer.dequeue() else {
+                break;
+            };
+            *slot = byte;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl<const N: usize> ReadReady for IsrConsumer<'_, N> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.consumer.ready())
+    }
+}
+
+/// A serial device that reads from an [`IsrConsumer`] and writes to a UART (usually its
+/// transmit half).
+pub struct IsrBuffered<'a, Tx, const N: usize> {
+    rx: IsrConsumer<'a, N>,
+    tx: Tx,
+}
+
+impl<'a, Tx, const N: usize> IsrBuffered<'a, Tx, N> {
+    /// Combine the receive buffer with the transmitting device
+    pub fn new(rx: IsrConsumer<'a, N>, tx: Tx) -> Self {
+        Self { rx, tx }
+    }
+
+    /// The receive half, for overrun and high-water-mark queries
+    pub fn rx(&self) -> &IsrConsumer<'a, N> {
+        &self.rx
+    }
+
+    /// Decompose into the receive buffer and the transmitting device
+    pub fn inner(self) -> (IsrConsumer<'a, N>, Tx) {
+        (self.rx, self.tx)
+    }
+}
+
+impl<Tx: ErrorType, const N: usize> ErrorType for IsrBuffered<'_, Tx, N> {
+    type Error = Tx::Error;
+}
+
+impl<Tx: ErrorType, const N: usize> Read for IsrBuffered<'_, Tx, N> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.rx.read(buf).map_err(|never| match never {})
+    }
+}
+
+impl<Tx: ErrorType, const N: usize> ReadReady for IsrBuffered<'_, Tx, N> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.rx.read_ready().map_err(|never| match never {})
+    }
+}
+
+impl<Tx: Write, const N: usize> Write for IsrBuffered<'_, Tx, N> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.tx.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.tx.flush()
+    }
+}
+
+impl<Tx: WriteReady, const N: usize> WriteReady for IsrBuffered<'_, Tx, N> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        self.tx.write_ready()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    #[test]
+    fn counts_overruns_and_high_water() {
+        let mut buffer: IsrBuffer<4> = IsrBuffer::new();
+        let (mut producer, mut consumer) = buffer.split();
+
+        assert!(producer.push_rx_byte(1));
+        assert!(producer.push_rx_byte(2));
+        assert!(producer.push_rx_byte(3));
+        assert!(!producer.push_rx_byte(4));
+        assert!(!producer.push_rx_byte(5));
+
+        let mut out = [0u8; 8];
+        assert_eq!(consumer.read(&mut out).unwrap(), 3);
+        assert_eq!(&out[..3], &[1, 2, 3]);
+        assert!(!consumer.read_ready().unwrap());
+        assert_eq!(consumer.overruns(), 2);
+        assert_eq!(consumer.high_water_mark(), 3);
+    }
+
+    #[cfg(feature = "programming")]
+    #[test]
+    fn at_exchange_fed_from_another_thread() {
+        use crate::commands::test::run_command;
+        use crate::speeds::B9600;
+        use crate::test_utils::Sink;
+        use embedded_hal::delay::DelayNs;
+
+        /// Really waits, as the response timeout only runs while the buffer is empty
+        struct Sleep;
+
+        impl DelayNs for Sleep {
+            fn delay_ns(&mut self, ns: u32) {
+                std::thread::sleep(std::time::Duration::from_nanos(ns.into()));
+            }
+        }
+
+        let mut buffer: IsrBuffer<4> = IsrBuffer::new();
+        let (mut producer, consumer) = buffer.split();
+
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                for byte in b"OK+B9600\r\n" {
+                    while !producer.push_rx_byte(*byte) {
+                        std::thread::yield_now();
+                    }
+                }
+            });
+
+            let mut device = IsrBuffered::new(consumer, Sink::new().accept_data(10));
+            run_command(&mut device, B9600::default(), &mut Sleep, None).unwrap();
+
+            let (consumer, sink) = device.inner();
+            assert_eq!(sink.into_inner_data(), b"AT+B9600\r\n");
+            assert!(consumer.high_water_mark() <= 3);
+        });
+    }
+}
@@ -1,406 +1,437 @@
+//! A driver for the HC-12 433MHz serial radio module.
+//!
+//! An [`HC12`] programs the module's settings over AT commands, with the serial speed and
+//! transmission mode checked against each other at compile time. Once programmed, it
+//! becomes a [`TransparentHC12`], which reads and writes radio traffic through
+//! `embedded-io`.
+//!
+//! Operations that wait borrow a delay for their duration and never keep it, so a single
+//! timer can serve several modules: while one is being programmed, the others carry on
+//! with transparent IO.
+//!
+//! The example runs against a [`MockHc12`](mock::MockHc12), checking what the module was
+//! programmed to and what it transmitted.
+//! ```
+//! # #[cfg(feature = "programming")]
+//! # fn main() {
+//! use embedded_io::Write;
+//! use hc12_rs::mock::MockHc12;
+//! use hc12_rs::paramaters::{Channel, Power};
+//! use hc12_rs::HC12;
+//!
+//! let module = MockHc12::new();
+//! let mut delay = module.delay();
+//!
+//! let mut hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+//!     .unwrap()
+//!     .channel(Channel::new(15).unwrap())
+//!     .power(Power::MAX)
+//!     .b4800()
+//!     .fu3()
+//!     .program(&mut delay)
+//!     .unwrap()
+//!     .into_transparent_mode(&mut delay)
+//!     .unwrap();
+//!
+//! // the host follows the module to its new serial speed
+//! module.set_host_baudrate(4800);
+//! hc12.write_all(b"Hello world!").unwrap();
+//! assert_eq!(module.transmitted().as_slice(), b"Hello world!");
+//!
+//! let mut hc12_low_power = hc12
+//!     .into_programming_mode(&mut delay)
+//!     .unwrap()
+//!     .fu1()
+//!     .program(&mut delay)
+//!     .unwrap()
+//!     .into_transparent_mode(&mut delay)
+//!     .unwrap();
+//! assert_eq!(module.settings().mode, 1);
+//!
+//! hc12_low_power.write_all(b"Hello from FU1!").unwrap();
+//! # }
+//! # #[cfg(not(feature = "programming"))]
+//! # fn main() {}
+//! ```
+
 #![cfg_attr(not(all(test, feature = "std")), no_std)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "programming")]
+#[macro_use]
+mod fmt;
+
+pub mod adapters;
+pub mod airtime;
+#[cfg(feature = "programming")]
+pub mod autopower;
+#[cfg(feature = "programming")]
+pub mod autosleep;
+pub mod beacon;
+#[cfg(feature = "programming")]
+pub mod capabilities;
+#[cfg(feature = "programming")]
+pub mod changes;
+#[cfg(feature = "programming")]
 mod commands;
+#[cfg(feature = "programming")]
+pub mod diagnostics;
+pub mod dispatch;
+#[cfg(feature = "programming")]
+pub mod dutycycle;
+#[cfg(feature = "cortex-m")]
+pub mod dwt;
+#[cfg(feature = "compat-eh02")]
+pub mod eh02;
+#[cfg(feature = "programming")]
 pub mod error;
+#[cfg(feature = "programming")]
+pub mod events;
+pub mod framing;
+pub mod fu2;
+pub mod heartbeat;
+#[cfg(feature = "hil")]
+pub mod hil;
+pub mod isr;
+pub mod linktest;
+#[cfg(feature = "postcard")]
+pub mod message;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod modes;
 pub mod paramaters;
+#[cfg(feature = "persist")]
+pub mod persist;
+pub mod profile;
+#[cfg(feature = "programming")]
+mod programming;
+pub mod provision;
+pub mod queue;
+pub mod reading;
+#[cfg(feature = "programming")]
+pub mod reprogram;
+pub mod response;
+#[cfg(feature = "critical-section")]
+pub mod shared;
+#[cfg(feature = "programming")]
+pub mod shutdown;
+#[cfg(feature = "programming")]
+pub mod sleep;
 pub mod speeds;
+pub mod storage;
+#[cfg(feature = "programming")]
+pub mod supervise;
+pub mod supply;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod time;
+#[cfg(feature = "transaction-log")]
+pub mod transactions;
+pub mod transfer;
+pub mod validation;
 
 use core::marker::PhantomData;
 
-use commands::run_command;
-use embedded_hal::{delay::DelayNs, digital::OutputPin};
+#[cfg(feature = "programming")]
+pub use commands::AtRead;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
 use embedded_io::{ErrorType, Read, ReadReady, Write, WriteReady};
+#[cfg(feature = "programming")]
 pub use error::*;
+#[cfg(feature = "programming")]
+pub use programming::{AtTimings, NotListening, ResolvedMode, Unresolved, HC12};
 
 use modes::*;
-use paramaters::{Channel, Power};
-use speeds::*;
-
-/// An HC-12 device programmer
-///
-/// # Example
-/// ```ignore
-/// let serial = hal::serial;
-/// let programming_pin = hal::gpio::Gpio1;
-/// let delay = hal::delay::Timer;
-///
-/// let mut hc12 = HC12::new(serial, programming_pin, &mut delay)
-///   .unwrap()
-///   .channel(Channel::new(15).unwrap())
-///   .power(Power::P8)
-///   .b4800()
-///   .fu3()
-///   .program(&mut timer_two)
-///   .unwrap()
-///   .into_transparent_mode()
-///   .unwrap();
-///
-/// hc12.write_all("Hello world!".as_bytes()).ok();
-///
-/// let mut hc12_low_power = hc12.into_programming_mode()
-///     .fu1()
-///     .unwrap()
-///     .into_transparent_mode()
-///     .unwrap();
-///
-/// hc12_low_power.write_all(b"Hello from the low power mode!").ok();
-/// ```
-pub struct HC12<Device, Pin, Mode, Speed> {
-    device: Device,
-    programming_pin: Pin,
-
-    // zero-sized markers
-    _mode: PhantomData<Mode>,
-    _speed: PhantomData<Speed>,
+use paramaters::{Channel, Configuration, FullConfiguration, Power};
+use queue::QueuedWriter;
+use speeds::{ValidSpeed, B9600};
 
+/// A transparent HC-12 device. This can be used directly as a serial device,
+/// or returned to AT (programming) mode, or decomposed to return the pin and the
+/// serial device used in programming the module
+pub struct TransparentHC12<Device, Pin, Mode, Speed> {
+    device: Device,
+    pin: Pin,
+    mode: PhantomData<Mode>,
+    speed: PhantomData<Speed>,
     channel: Channel,
     power: Power,
+    /// Bytes written since the last [`flush_tx_complete`](Self::flush_tx_complete)
+    unsent: usize,
+    #[cfg(feature = "programming")]
+    session: programming::Session,
 }
 
-impl<Device, Pin> HC12<Device, Pin, Fu3, B9600>
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed>
 where
-    Device: Read + Write,
+    Device: ErrorType,
     Pin: OutputPin,
 {
-    /// Create a new builder in programming mode. The serial port
-    /// MUST be set to 9600 BPS to be able to communicate with the
-    /// on-board microcontroller, in order to program properly.
-    ///
-    /// For most HALs, this is an infallible operation, as setting a pin
-    /// is a default item.
+    /// Use a module that has already been programmed, for example at the factory or by an
+    /// earlier firmware, without talking to it. The module must already be in transparent
+    /// mode (the programming pin high), using `Mode`, `Speed`, `channel` and `power`.
     ///
-    /// This function will block for not less than 40ms.
-    pub fn factor_settings(
+    /// The pin is not touched. A module whose pin was only just released needs another
+    /// 80ms before it is in transparent mode, and drops bytes written before then; use
+    /// [`assume_configured_with_delay`](Self::assume_configured_with_delay) to wait for it.
+    pub const fn assume_programmed(
+        device: Device,
+        pin: Pin,
+        channel: Channel,
+        power: Power,
+    ) -> Self {
+        Self {
+            device,
+            pin,
+            channel,
+            power,
+            unsent: 0,
+            #[cfg(feature = "programming")]
+            session: programming::Session::new(),
+            speed: PhantomData,
+            mode: PhantomData,
+        }
+    }
+
+    /// Use a module that has already been programmed with `configuration`, see
+    /// [`assume_programmed`](Self::assume_programmed)
+    pub const fn assume_configured(device: Device, pin: Pin, configuration: Configuration) -> Self {
+        Self::assume_programmed(device, pin, configuration.channel, configuration.power)
+    }
+
+    /// Use a module that has already been programmed with `configuration`, driving the
+    /// programming pin high and waiting for the module to settle into transparent mode.
+    /// This blocks for not less than 80ms.
+    pub fn assume_configured_with_delay(
         device: Device,
-        mut programming_pin: Pin,
+        mut pin: Pin,
+        configuration: Configuration,
         delay: &mut impl DelayNs,
-    ) -> Result<Self, Error<Pin::Error>> {
-        // enter AT (programming) mode
-        programming_pin.set_low().map_err(Error::DeviceError)?;
-        delay.delay_ms(40);
+    ) -> Result<Self, Pin::Error> {
+        pin.set_high()?;
+        delay.delay_ms(80);
+        Ok(Self::assume_configured(device, pin, configuration))
+    }
 
-        Ok(HC12 {
-            device,
-            programming_pin,
-            _mode: PhantomData,
-            _speed: PhantomData,
-            channel: Channel::default(),
-            power: Power::default(),
-        })
+    /// Get the current programmed channel
+    pub fn channel(&self) -> &Channel {
+        &self.channel
     }
-}
 
-impl<Device, Pin, Mode, Speed> HC12<Device, Pin, Mode, Speed> {
-    /// Set the power of the module. The default power is the maxumum
-    /// of P8
-    pub fn power(self, power: Power) -> Self {
-        HC12 { power, ..self }
+    /// The current programmed power
+    pub fn power(&self) -> &Power {
+        &self.power
     }
 
-    /// Set the channel. The module by default is set to Channel 0
-    pub fn channel(self, channel: Channel) -> Self {
-        HC12 { channel, ..self }
+    /// The current programmed channel and power
+    pub fn configuration(&self) -> Configuration {
+        Configuration::new(self.channel, self.power)
     }
 
-    /// Program into Fu1 mode.
-    ///
-    /// Fu1 is a moderate power-saving mode, with an idle current of ~3.5mA.
-    /// Fu1 supports all speeds, but the in-air baudrate remains 250000 bps
-    pub fn fu1(self) -> HC12<Device, Pin, Fu1, Speed>
-    where
-        Speed: ValidSpeed,
-        Fu1: ValidModeFor<Speed> + Default,
-    {
-        HC12 {
-            device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: PhantomData,
-            _speed: self._speed,
-            channel: self.channel,
-            power: self.power,
-        }
+    /// The serial format the module uses, as last set in programming mode. The host's
+    /// UART must match it.
+    #[cfg(feature = "programming")]
+    pub fn serial_format(&self) -> paramaters::SerialFormat {
+        self.session.serial_format
     }
 
-    /// Fu2 is the extreme power-saving mode of the HC-12. This mode only
-    /// supports B1200, B2400, and B4800 only. The in-air baudrate is a uniform 250000 bps.
-    /// It is reccomended to send packets over this mode at a frequency not exceeding 1Hz.
-    pub fn fu2(self) -> HC12<Device, Pin, Fu2, Speed>
-    where
-        Speed: ValidSpeed,
-        Fu3: ValidModeFor<Speed> + Default,
-    {
-        HC12 {
-            device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: PhantomData,
-            _speed: self._speed,
-            channel: self.channel,
-            power: self.power,
-        }
+    /// Wait for the module with `timings` whenever AT mode is entered or left, see
+    /// [`HC12::at_timings`]
+    #[cfg(feature = "programming")]
+    pub fn at_timings(mut self, timings: AtTimings) -> Self {
+        self.session.timings = timings;
+        self
+    }
+
+    /// Decompose the device to its serial port and programming pin
+    pub fn inner(self) -> (Device, Pin) {
+        (self.device, self.pin)
     }
 
-    /// Fu3 is the premier full-speed mode of the radio module. It accepts any speed, and will
-    /// adjust the in-air speed to the speed of the local serial speed. The higher the speed
-    /// the lower the sensitivity, and thus, the range. This is the default factory  mode of the
-    /// device.
-    pub fn fu3(self) -> HC12<Device, Pin, Fu3, Speed>
+    /// Wrap the device in a non-blocking transmit queue of `N` bytes, paced according
+    /// to the current mode.
+    pub fn queued<const N: usize>(self) -> QueuedWriter<Self, N>
     where
-        Speed: ValidSpeed,
-        Fu3: ValidModeFor<Speed> + Default,
+        Mode: ValidMode,
     {
-        HC12 {
-            device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: PhantomData,
-            _speed: self._speed,
-            channel: self.channel,
-            power: self.power,
-        }
+        QueuedWriter::new(self, Mode::PACKET_INTERVAL_MS)
     }
 
-    /// Fu4 mode is the long range mode of the device, and can achive communication distances of up
-    /// to 1.8km. Only 1200 bps is supported. In the air the baud rate will be redueced to a
-    /// whopping 500bps.
-    ///
-    /// Usage notes:
-    /// - Avoid transmitting more than 60 bytes in a packet
-    /// - Transmit a packet not more than once every two seconds.
-    pub fn fu4(self) -> HC12<Device, Pin, Fu4, Speed>
+    /// The serial speed the module was programmed to, in bits per second
+    pub fn programmed_baudrate_bps(&self) -> u32
     where
         Speed: ValidSpeed,
-        Fu4: ValidModeFor<Speed> + Default,
     {
-        HC12 {
-            device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: PhantomData,
-            _speed: self._speed,
-            channel: self.channel,
-            power: self.power,
-        }
+        Speed::bps()
     }
 
-    /// Program into 1200 bps.
-    pub fn b1200(self) -> HC12<Device, Pin, Mode, B1200>
+    /// The number of the mode the module was programmed to, as in `AT+FUn`
+    pub fn programmed_mode(&self) -> u8
     where
-        Mode: ValidModeFor<B1200> + Default,
-        B1200: ValidSpeed + Default,
+        Mode: ValidMode,
     {
-        HC12 {
-            device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: self._mode,
-            _speed: PhantomData,
-            channel: self.channel,
-            power: self.power,
-        }
+        Mode::NUMBER
     }
 
-    /// Program into 2400 bps.
-    pub fn b2400(self) -> HC12<Device, Pin, Mode, B2400>
+    /// Every setting the module was programmed to, for storing with
+    /// [`to_bytes`](FullConfiguration::to_bytes)
+    pub fn full_configuration(&self) -> FullConfiguration
     where
-        Mode: ValidModeFor<B2400>,
+        Mode: ValidMode,
+        Speed: ValidSpeed,
     {
-        HC12 {
-            device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: self._mode,
-            _speed: PhantomData,
-            channel: self.channel,
-            power: self.power,
+        FullConfiguration {
+            configuration: self.configuration(),
+            mode: Mode::NUMBER,
+            baudrate_bps: Speed::bps(),
         }
     }
 
-    /// Program into 4800 bps.
-    pub fn b4800(self) -> HC12<Device, Pin, Mode, B4800>
+    /// The sustained payload rate in bits per second, see
+    /// [`effective_throughput_bps`](airtime::effective_throughput_bps)
+    pub fn effective_throughput_bps(&self) -> u32
     where
-        Mode: ValidModeFor<B4800>,
+        Mode: ValidMode,
+        Speed: ValidSpeed,
     {
-        HC12 {
-            device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: self._mode,
-            _speed: PhantomData,
-            channel: self.channel,
-            power: self.power,
-        }
+        airtime::effective_throughput_bps_for::<Mode, Speed>()
     }
 
-    /// Program into 9600 bps.
-    pub fn b9600(self) -> HC12<Device, Pin, Mode, B9600>
+    /// Microseconds on the air for a write of `payload_len` bytes, see
+    /// [`airtime`]
+    pub fn time_on_air_us(&self, payload_len: usize) -> u32
     where
-        Mode: ValidModeFor<B9600>,
+        Mode: ValidMode,
+        Speed: ValidSpeed,
     {
-        HC12 {
-            device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: self._mode,
-            _speed: PhantomData,
-            channel: self.channel,
-            power: self.power,
-        }
+        airtime::time_on_air_us_for::<Mode, Speed>(payload_len)
     }
+}
 
-    /// Program into 19200 bps.
-    pub fn b19200(self) -> HC12<Device, Pin, Mode, B19200>
-    where
-        Mode: ValidModeFor<B19200>,
-    {
-        HC12 {
-            device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: self._mode,
-            _speed: PhantomData,
-            channel: self.channel,
-            power: self.power,
-        }
+impl<Device, Pin> TransparentHC12<Device, Pin, Fu3, B9600>
+where
+    Device: ErrorType,
+    Pin: OutputPin,
+{
+    /// Use a module that still has its factory settings: FU3, 9600 bps and
+    /// [`Configuration::FACTORY`]. The programming pin must be high.
+    ///
+    /// ```
+    /// use hc12_rs::paramaters::Configuration;
+    /// use hc12_rs::test_utils::{PinLog, Sink};
+    /// use hc12_rs::TransparentHC12;
+    ///
+    /// let pins = PinLog::new();
+    /// // rather than `TransparentHC12::<_, _, Fu3, B9600>::assume_programmed(device, pin,
+    /// // Channel::default(), Power::default())`
+    /// let hc12 = TransparentHC12::assume_factory(Sink::new(), pins.pin());
+    /// assert_eq!(hc12.configuration(), Configuration::FACTORY);
+    /// ```
+    pub const fn assume_factory(device: Device, pin: Pin) -> Self {
+        Self::assume_configured(device, pin, Configuration::FACTORY)
     }
+}
 
-    /// Program into 39400 bps.
-    pub fn b39400(self) -> HC12<Device, Pin, Mode, B39400>
-    where
-        Mode: ValidModeFor<B39400>,
-    {
-        HC12 {
-            device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: self._mode,
-            _speed: PhantomData,
-            channel: self.channel,
-            power: self.power,
-        }
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed> {
+    /// Whether `other` was programmed the same. The mode and speed are part of the type,
+    /// so only the channel and power are compared; the devices and pins are not.
+    pub fn is_same_config<OtherDevice, OtherPin>(
+        &self,
+        other: &TransparentHC12<OtherDevice, OtherPin, Mode, Speed>,
+    ) -> bool {
+        self.channel == other.channel && self.power == other.power
     }
 
-    /// Program into 57600 bps.
-    pub fn b57600(self) -> HC12<Device, Pin, Mode, B57600>
-    where
-        Mode: ValidModeFor<B57600>,
-    {
-        HC12 {
-            device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: self._mode,
-            _speed: PhantomData,
+    /// Replace or wrap the serial device, keeping the mode, speed and configuration.
+    /// Useful to interpose a logging wrapper after the device has been built.
+    pub fn map_device<NewDevice>(
+        self,
+        f: impl FnOnce(Device) -> NewDevice,
+    ) -> TransparentHC12<NewDevice, Pin, Mode, Speed> {
+        TransparentHC12 {
+            device: f(self.device),
+            pin: self.pin,
+            mode: self.mode,
+            speed: self.speed,
             channel: self.channel,
             power: self.power,
+            unsent: self.unsent,
+            #[cfg(feature = "programming")]
+            session: self.session,
         }
     }
 
-    /// Program into 115200 bps.
-    pub fn b115200(self) -> HC12<Device, Pin, Mode, B115200>
+    /// Flush the serial device, then wait until the bytes written since the last call
+    /// have been sent over the air, by their [`time_on_air_us`](Self::time_on_air_us).
+    /// Call this before sleeping, powering down or changing channel, which would cut the
+    /// transmission short. If the flush fails, the bytes are forgotten, so the next call
+    /// does not wait for them.
+    pub fn flush_tx_complete(&mut self, delay: &mut impl DelayNs) -> Result<(), Device::Error>
     where
-        Mode: ValidModeFor<B115200>,
+        Device: Write,
+        Mode: ValidMode,
+        Speed: ValidSpeed,
     {
-        HC12 {
+        let unsent = core::mem::take(&mut self.unsent);
+        self.device.flush()?;
+        delay.delay_us(airtime::time_on_air_us_for::<Mode, Speed>(unsent));
+        Ok(())
+    }
+
+    /// Replace or wrap the programming pin, keeping the mode, speed and configuration.
+    pub fn map_pin<NewPin>(
+        self,
+        f: impl FnOnce(Pin) -> NewPin,
+    ) -> TransparentHC12<Device, NewPin, Mode, Speed> {
+        TransparentHC12 {
             device: self.device,
-            programming_pin: self.programming_pin,
-            _mode: self._mode,
-            _speed: PhantomData,
+            pin: f(self.pin),
+            mode: self.mode,
+            speed: self.speed,
             channel: self.channel,
             power: self.power,
+            unsent: self.unsent,
+            #[cfg(feature = "programming")]
+            session: self.session,
         }
     }
 }
 
-impl<Device, Pin, Mode, Speed> HC12<Device, Pin, Mode, Speed>
+/// Shows the mode, speed and configuration. The serial device and pin are not shown, so
+/// they do not need to implement `Debug`.
+impl<Device, Pin, Mode, Speed> core::fmt::Debug for TransparentHC12<Device, Pin, Mode, Speed>
 where
-    Device: Read + Write + ReadReady,
-    Pin: OutputPin,
-    Mode: ValidMode + ValidModeFor<Speed>,
+    Mode: ValidMode,
     Speed: ValidSpeed,
 {
-    /// Program the HC12
-    pub fn program(mut self, delay: &mut impl DelayNs) -> Result<(), Error<Device::Error>> {
-        run_command(&mut self.device, Speed::default(), delay)?;
-        run_command(&mut self.device, Mode::default(), delay)?;
-        run_command(&mut self.device, self.power, delay)?;
-        run_command(&mut self.device, self.channel, delay)
-    }
-
-    /// Return the HC-12 to transparent mode. For most HALs, this is
-    /// infallible, as it only relies on setting a pin high or low.
-    /// This function will block for not less than 80ms.
-    pub fn into_transparent_mode(
-        mut self,
-        delay: &mut impl DelayNs,
-    ) -> Result<TransparentHC12<Device, Pin, Mode, Speed>, Pin::Error> {
-        self.programming_pin.set_high()?;
-        delay.delay_ms(80);
-
-        Ok(TransparentHC12::new(
-            self.device,
-            self.programming_pin,
-            self.channel,
-            self.power,
-        ))
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TransparentHC12")
+            .field("mode", &format_args!("FU{}", Mode::NUMBER))
+            .field("baudrate_bps", &Speed::bps())
+            .field("channel", &self.channel)
+            .field("power", &self.power)
+            .finish_non_exhaustive()
     }
 }
 
-/// A transparent HC-12 device. This can be used directly as a serial device,
-/// or returned to AT (programming) mode, or decomposed to return the pin and the
-/// serial device used in programming the module
-pub struct TransparentHC12<Device, Pin, Mode, Speed> {
-    device: Device,
-    pin: Pin,
-    mode: PhantomData<Mode>,
-    speed: PhantomData<Speed>,
-    channel: Channel,
-    power: Power,
-}
-
-impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed>
+#[cfg(feature = "defmt-03")]
+impl<Device, Pin, Mode, Speed> defmt::Format for TransparentHC12<Device, Pin, Mode, Speed>
 where
-    Device: ErrorType,
-    Pin: OutputPin,
+    Mode: ValidMode,
+    Speed: ValidSpeed,
 {
-    pub(crate) fn new(device: Device, pin: Pin, channel: Channel, power: Power) -> Self {
-        Self {
-            device,
-            pin,
-            channel,
-            power,
-            speed: PhantomData,
-            mode: PhantomData,
-        }
-    }
-
-    /// Get the current programmed channel
-    pub fn channel(&self) -> &Channel {
-        &self.channel
-    }
-
-    /// The current programmed power
-    pub fn power(&self) -> &Power {
-        &self.power
-    }
-
-    /// Decompose the device to its serial port and programming pin
-    pub fn inner(self) -> (Device, Pin) {
-        (self.device, self.pin)
-    }
-
-    /// Return to programming mode. This persists the programming parameters from the last
-    /// probramming of the device. In most HALs this is infallible.
-    pub fn into_programming_mode(
-        mut self,
-        delay: &mut impl DelayNs,
-    ) -> Result<HC12<Device, Pin, Mode, Speed>, Error<Pin::Error>>
-    where
-        Device: Read + Write,
-        Pin: OutputPin,
-    {
-        self.pin.set_low().map_err(Error::DeviceError)?;
-        delay.delay_ms(40);
-
-        Ok(HC12 {
-            device: self.device,
-            programming_pin: self.pin,
-            _mode: PhantomData,
-            _speed: PhantomData,
-            channel: self.channel,
-            power: self.power,
-        })
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "TransparentHC12 {{ mode: FU{=u8}, baudrate_bps: {=u32}, channel: {}, power: {}, .. }}",
+            Mode::NUMBER,
+            Speed::bps(),
+            self.channel,
+            self.power,
+        )
     }
 }
 
@@ -434,7 +465,9 @@ where
     Device: Write,
 {
     fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
-        self.device.write(buf)
+        let written = self.device.write(buf)?;
+        self.unsent = self.unsent.saturating_add(written);
+        Ok(written)
     }
 
     fn flush(&mut self) -> Result<(), Self::Error> {
@@ -450,3 +483,149 @@ where
         self.device.write_ready()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::airtime::time_on_air_us_for;
+    use crate::test_utils::{CountingDelay, PinLog, Sink};
+    use speeds::{B2400, B9600};
+
+    #[test]
+    fn assume_programmed_passes_traffic_through() {
+        let pins = PinLog::new();
+        let mut hc12: TransparentHC12<_, _, Fu3, B9600> = TransparentHC12::assume_programmed(
+            Sink::new().accept_data(5),
+            pins.pin(),
+            Channel::new(21).unwrap(),
+            Power::P4,
+        );
+
+        hc12.write_all(b"hello").unwrap();
+        assert_eq!(u8::from(*hc12.channel()), 21);
+
+        let (sink, _) = hc12.inner();
+        assert_eq!(sink.into_inner_data(), b"hello");
+        assert!(pins.states().is_empty());
+    }
+
+    #[test]
+    fn assume_configured_with_delay_waits_after_releasing_the_pin() {
+        use crate::mock::{MockHc12, MockSetPin};
+        use core::{cell::Cell, convert::Infallible};
+        use embedded_hal::digital;
+
+        /// The mock's SET pin, noting when it was driven high
+        struct TimedPin<'a> {
+            pin: MockSetPin<'a>,
+            module: &'a MockHc12,
+            high_at_ms: &'a Cell<Option<u32>>,
+        }
+
+        impl digital::ErrorType for TimedPin<'_> {
+            type Error = Infallible;
+        }
+
+        impl OutputPin for TimedPin<'_> {
+            fn set_low(&mut self) -> Result<(), Self::Error> {
+                self.pin.set_low()
+            }
+
+            fn set_high(&mut self) -> Result<(), Self::Error> {
+                self.high_at_ms.set(Some(self.module.now_ms()));
+                self.pin.set_high()
+            }
+        }
+
+        let module = MockHc12::new();
+        let high_at_ms = Cell::new(None);
+        let pin = TimedPin {
+            pin: module.set_pin(),
+            module: &module,
+            high_at_ms: &high_at_ms,
+        };
+        let mut hc12: TransparentHC12<_, _, Fu3, B9600> =
+            TransparentHC12::assume_configured_with_delay(
+                module.serial(),
+                pin,
+                Configuration::FACTORY,
+                &mut module.delay(),
+            )
+            .unwrap();
+
+        let high_at_ms = high_at_ms.get().unwrap();
+        assert!(module.now_ms() - high_at_ms >= 80);
+        assert!(!module.in_at_mode());
+        hc12.write_all(b"first").unwrap();
+        assert_eq!(module.transmitted(), b"first");
+    }
+
+    #[test]
+    fn flush_tx_complete_waits_for_the_bytes_on_the_air() {
+        let pins = PinLog::new();
+        let mut fast: TransparentHC12<_, _, Fu3, B9600> =
+            TransparentHC12::assume_factory(Sink::new(), pins.pin());
+        let mut delay = CountingDelay::new();
+        fast.write_all(&[0; 4]).unwrap();
+        fast.write_all(&[0; 6]).unwrap();
+        fast.flush_tx_complete(&mut delay).unwrap();
+        // (10 + 9) bytes at 15 kbps
+        assert_eq!(delay.elapsed_us(), 10_134);
+        // nothing written since
+        fast.flush_tx_complete(&mut delay).unwrap();
+        assert_eq!(delay.elapsed_us(), 10_134);
+
+        let mut slow: TransparentHC12<_, _, Fu3, B2400> =
+            TransparentHC12::assume_configured(Sink::new(), pins.pin(), Default::default());
+        let mut delay = CountingDelay::new();
+        slow.write_all(&[0; 10]).unwrap();
+        slow.flush_tx_complete(&mut delay).unwrap();
+        // the same bytes at 5 kbps
+        assert_eq!(delay.elapsed_us(), 30_400);
+    }
+
+    #[test]
+    fn flush_tx_complete_only_counts_accepted_bytes() {
+        let pins = PinLog::new();
+        let mut hc12: TransparentHC12<_, _, Fu3, B9600> =
+            TransparentHC12::assume_factory(Sink::new().accept_data(1), pins.pin());
+        let mut delay = CountingDelay::new();
+        assert!(hc12.write_all(&[0; 10]).is_err());
+        hc12.flush_tx_complete(&mut delay).unwrap();
+        assert_eq!(
+            delay.elapsed_us(),
+            time_on_air_us_for::<Fu3, B9600>(1) as u64
+        );
+    }
+
+    #[test]
+    fn debug_skips_device_and_pin() {
+        extern crate std;
+        use std::format;
+
+        /// A device that does not implement `Debug`
+        struct Opaque;
+
+        impl ErrorType for Opaque {
+            type Error = core::convert::Infallible;
+        }
+
+        let pins = PinLog::new();
+        let hc12: TransparentHC12<_, _, Fu1, B9600> = TransparentHC12::assume_programmed(
+            Opaque,
+            pins.pin(),
+            Channel::new(7).unwrap(),
+            Power::P2,
+        );
+        assert_eq!(
+            (hc12.programmed_mode(), hc12.programmed_baudrate_bps()),
+            (1, 9600)
+        );
+        assert_eq!(hc12.effective_throughput_bps(), 7_680);
+        assert_eq!(hc12.full_configuration().mode, 1);
+        let debug = format!("{hc12:?}");
+        assert!(debug.contains("mode: FU1"), "{debug}");
+        assert!(debug.contains("channel: Channel(7)"), "{debug}");
+        assert!(debug.ends_with(", .. }"), "{debug}");
+    }
+}
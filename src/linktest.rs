@@ -0,0 +1,824 @@
+//! Link measurements between two modules.
+//!
+//! For the packet error rate, one side runs [`echo_forever`], which returns every probe it
+//! receives. The other runs [`measure_per`], which sends numbered, CRC-checked probes over
+//! the [framing layer](crate::framing) and counts how many come back intact. With the
+//! `programming` feature, [`measure_per_sweep`] repeats the measurement at several power
+//! levels.
+//!
+//! For round trip times, the peer also runs [`echo_forever`], while [`ping`] times small
+//! probes.
+//!
+//! For throughput, one side runs [`measure_throughput`], streaming numbered frames as fast
+//! as the device accepts them, while the other runs [`receive_throughput`] over the same
+//! window, counting what arrives.
+
+use embedded_hal::delay::DelayNs;
+use embedded_io::{Read, ReadReady, Write};
+
+use crate::framing::{encode, kinds, FrameError, FrameReader, MAX_ENCODED};
+use crate::time::{Clock, Deadline, IntoMillis};
+
+/// The longest probe payload, including the two-byte sequence number
+pub const MAX_PROBE_PAYLOAD: usize = 64;
+
+/// The longest throughput frame payload, including the four-byte sequence number
+pub const MAX_STREAM_PAYLOAD: usize = 128;
+
+/// The outcome of a packet error rate measurement
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct PerReport {
+    /// Probes sent
+    pub sent: u32,
+    /// Probes returned intact
+    pub received: u32,
+    /// Probes returned with errors
+    pub corrupted: u32,
+}
+
+impl PerReport {
+    /// Probes that never came back
+    pub fn lost(&self) -> u32 {
+        self.sent - self.received - self.corrupted
+    }
+
+    /// Probes that did not come back intact, in parts per million of those sent
+    pub fn per_ppm(&self) -> u32 {
+        if self.sent == 0 {
+            return 0;
+        }
+        ((self.sent - self.received) as u64 * 1_000_000 / self.sent as u64) as u32
+    }
+}
+
+/// The outcome of one side of a throughput measurement. Byte counts are payload bytes, so
+/// the rate is the goodput after framing overhead.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct ThroughputReport {
+    /// Frames sent or received intact
+    pub frames: u32,
+    /// Payload bytes sent or received intact
+    pub bytes: u32,
+    /// Frames missing from the received sequence. Always zero for the sender.
+    pub lost: u32,
+    /// Length of the measurement window, in milliseconds
+    pub elapsed_ms: u32,
+}
+
+impl ThroughputReport {
+    /// Payload bytes per second over the window
+    pub fn bytes_per_second(&self) -> u32 {
+        if self.elapsed_ms == 0 {
+            return 0;
+        }
+        (self.bytes as u64 * 1000 / self.elapsed_ms as u64) as u32
+    }
+}
+
+/// Fill `payload` with the probe for sequence number `seq`
+pub(crate) fn probe_payload(seq: u16, payload: &mut [u8]) {
+    payload[..2].copy_from_slice(&seq.to_le_bytes());
+    for (i, byte) in payload.iter_mut().enumerate().skip(2) {
+        *byte = (seq as u8) ^ (i as u8);
+    }
+}
+
+/// Return every intact probe received until `deadline`, returning the number of probes
+/// echoed. Other frames and corrupted data are ignored.
+pub fn echo_forever<D>(
+    device: &mut D,
+    clock: &impl Clock,
+    deadline: Deadline,
+) -> Result<u32, D::Error>
+where
+    D: Read + ReadReady + Write,
+{
+    let mut reader: FrameReader = FrameReader::new();
+    let mut echoed = 0;
+
+    while !deadline.is_expired(clock) {
+        echoed += echo_ready(device, &mut reader)?;
+    }
+    Ok(echoed)
+}
+
+/// Return every intact probe in what can be read without waiting, returning the number of
+/// probes echoed. A frame cut short is completed by the next call with the same `reader`.
+fn echo_ready<D>(device: &mut D, reader: &mut FrameReader) -> Result<u32, D::Error>
+where
+    D: Read + ReadReady + Write,
+{
+    let mut wire = [0u8; MAX_ENCODED];
+    let mut chunk = [0u8; 32];
+    let mut echoed = 0;
+
+    while device.read_ready()? {
+        let count = device.read(&mut chunk)?;
+        for byte in &chunk[..count] {
+            let Some(Ok(frame)) = reader.push(*byte) else {
+                continue;
+            };
+            if frame.kind != kinds::PROBE {
+                continue;
+            }
+            // the reader accepts one byte more than can be sent, so that probe is dropped
+            let Ok(len) = encode(frame.kind, frame.payload, &mut wire) else {
+                continue;
+            };
+            device.write_all(&wire[..len])?;
+            echoed += 1;
+        }
+    }
+    Ok(echoed)
+}
+
+/// Send `count` probes of `payload_len` bytes to a peer running [`echo_forever`], waiting
+/// up to `timeout` for each to return. `payload_len` is clamped to between 2 and
+/// [`MAX_PROBE_PAYLOAD`]. Late replies to earlier probes are discarded, so a lost
+/// response never stalls the measurement.
+pub fn measure_per<D>(
+    device: &mut D,
+    count: u16,
+    payload_len: usize,
+    timeout: impl IntoMillis,
+    delay: &mut impl DelayNs,
+) -> Result<PerReport, D::Error>
+where
+    D: Read + ReadReady + Write,
+{
+    let timeout_ms = timeout.into_ms();
+    let payload_len = payload_len.clamp(2, MAX_PROBE_PAYLOAD);
+    let mut reader: FrameReader = FrameReader::new();
+    let mut report = PerReport::default();
+    let mut probe = [0u8; MAX_PROBE_PAYLOAD];
+
+    for seq in 0..count {
+        let probe = &mut probe[..payload_len];
+        send_probe(device, seq, probe)?;
+        report.sent += 1;
+
+        let mut waited_ms = 0;
+        let expired = || {
+            if waited_ms >= timeout_ms {
+                return true;
+            }
+            delay.delay_ms(1);
+            waited_ms += 1;
+            false
+        };
+        match await_echo(device, &mut reader, probe, expired)? {
+            Echo::Intact => report.received += 1,
+            Echo::Corrupted => report.corrupted += 1,
+            Echo::Missing => {}
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(feature = "programming")]
+pub use sweep::{measure_per_sweep, SweepError, SweepReports};
+
+#[cfg(feature = "programming")]
+mod sweep {
+    use embedded_hal::{delay::DelayNs, digital::OutputPin};
+    use embedded_io::{Read, ReadReady, Write};
+
+    use super::{measure_per, PerReport};
+    use crate::paramaters::Power;
+    use crate::time::IntoMillis;
+    use crate::{Error, RoundTripError, TransparentHC12};
+
+    /// The reports of [`measure_per_sweep`], indexed from [`Power::P1`]
+    pub type SweepReports = [Option<PerReport>; 8];
+
+    /// A power sweep could not be completed: a level was not accepted, or the link failed
+    /// during a measurement
+    pub type SweepError<D, P> = RoundTripError<D, P>;
+
+    /// Run [`measure_per`] at each of `levels` in turn, switching the power with a brief
+    /// return to AT mode. The reports are indexed from [`Power::P1`], with `None` for the
+    /// levels not in `levels`. Nothing is sent if a level is above the build's
+    /// [`MAX_POWER`](crate::paramaters::MAX_POWER).
+    ///
+    /// The module is returned to the power it had, or to `MAX_POWER` if it had more. If the
+    /// sweep fails part way, it is left at the power reported by
+    /// [`power`](TransparentHC12::power).
+    pub fn measure_per_sweep<Device, Pin, Mode, Speed>(
+        hc12: &mut TransparentHC12<Device, Pin, Mode, Speed>,
+        levels: &[Power],
+        count: u16,
+        payload_len: usize,
+        timeout: impl IntoMillis,
+        delay: &mut impl DelayNs,
+    ) -> Result<SweepReports, SweepError<Device::Error, Pin::Error>>
+    where
+        Device: Read + ReadReady + Write,
+        Pin: OutputPin,
+    {
+        for level in levels {
+            level
+                .within_cap()
+                .map_err(|_| SweepError::At(Error::ExceedsBuildCap(*level)))?;
+        }
+
+        let timeout_ms = timeout.into_ms();
+        let original = hc12.power.within_cap().unwrap_or(Power::MAX);
+        let mut reports = [None; 8];
+        for &level in levels {
+            if hc12.power != level {
+                hc12.set_power(level, delay)?;
+            }
+            let report = measure_per(hc12, count, payload_len, timeout_ms, delay)
+                .map_err(SweepError::Link)?;
+            reports[level as usize - 1] = Some(report);
+        }
+
+        if hc12.power != original {
+            hc12.set_power(original, delay)?;
+        }
+        Ok(reports)
+    }
+}
+
+/// Round trip times measured by [`ping`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct PingReport {
+    /// The shortest round trip, in milliseconds
+    pub min_ms: u32,
+    /// The longest round trip, in milliseconds
+    pub max_ms: u32,
+    /// The mean round trip, in milliseconds
+    pub avg_ms: u32,
+    /// Probes that did not come back intact in time
+    pub lost: u32,
+}
+
+/// Measure the round trip time to a peer running [`echo_forever`], with `count` small
+/// probes, each given up to `timeout` to return. The times are zero if no probe came
+/// back.
+pub fn ping<D>(
+    device: &mut D,
+    clock: &impl Clock,
+    count: u16,
+    timeout: impl IntoMillis,
+) -> Result<PingReport, D::Error>
+where
+    D: Read + ReadReady + Write,
+{
+    let timeout_ms = timeout.into_ms();
+    let mut reader: FrameReader = FrameReader::new();
+    let mut report = PingReport {
+        min_ms: u32::MAX,
+        ..Default::default()
+    };
+    let mut total_ms: u64 = 0;
+    let mut probe = [0u8; 4];
+
+    for seq in 0..count {
+        let sent = Deadline::after(clock, timeout_ms);
+        send_probe(device, seq, &mut probe)?;
+
+        match await_echo(device, &mut reader, &probe, || sent.is_expired(clock))? {
+            Echo::Intact => {
+                let rtt_ms = sent.elapsed_ms(clock);
+                report.min_ms = report.min_ms.min(rtt_ms);
+                report.max_ms = report.max_ms.max(rtt_ms);
+                total_ms += rtt_ms as u64;
+            }
+            Echo::Corrupted | Echo::Missing => report.lost += 1,
+        }
+    }
+
+    let received = count as u32 - report.lost;
+    if received == 0 {
+        report.min_ms = 0;
+    } else {
+        report.avg_ms = (total_ms / received as u64) as u32;
+    }
+    Ok(report)
+}
+
+/// What came back for a probe
+enum Echo {
+    Intact,
+    Corrupted,
+    Missing,
+}
+
+/// Fill `probe` for sequence number `seq`, and send it
+fn send_probe<D: Write>(device: &mut D, seq: u16, probe: &mut [u8]) -> Result<(), D::Error> {
+    let mut wire = [0u8; MAX_ENCODED];
+    probe_payload(seq, probe);
+    // the probe is shorter than the longest frame, so this cannot fail
+    let len = encode(kinds::PROBE, probe, &mut wire).unwrap();
+    device.write_all(&wire[..len])?;
+    device.flush()
+}
+
+/// Wait for the echo of `probe`, discarding replies to earlier probes and other traffic.
+/// `expired` is called before each read, and ends the wait by returning true.
+fn await_echo<D: Read + ReadReady>(
+    device: &mut D,
+    reader: &mut FrameReader,
+    probe: &[u8],
+    mut expired: impl FnMut() -> bool,
+) -> Result<Echo, D::Error> {
+    let mut chunk = [0u8; 32];
+    loop {
+        if expired() {
+            return Ok(Echo::Missing);
+        }
+        if !device.read_ready()? {
+            continue;
+        }
+
+        let read = device.read(&mut chunk)?;
+        let mut echo = None;
+        for byte in &chunk[..read] {
+            match reader.push(*byte) {
+                Some(Ok(frame)) if frame.kind == kinds::PROBE && echo.is_none() => {
+                    if frame.payload.get(..2) != probe.get(..2) {
+                        // a straggler from an earlier probe
+                        continue;
+                    }
+                    echo = Some(if frame.payload == probe {
+                        Echo::Intact
+                    } else {
+                        Echo::Corrupted
+                    });
+                }
+                Some(Err(FrameError::Corrupt | FrameError::Checksum)) if echo.is_none() => {
+                    echo = Some(Echo::Corrupted);
+                }
+                _ => {}
+            }
+        }
+        if let Some(echo) = echo {
+            return Ok(echo);
+        }
+    }
+}
+
+/// Stream numbered frames of `frame_len` payload bytes for `duration`, as fast as the
+/// device accepts them, to a peer running [`receive_throughput`]. `frame_len` is clamped to
+/// between 4 and [`MAX_STREAM_PAYLOAD`].
+pub fn measure_throughput<D: Write>(
+    device: &mut D,
+    duration: impl IntoMillis,
+    clock: &impl Clock,
+    frame_len: usize,
+) -> Result<ThroughputReport, D::Error> {
+    let frame_len = frame_len.clamp(4, MAX_STREAM_PAYLOAD);
+    let deadline = Deadline::after(clock, duration);
+    let mut report = ThroughputReport::default();
+    let mut payload = [0u8; MAX_STREAM_PAYLOAD];
+    let mut wire = [0u8; MAX_ENCODED];
+
+    while !deadline.is_expired(clock) {
+        payload[..4].copy_from_slice(&report.frames.to_le_bytes());
+        // the payload is shorter than the longest frame, so this cannot fail
+        let len = encode(kinds::STREAM, &payload[..frame_len], &mut wire).unwrap();
+        device.write_all(&wire[..len])?;
+        report.frames += 1;
+        report.bytes += frame_len as u32;
+    }
+    device.flush()?;
+
+    report.elapsed_ms = deadline.elapsed_ms(clock);
+    Ok(report)
+}
+
+/// Count the frames sent by [`measure_throughput`] for `duration`. Frames missing from
+/// the sequence between the first and last received are reported as lost.
+pub fn receive_throughput<D: Read + ReadReady>(
+    device: &mut D,
+    duration: impl IntoMillis,
+    clock: &impl Clock,
+) -> Result<ThroughputReport, D::Error> {
+    let deadline = Deadline::after(clock, duration);
+    let mut reader: FrameReader = FrameReader::new();
+    let mut report = ThroughputReport::default();
+    let mut span: Option<(u32, u32)> = None;
+    let mut chunk = [0u8; 32];
+
+    while !deadline.is_expired(clock) {
+        if !device.read_ready()? {
+            continue;
+        }
+
+        let read = device.read(&mut chunk)?;
+        for byte in &chunk[..read] {
+            let Some(Ok(frame)) = reader.push(*byte) else {
+                continue;
+            };
+            let Some(seq) = frame.payload.get(..4) else {
+                continue;
+            };
+            if frame.kind != kinds::STREAM {
+                continue;
+            }
+
+            // the slice is four bytes long
+            let seq = u32::from_le_bytes(seq.try_into().unwrap());
+            span = Some(match span {
+                Some((first, last)) => (first.min(seq), last.max(seq)),
+                None => (seq, seq),
+            });
+            report.frames += 1;
+            report.bytes += frame.payload.len() as u32;
+        }
+    }
+
+    if let Some((first, last)) = span {
+        report.lost = (last - first + 1).saturating_sub(report.frames);
+    }
+    report.elapsed_ms = deadline.elapsed_ms(clock);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockHc12, Settings, SimulatedAir};
+    use core::cell::Cell;
+    use core::convert::Infallible;
+    use embedded_io::ErrorType;
+    use heapless::Vec;
+
+    /// The serial port of `near`, talking to `far` over `air`. Time passes on `near` while
+    /// nothing can be read, and `far` keeps up with it.
+    struct Link<'a> {
+        near: &'a MockHc12,
+        far: &'a MockHc12,
+        air: SimulatedAir<'a>,
+        /// Set when `far` runs [`echo_ready`]
+        echo: Option<FrameReader>,
+        /// The time taken to send each byte
+        byte_ms: u32,
+    }
+
+    impl<'a> Link<'a> {
+        fn new(near: &'a MockHc12, far: &'a MockHc12, air: SimulatedAir<'a>) -> Self {
+            Self {
+                near,
+                far,
+                air,
+                echo: None,
+                byte_ms: 0,
+            }
+        }
+
+        fn echoing(self) -> Self {
+            Self {
+                echo: Some(FrameReader::new()),
+                ..self
+            }
+        }
+
+        fn pump(&mut self) {
+            self.far
+                .advance_ms(self.near.now_ms().saturating_sub(self.far.now_ms()));
+            self.air.pump();
+            if let Some(reader) = &mut self.echo {
+                echo_ready(&mut self.far.serial(), reader).unwrap();
+                self.air.pump();
+            }
+        }
+    }
+
+    impl ErrorType for Link<'_> {
+        type Error = Infallible;
+    }
+
+    impl Write for Link<'_> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let written = self.near.serial().write(buf)?;
+            self.near.advance_ms(self.byte_ms * written as u32);
+            self.pump();
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl Read for Link<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.near.serial().read(buf)
+        }
+    }
+
+    impl ReadReady for Link<'_> {
+        fn read_ready(&mut self) -> Result<bool, Self::Error> {
+            self.pump();
+            let ready = self.near.serial().read_ready()?;
+            if !ready {
+                self.near.advance_ms(1);
+            }
+            Ok(ready)
+        }
+    }
+
+    fn probe_frame(kind: u8, seq: u16) -> Vec<u8, MAX_ENCODED> {
+        let mut wire = [0u8; MAX_ENCODED];
+        let mut probe = [0u8; 8];
+        probe_payload(seq, &mut probe);
+        let len = encode(kind, &probe, &mut wire).unwrap();
+        Vec::from_slice(&wire[..len]).unwrap()
+    }
+
+    #[test]
+    fn every_probe_returns_over_a_perfect_channel() {
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        let mut link = Link::new(&a, &b, SimulatedAir::new(&a, &b, 1)).echoing();
+        let report = measure_per(&mut link, 20, 16, 10, &mut a.delay()).unwrap();
+
+        assert_eq!(
+            report,
+            PerReport {
+                sent: 20,
+                received: 20,
+                corrupted: 0,
+            }
+        );
+        assert_eq!(report.per_ppm(), 0);
+    }
+
+    #[test]
+    fn counts_lost_and_corrupted_probes() {
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        let air = SimulatedAir::new(&a, &b, 3)
+            .loss_ppm(5_000)
+            .corrupt_ppm(5_000);
+        let mut link = Link::new(&a, &b, air).echoing();
+        let report = measure_per(&mut link, 20, 16, 10, &mut a.delay()).unwrap();
+
+        assert_eq!(report.sent, 20);
+        assert!(report.lost() > 0 && report.corrupted > 0);
+        assert_eq!(
+            report.received + report.corrupted + report.lost(),
+            report.sent
+        );
+    }
+
+    #[test]
+    fn stragglers_are_discarded() {
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        // a late reply to an earlier probe is already waiting
+        a.receive(&probe_frame(kinds::PROBE, 7));
+
+        let mut link = Link::new(&a, &b, SimulatedAir::new(&a, &b, 1)).echoing();
+        let report = measure_per(&mut link, 1, 8, 10, &mut a.delay()).unwrap();
+        assert_eq!(report.received, 1);
+        assert_eq!(report.corrupted, 0);
+    }
+
+    #[test]
+    fn probes_too_long_to_send_are_not_echoed() {
+        use crate::framing::{crc16, MAX_PAYLOAD};
+
+        // a valid probe one byte over MAX_PAYLOAD, filling the reader, which encode()
+        // refuses. The payload is chosen so that nothing but the delimiter is zero, leaving
+        // a single COBS block.
+        let mut body: Vec<u8, MAX_ENCODED> = Vec::new();
+        body.push(kinds::PROBE).unwrap();
+        let fill = (1..=u8::MAX)
+            .find(|fill| {
+                let mut probe = body.clone();
+                probe.resize(MAX_PAYLOAD + 2, *fill).unwrap();
+                !crc16(&probe).to_le_bytes().contains(&0)
+            })
+            .unwrap();
+        body.resize(MAX_PAYLOAD + 2, fill).unwrap();
+        let crc = crc16(&body).to_le_bytes();
+        let mut wire: Vec<u8, 300> = Vec::new();
+        wire.push(0xFF).unwrap();
+        wire.extend_from_slice(&body).unwrap();
+        wire.extend_from_slice(&crc).unwrap();
+        wire.push(0).unwrap();
+
+        let b = MockHc12::new();
+        let mut reader: FrameReader = FrameReader::new();
+        let mut check: FrameReader = FrameReader::new();
+        let decoded = wire.iter().find_map(|byte| {
+            check
+                .push(*byte)
+                .map(|frame| frame.map(|f| f.payload.len()))
+        });
+        assert_eq!(decoded, Some(Ok(MAX_PAYLOAD + 1)));
+
+        // the oversized probe is dropped, and the one after it is still answered
+        b.receive(&wire[..200]);
+        assert_eq!(echo_ready(&mut b.serial(), &mut reader), Ok(0));
+        b.receive(&wire[200..]);
+        b.receive(&probe_frame(kinds::PROBE, 1));
+        assert_eq!(echo_ready(&mut b.serial(), &mut reader), Ok(1));
+        assert_eq!(
+            b.transmitted().as_slice(),
+            probe_frame(kinds::PROBE, 1).as_slice()
+        );
+    }
+
+    #[test]
+    fn echoes_probes_until_deadline() {
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        let mut air = SimulatedAir::new(&a, &b, 1);
+
+        // two probes and an unrelated frame, then noise
+        let mut serial = a.serial();
+        for (kind, seq) in [(kinds::PROBE, 1), (0x80, 2), (kinds::PROBE, 3)] {
+            serial.write_all(&probe_frame(kind, seq)).unwrap();
+        }
+        serial.write_all(&[1, 2, 3, 0]).unwrap();
+        air.pump();
+
+        let now = Cell::new(0);
+        let clock = || {
+            now.set(now.get() + 1);
+            now.get()
+        };
+        let deadline = Deadline::after(&clock, 100);
+        assert_eq!(echo_forever(&mut b.serial(), &clock, deadline).unwrap(), 2);
+        air.pump();
+
+        let mut reader: FrameReader = FrameReader::new();
+        let mut seqs: Vec<u8, 4> = Vec::new();
+        let mut chunk = [0u8; 32];
+        loop {
+            let read = serial.read(&mut chunk).unwrap();
+            if read == 0 {
+                break;
+            }
+            for byte in &chunk[..read] {
+                if let Some(Ok(frame)) = reader.push(*byte) {
+                    assert_eq!(frame.kind, kinds::PROBE);
+                    seqs.push(frame.payload[0]).unwrap();
+                }
+            }
+        }
+        assert_eq!(seqs.as_slice(), [1, 3]);
+    }
+
+    #[test]
+    fn throughput_at_known_line_rate() {
+        // a line that takes one millisecond per byte, so 1000 bytes per second
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        let mut link = Link::new(&a, &b, SimulatedAir::new(&a, &b, 1));
+        link.byte_ms = 1;
+        // 15 payload bytes are 20 bytes on the wire, so 10 frames fit in 200ms
+        let sent = measure_throughput(&mut link, 200, &|| a.now_ms(), 15).unwrap();
+        assert_eq!(
+            sent,
+            ThroughputReport {
+                frames: 10,
+                bytes: 150,
+                lost: 0,
+                elapsed_ms: 200,
+            }
+        );
+        assert_eq!(sent.bytes_per_second(), 750);
+
+        let mut link = Link::new(&b, &a, SimulatedAir::new(&b, &a, 1));
+        let received = receive_throughput(&mut link, 200, &|| b.now_ms()).unwrap();
+        assert_eq!(received, sent);
+    }
+
+    #[test]
+    fn throughput_counts_gaps_in_the_sequence() {
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        let air = SimulatedAir::new(&a, &b, 5).loss_ppm(20_000);
+        let mut link = Link::new(&a, &b, air);
+        link.byte_ms = 1;
+        let sent = measure_throughput(&mut link, 200, &|| a.now_ms(), 15).unwrap();
+        assert_eq!(sent.frames, 10);
+
+        let mut link = Link::new(&b, &a, SimulatedAir::new(&b, &a, 1));
+        let received = receive_throughput(&mut link, 200, &|| b.now_ms()).unwrap();
+        assert!(received.lost > 0);
+        assert!(received.frames + received.lost <= sent.frames);
+        assert_eq!(received.bytes, received.frames * 15);
+    }
+
+    #[test]
+    fn unrelated_traffic_does_not_stall() {
+        use crate::test_utils::CountingDelay;
+
+        /// A busy channel, always with another application's frame to read
+        struct Chatter(Vec<u8, MAX_ENCODED>, usize);
+
+        impl ErrorType for Chatter {
+            type Error = Infallible;
+        }
+
+        impl Read for Chatter {
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+                for slot in buf.iter_mut() {
+                    *slot = self.0[self.1];
+                    self.1 = (self.1 + 1) % self.0.len();
+                }
+                Ok(buf.len())
+            }
+        }
+
+        impl ReadReady for Chatter {
+            fn read_ready(&mut self) -> Result<bool, Self::Error> {
+                Ok(true)
+            }
+        }
+
+        impl Write for Chatter {
+            fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> Result<(), Self::Error> {
+                Ok(())
+            }
+        }
+
+        let mut device = Chatter(probe_frame(0x80, 1), 0);
+        let mut delay = CountingDelay::new();
+        let report = measure_per(&mut device, 3, 8, 10, &mut delay).unwrap();
+        assert_eq!(report.lost(), 3);
+
+        let now = Cell::new(0);
+        let clock = || {
+            now.set(now.get() + 1);
+            now.get()
+        };
+        let report = ping(&mut device, &clock, 2, 10).unwrap();
+        assert_eq!(report.lost, 2);
+    }
+
+    #[test]
+    fn ping_reports_round_trip_times() {
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        let air = SimulatedAir::new(&a, &b, 1).latency_ms(5);
+        let mut link = Link::new(&a, &b, air).echoing();
+
+        let report = ping(&mut link, &|| a.now_ms(), 5, 20).unwrap();
+        assert_eq!(
+            report,
+            PingReport {
+                min_ms: 10,
+                max_ms: 10,
+                avg_ms: 10,
+                lost: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn ping_without_answers() {
+        // the peer is on another channel
+        let a = MockHc12::new();
+        let b = MockHc12::with_settings(Settings {
+            channel: 2,
+            ..Settings::default()
+        });
+        let mut link = Link::new(&a, &b, SimulatedAir::new(&a, &b, 1)).echoing();
+
+        let report = ping(&mut link, &|| a.now_ms(), 2, 10).unwrap();
+        assert_eq!(
+            report,
+            PingReport {
+                lost: 2,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[cfg(feature = "programming")]
+    #[test]
+    fn sweeps_the_requested_levels() {
+        use crate::paramaters::Power;
+        use crate::HC12;
+
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        let link = Link::new(&a, &b, SimulatedAir::new(&a, &b, 1)).echoing();
+        let mut delay = a.delay();
+        let mut hc12 = HC12::factor_settings(link, a.set_pin(), &mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+        let before = a.settings().power;
+
+        let reports =
+            measure_per_sweep(&mut hc12, &[Power::MIN, Power::MAX], 5, 8, 10, &mut delay).unwrap();
+        for (index, report) in reports.iter().enumerate() {
+            let swept = index == Power::MIN as usize - 1 || index == Power::MAX as usize - 1;
+            let expected = swept.then_some(PerReport {
+                sent: 5,
+                received: 5,
+                corrupted: 0,
+            });
+            assert_eq!(*report, expected);
+        }
+        assert_eq!(a.settings().power, before.min(Power::MAX as u8));
+        assert_eq!(*hc12.power() as u8, a.settings().power);
+    }
+}
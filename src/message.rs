@@ -0,0 +1,204 @@
+//! Sending and receiving serde types, serialized with postcard.
+//!
+//! [`send_msg`](TransparentHC12::send_msg) serializes a value, wraps it in a
+//! [`MESSAGE`](kinds::MESSAGE) frame and writes it a packet at a time with
+//! [`write_all_chunked`](TransparentHC12::write_all_chunked), so a message longer than a
+//! radio packet is paced like any other long write and reassembled by the framing on the
+//! other side. [`recv_msg`](TransparentHC12::recv_msg) reads until a message frame
+//! arrives and deserializes it, skipping frames of other kinds.
+//!
+//! `N` is the size of the buffers used, and must hold the encoded frame: the serialized
+//! message plus [`OVERHEAD`] bytes, and no more than
+//! [`MAX_ENCODED`](crate::framing::MAX_ENCODED).
+
+use embedded_hal::delay::DelayNs;
+use embedded_io::{Read, ReadReady, Write};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::framing::{encode, kinds, FrameError, FrameReader, OVERHEAD};
+use crate::modes::ValidMode;
+use crate::speeds::ValidSpeed;
+use crate::time::{Clock, Deadline};
+use crate::TransparentHC12;
+
+/// A message could not be sent or received
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MsgError<E> {
+    /// The serial device failed
+    Link(E),
+    /// The value could not be serialized, or did not fit in the buffer
+    Serialize(postcard::Error),
+    /// A frame was malformed, or did not fit in the buffer
+    Frame(FrameError),
+    /// A frame arrived whole, but failed its CRC
+    Checksum,
+    /// A message frame arrived intact, but did not hold a value of the expected type
+    Deserialize(postcard::Error),
+    /// The deadline passed before a message arrived
+    TimedOut,
+}
+
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed>
+where
+    Device: Write,
+    Mode: ValidMode,
+    Speed: ValidSpeed,
+{
+    /// Serialize `msg` and send it in a [`MESSAGE`](kinds::MESSAGE) frame of up to `N`
+    /// bytes, see the [module documentation](crate::message)
+    pub fn send_msg<T: Serialize, const N: usize>(
+        &mut self,
+        msg: &T,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), MsgError<Device::Error>> {
+        let mut payload = [0u8; N];
+        let room = N.saturating_sub(OVERHEAD);
+        let payload = postcard::to_slice(msg, &mut payload[..room]).map_err(MsgError::Serialize)?;
+
+        let mut frame = [0u8; N];
+        let len = encode(kinds::MESSAGE, payload, &mut frame).map_err(MsgError::Frame)?;
+        self.write_all_chunked(&frame[..len], delay)
+            .map_err(MsgError::Link)
+    }
+}
+
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed>
+where
+    Device: Read + ReadReady,
+{
+    /// Read until a [`MESSAGE`](kinds::MESSAGE) frame of up to `N` bytes arrives, and
+    /// deserialize it. Gives up once `deadline` has passed on `clock`. Bytes after the
+    /// message are left unread.
+    pub fn recv_msg<T: DeserializeOwned, const N: usize>(
+        &mut self,
+        clock: &impl Clock,
+        deadline: Deadline,
+    ) -> Result<T, MsgError<Device::Error>> {
+        let mut reader = FrameReader::<N>::new();
+        while !deadline.is_expired(clock) {
+            while self.read_ready().map_err(MsgError::Link)? {
+                let mut byte = [0];
+                if self.read(&mut byte).map_err(MsgError::Link)? == 0 {
+                    break;
+                }
+                match reader.push(byte[0]) {
+                    Some(Ok(frame)) if frame.kind == kinds::MESSAGE => {
+                        return postcard::from_bytes(frame.payload).map_err(MsgError::Deserialize);
+                    }
+                    Some(Err(FrameError::Checksum)) => return Err(MsgError::Checksum),
+                    Some(Err(error)) => return Err(MsgError::Frame(error)),
+                    Some(Ok(_)) | None => {}
+                }
+            }
+        }
+        Err(MsgError::TimedOut)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockHc12, SimulatedAir};
+    use crate::HC12;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Telemetry {
+        sequence: u16,
+        battery_mv: u16,
+        temperature_c: i8,
+        position: (i32, i32),
+        cells_mv: [u16; 4],
+    }
+
+    fn telemetry(sequence: u16) -> Telemetry {
+        Telemetry {
+            sequence,
+            battery_mv: 3712,
+            temperature_c: -7,
+            position: (51_477_928, -1_534),
+            cells_mv: [3701, 3712, 3698, 3720],
+        }
+    }
+
+    #[test]
+    fn round_trip_over_the_air() {
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        let mut air = SimulatedAir::new(&a, &b, 7);
+        let mut delay = a.delay();
+        let mut sender = HC12::factor_settings(a.serial(), a.set_pin(), &mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+        let mut delay = b.delay();
+        let mut receiver = HC12::factor_settings(b.serial(), b.set_pin(), &mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+
+        for sequence in 0..3 {
+            sender
+                .send_msg::<_, 64>(&telemetry(sequence), &mut a.delay())
+                .unwrap();
+        }
+        b.advance_ms(a.now_ms() - b.now_ms());
+        air.pump();
+
+        let deadline = Deadline::after(&|| b.now_ms(), 100);
+        for sequence in 0..3 {
+            let received: Telemetry = receiver
+                .recv_msg::<_, 64>(&|| b.now_ms(), deadline)
+                .unwrap();
+            assert_eq!(received, telemetry(sequence));
+        }
+        assert_eq!(
+            receiver.recv_msg::<Telemetry, 64>(&|| b.now_ms(), Deadline::starting_at(0, 0)),
+            Err(MsgError::TimedOut)
+        );
+    }
+
+    #[test]
+    fn failures_are_told_apart() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+        let clock = || module.now_ms();
+        let deadline = Deadline::after(&clock, 10);
+
+        // too big for the buffer
+        assert!(matches!(
+            hc12.send_msg::<_, 16>(&telemetry(1), &mut delay),
+            Err(MsgError::Serialize(_))
+        ));
+
+        // a frame of another kind is skipped, a damaged one is reported
+        let mut frame = [0; 16];
+        let len = encode(kinds::BEACON, b"hi", &mut frame).unwrap();
+        module.receive(&frame[..len]);
+        let len = encode(kinds::MESSAGE, b"\x01\x02\x03", &mut frame).unwrap();
+        frame[2] ^= 0x10;
+        module.receive(&frame[..len]);
+        assert_eq!(
+            hc12.recv_msg::<Telemetry, 64>(&clock, deadline),
+            Err(MsgError::Checksum)
+        );
+
+        // a code byte pointing past the end of the frame
+        module.receive(&[0x05, 0x04, 0x00]);
+        assert_eq!(
+            hc12.recv_msg::<Telemetry, 64>(&clock, deadline),
+            Err(MsgError::Frame(FrameError::Corrupt))
+        );
+
+        // intact, but not a Telemetry
+        let len = encode(kinds::MESSAGE, b"\x01", &mut frame).unwrap();
+        module.receive(&frame[..len]);
+        assert!(matches!(
+            hc12.recv_msg::<Telemetry, 64>(&clock, deadline),
+            Err(MsgError::Deserialize(_))
+        ));
+    }
+}
@@ -0,0 +1,846 @@
+//! A simulated HC-12, for testing provisioning logic without hardware.
+//!
+//! [`MockHc12`] holds the module's state, and hands out the pieces a driver needs: the
+//! serial port with [`serial`](MockHc12::serial), the SET input with
+//! [`set_pin`](MockHc12::set_pin), and a delay that advances the module's clock with
+//! [`delay`](MockHc12::delay). The module behaves as the datasheet describes:
+//!
+//! - AT commands are only accepted once SET has been low for [`AT_ENTRY_MS`]
+//! - responses arrive after a configurable latency, so a driver that does not wait for
+//!   them sees nothing
+//! - commands are answered with `OK+` echoes, or `ERROR`
+//! - the queries `AT+RB`, `AT+RC`, `AT+RP` and `AT+RF` answer with the stored settings
+//! - settings persist across mode changes, and a new serial speed takes effect when the
+//!   module leaves AT mode
+//! - if the host's serial speed does not match the module's, commands are not understood
+//!   and received traffic is garbled
+//! - after `AT+SLEEP`, the module sleeps once it leaves AT mode, neither sending nor
+//!   receiving until SET is pulled low again
+//!
+//! In transparent mode, written bytes are collected for [`transmitted`](MockHc12::transmitted),
+//! and bytes given to [`receive`](MockHc12::receive) can be read back. [`SimulatedAir`]
+//! connects two modules this way, over an unreliable channel.
+//!
+//! ```
+//! use hc12_rs::mock::MockHc12;
+//! # #[cfg(feature = "programming")]
+//! use hc12_rs::{paramaters::{Channel, Power}, HC12};
+//!
+//! let module = MockHc12::new();
+//! let mut delay = module.delay();
+//! # #[cfg(feature = "programming")]
+//! HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+//!     .unwrap()
+//!     .channel(Channel::new(21).unwrap())
+//!     .power(Power::MAX)
+//!     .program(&mut delay)
+//!     .unwrap();
+//! # #[cfg(feature = "programming")]
+//! assert_eq!(module.settings().channel, 21);
+//! ```
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use core::fmt::Write as _;
+
+use embedded_hal::{delay::DelayNs, digital};
+use embedded_io::{ErrorType, Read, ReadReady, Write, WriteReady};
+use heapless::{Deque, String, Vec};
+
+use crate::airtime::air_bps;
+
+/// How long SET must be low before AT commands are accepted
+pub const AT_ENTRY_MS: u32 = 40;
+
+/// The default delay between a command and its response
+pub const RESPONSE_LATENCY_MS: u32 = 20;
+
+/// The line returned by `AT+V` by default
+pub const FIRMWARE: &str = "www.hc01.com HC-12_V2.6";
+
+/// Serial speeds accepted by `AT+B`
+const SPEEDS: [u32; 8] = [1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200];
+
+/// The transmit power of each level, in dBm, as answered to `AT+RP`
+const POWER_DBM: [i8; 8] = [-1, 2, 5, 8, 11, 14, 17, 20];
+
+/// The persistent settings of the module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Settings {
+    /// Serial speed in bits per second
+    pub baudrate_bps: u32,
+    /// Channel, from 1 to 127
+    pub channel: u8,
+    /// Power level, from 1 to 8
+    pub power: u8,
+    /// Mode number, as in `AT+FUn`
+    pub mode: u8,
+}
+
+impl Default for Settings {
+    /// The factory settings: 9600 bps, channel 1, P8, FU3
+    fn default() -> Self {
+        Self {
+            baudrate_bps: 9600,
+            channel: 1,
+            power: 8,
+            mode: 3,
+        }
+    }
+}
+
+/// A misbehaviour applied to the next AT command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Fault {
+    /// Do not answer
+    Ignore,
+    /// Answer `ERROR`
+    Error,
+    /// Answer `OK+`, but echo a different value, and do not apply it
+    WrongEcho,
+}
+
+struct State {
+    now_ns: u64,
+    set_low_since_ms: Option<u32>,
+    settings: Settings,
+    active_bps: u32,
+    host_bps: u32,
+    latency_ms: u32,
+    fault: Option<Fault>,
+    firmware: &'static str,
+    sleep_requested: bool,
+    asleep: bool,
+    command: Vec<u8, 32>,
+    responses: Deque<(u32, u8), 128>,
+    transmitted: Vec<u8, 256>,
+    received: Deque<u8, 256>,
+}
+
+impl State {
+    fn now_ms(&self) -> u32 {
+        (self.now_ns / 1_000_000) as u32
+    }
+
+    fn in_at_mode(&self) -> bool {
+        self.set_low_since_ms
+            .is_some_and(|since_ms| self.now_ms().wrapping_sub(since_ms) >= AT_ENTRY_MS)
+    }
+
+    fn mismatched(&self) -> bool {
+        self.host_bps != self.active_bps
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        if self.asleep {
+            return;
+        }
+        if self.set_low_since_ms.is_none() {
+            self.transmitted.push(byte).ok();
+            return;
+        }
+        if !self.in_at_mode() || self.mismatched() {
+            return;
+        }
+
+        if byte == b'\n' {
+            let command = core::mem::take(&mut self.command);
+            let command = core::str::from_utf8(&command).unwrap_or("");
+            let Some(response) = self.execute(command.trim_end_matches('\r')) else {
+                return;
+            };
+            let due_ms = self.now_ms().wrapping_add(self.latency_ms);
+            for byte in response.bytes().chain(*b"\r\n") {
+                self.responses.push_back((due_ms, byte)).ok();
+            }
+        } else {
+            self.command.push(byte).ok();
+        }
+    }
+
+    /// Run a command, returning the response line, if any
+    fn execute(&mut self, command: &str) -> Option<String<64>> {
+        let mut response = String::new();
+        match self.fault.take() {
+            Some(Fault::Ignore) => return None,
+            Some(Fault::Error) => {
+                response.push_str("ERROR").ok();
+                return Some(response);
+            }
+            Some(Fault::WrongEcho) => {
+                if let Some(echo) = command.strip_prefix("AT+") {
+                    let (head, last) = echo.split_at(echo.len().saturating_sub(1));
+                    let last = if last == "0" { "1" } else { "0" };
+                    write!(response, "OK+{head}{last}").ok();
+                }
+                return Some(response);
+            }
+            None => {}
+        }
+
+        let mut settings = self.settings;
+        let accepted = match command.strip_prefix("AT") {
+            Some("") => true,
+            Some("+V") => {
+                response.push_str(self.firmware).ok();
+                return Some(response);
+            }
+            Some("+DEFAULT") => {
+                settings = Settings::default();
+                true
+            }
+            Some("+SLEEP") => {
+                self.sleep_requested = true;
+                true
+            }
+            Some("+RB") => {
+                write!(response, "OK+B{}", settings.baudrate_bps).ok();
+                return Some(response);
+            }
+            Some("+RC") => {
+                write!(response, "OK+RC{:03}", settings.channel).ok();
+                return Some(response);
+            }
+            Some("+RP") => {
+                let dbm = POWER_DBM[usize::from(settings.power) - 1];
+                write!(response, "OK+RP:{dbm:+}dBm").ok();
+                return Some(response);
+            }
+            Some("+RF") => {
+                write!(response, "OK+FU{}", settings.mode).ok();
+                return Some(response);
+            }
+            Some("+RX") => {
+                let dbm = POWER_DBM[usize::from(settings.power) - 1];
+                write!(
+                    response,
+                    "OK+B{}\r\nOK+RC{:03}\r\nOK+RP:{dbm:+}dBm\r\nOK+FU{}",
+                    settings.baudrate_bps, settings.channel, settings.mode
+                )
+                .ok();
+                return Some(response);
+            }
+            // the serial format is accepted, but the port keeps exchanging whole bytes
+            Some(format)
+                if matches!(
+                    format.as_bytes(),
+                    [b'+', b'U', b'7'..=b'9', b'N' | b'O' | b'E', b'1'..=b'3']
+                ) =>
+            {
+                true
+            }
+            Some(setting) => {
+                let (name, value) = setting.split_at(
+                    setting
+                        .find(|c: char| c.is_ascii_digit())
+                        .unwrap_or(setting.len()),
+                );
+                match (name, value.parse::<u32>()) {
+                    ("+B", Ok(bps)) if SPEEDS.contains(&bps) => {
+                        settings.baudrate_bps = bps;
+                        true
+                    }
+                    ("+C", Ok(channel @ 1..=127)) if value.len() == 3 => {
+                        settings.channel = channel as u8;
+                        true
+                    }
+                    ("+P", Ok(power @ 1..=8)) => {
+                        settings.power = power as u8;
+                        true
+                    }
+                    ("+FU", Ok(mode @ 1..=4)) => {
+                        settings.mode = mode as u8;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            None => false,
+        };
+
+        if accepted {
+            self.settings = settings;
+            let echo = command.strip_prefix("AT").unwrap_or("");
+            write!(response, "OK{echo}").ok();
+        } else {
+            response.push_str("ERROR").ok();
+        }
+        Some(response)
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        let now_ms = self.now_ms();
+        if let Some((due_ms, byte)) = self.responses.front().copied() {
+            if (now_ms.wrapping_sub(due_ms) as i32) >= 0 {
+                self.responses.pop_front();
+                return Some(byte);
+            }
+        }
+        if self.set_low_since_ms.is_some() {
+            return None;
+        }
+
+        let byte = self.received.pop_front()?;
+        // a mismatched speed garbles every byte
+        Some(if self.mismatched() { !byte } else { byte })
+    }
+
+    fn has_data(&self) -> bool {
+        let now_ms = self.now_ms();
+        let response_due = self
+            .responses
+            .front()
+            .is_some_and(|(due_ms, _)| (now_ms.wrapping_sub(*due_ms) as i32) >= 0);
+        response_due || (self.set_low_since_ms.is_none() && !self.received.is_empty())
+    }
+}
+
+/// A simulated HC-12 module
+pub struct MockHc12 {
+    state: RefCell<State>,
+}
+
+impl Default for MockHc12 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockHc12 {
+    /// A module with factory settings, in transparent mode, and a host at 9600 bps
+    pub fn new() -> Self {
+        Self::with_settings(Settings::default())
+    }
+
+    /// A module that was configured earlier, with the host already at its serial speed
+    pub fn with_settings(settings: Settings) -> Self {
+        Self {
+            state: RefCell::new(State {
+                now_ns: 0,
+                set_low_since_ms: None,
+                settings,
+                active_bps: settings.baudrate_bps,
+                host_bps: settings.baudrate_bps,
+                latency_ms: RESPONSE_LATENCY_MS,
+                fault: None,
+                firmware: FIRMWARE,
+                sleep_requested: false,
+                asleep: false,
+                command: Vec::new(),
+                responses: Deque::new(),
+                transmitted: Vec::new(),
+                received: Deque::new(),
+            }),
+        }
+    }
+
+    /// The serial port of the module
+    pub fn serial(&self) -> MockSerial<'_> {
+        MockSerial { module: self }
+    }
+
+    /// The SET input of the module
+    pub fn set_pin(&self) -> MockSetPin<'_> {
+        MockSetPin { module: self }
+    }
+
+    /// A delay that advances the module's clock
+    pub fn delay(&self) -> MockDelay<'_> {
+        MockDelay { module: self }
+    }
+
+    /// Advance the module's clock
+    pub fn advance_ms(&self, ms: u32) {
+        self.state.borrow_mut().now_ns += ms as u64 * 1_000_000;
+    }
+
+    /// The module's clock, in milliseconds since it was created
+    pub fn now_ms(&self) -> u32 {
+        self.state.borrow().now_ms()
+    }
+
+    /// The stored settings
+    pub fn settings(&self) -> Settings {
+        self.state.borrow().settings
+    }
+
+    /// Whether the module is accepting AT commands
+    pub fn in_at_mode(&self) -> bool {
+        self.state.borrow().in_at_mode()
+    }
+
+    /// Whether the module is asleep after `AT+SLEEP`
+    pub fn is_asleep(&self) -> bool {
+        self.state.borrow().asleep
+    }
+
+    /// Set the delay between a command and its response
+    pub fn set_response_latency_ms(&self, latency_ms: u32) {
+        self.state.borrow_mut().latency_ms = latency_ms;
+    }
+
+    /// Set the speed the host's serial port runs at
+    pub fn set_host_baudrate(&self, bps: u32) {
+        self.state.borrow_mut().host_bps = bps;
+    }
+
+    /// Simulate a reset, such as a brown-out, after which the module runs with `settings`,
+    /// for example older settings than the driver last programmed. Anything being sent,
+    /// received or answered is lost, and the module is awake.
+    pub fn reset(&self, settings: Settings) {
+        let mut state = self.state.borrow_mut();
+        state.settings = settings;
+        state.active_bps = settings.baudrate_bps;
+        state.sleep_requested = false;
+        state.asleep = false;
+        state.command.clear();
+        state.responses.clear();
+        state.received.clear();
+    }
+
+    /// Set the line returned by `AT+V`, [`FIRMWARE`] until then
+    pub fn set_firmware(&self, firmware: &'static str) {
+        self.state.borrow_mut().firmware = firmware;
+    }
+
+    /// Misbehave on the next AT command
+    pub fn inject_fault(&self, fault: Fault) {
+        self.state.borrow_mut().fault = Some(fault);
+    }
+
+    /// Take the bytes written in transparent mode, which would have been sent over the air
+    pub fn transmitted(&self) -> Vec<u8, 256> {
+        core::mem::take(&mut self.state.borrow_mut().transmitted)
+    }
+
+    /// Deliver bytes as if received over the air. Bytes that do not fit, or arrive while
+    /// the module is asleep, are dropped.
+    pub fn receive(&self, bytes: &[u8]) {
+        let mut state = self.state.borrow_mut();
+        if state.asleep {
+            return;
+        }
+        for byte in bytes {
+            state.received.push_back(*byte).ok();
+        }
+    }
+}
+
+/// The serial port of a [`MockHc12`]
+pub struct MockSerial<'a> {
+    module: &'a MockHc12,
+}
+
+impl ErrorType for MockSerial<'_> {
+    type Error = Infallible;
+}
+
+impl Write for MockSerial<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let mut state = self.module.state.borrow_mut();
+        for byte in buf {
+            state.write_byte(*byte);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl Read for MockSerial<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut state = self.module.state.borrow_mut();
+        let mut count = 0;
+        for slot in buf.iter_mut() {
+            let Some(byte) = state.read_byte() else {
+                break;
+            };
+            *slot = byte;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl WriteReady for MockSerial<'_> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+}
+
+impl ReadReady for MockSerial<'_> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.module.state.borrow().has_data())
+    }
+}
+
+/// The SET input of a [`MockHc12`]
+pub struct MockSetPin<'a> {
+    module: &'a MockHc12,
+}
+
+impl digital::ErrorType for MockSetPin<'_> {
+    type Error = Infallible;
+}
+
+impl digital::OutputPin for MockSetPin<'_> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        let mut state = self.module.state.borrow_mut();
+        if state.set_low_since_ms.is_none() {
+            state.set_low_since_ms = Some(state.now_ms());
+            state.command.clear();
+            state.asleep = false;
+        }
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let mut state = self.module.state.borrow_mut();
+        if state.set_low_since_ms.take().is_some() {
+            state.active_bps = state.settings.baudrate_bps;
+            state.asleep = core::mem::take(&mut state.sleep_requested);
+        }
+        Ok(())
+    }
+}
+
+/// A delay advancing the clock of a [`MockHc12`]
+pub struct MockDelay<'a> {
+    module: &'a MockHc12,
+}
+
+impl DelayNs for MockDelay<'_> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.module.state.borrow_mut().now_ns += ns as u64;
+    }
+}
+
+/// Two [`MockHc12`]s within range of each other, over a channel that can lose, delay,
+/// duplicate and corrupt bytes.
+///
+/// [`pump`](SimulatedAir::pump) moves bytes transmitted by either module to the other,
+/// as long as both are on the same channel, in the same mode and at the same air rate.
+/// Bytes arrive once the receiving module's clock has passed their latency. Random
+/// choices come from a generator seeded at creation, so a run can be reproduced.
+pub struct SimulatedAir<'a> {
+    ends: [&'a MockHc12; 2],
+    in_flight: [Deque<(u32, u8), 512>; 2],
+    rng: u32,
+    latency_ms: u32,
+    loss_ppm: u32,
+    duplicate_ppm: u32,
+    corrupt_ppm: u32,
+}
+
+impl<'a> SimulatedAir<'a> {
+    /// A perfect channel between `a` and `b`
+    pub fn new(a: &'a MockHc12, b: &'a MockHc12, seed: u32) -> Self {
+        Self {
+            ends: [a, b],
+            in_flight: [Deque::new(), Deque::new()],
+            // xorshift never leaves zero
+            rng: seed.max(1),
+            latency_ms: 0,
+            loss_ppm: 0,
+            duplicate_ppm: 0,
+            corrupt_ppm: 0,
+        }
+    }
+
+    /// Delay every byte by `latency_ms`
+    pub fn latency_ms(mut self, latency_ms: u32) -> Self {
+        self.latency_ms = latency_ms;
+        self
+    }
+
+    /// Lose each byte with a chance of `ppm` parts per million
+    pub fn loss_ppm(mut self, ppm: u32) -> Self {
+        self.loss_ppm = ppm;
+        self
+    }
+
+    /// Deliver each byte twice with a chance of `ppm` parts per million
+    pub fn duplicate_ppm(mut self, ppm: u32) -> Self {
+        self.duplicate_ppm = ppm;
+        self
+    }
+
+    /// Flip a bit in each byte with a chance of `ppm` parts per million
+    pub fn corrupt_ppm(mut self, ppm: u32) -> Self {
+        self.corrupt_ppm = ppm;
+        self
+    }
+
+    /// Whether the two modules can hear each other
+    pub fn linked(&self) -> bool {
+        let [a, b] = self.ends.map(MockHc12::settings);
+        a.channel == b.channel
+            && a.mode == b.mode
+            && air_bps(a.mode, a.baudrate_bps) == air_bps(b.mode, b.baudrate_bps)
+    }
+
+    /// Carry transmitted bytes into the air, and deliver those that are due. Bytes that
+    /// do not fit in the air are dropped.
+    pub fn pump(&mut self) {
+        let linked = self.linked();
+        for from in 0..2 {
+            let to = 1 - from;
+            let transmitted = self.ends[from].transmitted();
+            if !linked {
+                continue;
+            }
+
+            let due_ms = self.ends[from].now_ms().wrapping_add(self.latency_ms);
+            for mut byte in transmitted {
+                if self.chance(self.loss_ppm) {
+                    continue;
+                }
+                if self.chance(self.corrupt_ppm) {
+                    byte ^= 1 << (self.next() % 8);
+                }
+                self.in_flight[to].push_back((due_ms, byte)).ok();
+                if self.chance(self.duplicate_ppm) {
+                    self.in_flight[to].push_back((due_ms, byte)).ok();
+                }
+            }
+        }
+
+        for to in 0..2 {
+            let now_ms = self.ends[to].now_ms();
+            while let Some((due_ms, byte)) = self.in_flight[to].front().copied() {
+                if (now_ms.wrapping_sub(due_ms) as i32) < 0 {
+                    break;
+                }
+                self.in_flight[to].pop_front();
+                self.ends[to].receive(&[byte]);
+            }
+        }
+    }
+
+    fn chance(&mut self, ppm: u32) -> bool {
+        ppm > 0 && self.next() % 1_000_000 < ppm
+    }
+
+    /// xorshift32
+    fn next(&mut self) -> u32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::digital::OutputPin;
+
+    fn command(module: &MockHc12, command: &str) -> String<32> {
+        let mut serial = module.serial();
+        serial.write_all(command.as_bytes()).unwrap();
+        serial.write_all(b"\r\n").unwrap();
+        module.advance_ms(RESPONSE_LATENCY_MS);
+
+        let mut buffer = [0u8; 32];
+        let len = serial.read(&mut buffer).unwrap();
+        core::str::from_utf8(&buffer[..len])
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    fn enter_at(module: &MockHc12) {
+        module.set_pin().set_low().unwrap();
+        module.advance_ms(AT_ENTRY_MS);
+    }
+
+    #[test]
+    fn answers_only_in_at_mode() {
+        let module = MockHc12::new();
+        assert_eq!(command(&module, "AT+P4"), "");
+        assert_eq!(module.transmitted().as_slice(), b"AT+P4\r\n");
+
+        module.set_pin().set_low().unwrap();
+        assert_eq!(command(&module, "AT+P4"), "");
+        module.advance_ms(AT_ENTRY_MS);
+        assert_eq!(command(&module, "AT+P4"), "OK+P4\r\n");
+        assert_eq!(module.settings().power, 4);
+    }
+
+    #[test]
+    fn responses_wait_for_latency() {
+        let module = MockHc12::new();
+        enter_at(&module);
+        let mut serial = module.serial();
+        serial.write_all(b"AT+V\r\n").unwrap();
+        assert!(!serial.read_ready().unwrap());
+        module.advance_ms(RESPONSE_LATENCY_MS);
+        assert!(serial.read_ready().unwrap());
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        let module = MockHc12::new();
+        enter_at(&module);
+        assert_eq!(command(&module, "AT+C128"), "ERROR\r\n");
+        assert_eq!(command(&module, "AT+B1234"), "ERROR\r\n");
+        assert_eq!(command(&module, "AT+FU5"), "ERROR\r\n");
+        assert_eq!(command(&module, "HELLO"), "ERROR\r\n");
+        assert_eq!(module.settings(), Settings::default());
+    }
+
+    #[test]
+    fn queries_answer_with_the_settings() {
+        let module = MockHc12::with_settings(Settings {
+            baudrate_bps: 4800,
+            channel: 21,
+            power: 1,
+            mode: 1,
+        });
+        enter_at(&module);
+        assert_eq!(command(&module, "AT+RB"), "OK+B4800\r\n");
+        assert_eq!(command(&module, "AT+RC"), "OK+RC021\r\n");
+        assert_eq!(command(&module, "AT+RP"), "OK+RP:-1dBm\r\n");
+        assert_eq!(command(&module, "AT+RF"), "OK+FU1\r\n");
+
+        module.reset(Settings {
+            baudrate_bps: 4800,
+            ..Settings::default()
+        });
+        assert_eq!(command(&module, "AT+RP"), "OK+RP:+20dBm\r\n");
+        assert_eq!(command(&module, "AT+RC"), "OK+RC001\r\n");
+    }
+
+    #[test]
+    fn speed_changes_on_leaving_at_mode() {
+        let module = MockHc12::new();
+        enter_at(&module);
+        assert_eq!(command(&module, "AT+B19200"), "OK+B19200\r\n");
+        assert_eq!(command(&module, "AT"), "OK\r\n");
+        module.set_pin().set_high().unwrap();
+
+        // the host is still at 9600
+        module.receive(b"\x0f");
+        let mut buffer = [0u8; 1];
+        module.serial().read(&mut buffer).unwrap();
+        assert_eq!(buffer, [!0x0f]);
+
+        enter_at(&module);
+        assert_eq!(command(&module, "AT"), "");
+        module.set_host_baudrate(19200);
+        assert_eq!(command(&module, "AT"), "OK\r\n");
+    }
+
+    #[test]
+    fn injected_faults() {
+        let module = MockHc12::new();
+        enter_at(&module);
+
+        module.inject_fault(Fault::Ignore);
+        assert_eq!(command(&module, "AT+P2"), "");
+        module.inject_fault(Fault::Error);
+        assert_eq!(command(&module, "AT+P2"), "ERROR\r\n");
+        module.inject_fault(Fault::WrongEcho);
+        assert_eq!(command(&module, "AT+P2"), "OK+P0\r\n");
+        assert_eq!(module.settings().power, 8);
+
+        assert_eq!(command(&module, "AT+P2"), "OK+P2\r\n");
+    }
+
+    fn read_all(module: &MockHc12) -> Vec<u8, 256> {
+        let mut buffer = [0u8; 256];
+        let len = module.serial().read(&mut buffer).unwrap();
+        Vec::from_slice(&buffer[..len]).unwrap()
+    }
+
+    #[test]
+    fn air_delivers_after_latency() {
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        let mut air = SimulatedAir::new(&a, &b, 1).latency_ms(30);
+
+        a.serial().write_all(b"hello").unwrap();
+        b.serial().write_all(b"world").unwrap();
+        air.pump();
+        assert!(read_all(&b).is_empty());
+
+        a.advance_ms(30);
+        b.advance_ms(30);
+        air.pump();
+        assert_eq!(read_all(&b).as_slice(), b"hello");
+        assert_eq!(read_all(&a).as_slice(), b"world");
+    }
+
+    #[test]
+    fn air_needs_matching_settings() {
+        let a = MockHc12::new();
+        let b = MockHc12::with_settings(Settings {
+            channel: 2,
+            ..Settings::default()
+        });
+        let mut air = SimulatedAir::new(&a, &b, 1);
+        assert!(!air.linked());
+        a.serial().write_all(b"hello").unwrap();
+        air.pump();
+        assert!(read_all(&b).is_empty());
+
+        // FU3 at 9600 and 19200 use different air rates
+        let b = MockHc12::with_settings(Settings {
+            baudrate_bps: 19200,
+            ..Settings::default()
+        });
+        assert!(!SimulatedAir::new(&a, &b, 1).linked());
+    }
+
+    #[test]
+    fn air_is_reproducible() {
+        let run = |seed| {
+            let (a, b) = (MockHc12::new(), MockHc12::new());
+            let mut air = SimulatedAir::new(&a, &b, seed)
+                .loss_ppm(100_000)
+                .duplicate_ppm(100_000)
+                .corrupt_ppm(100_000);
+            a.serial().write_all(&[0x55; 200]).unwrap();
+            air.pump();
+            read_all(&b)
+        };
+
+        let first = run(7);
+        assert_eq!(first, run(7));
+        assert_ne!(first, run(8));
+        assert!(first.len() > 150 && first.len() < 250);
+        assert!(first.iter().any(|byte| *byte != 0x55));
+    }
+
+    #[test]
+    fn corruption_is_caught_by_framing() {
+        use crate::framing::{encode, FrameError, FrameReader, MAX_ENCODED};
+
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        let mut air = SimulatedAir::new(&a, &b, 3).corrupt_ppm(1_000_000);
+        let mut wire = [0u8; MAX_ENCODED];
+        let len = encode(0x80, b"payload", &mut wire).unwrap();
+        // keep the delimiter, so the frame still ends
+        a.serial().write_all(&wire[..len - 1]).unwrap();
+        air.pump();
+        air = air.corrupt_ppm(0);
+        a.serial().write_all(&wire[len - 1..len]).unwrap();
+        air.pump();
+
+        let mut reader: FrameReader = FrameReader::new();
+        let mut results = 0;
+        for byte in read_all(&b) {
+            if let Some(result) = reader.push(byte) {
+                assert!(matches!(
+                    result,
+                    Err(FrameError::Corrupt | FrameError::Checksum | FrameError::TooLong)
+                ));
+                results += 1;
+            }
+        }
+        assert!(results >= 1);
+    }
+}
@@ -1,51 +1,59 @@
-use crate::{
-    commands::Command,
-    speeds::{ValidSpeed, B1200, B2400, B4800},
-};
+use crate::profile::{self, PowerProfile};
+use crate::speeds::{ValidSpeed, B1200, B2400, B4800};
 
 /// A valid Mode for the HC12
-pub trait ValidMode: Default + Command {}
+pub trait ValidMode: Default {
+    /// The number of the mode, as in `AT+FUn`
+    const NUMBER: u8;
+
+    /// Minimum time between the starts of two transmitted packets, in milliseconds, as
+    /// reccomended by the datasheet. Zero when the mode has no pacing requirement.
+    const PACKET_INTERVAL_MS: u32;
+
+    /// Current drawn in this mode, see [`profile`]
+    const PROFILE: PowerProfile;
+}
 
 /// A valid speed combination for a mode
 pub trait ValidModeFor<Speed: ValidSpeed>: ValidMode {}
 
 /// Moderate power saving mode, draws 3.6mA. Can be set to any speed
-#[derive(Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
 pub struct Fu1 {}
-impl ValidMode for Fu1 {}
-impl Command for Fu1 {
-    fn command(&self) -> heapless::String<16> {
-        "AT+FU1".try_into().unwrap()
-    }
+impl ValidMode for Fu1 {
+    const NUMBER: u8 = 1;
+    const PACKET_INTERVAL_MS: u32 = 0;
+    const PROFILE: PowerProfile = profile::FU1;
 }
 
 /// Extreme power saving mode, only supports 1200, 2400, and 4800 BPS
-#[derive(Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
 pub struct Fu2 {}
-impl ValidMode for Fu2 {}
-impl Command for Fu2 {
-    fn command(&self) -> heapless::String<16> {
-        "AT+FU2".try_into().unwrap()
-    }
+impl ValidMode for Fu2 {
+    const NUMBER: u8 = 2;
+    const PACKET_INTERVAL_MS: u32 = 1000;
+    const PROFILE: PowerProfile = profile::FU2;
 }
 /// Standard full-speed mode, any speed supported
-#[derive(Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
 pub struct Fu3 {}
-impl ValidMode for Fu3 {}
-impl Command for Fu3 {
-    fn command(&self) -> heapless::String<16> {
-        "AT+FU3".try_into().unwrap()
-    }
+impl ValidMode for Fu3 {
+    const NUMBER: u8 = 3;
+    const PACKET_INTERVAL_MS: u32 = 0;
+    const PROFILE: PowerProfile = profile::FU3;
 }
 
 /// Maximum range mode, only supports 1200 BPS
-#[derive(Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
 pub struct Fu4 {}
-impl ValidMode for Fu4 {}
-impl Command for Fu4 {
-    fn command(&self) -> heapless::String<16> {
-        "AT+FU4".try_into().unwrap()
-    }
+impl ValidMode for Fu4 {
+    const NUMBER: u8 = 4;
+    const PACKET_INTERVAL_MS: u32 = 2000;
+    const PROFILE: PowerProfile = profile::FU4;
 }
 
 impl<T: ValidSpeed> ValidModeFor<T> for Fu1 {}
@@ -57,15 +65,39 @@ impl ValidModeFor<B4800> for Fu2 {}
 
 impl ValidModeFor<B1200> for Fu4 {}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// A mode known only at runtime, such as one read back from the module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum FuMode {
+    /// See [`Fu1`]
+    Fu1,
+    /// See [`Fu2`]
+    Fu2,
+    /// See [`Fu3`]
+    Fu3,
+    /// See [`Fu4`]
+    Fu4,
+}
+
+impl FuMode {
+    /// The mode with number `number`, as in `AT+FUn`
+    pub const fn from_number(number: u8) -> Option<FuMode> {
+        match number {
+            1 => Some(FuMode::Fu1),
+            2 => Some(FuMode::Fu2),
+            3 => Some(FuMode::Fu3),
+            4 => Some(FuMode::Fu4),
+            _ => None,
+        }
+    }
 
-    #[test]
-    fn mode_commands_are_correct() {
-        assert_eq!(Fu1::default().command().as_str(), "AT+FU1");
-        assert_eq!(Fu2::default().command().as_str(), "AT+FU2");
-        assert_eq!(Fu3::default().command().as_str(), "AT+FU3");
-        assert_eq!(Fu4::default().command().as_str(), "AT+FU4");
+    /// The number of the mode, as in `AT+FUn`
+    pub const fn number(self) -> u8 {
+        match self {
+            FuMode::Fu1 => Fu1::NUMBER,
+            FuMode::Fu2 => Fu2::NUMBER,
+            FuMode::Fu3 => Fu3::NUMBER,
+            FuMode::Fu4 => Fu4::NUMBER,
+        }
     }
 }
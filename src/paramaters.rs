@@ -1,14 +1,13 @@
-use core::fmt::Write;
-use heapless::String;
-
-use crate::commands::Command;
+use core::fmt;
 
 /// A channel - channels between 1 and 127 are valid
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
 pub struct Channel(u8);
 
 /// A bad channel was attempted to be created
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
 pub struct BadChannel(u8);
 
 impl From<Channel> for u8 {
@@ -29,39 +28,99 @@ impl From<u8> for BadChannel {
     }
 }
 
+/// A channel outside the allowed [`ChannelSet`] was selected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct ChannelNotAllowed(pub Channel);
+
 impl Default for Channel {
     fn default() -> Self {
         Channel(1)
     }
 }
 
-impl Command for Channel {
-    fn command(&self) -> heapless::String<16> {
-        let mut s = String::new();
-
-        write!(&mut s, "AT+C{:03}", self.0).ok();
-
-        s
-    }
-}
-
 impl Channel {
     /// Try to create a channel with a u8
-    pub fn new(channel: u8) -> Result<Self, BadChannel> {
+    pub const fn new(channel: u8) -> Result<Self, BadChannel> {
         if channel < 128 && channel > 0 {
             Ok(Self(channel))
         } else {
-            Err(channel.into())
+            Err(BadChannel(channel))
+        }
+    }
+
+    /// Create channel `N`, checked at compile time
+    ///
+    /// ```
+    /// use hc12_rs::paramaters::Channel;
+    ///
+    /// static CHANNEL: Channel = Channel::new_const::<21>();
+    /// assert_eq!(u8::from(CHANNEL), 21);
+    /// ```
+    ///
+    /// A channel outside 1 to 127 does not compile:
+    /// ```compile_fail
+    /// # use hc12_rs::paramaters::Channel;
+    /// static CHANNEL: Channel = Channel::new_const::<200>();
+    /// ```
+    pub const fn new_const<const N: u8>() -> Self {
+        const { assert!(N > 0 && N < 128, "channels are 1 to 127") };
+        Self(N)
+    }
+
+    /// The lowest channel
+    pub const MIN: Channel = Channel(1);
+
+    /// The highest channel
+    pub const MAX: Channel = Channel(127);
+
+    /// Every channel, from the lowest
+    pub fn iter() -> impl DoubleEndedIterator<Item = Channel> {
+        (Self::MIN.0..=Self::MAX.0).map(Channel)
+    }
+
+    /// The channel `offset` channels away, or `None` if that is outside 1 to 127
+    pub const fn checked_add(self, offset: i8) -> Option<Channel> {
+        let channel = self.0 as i16 + offset as i16;
+        if channel >= Self::MIN.0 as i16 && channel <= Self::MAX.0 as i16 {
+            Some(Channel(channel as u8))
+        } else {
+            None
         }
     }
 
+    /// The channel `offset` channels away, stopping at channel 1 or 127
+    pub const fn saturating_add(self, offset: i8) -> Channel {
+        let channel = self.0 as i16 + offset as i16;
+        if channel < Self::MIN.0 as i16 {
+            Self::MIN
+        } else if channel > Self::MAX.0 as i16 {
+            Self::MAX
+        } else {
+            Channel(channel as u8)
+        }
+    }
+
+    /// The channel `offset` channels away, counting on from channel 1 past 127, and back
+    /// from 127 past 1
+    pub const fn wrapping_add(self, offset: i8) -> Channel {
+        let count = Self::MAX.0 as i16;
+        let index = (self.0 as i16 - 1 + offset as i16).rem_euclid(count);
+        Channel(index as u8 + 1)
+    }
+
+    /// How many channels apart two channels are
+    pub const fn distance(self, other: Channel) -> u8 {
+        self.0.abs_diff(other.0)
+    }
+
     /// Get the frequency of the channel, in  MHz
     pub fn mhz(&self) -> f32 {
         433_000.0 + 400.0 * self.0 as f32 / 1000.0
     }
 
     /// Get the frequency of the channel, in KHz
-    pub fn khz(&self) -> u32 {
+    pub const fn khz(&self) -> u32 {
         433_000 + 400 * self.0 as u32
     }
 }
@@ -73,9 +132,155 @@ impl TryFrom<u8> for Channel {
     }
 }
 
+/// A set of channels, such as the channels a product is certified for. Sets can be built
+/// in const context, so a whitelist can be checked at compile time.
+///
+/// ```
+/// use hc12_rs::paramaters::{Channel, ChannelSet};
+///
+/// static CERTIFIED: ChannelSet = ChannelSet::range(1, 40);
+/// assert!(CERTIFIED.contains(Channel::new_const::<40>()));
+/// assert!(!CERTIFIED.contains(Channel::new_const::<41>()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct ChannelSet {
+    // bit `n` is channel `n`, bit 0 is never set
+    bits: u128,
+}
+
+impl Default for ChannelSet {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl ChannelSet {
+    /// No channels
+    pub const EMPTY: ChannelSet = ChannelSet { bits: 0 };
+
+    /// Every channel, from 1 to 127
+    pub const ALL: ChannelSet = ChannelSet { bits: !1 };
+
+    /// Channels `first` to `last`, inclusive. Panics if either is not a valid channel,
+    /// which fails compilation in const context.
+    pub const fn range(first: u8, last: u8) -> Self {
+        assert!(first > 0 && last < 128, "channels are 1 to 127");
+        let mut set = Self::EMPTY;
+        let mut channel = first;
+        while channel <= last {
+            set.bits |= 1 << channel;
+            channel += 1;
+        }
+        set
+    }
+
+    /// The channels in `list`. Panics if any is not a valid channel, which fails
+    /// compilation in const context.
+    pub const fn list(list: &[u8]) -> Self {
+        let mut set = Self::EMPTY;
+        let mut index = 0;
+        while index < list.len() {
+            let channel = list[index];
+            assert!(channel > 0 && channel < 128, "channels are 1 to 127");
+            set.bits |= 1 << channel;
+            index += 1;
+        }
+        set
+    }
+
+    /// This set, with `channel` added
+    pub const fn with(self, channel: Channel) -> Self {
+        Self {
+            bits: self.bits | 1 << channel.0,
+        }
+    }
+
+    /// This set, without `channel`
+    pub const fn without(self, channel: Channel) -> Self {
+        Self {
+            bits: self.bits & !(1 << channel.0),
+        }
+    }
+
+    /// Whether `channel` is in the set
+    pub const fn contains(&self, channel: Channel) -> bool {
+        self.bits & 1 << channel.0 != 0
+    }
+
+    /// `channel`, if it is in the set
+    pub const fn check(&self, channel: Channel) -> Result<Channel, ChannelNotAllowed> {
+        if self.contains(channel) {
+            Ok(channel)
+        } else {
+            Err(ChannelNotAllowed(channel))
+        }
+    }
+
+    /// How many channels are in the set
+    pub const fn len(&self) -> u32 {
+        self.bits.count_ones()
+    }
+
+    /// Whether the set has no channels
+    pub const fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// The channels in the set, from the lowest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Channel> + '_ {
+        Channel::iter().filter(|channel| self.contains(*channel))
+    }
+
+    /// The first channel in the set after `channel`, wrapping around past 127, for
+    /// hopping through the set. `None` if the set is empty.
+    pub fn next_after(&self, channel: Channel) -> Option<Channel> {
+        (1..=127)
+            .map(|offset| channel.wrapping_add(offset))
+            .find(|next| self.contains(*next))
+    }
+}
+
+/// The highest power level this build allows, set by one of the `max-power-p1` to
+/// `max-power-p7` features. Without any it is [`Power::P8`]; enabling more than one does
+/// not build. Levels above it are refused by [`Power::try_from`] and [`Power::from_dbm`],
+/// and programming one fails with `Error::ExceedsBuildCap`. The default power stays P8,
+/// so a capped build must choose its power.
+pub const MAX_POWER: Power = if cfg!(feature = "max-power-p1") {
+    Power::P1
+} else if cfg!(feature = "max-power-p2") {
+    Power::P2
+} else if cfg!(feature = "max-power-p3") {
+    Power::P3
+} else if cfg!(feature = "max-power-p4") {
+    Power::P4
+} else if cfg!(feature = "max-power-p5") {
+    Power::P5
+} else if cfg!(feature = "max-power-p6") {
+    Power::P6
+} else if cfg!(feature = "max-power-p7") {
+    Power::P7
+} else {
+    Power::P8
+};
+
+// the caps are a ladder of exclusive choices, not limits that combine
+const _: () = assert!(
+    cfg!(feature = "max-power-p1") as u8
+        + cfg!(feature = "max-power-p2") as u8
+        + cfg!(feature = "max-power-p3") as u8
+        + cfg!(feature = "max-power-p4") as u8
+        + cfg!(feature = "max-power-p5") as u8
+        + cfg!(feature = "max-power-p6") as u8
+        + cfg!(feature = "max-power-p7") as u8
+        <= 1,
+    "enable at most one of the max-power-p1 to max-power-p7 features"
+);
+
 /// A valid power level
 #[repr(u8)]
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
 pub enum Power {
     P1 = 1,
     P2 = 2,
@@ -84,28 +289,126 @@ pub enum Power {
     P5 = 5,
     P6 = 6,
     P7 = 7,
-    #[default]
     P8 = 8,
 }
 
+impl Default for Power {
+    /// P8, the factory setting, even if the build is capped below it, see [`MAX_POWER`]
+    fn default() -> Self {
+        Power::P8
+    }
+}
+
 impl From<&Power> for u8 {
     fn from(value: &Power) -> Self {
         *value as u8
     }
 }
 
-impl Command for Power {
-    fn command(&self) -> String<16> {
-        let mut s = String::new();
-        let p: u8 = self.into();
-        write!(&mut s, "AT+P{}", p).ok();
-        s
+/// A power level that does not exist was asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum BadPower {
+    /// A level outside 1 to 8
+    Level(u8),
+    /// An output power no level has, in dBm
+    Dbm(i8),
+    /// A level above the build's [`MAX_POWER`]
+    ExceedsBuildCap(Power),
+}
+
+impl TryFrom<u8> for Power {
+    type Error = BadPower;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|power| *power as u8 == value)
+            .ok_or(BadPower::Level(value))?
+            .within_cap()
     }
 }
 
 impl Power {
+    /// The lowest level
+    pub const MIN: Power = Power::P1;
+
+    /// The highest level this build allows, see [`MAX_POWER`]
+    pub const MAX: Power = MAX_POWER;
+
+    /// Every level, from the lowest
+    pub(crate) const ALL: [Power; 8] = [
+        Power::P1,
+        Power::P2,
+        Power::P3,
+        Power::P4,
+        Power::P5,
+        Power::P6,
+        Power::P7,
+        Power::P8,
+    ];
+
+    /// Every level this build allows, from the lowest
+    pub fn iter() -> impl DoubleEndedIterator<Item = Power> {
+        Self::ALL.into_iter().take(Self::MAX as usize)
+    }
+
+    /// This level, if it is not above the build's [`MAX_POWER`]
+    pub const fn within_cap(self) -> Result<Power, BadPower> {
+        if self as u8 > Self::MAX as u8 {
+            Err(BadPower::ExceedsBuildCap(self))
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// The next level up, or `None` at [`MAX`](Self::MAX)
+    pub const fn checked_increase(self) -> Option<Power> {
+        if self as u8 >= Self::MAX as u8 {
+            return None;
+        }
+        // levels are numbered from 1, so the next level is at the index of this one
+        Some(Self::ALL[self as usize])
+    }
+
+    /// The next level down, or `None` at [`MIN`](Self::MIN)
+    pub const fn checked_decrease(self) -> Option<Power> {
+        match self {
+            Power::P1 => None,
+            _ => Some(Self::ALL[self as usize - 2]),
+        }
+    }
+
+    /// The next level up, staying at [`MAX`](Self::MAX)
+    pub const fn saturating_increase(self) -> Power {
+        match self.checked_increase() {
+            Some(power) => power,
+            None => self,
+        }
+    }
+
+    /// The next level down, staying at [`MIN`](Self::MIN)
+    pub const fn saturating_decrease(self) -> Power {
+        match self.checked_decrease() {
+            Some(power) => power,
+            None => self,
+        }
+    }
+
+    /// The level with an output power of exactly `dbm`
+    pub fn from_dbm(dbm: i8) -> Result<Power, BadPower> {
+        Self::level_at(dbm).ok_or(BadPower::Dbm(dbm))?.within_cap()
+    }
+
+    /// The level with an output power of exactly `dbm`, even if it is above the build's
+    /// cap, as when reading back the module's settings
+    pub(crate) fn level_at(dbm: i8) -> Option<Power> {
+        Self::ALL
+            .into_iter()
+            .find(|power| power.power_decible_milliwatts() == dbm)
+    }
+
     /// Power of the modules in dBm
-    pub fn power_decible_milliwatts(&self) -> i8 {
+    pub const fn power_decible_milliwatts(&self) -> i8 {
         match self {
             Power::P1 => -1,
             Power::P2 => 2,
@@ -119,6 +422,421 @@ impl Power {
     }
 }
 
+/// The data bits in each character on the module's serial port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum DataBits {
+    /// 7 data bits
+    Seven = 7,
+    /// 8 data bits
+    Eight = 8,
+    /// 9 data bits
+    Nine = 9,
+}
+
+/// The parity bit on the module's serial port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Parity {
+    /// No parity bit, `N`
+    None,
+    /// Odd parity, `O`
+    Odd,
+    /// Even parity, `E`
+    Even,
+}
+
+/// The stop bits on the module's serial port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum StopBits {
+    /// 1 stop bit
+    One,
+    /// 1.5 stop bits
+    OneAndHalf,
+    /// 2 stop bits
+    Two,
+}
+
+/// A serial format the module's UART cannot use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum BadSerialFormat {
+    /// 7 data bits need a parity bit to make up the 8 bit word
+    SevenBitsWithoutParity,
+    /// 9 data bits leave no room for a parity bit in the 9 bit word
+    NineBitsWithParity,
+}
+
+/// The data, parity and stop bits of the module's serial port, set with `AT+Uxyz`. The
+/// UART sends words of 8 or 9 bits, data and parity together, so 7 data bits need parity
+/// and 9 data bits cannot have it. The default is [`EIGHT_N_ONE`](Self::EIGHT_N_ONE).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct SerialFormat {
+    data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+}
+
+impl Default for SerialFormat {
+    fn default() -> Self {
+        Self::EIGHT_N_ONE
+    }
+}
+
+impl SerialFormat {
+    /// 8 data bits, no parity and 1 stop bit, as the module leaves the factory
+    pub const EIGHT_N_ONE: Self = Self {
+        data_bits: DataBits::Eight,
+        parity: Parity::None,
+        stop_bits: StopBits::One,
+    };
+
+    /// A serial format, if the module can use it
+    pub const fn new(
+        data_bits: DataBits,
+        parity: Parity,
+        stop_bits: StopBits,
+    ) -> Result<Self, BadSerialFormat> {
+        match (data_bits, parity) {
+            (DataBits::Seven, Parity::None) => Err(BadSerialFormat::SevenBitsWithoutParity),
+            (DataBits::Nine, Parity::Odd | Parity::Even) => {
+                Err(BadSerialFormat::NineBitsWithParity)
+            }
+            _ => Ok(Self {
+                data_bits,
+                parity,
+                stop_bits,
+            }),
+        }
+    }
+
+    /// The data bits
+    pub const fn data_bits(&self) -> DataBits {
+        self.data_bits
+    }
+
+    /// The parity
+    pub const fn parity(&self) -> Parity {
+        self.parity
+    }
+
+    /// The stop bits
+    pub const fn stop_bits(&self) -> StopBits {
+        self.stop_bits
+    }
+}
+
+/// The settings of a module that are not part of a device's type: the mode and speed
+/// are type parameters, the channel and power are held here. The default is
+/// [`FACTORY`](Self::FACTORY).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Configuration {
+    /// The radio channel
+    pub channel: Channel,
+    /// The transmit power
+    pub power: Power,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self::FACTORY
+    }
+}
+
+impl Configuration {
+    /// The settings of a new module, or one reset with `AT+DEFAULT`: channel 1 and P8
+    pub const FACTORY: Self = Self::new(Channel(1), Power::P8);
+
+    /// A configuration of `channel` and `power`
+    pub const fn new(channel: Channel, power: Power) -> Self {
+        Self { channel, power }
+    }
+
+    /// A builder validating raw values, such as ones typed at a console, and reporting
+    /// every invalid one at once. Values that are not set are taken from
+    /// [`FACTORY`](Self::FACTORY).
+    ///
+    /// ```
+    /// use hc12_rs::paramaters::{BadPower, Configuration};
+    ///
+    /// let error = Configuration::builder().channel(0).power_dbm(3).build().unwrap_err();
+    /// assert_eq!(error.channel.map(u8::from), Some(0));
+    /// assert_eq!(error.power, Some(BadPower::Dbm(3)));
+    /// ```
+    pub fn builder() -> ConfigurationBuilder {
+        ConfigurationBuilder::default()
+    }
+}
+
+impl Configuration {
+    /// The channel as `CH021 (441.4 MHz)`
+    fn fmt_channel(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let khz = self.channel.khz();
+        write!(
+            f,
+            "CH{:03} ({}.{} MHz)",
+            self.channel.0,
+            khz / 1000,
+            khz % 1000 / 100
+        )
+    }
+
+    /// The power as `P8 (20 dBm)`
+    fn fmt_power(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let power = self.power;
+        write!(
+            f,
+            "P{} ({} dBm)",
+            power as u8,
+            power.power_decible_milliwatts()
+        )
+    }
+}
+
+impl fmt::Display for Configuration {
+    /// `CH021 (441.4 MHz), P8 (20 dBm)`, or with `{:#}` one setting per line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            f.write_str("channel: ")?;
+            self.fmt_channel(f)?;
+            f.write_str("\npower:   ")?;
+            self.fmt_power(f)
+        } else {
+            self.fmt_channel(f)?;
+            f.write_str(", ")?;
+            self.fmt_power(f)
+        }
+    }
+}
+
+#[cfg(feature = "defmt-03")]
+impl defmt::Format for Configuration {
+    fn format(&self, f: defmt::Formatter) {
+        let khz = self.channel.khz();
+        defmt::write!(
+            f,
+            "CH{=u8:03} ({=u32}.{=u32} MHz), P{=u8} ({=i8} dBm)",
+            self.channel.0,
+            khz / 1000,
+            khz % 1000 / 100,
+            self.power as u8,
+            self.power.power_decible_milliwatts(),
+        )
+    }
+}
+
+/// Every invalid value given to a [`ConfigurationBuilder`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct ConfigError {
+    /// The channel, if it was invalid
+    pub channel: Option<BadChannel>,
+    /// The power, if it was invalid
+    pub power: Option<BadPower>,
+}
+
+/// Builds a [`Configuration`] from raw values, see [`Configuration::builder`]. When a
+/// value is set more than once, the last one counts.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct ConfigurationBuilder {
+    channel: Result<Channel, BadChannel>,
+    power: Result<Power, BadPower>,
+}
+
+impl Default for ConfigurationBuilder {
+    fn default() -> Self {
+        Self {
+            channel: Ok(Configuration::FACTORY.channel),
+            power: Ok(Configuration::FACTORY.power),
+        }
+    }
+}
+
+impl ConfigurationBuilder {
+    /// Set the channel from its number
+    pub fn channel(self, channel: u8) -> Self {
+        Self {
+            channel: Channel::new(channel),
+            ..self
+        }
+    }
+
+    /// Set an already valid channel
+    pub fn channel_typed(self, channel: Channel) -> Self {
+        Self {
+            channel: Ok(channel),
+            ..self
+        }
+    }
+
+    /// Set the power from its level, 1 to 8
+    pub fn power(self, level: u8) -> Self {
+        Self {
+            power: Power::try_from(level),
+            ..self
+        }
+    }
+
+    /// Set the power from its output in dBm, which must match a level exactly
+    pub fn power_dbm(self, dbm: i8) -> Self {
+        Self {
+            power: Power::from_dbm(dbm),
+            ..self
+        }
+    }
+
+    /// The configuration, or every value that was invalid
+    pub fn build(self) -> Result<Configuration, ConfigError> {
+        match (self.channel, self.power) {
+            (Ok(channel), Ok(power)) => Ok(Configuration::new(channel, power)),
+            (channel, power) => Err(ConfigError {
+                channel: channel.err(),
+                power: power.err(),
+            }),
+        }
+    }
+}
+
+/// Every setting of a module: the [`Configuration`], and the mode and serial speed that a
+/// device holds in its type
+///
+/// # Encoding
+/// [`to_bytes`](Self::to_bytes) stores it in [`ENCODED_LEN`](Self::ENCODED_LEN) bytes, for
+/// EEPROM or flash. This layout is stable; a change to it gets a new version byte.
+///
+/// | Byte | Contents                                           |
+/// |------|----------------------------------------------------|
+/// | 0    | Layout version, currently 1                        |
+/// | 1    | Mode number, 1 to 4                                |
+/// | 2    | Channel, 1 to 127                                  |
+/// | 3    | Power level, 1 to 8                                |
+/// | 4-7  | Serial speed in bits per second, little endian     |
+/// | 8-9  | Fletcher-16 checksum of bytes 0 to 7, little endian |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FullConfiguration {
+    /// The channel and power
+    pub configuration: Configuration,
+    /// The mode number, as in `AT+FUn`
+    pub mode: u8,
+    /// The serial speed in bits per second
+    pub baudrate_bps: u32,
+}
+
+/// Stored settings could not be read back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum DecodeError {
+    /// Fewer than [`FullConfiguration::ENCODED_LEN`] bytes were given
+    TooShort,
+    /// The bytes were written with a layout this version does not know
+    UnknownVersion(u8),
+    /// The checksum does not match, so the bytes are corrupt or were never written
+    Checksum,
+    /// The checksum matches, but a field is out of range
+    InvalidField,
+}
+
+impl Default for FullConfiguration {
+    /// The factory settings: FU3 at 9600 bps, on [`Configuration::FACTORY`]
+    fn default() -> Self {
+        Self {
+            configuration: Configuration::FACTORY,
+            mode: 3,
+            baudrate_bps: 9600,
+        }
+    }
+}
+
+impl FullConfiguration {
+    /// Bytes in the encoding
+    pub const ENCODED_LEN: usize = 10;
+
+    /// The layout written by [`to_bytes`](Self::to_bytes)
+    const VERSION: u8 = 1;
+
+    /// Encode the settings, see the [layout](Self#encoding)
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0; Self::ENCODED_LEN];
+        bytes[0] = Self::VERSION;
+        bytes[1] = self.mode;
+        bytes[2] = self.configuration.channel.into();
+        bytes[3] = self.configuration.power as u8;
+        bytes[4..8].copy_from_slice(&self.baudrate_bps.to_le_bytes());
+        let checksum = fletcher16(&bytes[..8]);
+        bytes[8..].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Decode settings written by [`to_bytes`](Self::to_bytes). Bytes after the encoding
+    /// are ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let bytes = bytes
+            .get(..Self::ENCODED_LEN)
+            .ok_or(DecodeError::TooShort)?;
+        if u16::from_le_bytes([bytes[8], bytes[9]]) != fletcher16(&bytes[..8]) {
+            return Err(DecodeError::Checksum);
+        }
+        if bytes[0] != Self::VERSION {
+            return Err(DecodeError::UnknownVersion(bytes[0]));
+        }
+
+        let channel = Channel::new(bytes[2]).map_err(|_| DecodeError::InvalidField)?;
+        let power = Power::try_from(bytes[3]).map_err(|_| DecodeError::InvalidField)?;
+        let mode = bytes[1];
+        if !(1..=4).contains(&mode) {
+            return Err(DecodeError::InvalidField);
+        }
+        Ok(Self {
+            configuration: Configuration::new(channel, power),
+            mode,
+            baudrate_bps: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        })
+    }
+}
+
+impl fmt::Display for FullConfiguration {
+    /// `CH021 (441.4 MHz), P8 (20 dBm), FU3 @ 9600`, or with `{:#}` one setting per line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{:#}", self.configuration)?;
+            write!(f, "\nmode:    FU{}", self.mode)?;
+            write!(f, "\nspeed:   {} bps", self.baudrate_bps)
+        } else {
+            write!(
+                f,
+                "{}, FU{} @ {}",
+                self.configuration, self.mode, self.baudrate_bps
+            )
+        }
+    }
+}
+
+#[cfg(feature = "defmt-03")]
+impl defmt::Format for FullConfiguration {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "{}, FU{=u8} @ {=u32}",
+            self.configuration,
+            self.mode,
+            self.baudrate_bps,
+        )
+    }
+}
+
+/// The Fletcher-16 checksum of `bytes`
+pub(crate) fn fletcher16(bytes: &[u8]) -> u16 {
+    let (mut low, mut high) = (0u16, 0u16);
+    for byte in bytes {
+        low = (low + *byte as u16) % 255;
+        high = (high + low) % 255;
+    }
+    high << 8 | low
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,13 +859,6 @@ mod tests {
         assert_eq!(u8::from(default), 1);
     }
 
-    #[test]
-    fn channel_command_format() {
-        let ch = Channel::new(5).unwrap();
-        // zero-padded three-digit decimal
-        assert_eq!(ch.command().as_str(), "AT+C005");
-    }
-
     #[test]
     fn channel_mhz_calculation() {
         let ch = Channel::new(10).unwrap();
@@ -162,10 +873,347 @@ mod tests {
     }
 
     #[test]
-    fn power_variants_and_default() {
-        // Explicit variant
-        assert_eq!(Power::P3.command().as_str(), "AT+P3");
-        // Default is P8
-        assert_eq!(Power::default().command().as_str(), "AT+P8");
+    fn configuration_in_a_static() {
+        static RADIO: Configuration = Configuration::new(Channel::new_const::<40>(), Power::P5);
+        const KHZ: u32 = RADIO.channel.khz();
+        const DBM: i8 = RADIO.power.power_decible_milliwatts();
+
+        assert_eq!(RADIO.channel, Channel::new(40).unwrap());
+        assert_eq!((KHZ, DBM), (449_000, 11));
+        assert!(matches!(Channel::new(0), Err(BadChannel(0))));
+    }
+
+    #[test]
+    fn configuration_display() {
+        extern crate std;
+        use std::format;
+
+        let radio = Configuration::new(Channel::new_const::<21>(), Power::P8);
+        assert_eq!(format!("{radio}"), "CH021 (441.4 MHz), P8 (20 dBm)");
+        assert_eq!(
+            format!("{radio:#}"),
+            "channel: CH021 (441.4 MHz)\npower:   P8 (20 dBm)"
+        );
+
+        let low = Configuration::new(Channel::MIN, Power::P1);
+        assert_eq!(format!("{low}"), "CH001 (433.4 MHz), P1 (-1 dBm)");
+
+        let full = FullConfiguration {
+            configuration: radio,
+            ..Default::default()
+        };
+        assert_eq!(
+            format!("{full}"),
+            "CH021 (441.4 MHz), P8 (20 dBm), FU3 @ 9600"
+        );
+        assert_eq!(
+            format!("{full:#}"),
+            "channel: CH021 (441.4 MHz)\npower:   P8 (20 dBm)\nmode:    FU3\nspeed:   9600 bps"
+        );
+    }
+
+    #[test]
+    fn factory_matches_defaults() {
+        assert_eq!(
+            Configuration::FACTORY,
+            Configuration::new(Channel::default(), Power::default())
+        );
+        assert_eq!(Configuration::default(), Configuration::FACTORY);
+    }
+
+    #[test]
+    fn channel_offsets_at_the_edges() {
+        // channel, offset, checked, saturating, wrapping
+        let cases: [(u8, i8, Option<u8>, u8, u8); 10] = [
+            (1, 0, Some(1), 1, 1),
+            (1, -1, None, 1, 127),
+            (1, -128, None, 1, 127),
+            (2, -1, Some(1), 1, 1),
+            (64, 63, Some(127), 127, 127),
+            (126, 1, Some(127), 127, 127),
+            (127, 1, None, 127, 1),
+            (127, 127, None, 127, 127),
+            (100, 100, None, 127, 73),
+            (127, -126, Some(1), 1, 1),
+        ];
+        for (channel, offset, checked, saturating, wrapping) in cases {
+            let channel = Channel::new(channel).unwrap();
+            let case = (channel, offset);
+            assert_eq!(
+                channel.checked_add(offset).map(u8::from),
+                checked,
+                "{case:?}"
+            );
+            assert_eq!(
+                u8::from(channel.saturating_add(offset)),
+                saturating,
+                "{case:?}"
+            );
+            assert_eq!(u8::from(channel.wrapping_add(offset)), wrapping, "{case:?}");
+        }
+    }
+
+    #[test]
+    fn channel_distance_and_order() {
+        let low = Channel::new(3).unwrap();
+        let high = Channel::new(40).unwrap();
+        assert_eq!(low.distance(high), 37);
+        assert_eq!(high.distance(low), 37);
+        assert_eq!(Channel::MIN.distance(Channel::MAX), 126);
+        assert!(low < high);
+
+        // stepping by one visits every channel in order, and wraps back to the first
+        let mut channel = Channel::MIN;
+        for expected in Channel::iter() {
+            assert_eq!(channel, expected);
+            channel = channel.wrapping_add(1);
+        }
+        assert_eq!(channel, Channel::MIN);
+        assert_eq!(Channel::iter().count(), 127);
+    }
+
+    #[test]
+    fn builder_reports_every_invalid_value() {
+        assert_eq!(Configuration::builder().build(), Ok(Configuration::FACTORY));
+        assert_eq!(
+            Configuration::builder().channel(21).power(1).build(),
+            Ok(Configuration::new(Channel(21), Power::P1))
+        );
+
+        let error = Configuration::builder().channel(128).power(9).build();
+        assert_eq!(
+            error,
+            Err(ConfigError {
+                channel: Some(BadChannel(128)),
+                power: Some(BadPower::Level(9)),
+            })
+        );
+
+        // a later valid value replaces an earlier invalid one
+        let error = Configuration::builder()
+            .channel(0)
+            .channel_typed(Channel::MAX)
+            .power(0)
+            .build();
+        assert_eq!(
+            error,
+            Err(ConfigError {
+                channel: None,
+                power: Some(BadPower::Level(0)),
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "max-power-p4")]
+    fn power_cap_p4() {
+        assert_eq!((MAX_POWER, Power::MAX), (Power::P4, Power::P4));
+        assert_eq!(Power::default(), Power::P8);
+        assert_eq!(Power::iter().count(), 4);
+        assert_eq!(Power::try_from(4), Ok(Power::P4));
+        assert_eq!(
+            Power::try_from(5),
+            Err(BadPower::ExceedsBuildCap(Power::P5))
+        );
+        assert_eq!(
+            Power::from_dbm(20),
+            Err(BadPower::ExceedsBuildCap(Power::P8))
+        );
+        assert_eq!(Power::P4.checked_increase(), None);
+        assert_eq!(
+            Power::P8.within_cap(),
+            Err(BadPower::ExceedsBuildCap(Power::P8))
+        );
+    }
+
+    #[test]
+    fn builder_power_from_dbm() {
+        for power in Power::iter() {
+            let dbm = power.power_decible_milliwatts();
+            let built = Configuration::builder().power_dbm(dbm).build().unwrap();
+            assert_eq!(built.power, power);
+        }
+        assert_eq!(Power::from_dbm(-1), Ok(Power::P1));
+        assert_eq!(Power::from_dbm(19), Err(BadPower::Dbm(19)));
+        assert_eq!(
+            Configuration::builder().power_dbm(21).build(),
+            Err(ConfigError {
+                channel: None,
+                power: Some(BadPower::Dbm(21)),
+            })
+        );
+    }
+
+    #[test]
+    fn full_configuration_round_trips() {
+        let settings = FullConfiguration {
+            configuration: Configuration::new(Channel(127), Power::P1),
+            mode: 4,
+            baudrate_bps: 1200,
+        };
+        let bytes = settings.to_bytes();
+        assert_eq!(&bytes[..8], [1, 4, 127, 1, 0xb0, 0x04, 0, 0]);
+        assert_eq!(FullConfiguration::from_bytes(&bytes), Ok(settings));
+
+        // trailing bytes, such as the rest of an EEPROM page, are ignored
+        let mut page = [0xff; 16];
+        page[..10].copy_from_slice(&settings.to_bytes());
+        assert_eq!(FullConfiguration::from_bytes(&page), Ok(settings));
+    }
+
+    #[test]
+    fn full_configuration_rejects_bad_bytes() {
+        let bytes = FullConfiguration::default().to_bytes();
+        assert_eq!(
+            FullConfiguration::from_bytes(&bytes[..9]),
+            Err(DecodeError::TooShort)
+        );
+
+        // every single bit flip is caught
+        for index in 0..bytes.len() {
+            for bit in 0..8 {
+                let mut corrupt = bytes;
+                corrupt[index] ^= 1 << bit;
+                assert_eq!(
+                    FullConfiguration::from_bytes(&corrupt),
+                    Err(DecodeError::Checksum),
+                    "byte {index} bit {bit}"
+                );
+            }
+        }
+        // erased flash
+        assert_eq!(
+            FullConfiguration::from_bytes(&[0xff; 10]),
+            Err(DecodeError::Checksum)
+        );
+
+        let resealed = |mut bytes: [u8; 10]| {
+            let checksum = fletcher16(&bytes[..8]).to_le_bytes();
+            bytes[8..].copy_from_slice(&checksum);
+            bytes
+        };
+        let mut future = bytes;
+        future[0] = 2;
+        assert_eq!(
+            FullConfiguration::from_bytes(&resealed(future)),
+            Err(DecodeError::UnknownVersion(2))
+        );
+        for (index, value) in [(1, 5), (2, 0), (3, 9)] {
+            let mut invalid = bytes;
+            invalid[index] = value;
+            assert_eq!(
+                FullConfiguration::from_bytes(&resealed(invalid)),
+                Err(DecodeError::InvalidField)
+            );
+        }
+    }
+
+    #[test]
+    fn channel_set_membership() {
+        const CERTIFIED: ChannelSet = ChannelSet::range(1, 40);
+        const LISTED: ChannelSet = ChannelSet::list(&[5, 9, 127]);
+
+        assert_eq!(CERTIFIED.len(), 40);
+        assert!(CERTIFIED.contains(Channel::MIN));
+        assert!(!CERTIFIED.contains(Channel::new(41).unwrap()));
+        assert_eq!(
+            CERTIFIED.check(Channel::new(41).unwrap()),
+            Err(ChannelNotAllowed(Channel::new(41).unwrap()))
+        );
+
+        let listed: heapless::Vec<u8, 3> = LISTED.iter().map(u8::from).collect();
+        assert_eq!(listed, [5, 9, 127]);
+        assert!(LISTED
+            .without(Channel::MAX)
+            .with(Channel::MIN)
+            .contains(Channel::MIN));
+        assert_eq!(ChannelSet::ALL.len(), 127);
+        assert!(ChannelSet::EMPTY.is_empty());
+        assert_eq!(ChannelSet::default(), ChannelSet::ALL);
+    }
+
+    #[test]
+    fn hopping_stays_in_the_set() {
+        let set = ChannelSet::list(&[5, 9, 127]);
+        let mut channel = Channel::new(9).unwrap();
+        let mut visited = [0u8; 4];
+        for slot in visited.iter_mut() {
+            channel = set.next_after(channel).unwrap();
+            *slot = channel.into();
+        }
+        assert_eq!(visited, [127, 5, 9, 127]);
+
+        // a channel outside the set hops to the next one inside it
+        assert_eq!(
+            set.next_after(Channel::new(6).unwrap()).map(u8::from),
+            Some(9)
+        );
+        assert_eq!(ChannelSet::EMPTY.next_after(Channel::MIN), None);
+    }
+
+    #[test]
+    // steps through every level, so only without a cap
+    #[cfg(not(any(
+        feature = "max-power-p1",
+        feature = "max-power-p2",
+        feature = "max-power-p3",
+        feature = "max-power-p4",
+        feature = "max-power-p5",
+        feature = "max-power-p6",
+        feature = "max-power-p7"
+    )))]
+    fn power_steps_and_saturates() {
+        assert_eq!(Power::P4.checked_increase(), Some(Power::P5));
+        assert_eq!(Power::P4.checked_decrease(), Some(Power::P3));
+        assert_eq!(Power::MAX.checked_increase(), None);
+        assert_eq!(Power::MIN.checked_decrease(), None);
+        assert_eq!(Power::P7.saturating_increase(), Power::P8);
+        assert_eq!(Power::MAX.saturating_increase(), Power::MAX);
+        assert_eq!(Power::P2.saturating_decrease(), Power::P1);
+        assert_eq!(Power::MIN.saturating_decrease(), Power::MIN);
+
+        let mut previous = None;
+        for power in Power::iter() {
+            assert_eq!(power.checked_decrease(), previous);
+            assert!(previous < Some(power));
+            previous = Some(power);
+        }
+        assert_eq!(previous, Some(Power::MAX));
+        assert_eq!(Power::iter().count(), 8);
+        assert!(Power::P1 < Power::P8);
+    }
+
+    #[test]
+    fn serial_formats_fit_the_uart_word() {
+        assert_eq!(SerialFormat::default(), SerialFormat::EIGHT_N_ONE);
+        for stop_bits in [StopBits::One, StopBits::OneAndHalf, StopBits::Two] {
+            for parity in [Parity::None, Parity::Odd, Parity::Even] {
+                let format = SerialFormat::new(DataBits::Eight, parity, stop_bits).unwrap();
+                assert_eq!(
+                    (format.data_bits(), format.parity(), format.stop_bits()),
+                    (DataBits::Eight, parity, stop_bits)
+                );
+            }
+            assert_eq!(
+                SerialFormat::new(DataBits::Seven, Parity::None, stop_bits),
+                Err(BadSerialFormat::SevenBitsWithoutParity)
+            );
+            assert!(SerialFormat::new(DataBits::Seven, Parity::Even, stop_bits).is_ok());
+            assert_eq!(
+                SerialFormat::new(DataBits::Nine, Parity::Odd, stop_bits),
+                Err(BadSerialFormat::NineBitsWithParity)
+            );
+            assert!(SerialFormat::new(DataBits::Nine, Parity::None, stop_bits).is_ok());
+        }
+    }
+
+    #[test]
+    fn channel_round_trips_through_u8() {
+        for n in 1..=127 {
+            let channel = Channel::try_from(n).unwrap();
+            assert_eq!(channel, Channel::new(n).unwrap());
+            assert_eq!(u8::from(channel), n);
+        }
+        assert_eq!(Channel::try_from(0), Err(BadChannel(0)));
     }
 }
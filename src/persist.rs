@@ -0,0 +1,368 @@
+//! Keeping a [`FullConfiguration`] in NOR flash across resets.
+//!
+//! The settings are stored twice, in two slots of one erase sector each, starting at the
+//! given offset. [`save_config`] always erases and writes the slot that does not hold the
+//! newest copy, so a reset part way through a save leaves the previous copy intact, and the
+//! two sectors share the wear. [`load_config`] returns the newest copy that passes its
+//! checksum.
+//!
+//! # Record layout
+//! Each slot starts with a record of [`RECORD_LEN`] bytes, padded with `0xff` to the
+//! flash's read and write sizes.
+//!
+//! | Byte  | Contents                                              |
+//! |-------|-------------------------------------------------------|
+//! | 0-3   | Sequence number, little endian, one more at each save |
+//! | 4-13  | [`FullConfiguration::to_bytes`]                       |
+//! | 14-15 | Fletcher-16 checksum of bytes 0 to 13, little endian  |
+//!
+//! ```
+//! # use embedded_storage::nor_flash::{ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash};
+//! # struct Flash([u8; 512]);
+//! # impl ErrorType for Flash { type Error = NorFlashErrorKind; }
+//! # impl ReadNorFlash for Flash {
+//! #     const READ_SIZE: usize = 1;
+//! #     fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+//! #         let offset = offset as usize;
+//! #         bytes.copy_from_slice(&self.0[offset..offset + bytes.len()]);
+//! #         Ok(())
+//! #     }
+//! #     fn capacity(&self) -> usize { self.0.len() }
+//! # }
+//! # impl NorFlash for Flash {
+//! #     const WRITE_SIZE: usize = 4;
+//! #     const ERASE_SIZE: usize = 256;
+//! #     fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+//! #         self.0[from as usize..to as usize].fill(0xff);
+//! #         Ok(())
+//! #     }
+//! #     fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+//! #         let offset = offset as usize;
+//! #         self.0[offset..offset + bytes.len()].copy_from_slice(bytes);
+//! #         Ok(())
+//! #     }
+//! # }
+//! # let mut flash = Flash([0xff; 512]);
+//! use hc12_rs::paramaters::{FullConfiguration, Power};
+//! use hc12_rs::persist::{load_config, save_config};
+//!
+//! // nothing saved yet
+//! assert_eq!(load_config(&mut flash, 0), Ok(None));
+//!
+//! let mut settings = FullConfiguration::default();
+//! settings.configuration.power = Power::MIN;
+//! save_config(&mut flash, 0, &settings).unwrap();
+//! assert_eq!(load_config(&mut flash, 0), Ok(Some(settings)));
+//! ```
+
+use embedded_storage::nor_flash::NorFlash;
+
+use crate::paramaters::{fletcher16, FullConfiguration};
+
+/// Bytes in a record, before padding
+pub const RECORD_LEN: usize = 16;
+
+/// The largest padded record, which limits the flash's read and write sizes
+const MAX_PADDED_LEN: usize = 64;
+
+/// Settings could not be saved or loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum PersistError<E> {
+    /// The flash failed
+    Storage(E),
+    /// The offset is not at the start of an erase sector, the two slots run past the end
+    /// of the flash, or its read or write size is over 64 bytes
+    Layout,
+}
+
+/// A record read back from a slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Record {
+    sequence: u32,
+    config: FullConfiguration,
+}
+
+impl Record {
+    fn encode(&self) -> [u8; RECORD_LEN] {
+        let mut bytes = [0; RECORD_LEN];
+        bytes[..4].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[4..14].copy_from_slice(&self.config.to_bytes());
+        let checksum = fletcher16(&bytes[..14]);
+        bytes[14..].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// The record in `bytes`, or `None` if the slot is erased, half written or corrupt
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if u16::from_le_bytes([bytes[14], bytes[15]]) != fletcher16(&bytes[..14]) {
+            return None;
+        }
+        Some(Self {
+            sequence: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            config: FullConfiguration::from_bytes(&bytes[4..14]).ok()?,
+        })
+    }
+
+    /// Whether this record was saved after `other`, allowing for the sequence number
+    /// wrapping around
+    fn is_newer_than(&self, other: &Self) -> bool {
+        (self.sequence.wrapping_sub(other.sequence) as i32) > 0
+    }
+}
+
+/// Save `config` to the slots starting at `offset`, which must be the start of an erase
+/// sector. Two erase sectors are used.
+pub fn save_config<S: NorFlash>(
+    storage: &mut S,
+    offset: u32,
+    config: &FullConfiguration,
+) -> Result<(), PersistError<S::Error>> {
+    let padded_len = check_layout(storage, offset)?;
+    let [first, second] = read_slots(storage, offset, padded_len)?;
+
+    // overwrite whichever slot does not hold the newest copy
+    let (slot, sequence) = match (first, second) {
+        (Some(first), Some(second)) if second.is_newer_than(&first) => {
+            (0, second.sequence.wrapping_add(1))
+        }
+        (Some(first), _) => (1, first.sequence.wrapping_add(1)),
+        (None, Some(second)) => (0, second.sequence.wrapping_add(1)),
+        (None, None) => (0, 0),
+    };
+    let record = Record {
+        sequence,
+        config: *config,
+    };
+
+    let mut padded = [0xff; MAX_PADDED_LEN];
+    padded[..RECORD_LEN].copy_from_slice(&record.encode());
+    let start = slot_offset::<S>(offset, slot);
+    storage
+        .erase(start, start + S::ERASE_SIZE as u32)
+        .map_err(PersistError::Storage)?;
+    storage
+        .write(start, &padded[..padded_len])
+        .map_err(PersistError::Storage)
+}
+
+/// Load the newest valid settings from the slots starting at `offset`, or `None` if
+/// neither slot holds any
+pub fn load_config<S: NorFlash>(
+    storage: &mut S,
+    offset: u32,
+) -> Result<Option<FullConfiguration>, PersistError<S::Error>> {
+    let padded_len = check_layout(storage, offset)?;
+    let newest = match read_slots(storage, offset, padded_len)? {
+        [Some(first), Some(second)] if second.is_newer_than(&first) => Some(second),
+        [Some(first), _] => Some(first),
+        [None, second] => second,
+    };
+    Ok(newest.map(|record| record.config))
+}
+
+/// The length of a padded record, if the slots fit the flash
+fn check_layout<S: NorFlash>(storage: &S, offset: u32) -> Result<usize, PersistError<S::Error>> {
+    let unit = S::READ_SIZE.max(S::WRITE_SIZE);
+    let padded_len = RECORD_LEN.div_ceil(unit) * unit;
+    let end = offset as usize + 2 * S::ERASE_SIZE;
+    if !(offset as usize).is_multiple_of(S::ERASE_SIZE)
+        || end > storage.capacity()
+        || padded_len > MAX_PADDED_LEN
+    {
+        return Err(PersistError::Layout);
+    }
+    Ok(padded_len)
+}
+
+fn slot_offset<S: NorFlash>(offset: u32, slot: u32) -> u32 {
+    offset + slot * S::ERASE_SIZE as u32
+}
+
+fn read_slots<S: NorFlash>(
+    storage: &mut S,
+    offset: u32,
+    padded_len: usize,
+) -> Result<[Option<Record>; 2], PersistError<S::Error>> {
+    let mut records = [None; 2];
+    for (slot, record) in (0..).zip(records.iter_mut()) {
+        let mut padded = [0; MAX_PADDED_LEN];
+        storage
+            .read(slot_offset::<S>(offset, slot), &mut padded[..padded_len])
+            .map_err(PersistError::Storage)?;
+        *record = Record::decode(&padded);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paramaters::{Channel, Configuration, Power};
+    use embedded_storage::nor_flash::{
+        check_erase, check_read, check_write, ErrorType, NorFlashErrorKind, ReadNorFlash,
+    };
+
+    const SECTOR: usize = 64;
+
+    /// Four sectors of NOR flash, which can only clear bits until erased
+    struct Flash {
+        bytes: [u8; 4 * SECTOR],
+        erases: [u32; 4],
+        /// Fail every write from now on, as if reset part way through a save
+        fail_writes: bool,
+    }
+
+    impl Flash {
+        fn new() -> Self {
+            Self {
+                bytes: [0xff; 4 * SECTOR],
+                erases: [0; 4],
+                fail_writes: false,
+            }
+        }
+    }
+
+    impl ErrorType for Flash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for Flash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            check_read(self, offset, bytes.len())?;
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.bytes[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.bytes.len()
+        }
+    }
+
+    impl NorFlash for Flash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = SECTOR;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            check_erase(self, from, to)?;
+            self.bytes[from as usize..to as usize].fill(0xff);
+            for sector in from as usize / SECTOR..to as usize / SECTOR {
+                self.erases[sector] += 1;
+            }
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            check_write(self, offset, bytes.len())?;
+            if self.fail_writes {
+                return Err(NorFlashErrorKind::Other);
+            }
+            let offset = offset as usize;
+            for (cell, byte) in self.bytes[offset..].iter_mut().zip(bytes) {
+                // writing can clear bits, but not set them
+                assert_eq!(*cell & byte, *byte, "write without erase at {offset}");
+                *cell = *byte;
+            }
+            Ok(())
+        }
+    }
+
+    fn settings(channel: u8) -> FullConfiguration {
+        FullConfiguration {
+            configuration: Configuration::new(Channel::new(channel).unwrap(), Power::P1),
+            mode: 1,
+            baudrate_bps: 19200,
+        }
+    }
+
+    #[test]
+    fn blank_flash_has_no_settings() {
+        assert_eq!(load_config(&mut Flash::new(), 0), Ok(None));
+    }
+
+    #[test]
+    fn saves_alternate_slots_and_load_the_newest() {
+        let mut flash = Flash::new();
+        for channel in 1..=5 {
+            save_config(&mut flash, SECTOR as u32, &settings(channel)).unwrap();
+            assert_eq!(
+                load_config(&mut flash, SECTOR as u32),
+                Ok(Some(settings(channel)))
+            );
+        }
+        // the sectors outside the slots are untouched, and the wear is shared
+        assert_eq!(flash.erases, [0, 3, 2, 0]);
+    }
+
+    #[test]
+    fn interrupted_save_keeps_the_previous_copy() {
+        let mut flash = Flash::new();
+        save_config(&mut flash, 0, &settings(1)).unwrap();
+        save_config(&mut flash, 0, &settings(2)).unwrap();
+
+        // the next save erases the slot holding channel 1, then fails to write it
+        flash.fail_writes = true;
+        assert_eq!(
+            save_config(&mut flash, 0, &settings(3)),
+            Err(PersistError::Storage(NorFlashErrorKind::Other))
+        );
+        assert_eq!(load_config(&mut flash, 0), Ok(Some(settings(2))));
+
+        // and a later save goes to the slot it left erased
+        flash.fail_writes = false;
+        save_config(&mut flash, 0, &settings(4)).unwrap();
+        assert_eq!(load_config(&mut flash, 0), Ok(Some(settings(4))));
+        assert_eq!(flash.erases[..2], [3, 1]);
+    }
+
+    #[test]
+    fn corrupt_copy_is_skipped() {
+        let mut flash = Flash::new();
+        save_config(&mut flash, 0, &settings(1)).unwrap();
+        save_config(&mut flash, 0, &settings(2)).unwrap();
+
+        // a bit of the newest copy's channel is cleared
+        flash.bytes[SECTOR + 6] &= !0x02;
+        assert_eq!(load_config(&mut flash, 0), Ok(Some(settings(1))));
+    }
+
+    #[test]
+    fn sequence_numbers_wrap() {
+        let old = Record {
+            sequence: u32::MAX,
+            config: settings(1),
+        };
+        let new = Record {
+            sequence: 0,
+            config: settings(2),
+        };
+        assert!(new.is_newer_than(&old));
+        assert!(!old.is_newer_than(&new));
+
+        let mut flash = Flash::new();
+        flash.bytes[..RECORD_LEN].copy_from_slice(&old.encode());
+        save_config(&mut flash, 0, &settings(2)).unwrap();
+        assert_eq!(Record::decode(&flash.bytes[SECTOR..]), Some(new));
+        assert_eq!(load_config(&mut flash, 0), Ok(Some(settings(2))));
+    }
+
+    #[test]
+    fn rejects_bad_layouts() {
+        let mut flash = Flash::new();
+        let config = settings(1);
+        // not the start of a sector
+        assert_eq!(
+            save_config(&mut flash, 4, &config),
+            Err(PersistError::Layout)
+        );
+        // the second slot would run past the end
+        assert_eq!(
+            load_config(&mut flash, 3 * SECTOR as u32),
+            Err(PersistError::Layout)
+        );
+        assert_eq!(flash.erases, [0; 4]);
+    }
+}
@@ -0,0 +1,128 @@
+//! Current draw of the module, for estimating battery life.
+//!
+//! Idle currents, the receive current and the transmit current at full power are from
+//! the HC-12 datasheet, revision 2.4. The datasheet gives no transmit current for the
+//! lower power levels, so those are estimates scaled from the transmit current curve in
+//! the Si4463 datasheet, revision 1.1.
+
+use crate::paramaters::Power;
+
+/// Currents drawn by the module in one mode, in microamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct PowerProfile {
+    /// Average current while waiting for traffic
+    pub idle_ua: u32,
+    /// Current while receiving
+    pub rx_ua: u32,
+    /// Current while transmitting, at each power level from [`Power::P1`] to
+    /// [`Power::P8`]
+    pub tx_ua: [u32; 8],
+}
+
+/// Transmit currents shared by all modes
+const TX_UA: [u32; 8] = [
+    13_000, 15_000, 17_000, 20_000, 25_000, 33_000, 50_000, 100_000,
+];
+
+/// Receive current shared by all modes
+const RX_UA: u32 = 16_000;
+
+/// FU1: 3.6 mA idle
+pub const FU1: PowerProfile = PowerProfile {
+    idle_ua: 3_600,
+    rx_ua: RX_UA,
+    tx_ua: TX_UA,
+};
+
+/// FU2: 80 µA idle
+pub const FU2: PowerProfile = PowerProfile {
+    idle_ua: 80,
+    rx_ua: RX_UA,
+    tx_ua: TX_UA,
+};
+
+/// FU3: 16 mA idle, the receiver is always on
+pub const FU3: PowerProfile = PowerProfile {
+    idle_ua: 16_000,
+    rx_ua: RX_UA,
+    tx_ua: TX_UA,
+};
+
+/// FU4: 16 mA idle, the receiver is always on
+pub const FU4: PowerProfile = PowerProfile {
+    idle_ua: 16_000,
+    rx_ua: RX_UA,
+    tx_ua: TX_UA,
+};
+
+impl PowerProfile {
+    /// Current while transmitting at `power`, in microamps
+    pub fn tx_ua(&self, power: Power) -> u32 {
+        self.tx_ua[power as usize - 1]
+    }
+}
+
+/// The average current in microamps, when transmitting at `power` for `tx_duty_ppm` and
+/// receiving for `rx_duty_ppm` parts per million of the time, and idle for the rest
+pub fn estimate_average_current(
+    profile: &PowerProfile,
+    power: Power,
+    tx_duty_ppm: u32,
+    rx_duty_ppm: u32,
+) -> u32 {
+    let tx_ppm = tx_duty_ppm.min(1_000_000) as u64;
+    let rx_ppm = (rx_duty_ppm as u64).min(1_000_000 - tx_ppm);
+    let idle_ppm = 1_000_000 - tx_ppm - rx_ppm;
+
+    let total = tx_ppm * profile.tx_ua(power) as u64
+        + rx_ppm * profile.rx_ua as u64
+        + idle_ppm * profile.idle_ua as u64;
+    total.div_ceil(1_000_000) as u32
+}
+
+/// Hours a battery of `capacity_mah` lasts at an average current of `average_ua`
+pub fn battery_life_hours(capacity_mah: u32, average_ua: u32) -> u32 {
+    if average_ua == 0 {
+        return u32::MAX;
+    }
+    (capacity_mah as u64 * 1000 / average_ua as u64).min(u32::MAX as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::{Fu1, Fu2, Fu3, ValidMode};
+
+    // checked when the tests are compiled
+    const _: () = {
+        assert!(Fu2::PROFILE.idle_ua < Fu1::PROFILE.idle_ua);
+        assert!(Fu1::PROFILE.idle_ua < Fu3::PROFILE.idle_ua);
+    };
+
+    #[test]
+    fn average_current() {
+        assert_eq!(estimate_average_current(&FU2, Power::P8, 0, 0), 80);
+        assert_eq!(
+            estimate_average_current(&FU3, Power::P8, 1_000_000, 0),
+            100_000
+        );
+        // 1% transmitting, 10% receiving: 1000 + 1600 + 0.89 * 80
+        assert_eq!(
+            estimate_average_current(&FU2, Power::P8, 10_000, 100_000),
+            2_672
+        );
+        // receive time is capped to what is left after transmitting
+        assert_eq!(
+            estimate_average_current(&FU1, Power::P1, 500_000, 900_000),
+            14_500
+        );
+    }
+
+    #[test]
+    fn battery_life() {
+        assert_eq!(battery_life_hours(2000, 80), 25_000);
+        assert_eq!(battery_life_hours(2000, 16_000), 125);
+        assert_eq!(battery_life_hours(2000, 0), u32::MAX);
+    }
+}
@@ -0,0 +1,2620 @@
+//! The AT (programming) mode device, and the transitions between it and transparent mode
+
+use core::convert::Infallible;
+use core::fmt;
+use core::marker::PhantomData;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{OutputPin, PinState};
+use embedded_io::{Read, ReadReady, Write};
+use heapless::Vec;
+
+use crate::adapters::SETTLE_MS;
+use crate::capabilities::{Capabilities, CapabilityTable, FirmwareVersion};
+use crate::changes::{ApplyError, ChangeSummary, FieldChange};
+use crate::commands::{
+    exchange, exchange_lines, verify_echo, Command, Ping, Query, Raw, Sleep, Version,
+};
+use crate::diagnostics::{DeviceStatus, Diagnostics};
+use crate::events::{notify, AtEvent, Observer, Transition};
+use crate::modes::*;
+use crate::paramaters::{
+    Channel, ChannelNotAllowed, ChannelSet, Configuration, FullConfiguration, Power, SerialFormat,
+};
+use crate::response;
+use crate::sleep::SleepingHC12;
+use crate::speeds::*;
+use crate::supply::{Supply, SupplyError};
+use crate::time::IntoMillis;
+#[cfg(feature = "transaction-log")]
+use crate::transactions::{Transaction, TransactionLog, DEVICE_LOG_DEPTH};
+use crate::validation::SERIAL_SPEEDS_BPS;
+use crate::{
+    AtRead, Error, Response, RoundTripError, TransparentHC12, RESPONSE_CAPACITY,
+    RESPONSE_TIMEOUT_MS,
+};
+
+/// How long the module is given to act on the programming pin and on AT commands. Clones
+/// are often slower than the original modules, and the originals are often faster than
+/// the datasheet; set these with [`at_timings`](HC12::at_timings), or from the start with
+/// [`factor_settings_with_timings`](HC12::factor_settings_with_timings).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct AtTimings {
+    /// How long a command is given before its answer is read
+    pub command_settle_ms: u32,
+    /// How long the module takes to enter transparent mode once the programming pin is
+    /// released. Bytes written sooner are lost.
+    pub mode_change_settle_ms: u32,
+    /// How much longer than other commands a command changing the serial speed or format
+    /// is given
+    pub baud_change_settle_ms: u32,
+    /// How long the module takes to enter AT mode once the programming pin is pulled low
+    pub pin_settle_ms: u32,
+    /// How long to wait before pinging again, when a ping entering AT mode is unanswered
+    pub ping_retry_ms: u32,
+}
+
+impl AtTimings {
+    /// The datasheet's timings: 40ms for a command, 80ms to leave AT mode and 40ms to
+    /// enter it, with nothing extra for a change of serial speed, which only takes effect
+    /// once AT mode is left. Unanswered pings are retried after 100ms.
+    pub const DATASHEET: Self = Self {
+        command_settle_ms: 40,
+        mode_change_settle_ms: SETTLE_MS,
+        baud_change_settle_ms: 0,
+        pin_settle_ms: 40,
+        ping_retry_ms: 100,
+    };
+
+    /// How long `command` is given before its answer is read
+    pub(crate) fn settle_ms(&self, command: &impl Command) -> u32 {
+        if command.changes_serial() {
+            self.command_settle_ms + self.baud_change_settle_ms
+        } else {
+            self.command_settle_ms
+        }
+    }
+}
+
+impl Default for AtTimings {
+    /// [`AtTimings::DATASHEET`]
+    fn default() -> Self {
+        Self::DATASHEET
+    }
+}
+
+/// AT-mode state that follows the module between programming and transparent mode
+pub(crate) struct Session {
+    pub(crate) observer: Option<Observer>,
+    pub(crate) allowed: ChannelSet,
+    pub(crate) response_timeout_ms: u32,
+    pub(crate) timings: AtTimings,
+    /// What the firmware supports, once its version has been queried
+    pub(crate) capabilities: Option<Capabilities>,
+    /// The serial format last set with `AT+U`
+    pub(crate) serial_format: SerialFormat,
+    #[cfg(feature = "transaction-log")]
+    pub(crate) transactions: TransactionLog<DEVICE_LOG_DEPTH>,
+}
+
+impl Session {
+    pub(crate) const fn new() -> Self {
+        Self {
+            observer: None,
+            allowed: ChannelSet::ALL,
+            response_timeout_ms: RESPONSE_TIMEOUT_MS,
+            timings: AtTimings::DATASHEET,
+            capabilities: None,
+            serial_format: SerialFormat::EIGHT_N_ONE,
+            #[cfg(feature = "transaction-log")]
+            transactions: TransactionLog::new(),
+        }
+    }
+
+    /// Refuse a command the firmware is known not to support. Until the version has been
+    /// queried, every command is allowed.
+    pub(crate) fn permits<D: fmt::Debug, P, const N: usize>(
+        &self,
+        command: &impl Command,
+    ) -> Result<(), Error<D, P, N>> {
+        match (command.requires(), self.capabilities) {
+            (Some(capability), Some(capabilities)) if !capabilities.supports(capability) => {
+                Err(Error::UnsupportedByFirmware(capability))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// An HC-12 device programmer
+///
+/// # Example
+/// Programming writes one AT command for each setting, and expects an `OK` line back
+/// for each. Here the serial port is a [`Duo`](crate::test_utils::Duo), scripted with the
+/// module's answers.
+/// ```
+/// use embedded_hal::digital::PinState;
+/// use hc12_rs::paramaters::{Channel, Power};
+/// use hc12_rs::test_utils::{CountingDelay, Duo, PinLog, Sink, Source};
+/// use hc12_rs::HC12;
+///
+/// let pins = PinLog::new();
+/// let mut delay = CountingDelay::new();
+/// let serial = Duo {
+///     sink: Sink::new(),
+///     src: Source::new().data(b"OK+B4800\r\nOK+FU3\r\nOK+P1\r\nOK+C015\r\n"),
+/// };
+///
+/// let hc12 = HC12::factor_settings(serial, pins.pin(), &mut delay)
+///     .unwrap()
+///     .channel(Channel::new(15).unwrap())
+///     .power(Power::P1)
+///     .b4800()
+///     .fu3()
+///     .program(&mut delay)
+///     .unwrap()
+///     .into_transparent_mode(&mut delay)
+///     .unwrap();
+///
+/// let (serial, _) = hc12.inner();
+/// assert_eq!(
+///     serial.sink.data(),
+///     b"AT+B4800\r\nAT+FU3\r\nAT+P1\r\nAT+C015\r\n"
+/// );
+/// assert_eq!(pins.states().as_slice(), [PinState::Low, PinState::High]);
+/// ```
+pub struct HC12<Device, Pin, Mode, Speed> {
+    device: Device,
+    programming_pin: Pin,
+
+    // zero-sized markers
+    _mode: PhantomData<Mode>,
+    _speed: PhantomData<Speed>,
+
+    channel: Channel,
+    power: Power,
+
+    session: Session,
+}
+
+/// An [`HC12`] in the mode the module reported, from
+/// [`resolve_mode`](HC12::resolve_mode). Match on it to continue in that mode's type.
+pub enum ResolvedMode<Device, Pin, Speed> {
+    /// The module is in FU1
+    Fu1(HC12<Device, Pin, Fu1, Speed>),
+    /// The module is in FU2
+    Fu2(HC12<Device, Pin, Fu2, Speed>),
+    /// The module is in FU3
+    Fu3(HC12<Device, Pin, Fu3, Speed>),
+    /// The module is in FU4
+    Fu4(HC12<Device, Pin, Fu4, Speed>),
+}
+
+impl<Device, Pin, Speed> ResolvedMode<Device, Pin, Speed> {
+    /// The mode the module reported
+    pub fn mode(&self) -> FuMode {
+        match self {
+            Self::Fu1(_) => FuMode::Fu1,
+            Self::Fu2(_) => FuMode::Fu2,
+            Self::Fu3(_) => FuMode::Fu3,
+            Self::Fu4(_) => FuMode::Fu4,
+        }
+    }
+}
+
+impl<Device, Pin, Speed: ValidSpeed> fmt::Debug for ResolvedMode<Device, Pin, Speed> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fu1(hc12) => hc12.fmt(f),
+            Self::Fu2(hc12) => hc12.fmt(f),
+            Self::Fu3(hc12) => hc12.fmt(f),
+            Self::Fu4(hc12) => hc12.fmt(f),
+        }
+    }
+}
+
+/// The mode could not be resolved. The device is handed back unchanged.
+#[derive(Debug)]
+pub struct Unresolved<T, D: fmt::Debug> {
+    /// The device, in the mode it had before
+    pub hc12: T,
+    /// Why the mode was not resolved: the query failed, or
+    /// [`Validation`](Error::Validation) if the mode does not support the serial speed
+    pub error: Error<D>,
+}
+
+/// The module did not answer `AT` once the programming pin was pulled low, so it may not
+/// be in AT mode. The pin has been released again, and the transparent device is handed
+/// back.
+#[derive(Debug)]
+pub struct NotListening<T, D: fmt::Debug, P> {
+    /// The device, in transparent mode
+    pub hc12: T,
+    /// The last ping's failure, or [`PinError`](Error::PinError) if the pin could not be
+    /// switched
+    pub error: Error<D, P>,
+}
+
+/// Shows the mode, speed and configuration. The serial device and pin are not shown, so
+/// they do not need to implement `Debug`.
+impl<Device, Pin, Mode, Speed> fmt::Debug for HC12<Device, Pin, Mode, Speed>
+where
+    Mode: ValidMode,
+    Speed: ValidSpeed,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HC12")
+            .field("mode", &format_args!("FU{}", Mode::NUMBER))
+            .field("baudrate_bps", &Speed::bps())
+            .field("channel", &self.channel)
+            .field("power", &self.power)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "defmt-03")]
+impl<Device, Pin, Mode, Speed> defmt::Format for HC12<Device, Pin, Mode, Speed>
+where
+    Mode: ValidMode,
+    Speed: ValidSpeed,
+{
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "HC12 {{ mode: FU{=u8}, baudrate_bps: {=u32}, channel: {}, power: {}, .. }}",
+            Mode::NUMBER,
+            Speed::bps(),
+            self.channel,
+            self.power,
+        )
+    }
+}
+
+impl<Device, Pin> HC12<Device, Pin, Fu3, B9600>
+where
+    Device: AtRead + Write,
+    Pin: OutputPin,
+{
+    /// Create a new builder in programming mode. The serial port
+    /// MUST be set to 9600 BPS to be able to communicate with the
+    /// on-board microcontroller, in order to program properly.
+    ///
+    /// For most HALs, this is an infallible operation, as setting a pin
+    /// is a default item.
+    ///
+    /// This function will block for not less than 40ms.
+    pub fn factor_settings(
+        device: Device,
+        programming_pin: Pin,
+        delay: &mut impl DelayNs,
+    ) -> Result<Self, Error<Device::Error, Pin::Error>> {
+        Self::factor_settings_with_timings(device, programming_pin, AtTimings::DATASHEET, delay)
+    }
+
+    /// Like [`factor_settings`](Self::factor_settings), but waiting for the module with
+    /// `timings` from the start. Blocks for the pin settle time of `timings`.
+    pub fn factor_settings_with_timings(
+        device: Device,
+        mut programming_pin: Pin,
+        timings: AtTimings,
+        delay: &mut impl DelayNs,
+    ) -> Result<Self, Error<Device::Error, Pin::Error>> {
+        // enter AT (programming) mode
+        programming_pin.set_low().map_err(Error::PinError)?;
+        delay.delay_ms(timings.pin_settle_ms);
+        trace_at!(debug, "HC-12 entered programming mode");
+
+        Ok(HC12 {
+            device,
+            programming_pin,
+            _mode: PhantomData,
+            _speed: PhantomData,
+            channel: Channel::default(),
+            power: Power::default(),
+            session: Session {
+                timings,
+                ..Session::new()
+            },
+        })
+    }
+
+    /// Like [`factor_settings`](Self::factor_settings), but [`ping`](Self::ping) the
+    /// module to check it entered AT mode, up to `attempts` times, see
+    /// [`into_programming_mode_verified`](TransparentHC12::into_programming_mode_verified).
+    // the device handed back on failure is the same size as the one returned on success
+    #[allow(clippy::type_complexity, clippy::result_large_err)]
+    pub fn factor_settings_verified(
+        device: Device,
+        programming_pin: Pin,
+        attempts: u8,
+        delay: &mut impl DelayNs,
+    ) -> Result<
+        Self,
+        NotListening<TransparentHC12<Device, Pin, Fu3, B9600>, Device::Error, Pin::Error>,
+    > {
+        TransparentHC12::assume_programmed(
+            device,
+            programming_pin,
+            Channel::default(),
+            Power::default(),
+        )
+        .into_programming_mode_verified(attempts, delay)
+    }
+}
+
+impl<Device, Pin: OutputPin, Mode> HC12<Device, Pin, Mode, B9600> {
+    /// Power cycle the module with the programming pin held low, so it boots into AT
+    /// mode. A module powered on this way answers at 9600 bps whatever speed it was
+    /// programmed to, so this is only available on a device at 9600 bps.
+    pub fn hard_reset<V: OutputPin>(
+        &mut self,
+        supply: &mut Supply<V>,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), SupplyError<V::Error, Pin::Error>> {
+        supply.power_cycle(&mut self.programming_pin, PinState::Low, delay)
+    }
+}
+
+impl<Device: Read, Pin, Mode, Speed> HC12<Device, Pin, Mode, Speed> {
+    /// For diagnostics only: read whatever bytes the module has sent, straight from the
+    /// serial device. Nothing is recorded in the transaction log or seen by the observer,
+    /// and bytes read here are lost to the next AT exchange.
+    pub fn debug_read_raw(&mut self, buf: &mut [u8]) -> Result<usize, Device::Error> {
+        self.device.read(buf)
+    }
+
+    /// For diagnostics only: read everything the serial device has ready, up to `N`
+    /// bytes, as [`debug_read_raw`](Self::debug_read_raw) does
+    pub fn debug_drain_into<const N: usize>(&mut self) -> Result<Vec<u8, N>, Device::Error>
+    where
+        Device: ReadReady,
+    {
+        let mut drained = Vec::new();
+        let mut buf = [0u8; 16];
+        while !drained.is_full() && self.device.read_ready()? {
+            let room = (N - drained.len()).min(buf.len());
+            let count = self.device.read(&mut buf[..room])?;
+            if count == 0 {
+                break;
+            }
+            // the count is within the room left
+            drained.extend_from_slice(&buf[..count]).ok();
+        }
+        Ok(drained)
+    }
+}
+
+impl<Device, Pin, Mode, Speed> HC12<Device, Pin, Mode, Speed> {
+    /// What the firmware supports, once [`query_version`](Self::query_version) has been
+    /// answered
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        self.session.capabilities
+    }
+
+    /// The serial format the module uses once it leaves AT mode, as last set with
+    /// [`set_serial_format`](Self::set_serial_format)
+    pub fn serial_format(&self) -> SerialFormat {
+        self.session.serial_format
+    }
+
+    /// Set the power of the module. The default power is the maxumum of P8, which a build
+    /// capped with [`MAX_POWER`](crate::paramaters::MAX_POWER) refuses to program
+    pub fn power(self, power: Power) -> Self {
+        HC12 { power, ..self }
+    }
+
+    /// Set the channel. The module by default is set to Channel 0
+    pub fn channel(self, channel: Channel) -> Self {
+        HC12 { channel, ..self }
+    }
+
+    /// Set the channel, if it is in `allowed`
+    pub fn channel_in(
+        self,
+        allowed: &ChannelSet,
+        channel: Channel,
+    ) -> Result<Self, ChannelNotAllowed> {
+        Ok(self.channel(allowed.check(channel)?))
+    }
+
+    /// Only allow programming the channels in `allowed`. The set carries over to the
+    /// transparent device and back; [`program`](Self::program) fails with
+    /// `Error::ChannelNotAllowed`, before sending anything, for any other channel.
+    pub fn allowed_channels(mut self, allowed: ChannelSet) -> Self {
+        self.session.allowed = allowed;
+        self
+    }
+
+    /// Give up on a response with `Error::NoResponse` once the module has sent nothing for
+    /// `timeout`, by default [`RESPONSE_TIMEOUT_MS`]. The timeout carries over to the
+    /// transparent device and back. It is counted while the device is not
+    /// [`ReadReady`], so reads that block until data arrives are never started. Through
+    /// [`Unpolled`](crate::adapters::Unpolled) it is counted while reads return no bytes,
+    /// and a device whose reads block must time out itself.
+    pub fn response_timeout_ms(mut self, timeout: impl IntoMillis) -> Self {
+        self.session.response_timeout_ms = timeout.into_ms();
+        self
+    }
+
+    /// Wait for the module with `timings`, by default [`AtTimings::DATASHEET`]. The
+    /// timings carry over to the transparent device and back.
+    pub fn at_timings(mut self, timings: AtTimings) -> Self {
+        self.session.timings = timings;
+        self
+    }
+
+    /// Set the channel and power together
+    pub fn configuration(self, configuration: Configuration) -> Self {
+        HC12 {
+            channel: configuration.channel,
+            power: configuration.power,
+            ..self
+        }
+    }
+
+    /// The channel and power the device holds, which [`program`](Self::program) sends to
+    /// the module
+    pub fn current_configuration(&self) -> Configuration {
+        Configuration::new(self.channel, self.power)
+    }
+
+    /// The serial speed of the module once it leaves AT mode, in bits per second. The host
+    /// serial port must be switched to it after programming.
+    ///
+    /// ```
+    /// use embedded_io::Write;
+    /// use hc12_rs::mock::MockHc12;
+    /// use hc12_rs::paramaters::Power;
+    /// use hc12_rs::HC12;
+    ///
+    /// let module = MockHc12::new();
+    /// let mut delay = module.delay();
+    /// let hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+    ///     .unwrap()
+    ///     .power(Power::MAX)
+    ///     .b19200()
+    ///     .program(&mut delay)
+    ///     .unwrap();
+    ///
+    /// // reconfigure the host serial port before talking to the module again
+    /// module.set_host_baudrate(hc12.programmed_baudrate_bps());
+    /// let mut hc12 = hc12.into_transparent_mode(&mut delay).unwrap();
+    /// hc12.write_all(b"ping").unwrap();
+    /// assert_eq!(module.transmitted().as_slice(), b"ping");
+    /// ```
+    pub fn programmed_baudrate_bps(&self) -> u32
+    where
+        Speed: ValidSpeed,
+    {
+        Speed::bps()
+    }
+
+    /// The number of the mode the module transmits in once it leaves AT mode, as in
+    /// `AT+FUn`
+    pub fn programmed_mode(&self) -> u8
+    where
+        Mode: ValidMode,
+    {
+        Mode::NUMBER
+    }
+
+    /// Whether `other` holds the same configuration. The mode and speed are part of the
+    /// type, so only the channel and power are compared; the devices and pins are not.
+    pub fn is_same_config<OtherDevice, OtherPin>(
+        &self,
+        other: &HC12<OtherDevice, OtherPin, Mode, Speed>,
+    ) -> bool {
+        self.channel == other.channel && self.power == other.power
+    }
+
+    /// Install an observer, which is called with every AT command and response, and on
+    /// every change between programming and transparent mode. The observer carries over
+    /// to the transparent device.
+    pub fn observer(mut self, observer: Observer) -> Self {
+        self.session.observer = Some(observer);
+        self
+    }
+
+    /// The last AT transactions run by this device, oldest first
+    #[cfg(feature = "transaction-log")]
+    pub fn transaction_log(&self) -> impl Iterator<Item = &Transaction> {
+        self.session.transactions.iter()
+    }
+
+    /// Forget the logged AT transactions
+    #[cfg(feature = "transaction-log")]
+    pub fn clear_log(&mut self) {
+        self.session.transactions.clear();
+    }
+
+    /// Decompose the builder to its serial port and programming pin. The module is left
+    /// in AT mode, with the pin low. The delay is only borrowed by each operation, so
+    /// there is nothing else to hand back.
+    pub fn inner(self) -> (Device, Pin) {
+        (self.device, self.programming_pin)
+    }
+
+    /// Replace or wrap the serial device, keeping the mode, speed and configuration.
+    /// Useful to interpose a logging wrapper after the device has been built.
+    pub fn map_device<NewDevice>(
+        self,
+        f: impl FnOnce(Device) -> NewDevice,
+    ) -> HC12<NewDevice, Pin, Mode, Speed> {
+        HC12 {
+            device: f(self.device),
+            programming_pin: self.programming_pin,
+            _mode: self._mode,
+            _speed: self._speed,
+            channel: self.channel,
+            power: self.power,
+            session: self.session,
+        }
+    }
+
+    /// Replace or wrap the programming pin, keeping the mode, speed and configuration.
+    pub fn map_pin<NewPin>(
+        self,
+        f: impl FnOnce(Pin) -> NewPin,
+    ) -> HC12<Device, NewPin, Mode, Speed> {
+        HC12 {
+            device: self.device,
+            programming_pin: f(self.programming_pin),
+            _mode: self._mode,
+            _speed: self._speed,
+            channel: self.channel,
+            power: self.power,
+            session: self.session,
+        }
+    }
+
+    /// Change the mode and speed markers, keeping everything else
+    fn retype<NewMode, NewSpeed>(self) -> HC12<Device, Pin, NewMode, NewSpeed> {
+        HC12 {
+            device: self.device,
+            programming_pin: self.programming_pin,
+            _mode: PhantomData,
+            _speed: PhantomData,
+            channel: self.channel,
+            power: self.power,
+            session: self.session,
+        }
+    }
+
+    /// Program into Fu1 mode.
+    ///
+    /// Fu1 is a moderate power-saving mode, with an idle current of ~3.5mA.
+    /// Fu1 supports all speeds, but the in-air baudrate remains 250000 bps
+    pub fn fu1(self) -> HC12<Device, Pin, Fu1, Speed>
+    where
+        Speed: ValidSpeed,
+        Fu1: ValidModeFor<Speed> + Default,
+    {
+        self.retype()
+    }
+
+    /// Fu2 is the extreme power-saving mode of the HC-12. This mode only
+    /// supports B1200, B2400, and B4800 only. The in-air baudrate is a uniform 250000 bps.
+    /// It is reccomended to send packets over this mode at a frequency not exceeding 1Hz.
+    pub fn fu2(self) -> HC12<Device, Pin, Fu2, Speed>
+    where
+        Speed: ValidSpeed,
+        Fu2: ValidModeFor<Speed> + Default,
+    {
+        self.retype()
+    }
+
+    /// Fu3 is the premier full-speed mode of the radio module. It accepts any speed, and will
+    /// adjust the in-air speed to the speed of the local serial speed. The higher the speed
+    /// the lower the sensitivity, and thus, the range. This is the default factory  mode of the
+    /// device.
+    pub fn fu3(self) -> HC12<Device, Pin, Fu3, Speed>
+    where
+        Speed: ValidSpeed,
+        Fu3: ValidModeFor<Speed> + Default,
+    {
+        self.retype()
+    }
+
+    /// Fu4 mode is the long range mode of the device, and can achive communication distances of up
+    /// to 1.8km. Only 1200 bps is supported. In the air the baud rate will be redueced to a
+    /// whopping 500bps.
+    ///
+    /// Usage notes:
+    /// - Avoid transmitting more than 60 bytes in a packet
+    /// - Transmit a packet not more than once every two seconds.
+    pub fn fu4(self) -> HC12<Device, Pin, Fu4, Speed>
+    where
+        Speed: ValidSpeed,
+        Fu4: ValidModeFor<Speed> + Default,
+    {
+        self.retype()
+    }
+
+    /// Program into 1200 bps.
+    pub fn b1200(self) -> HC12<Device, Pin, Mode, B1200>
+    where
+        Mode: ValidModeFor<B1200> + Default,
+        B1200: ValidSpeed + Default,
+    {
+        self.retype()
+    }
+
+    /// Program into 2400 bps.
+    pub fn b2400(self) -> HC12<Device, Pin, Mode, B2400>
+    where
+        Mode: ValidModeFor<B2400>,
+    {
+        self.retype()
+    }
+
+    /// Program into 4800 bps.
+    pub fn b4800(self) -> HC12<Device, Pin, Mode, B4800>
+    where
+        Mode: ValidModeFor<B4800>,
+    {
+        self.retype()
+    }
+
+    /// Program into 9600 bps.
+    pub fn b9600(self) -> HC12<Device, Pin, Mode, B9600>
+    where
+        Mode: ValidModeFor<B9600>,
+    {
+        self.retype()
+    }
+
+    /// Program into 19200 bps.
+    pub fn b19200(self) -> HC12<Device, Pin, Mode, B19200>
+    where
+        Mode: ValidModeFor<B19200>,
+    {
+        self.retype()
+    }
+
+    /// Program into 38400 bps.
+    pub fn b38400(self) -> HC12<Device, Pin, Mode, B38400>
+    where
+        Mode: ValidModeFor<B38400>,
+    {
+        self.retype()
+    }
+
+    /// Program into 57600 bps.
+    pub fn b57600(self) -> HC12<Device, Pin, Mode, B57600>
+    where
+        Mode: ValidModeFor<B57600>,
+    {
+        self.retype()
+    }
+
+    /// Program into 115200 bps.
+    pub fn b115200(self) -> HC12<Device, Pin, Mode, B115200>
+    where
+        Mode: ValidModeFor<B115200>,
+    {
+        self.retype()
+    }
+}
+
+impl<Device, Pin, Mode, Speed> HC12<Device, Pin, Mode, Speed>
+where
+    Device: AtRead + Write,
+    Pin: OutputPin,
+    Mode: ValidMode + ValidModeFor<Speed> + Command,
+    Speed: ValidSpeed,
+{
+    /// Program the HC12, returning the programmer so it can be switched to transparent
+    /// mode. A serial device without `ReadReady` can be wrapped in
+    /// [`Unpolled`](crate::adapters::Unpolled). Each answer must echo the value sent, or
+    /// programming stops with [`Mismatch`](Error::Mismatch).
+    pub fn program(mut self, delay: &mut impl DelayNs) -> Result<Self, Error<Device::Error>> {
+        self.session.allowed.check(self.channel)?;
+        self.power
+            .within_cap()
+            .map_err(|_| Error::ExceedsBuildCap(self.power))?;
+        self.run(Speed::default(), delay)?;
+        self.run(Mode::default(), delay)?;
+        self.run(self.power, delay)?;
+        self.run(self.channel, delay)?;
+        Ok(self)
+    }
+
+    /// [`program`](Self::program), after checking the configuration with
+    /// [`validate_for`](Configuration::validate_for). With `strict`, advisories stop
+    /// programming as well as errors. Nothing is sent if programming is stopped.
+    pub fn program_checked(
+        self,
+        strict: bool,
+        delay: &mut impl DelayNs,
+    ) -> Result<Self, Error<Device::Error>> {
+        let configuration = Configuration::new(self.channel, self.power);
+        if let Err(findings) = configuration.validate_for(Mode::NUMBER, Speed::bps()) {
+            if let Some(finding) = findings.iter().find(|finding| strict || finding.is_error()) {
+                return Err(Error::Validation(*finding));
+            }
+        }
+        self.program(delay)
+    }
+
+    /// The mode the module reports with `AT+RF`
+    pub fn query_mode(&mut self, delay: &mut impl DelayNs) -> Result<FuMode, Error<Device::Error>> {
+        let line = self.run(Query::Mode, delay)?;
+        response::mode(line.as_bytes())
+            .and_then(FuMode::from_number)
+            .ok_or(Error::InvalidResponse(line))
+    }
+
+    /// Ask the module for its mode with [`query_mode`](Self::query_mode), and continue in
+    /// that mode's type, for a module whose mode is not known. If the mode cannot be read,
+    /// or does not support the device's serial speed, the device is handed back with the
+    /// error.
+    // the device handed back on failure is the same size as the one returned on success
+    #[allow(clippy::result_large_err)]
+    pub fn resolve_mode(
+        mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<ResolvedMode<Device, Pin, Speed>, Unresolved<Self, Device::Error>> {
+        let mode = match self.query_mode(delay) {
+            Ok(mode) => mode,
+            Err(error) => return Err(Unresolved { hc12: self, error }),
+        };
+        let configuration = Configuration::new(self.channel, self.power);
+        if let Err(findings) = configuration.validate_for(mode.number(), Speed::bps()) {
+            if let Some(finding) = findings.iter().find(|finding| finding.is_error()) {
+                let error = Error::Validation(*finding);
+                return Err(Unresolved { hc12: self, error });
+            }
+        }
+
+        Ok(match mode {
+            FuMode::Fu1 => ResolvedMode::Fu1(self.retype()),
+            FuMode::Fu2 => ResolvedMode::Fu2(self.retype()),
+            FuMode::Fu3 => ResolvedMode::Fu3(self.retype()),
+            FuMode::Fu4 => ResolvedMode::Fu4(self.retype()),
+        })
+    }
+
+    /// Take a diagnostic snapshot: the configuration the driver holds, the settings and
+    /// firmware version reported by the module, and the recent AT transactions. A module
+    /// that does not answer still produces a report, without what it would have said. The
+    /// link statistics are left for the application to fill in.
+    pub fn dump(&mut self, delay: &mut impl DelayNs) -> Diagnostics {
+        let reported = self.query_all(delay).ok();
+        let firmware = match self.run_with::<32>(Version, delay) {
+            // the version line does not contain OK
+            Ok(line) | Err(Error::NoOK(line)) => Some(line),
+            Err(_) => None,
+        };
+
+        Diagnostics {
+            configuration: Some(FullConfiguration {
+                configuration: Configuration::new(self.channel, self.power),
+                mode: self.programmed_mode(),
+                baudrate_bps: self.programmed_baudrate_bps(),
+            }),
+            reported,
+            firmware,
+            link: None,
+            #[cfg(feature = "transaction-log")]
+            transactions: self.session.transactions.iter().cloned().collect(),
+        }
+    }
+
+    /// Ask the module for its firmware version, and keep what `table` says it supports,
+    /// see the [module documentation](crate::capabilities). The version line is returned.
+    /// If the module does not answer, the capabilities kept before are unchanged.
+    pub fn query_version<const T: usize>(
+        &mut self,
+        table: &CapabilityTable<T>,
+        delay: &mut impl DelayNs,
+    ) -> Result<Response<32>, Error<Device::Error, Infallible, 32>> {
+        let line = self.version_line(delay)?;
+        self.session.capabilities = Some(table.lookup(&line));
+        Ok(line)
+    }
+
+    /// The firmware version line, and the version read from it if it could be, for
+    /// branching on firmware differences at runtime. The line is longer than most
+    /// answers, so it has a larger buffer.
+    pub fn firmware_version(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<FirmwareVersion, Error<Device::Error, Infallible, 32>> {
+        self.version_line(delay).map(FirmwareVersion::parse)
+    }
+
+    /// Send `AT+V`, returning the version line
+    fn version_line(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<Response<32>, Error<Device::Error, Infallible, 32>> {
+        match self.run_with::<32>(Version, delay) {
+            // the version line does not contain OK
+            Err(Error::NoOK(line)) => Ok(line),
+            result => result,
+        }
+    }
+
+    /// Read back the settings the module is running with, which may differ from the
+    /// device's if it was set up elsewhere. `AT+RX` is answered with a line for each
+    /// setting, which are read until all four have arrived.
+    pub fn query_all(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<DeviceStatus, Error<Device::Error>> {
+        let command = Query::All.command();
+        #[cfg(feature = "transaction-log")]
+        let sent = command.clone();
+
+        let result = exchange_lines::<_, RESPONSE_CAPACITY, 4>(
+            &mut self.device,
+            command,
+            delay,
+            self.session.observer,
+            self.session.timings.settle_ms(&Query::All),
+            self.session.response_timeout_ms,
+        );
+        #[cfg(feature = "transaction-log")]
+        self.session
+            .transactions
+            .record(sent, result.as_ref().map(|lines| &lines[lines.len() - 1]));
+
+        let [baudrate, channel, power, mode] = result?.into_array().unwrap();
+        let invalid = |line: &Response| Error::InvalidResponse(line.clone());
+        Ok(DeviceStatus {
+            baudrate_bps: reported_baudrate(&baudrate)?,
+            channel: response::channel(channel.as_bytes())
+                .and_then(|channel| Channel::new(channel).ok())
+                .ok_or_else(|| invalid(&channel))?,
+            power: reported_power(&power)?,
+            mode: response::mode(mode.as_bytes())
+                .and_then(FuMode::from_number)
+                .ok_or_else(|| invalid(&mode))?,
+        })
+    }
+
+    /// The serial speed the module reports with `AT+RB`, in bits per second. Use it to
+    /// pick the speed type when taking over a module that was set up elsewhere.
+    pub fn query_baudrate(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<u32, Error<Device::Error>> {
+        let line = self.run(Query::Baudrate, delay)?;
+        reported_baudrate(&line)
+    }
+
+    /// The channel the module reports with `AT+RC`. The device takes it over, so
+    /// [`current_configuration`](Self::current_configuration) reflects the module
+    /// afterwards.
+    pub fn query_channel(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<Channel, Error<Device::Error>> {
+        let line = self.run(Query::Channel, delay)?;
+        let channel = response::channel(line.as_bytes())
+            .and_then(|channel| Channel::new(channel).ok())
+            .ok_or_else(|| Error::InvalidResponse(line.clone()))?;
+        self.channel = channel;
+        Ok(channel)
+    }
+
+    /// The power level the module reports with `AT+RP`, matched by its output in dBm. The
+    /// device takes it over, even if it is above the build's
+    /// [`MAX_POWER`](crate::paramaters::MAX_POWER), so [`program`](Self::program) refuses
+    /// to send it back.
+    pub fn query_power(&mut self, delay: &mut impl DelayNs) -> Result<Power, Error<Device::Error>> {
+        let line = self.run(Query::Power, delay)?;
+        let power = reported_power(&line)?;
+        self.power = power;
+        Ok(power)
+    }
+
+    /// Set the data, parity and stop bits of the module's serial port with `AT+Uxyz`. The
+    /// module keeps 8N1 in AT mode and uses the new format once it leaves, so the host's
+    /// UART must be reconfigured to match after
+    /// [`into_transparent_mode`](Self::into_transparent_mode); the device remembers the
+    /// format, see [`serial_format`](Self::serial_format). Firmware that does not support
+    /// it, such as V2.3, is refused once its version is known. Returns the module's
+    /// answer, such as `OK+U8E1`.
+    pub fn set_serial_format(
+        &mut self,
+        format: SerialFormat,
+        delay: &mut impl DelayNs,
+    ) -> Result<Response, Error<Device::Error>> {
+        let line = self.run(format, delay)?;
+        self.session.serial_format = format;
+        Ok(line)
+    }
+
+    /// Send a bare `AT` and check the module answers `OK`, to confirm it is in AT mode
+    pub fn ping(&mut self, delay: &mut impl DelayNs) -> Result<(), Error<Device::Error>> {
+        let line = self.run(Ping, delay)?;
+        if line.trim_end() != "OK" {
+            return Err(Error::InvalidResponse(line));
+        }
+        Ok(())
+    }
+
+    /// Send a command the crate does not model, such as one from newer firmware, and
+    /// return the line that came back, whether or not it is OK. `command` is sent as
+    /// given, such as `AT+RX`, followed by `\r\n`. Anything already waiting on the serial
+    /// device is discarded first, so the answer is not mistaken for a late reply to an
+    /// earlier command. If nothing comes back within the response timeout, the line is
+    /// empty, and a line that stops part way is returned as it is. Only the first line of
+    /// a longer answer is read.
+    pub fn send_raw_at<const N: usize>(
+        &mut self,
+        command: &str,
+        delay: &mut impl DelayNs,
+    ) -> Result<Response<N>, Error<Device::Error, Infallible, N>> {
+        match self.send_raw(command, delay) {
+            Ok(line) | Err(Error::NoOK(line) | Error::Unterminated(line)) => Ok(line),
+            Err(Error::NoResponse) => Ok(Response::new()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Like [`send_raw_at`](Self::send_raw_at), but fail as a modelled command would if
+    /// the answer is not OK, or nothing came back
+    pub fn send_raw_at_expect_ok<const N: usize>(
+        &mut self,
+        command: &str,
+        delay: &mut impl DelayNs,
+    ) -> Result<Response<N>, Error<Device::Error, Infallible, N>> {
+        self.send_raw(command, delay)
+    }
+
+    /// Discard anything waiting on the serial device, then run `command`
+    fn send_raw<const N: usize>(
+        &mut self,
+        command: &str,
+        delay: &mut impl DelayNs,
+    ) -> Result<Response<N>, Error<Device::Error, Infallible, N>> {
+        let command = command.try_into().map_err(|_| Error::CommandTooLong)?;
+        let mut stale = [0u8; 16];
+        // a read with nothing waiting would block until the answer
+        while self.device.response_ready()? && self.device.read(&mut stale)? > 0 {}
+        self.run_with(Raw(command), delay)
+    }
+
+    /// Run a single AT command, recording it in the transaction log
+    fn run(
+        &mut self,
+        command: impl Command,
+        delay: &mut impl DelayNs,
+    ) -> Result<Response, Error<Device::Error>> {
+        self.run_with(command, delay)
+    }
+
+    /// Run a single AT command with a response buffer of `N` bytes, recording it in the
+    /// transaction log
+    fn run_with<const N: usize>(
+        &mut self,
+        command: impl Command,
+        delay: &mut impl DelayNs,
+    ) -> Result<Response<N>, Error<Device::Error, Infallible, N>> {
+        self.session.permits(&command)?;
+        let text = command.command();
+        #[cfg(feature = "transaction-log")]
+        let sent = text.clone();
+
+        let result = exchange(
+            &mut self.device,
+            text,
+            delay,
+            self.session.observer,
+            self.session.timings.settle_ms(&command),
+            self.session.response_timeout_ms,
+        )
+        .and_then(|line| verify_echo(&command, line));
+
+        #[cfg(feature = "transaction-log")]
+        self.session.transactions.record(sent, result.as_ref());
+        result
+    }
+
+    /// Return the HC-12 to transparent mode. For most HALs, this is
+    /// infallible, as it only relies on setting a pin high or low.
+    /// This function will block for the mode change settle time of the
+    /// [`AtTimings`], by default 80ms.
+    pub fn into_transparent_mode(
+        self,
+        delay: &mut impl DelayNs,
+    ) -> Result<TransparentHC12<Device, Pin, Mode, Speed>, Pin::Error> {
+        let hc12 = self.into_transparent_mode_unsettled()?;
+        delay.delay_ms(hc12.session.timings.mode_change_settle_ms);
+        Ok(hc12)
+    }
+
+    /// Release the programming pin and return at once, without waiting the
+    /// [`mode_change_settle_ms`](AtTimings::mode_change_settle_ms) the module takes to
+    /// enter transparent mode. Bytes written before then
+    /// are lost; wrap the device in a [`SettleGate`](crate::adapters::SettleGate) to hold
+    /// them back.
+    pub fn into_transparent_mode_unsettled(
+        mut self,
+    ) -> Result<TransparentHC12<Device, Pin, Mode, Speed>, Pin::Error> {
+        self.programming_pin.set_high()?;
+        trace_at!(debug, "HC-12 entered transparent mode");
+        notify(self.session.observer, || {
+            AtEvent::TransitionPerformed(Transition::IntoTransparent)
+        });
+
+        Ok(self.released())
+    }
+
+    /// The device in transparent mode, once the programming pin has been released
+    fn released(self) -> TransparentHC12<Device, Pin, Mode, Speed> {
+        TransparentHC12 {
+            device: self.device,
+            pin: self.programming_pin,
+            mode: PhantomData,
+            speed: PhantomData,
+            channel: self.channel,
+            power: self.power,
+            unsent: 0,
+            session: self.session,
+        }
+    }
+
+    /// Put the module to sleep with `AT+SLEEP` and leave AT mode, see
+    /// [`sleep`](crate::sleep). Blocks for at least 80ms, plus the exchange. If the
+    /// module does not answer `OK+SLEEP`, the pin is left low.
+    #[allow(clippy::type_complexity)]
+    pub fn into_sleep(
+        mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<SleepingHC12<Device, Pin, Mode, Speed>, Error<Device::Error, Pin::Error>> {
+        let line = self.run(Sleep, delay).map_err(Error::with_pin)?;
+        if line.trim_end() != "OK+SLEEP" {
+            return Err(Error::InvalidResponse(line));
+        }
+        let hc12 = self.into_transparent_mode(delay).map_err(Error::PinError)?;
+        Ok(SleepingHC12 { hc12 })
+    }
+}
+
+/// The serial speed in an answer to `AT+RB`, if it is one the module supports
+fn reported_baudrate<D: fmt::Debug>(line: &Response) -> Result<u32, Error<D>> {
+    let bps = response::baudrate_bps(line.as_bytes())
+        .ok_or_else(|| Error::InvalidResponse(line.clone()))?;
+    if !SERIAL_SPEEDS_BPS.contains(&bps) {
+        return Err(Error::UnknownBaudrate(bps));
+    }
+    Ok(bps)
+}
+
+/// The power level in an answer to `AT+RP`, if its output is one of the levels
+fn reported_power<D: fmt::Debug>(line: &Response) -> Result<Power, Error<D>> {
+    let dbm =
+        response::power_dbm(line.as_bytes()).ok_or_else(|| Error::InvalidResponse(line.clone()))?;
+    Power::level_at(dbm).ok_or(Error::UnknownPower(dbm))
+}
+
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed>
+where
+    Device: AtRead + Write,
+    Pin: OutputPin,
+{
+    /// Return to programming mode. This persists the programming parameters from the last
+    /// probramming of the device. In most HALs this is infallible.
+    #[allow(clippy::type_complexity)]
+    pub fn into_programming_mode(
+        mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<HC12<Device, Pin, Mode, Speed>, Error<Device::Error, Pin::Error>> {
+        self.pin.set_low().map_err(Error::PinError)?;
+        delay.delay_ms(self.session.timings.pin_settle_ms);
+        trace_at!(debug, "HC-12 entered programming mode");
+        notify(self.session.observer, || {
+            AtEvent::TransitionPerformed(Transition::IntoProgramming)
+        });
+
+        Ok(self.pulled_low())
+    }
+
+    /// Like [`into_programming_mode`](Self::into_programming_mode), but
+    /// [`ping`](HC12::ping) the module to check it entered AT mode, rather than assuming
+    /// it did. The ping is tried up to `attempts` times, at least once, 100ms apart. If
+    /// none is answered, the pin is released and the device handed back in transparent
+    /// mode. A module that missed the pin is still in transparent mode, and sends each
+    /// `AT` over the air.
+    // the device handed back on failure is the same size as the one returned on success
+    #[allow(clippy::type_complexity, clippy::result_large_err)]
+    pub fn into_programming_mode_verified(
+        mut self,
+        attempts: u8,
+        delay: &mut impl DelayNs,
+    ) -> Result<HC12<Device, Pin, Mode, Speed>, NotListening<Self, Device::Error, Pin::Error>>
+    where
+        Mode: ValidMode + ValidModeFor<Speed> + Command,
+        Speed: ValidSpeed,
+    {
+        if let Err(error) = self.pin.set_low() {
+            let error = Error::PinError(error);
+            return Err(NotListening { hc12: self, error });
+        }
+        delay.delay_ms(self.session.timings.pin_settle_ms);
+        notify(self.session.observer, || {
+            AtEvent::TransitionPerformed(Transition::IntoProgramming)
+        });
+        let mut hc12 = self.pulled_low();
+
+        let mut remaining = attempts.max(1);
+        let error = loop {
+            remaining -= 1;
+            match hc12.ping(delay) {
+                Ok(()) => {
+                    trace_at!(debug, "HC-12 entered programming mode");
+                    return Ok(hc12);
+                }
+                Err(error) if remaining == 0 => break error,
+                Err(_) => delay.delay_ms(hc12.session.timings.ping_retry_ms),
+            }
+        };
+
+        let mut hc12 = hc12.released();
+        let error = match hc12.pin.set_high() {
+            Ok(()) => {
+                delay.delay_ms(hc12.session.timings.mode_change_settle_ms);
+                notify(hc12.session.observer, || {
+                    AtEvent::TransitionPerformed(Transition::IntoTransparent)
+                });
+                error.with_pin()
+            }
+            Err(error) => Error::PinError(error),
+        };
+        Err(NotListening { hc12, error })
+    }
+
+    /// The device in programming mode, once the programming pin has been pulled low
+    fn pulled_low(self) -> HC12<Device, Pin, Mode, Speed> {
+        HC12 {
+            device: self.device,
+            programming_pin: self.pin,
+            _mode: PhantomData,
+            _speed: PhantomData,
+            channel: self.channel,
+            power: self.power,
+            session: self.session,
+        }
+    }
+
+    /// Program the channel and power of `configuration`, only sending the commands for
+    /// settings that differ from the current ones. Each command briefly returns the module
+    /// to programming mode. Returns what changed.
+    pub fn apply_diff(
+        &mut self,
+        configuration: Configuration,
+        delay: &mut impl DelayNs,
+    ) -> Result<ChangeSummary, ApplyError<Device::Error, Pin::Error>> {
+        self.session
+            .allowed
+            .check(configuration.channel)
+            .map_err(|error| ApplyError::At(error.into()))?;
+        configuration
+            .power
+            .within_cap()
+            .map_err(|_| ApplyError::At(Error::ExceedsBuildCap(configuration.power)))?;
+
+        let mut summary = ChangeSummary {
+            channel: FieldChange {
+                old: self.channel,
+                new: configuration.channel,
+                sent: false,
+            },
+            power: FieldChange {
+                old: self.power,
+                new: configuration.power,
+                sent: false,
+            },
+        };
+        if summary.power.changed() {
+            self.set_power(configuration.power, delay)?;
+            summary.power.sent = true;
+        }
+        if summary.channel.changed() {
+            self.round_trip(configuration.channel, delay)
+                .map_err(ApplyError::Pin)?
+                .map_err(ApplyError::At)?;
+            self.channel = configuration.channel;
+            summary.channel.sent = true;
+        }
+        Ok(summary)
+    }
+
+    /// Program a new power with [`round_trip`](Self::round_trip), only updating the
+    /// device's record once the module accepts it
+    pub(crate) fn set_power(
+        &mut self,
+        power: Power,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), RoundTripError<Device::Error, Pin::Error>> {
+        self.round_trip(power, delay)
+            .map_err(RoundTripError::Pin)?
+            .map_err(RoundTripError::At)?;
+        self.power = power;
+        Ok(())
+    }
+
+    /// Briefly return to programming mode to run a single AT command, recording it in the
+    /// transaction log. The module is returned to transparent mode even if the command
+    /// fails.
+    pub(crate) fn round_trip(
+        &mut self,
+        command: impl Command,
+        delay: &mut impl DelayNs,
+    ) -> Result<Result<Response, Error<Device::Error>>, Pin::Error> {
+        if let Err(error) = self.session.permits(&command) {
+            return Ok(Err(error));
+        }
+        self.pin.set_low()?;
+        delay.delay_ms(self.session.timings.pin_settle_ms);
+        notify(self.session.observer, || {
+            AtEvent::TransitionPerformed(Transition::IntoProgramming)
+        });
+
+        let result = self.exchange_in_at_mode(command, delay);
+
+        self.pin.set_high()?;
+        delay.delay_ms(self.session.timings.mode_change_settle_ms);
+        notify(self.session.observer, || {
+            AtEvent::TransitionPerformed(Transition::IntoTransparent)
+        });
+        Ok(result)
+    }
+}
+
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed>
+where
+    Device: AtRead + Write,
+{
+    /// Run a single AT command and check its echo, recording it in the transaction log.
+    /// The module must already be in AT mode, and the command permitted.
+    pub(crate) fn exchange_in_at_mode<const N: usize>(
+        &mut self,
+        command: impl Command,
+        delay: &mut dyn DelayNs,
+    ) -> Result<Response<N>, Error<Device::Error, Infallible, N>> {
+        let text = command.command();
+        #[cfg(feature = "transaction-log")]
+        let sent = text.clone();
+        let result = exchange(
+            &mut self.device,
+            text,
+            delay,
+            self.session.observer,
+            self.session.timings.settle_ms(&command),
+            self.session.response_timeout_ms,
+        )
+        .and_then(|line| verify_echo(&command, line));
+        #[cfg(feature = "transaction-log")]
+        self.session.transactions.record(sent, result.as_ref());
+        result
+    }
+}
+
+impl<Device, Pin: OutputPin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed> {
+    /// Wake a module put to sleep with `AT+SLEEP`, by pulsing the programming pin low.
+    /// This blocks for [`wake_ms`](Self::wake_ms).
+    pub(crate) fn wake(&mut self, delay: &mut impl DelayNs) -> Result<(), Pin::Error> {
+        self.begin_wake()?;
+        delay.delay_ms(self.session.timings.pin_settle_ms);
+        self.end_wake()?;
+        delay.delay_ms(self.session.timings.mode_change_settle_ms);
+        Ok(())
+    }
+
+    /// How long waking takes: the pin and mode change settle times of the [`AtTimings`].
+    /// With [`AtTimings::DATASHEET`] this is [`WAKE_MS`](crate::beacon::WAKE_MS).
+    pub(crate) fn wake_ms(&self) -> u32 {
+        self.session.timings.pin_settle_ms + self.session.timings.mode_change_settle_ms
+    }
+
+    /// Start waking a sleeping module, for callers that cannot block. Call
+    /// [`end_wake`](Self::end_wake) once the pin settle time has passed; the module is
+    /// ready once the mode change settle time has passed after that.
+    pub(crate) fn begin_wake(&mut self) -> Result<(), Pin::Error> {
+        self.pin.set_low()
+    }
+
+    /// Release the programming pin after [`begin_wake`](Self::begin_wake)
+    pub(crate) fn end_wake(&mut self) -> Result<(), Pin::Error> {
+        self.pin.set_high()?;
+        notify(self.session.observer, || {
+            AtEvent::TransitionPerformed(Transition::IntoTransparent)
+        });
+        Ok(())
+    }
+}
+
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed> {
+    /// The AT transactions run before entering transparent mode, oldest first
+    #[cfg(feature = "transaction-log")]
+    pub fn transaction_log(&self) -> impl Iterator<Item = &Transaction> {
+        self.session.transactions.iter()
+    }
+
+    /// Forget the logged AT transactions
+    #[cfg(feature = "transaction-log")]
+    pub fn clear_log(&mut self) {
+        self.session.transactions.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test::{run_command, Fragments};
+    use crate::mock::{Fault, MockHc12, MockSerial, Settings};
+    use crate::test_utils::{CountingDelay, Duo, PinLog, Sink, Source};
+    use core::cell::Cell;
+    use core::convert::Infallible;
+    use embedded_hal::digital::PinState;
+    use embedded_io::ErrorType;
+
+    /// Counts the bytes written through it
+    struct Counting<'a, D> {
+        inner: D,
+        written: &'a Cell<usize>,
+    }
+
+    impl<D: ErrorType> ErrorType for Counting<'_, D> {
+        type Error = D::Error;
+    }
+
+    impl<D: Read> Read for Counting<'_, D> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl<D: ReadReady> ReadReady for Counting<'_, D> {
+        fn read_ready(&mut self) -> Result<bool, Self::Error> {
+            self.inner.read_ready()
+        }
+    }
+
+    impl<D: Write> Write for Counting<'_, D> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let count = self.inner.write(buf)?;
+            self.written.set(self.written.get() + count);
+            Ok(count)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.inner.flush()
+        }
+    }
+
+    #[test]
+    fn program_without_read_ready() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+
+        let device = crate::adapters::Unpolled::new(module.serial());
+        HC12::factor_settings(device, module.set_pin(), &mut delay)
+            .unwrap()
+            .channel(Channel::new(21).unwrap())
+            .power(Power::P1)
+            .program(&mut delay)
+            .unwrap();
+
+        assert_eq!(
+            module.settings(),
+            Settings {
+                baudrate_bps: 9600,
+                channel: 21,
+                power: 1,
+                mode: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn program_rejects_channels_not_allowed() {
+        const CERTIFIED: ChannelSet = ChannelSet::range(1, 40);
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .allowed_channels(CERTIFIED);
+        let hc12 = hc12
+            .channel_in(&CERTIFIED, Channel::new(40).unwrap())
+            .unwrap();
+        assert!(matches!(
+            hc12.channel_in(&CERTIFIED, Channel::new(41).unwrap()),
+            Err(ChannelNotAllowed(_))
+        ));
+
+        // the whitelist follows the device through transparent mode
+        let hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .allowed_channels(CERTIFIED)
+            .power(Power::MAX)
+            .program(&mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap()
+            .into_programming_mode(&mut delay)
+            .unwrap()
+            .channel(Channel::new(41).unwrap());
+        assert!(matches!(
+            hc12.program(&mut delay),
+            Err(Error::ChannelNotAllowed(41))
+        ));
+        assert_eq!(module.settings().channel, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "max-power-p4")]
+    fn program_refuses_power_above_the_cap() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .power(Power::P8);
+        assert!(matches!(
+            hc12.program(&mut delay),
+            Err(Error::ExceedsBuildCap(Power::P8))
+        ));
+        assert!(module.transmitted().is_empty());
+
+        // the default stays P8, so a capped build must choose its power
+        let hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay).unwrap();
+        assert_eq!(hc12.power, Power::P8);
+        let hc12 = hc12.power(Power::MAX).program(&mut delay).unwrap();
+        assert_eq!(hc12.power, Power::P4);
+        assert_eq!(module.settings().power, 4);
+    }
+
+    #[test]
+    fn program_checked_stops_on_advisories_when_strict() {
+        use crate::validation::ConfigWarning;
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .power(Power::MAX)
+            .b19200();
+        assert!(matches!(
+            hc12.program_checked(true, &mut delay),
+            Err(Error::Validation(ConfigWarning::Fu3ShortRange(19200)))
+        ));
+        assert_eq!(module.settings().baudrate_bps, 9600);
+
+        let hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .power(Power::MAX)
+            .b19200();
+        hc12.program_checked(false, &mut delay).unwrap();
+        assert_eq!(module.settings().baudrate_bps, 19200);
+    }
+
+    #[test]
+    fn program_checked_at_38400() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .power(Power::MAX)
+            .b38400();
+        let hc12 = hc12.program_checked(false, &mut delay).unwrap();
+        assert_eq!(hc12.programmed_baudrate_bps(), 38400);
+        assert_eq!(module.settings().baudrate_bps, 38400);
+    }
+
+    #[test]
+    fn program_static_configuration() {
+        static RADIO: Configuration = Configuration::new(Channel::new_const::<40>(), Power::P1);
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .configuration(RADIO)
+            .program(&mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+
+        assert_eq!(hc12.configuration(), RADIO);
+        assert_eq!(
+            (module.settings().channel, module.settings().power),
+            (40, 1)
+        );
+    }
+
+    #[test]
+    fn transition_paths_agree() {
+        let first = MockHc12::new();
+        let mut delay = first.delay();
+        let direct = HC12::factor_settings(first.serial(), first.set_pin(), &mut delay)
+            .unwrap()
+            .channel(Channel::new(9).unwrap())
+            .power(Power::P1)
+            .b4800()
+            .fu1()
+            .program(&mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+
+        // the same settings, applied in a different order over two programming sessions
+        let second = MockHc12::new();
+        let mut delay = second.delay();
+        let stepwise = HC12::factor_settings(second.serial(), second.set_pin(), &mut delay)
+            .unwrap()
+            .fu1()
+            .power(Power::P1)
+            .program(&mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap()
+            .into_programming_mode(&mut delay)
+            .unwrap()
+            .b4800()
+            .channel(Channel::new(9).unwrap())
+            .program(&mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+
+        assert!(direct.is_same_config(&stepwise));
+        assert_eq!(first.settings(), second.settings());
+        assert_eq!(Fu1::default(), Fu1 {});
+        assert_eq!(
+            Error::<Infallible>::NoOK("ERROR\r\n".try_into().unwrap()),
+            Error::NoOK("ERROR\r\n".try_into().unwrap())
+        );
+
+        let pins = PinLog::new();
+        let other = TransparentHC12::<_, _, Fu1, B4800>::assume_programmed(
+            Sink::new(),
+            pins.pin(),
+            Channel::new(9).unwrap(),
+            Power::P8,
+        );
+        assert!(!direct.is_same_config(&other));
+    }
+
+    #[test]
+    fn debug_shows_configuration() {
+        extern crate std;
+        use std::format;
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .channel(Channel::new(21).unwrap())
+            .b4800();
+
+        assert_eq!(
+            format!("{hc12:?}"),
+            "HC12 { mode: FU3, baudrate_bps: 4800, channel: Channel(21), power: P8, .. }"
+        );
+    }
+
+    #[test]
+    fn map_device_sees_later_traffic() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let device = Duo {
+            sink: Sink::new().accept_data(10 + 8 + 7 + 9),
+            src: Source::new().data(b"OK+B9600\r\nOK+FU3\r\nOK+P1\r\nOK+C021\r\n"),
+        };
+        let written = Cell::new(0);
+
+        HC12::factor_settings(device, pins.pin(), &mut delay)
+            .unwrap()
+            .channel(Channel::new(21).unwrap())
+            .power(Power::P1)
+            .map_device(|inner| Counting {
+                inner,
+                written: &written,
+            })
+            .program(&mut delay)
+            .unwrap();
+
+        assert_eq!(pins.states().as_slice(), [PinState::Low]);
+        assert_eq!(written.get(), 10 + 8 + 7 + 9);
+    }
+
+    #[cfg(feature = "transaction-log")]
+    #[test]
+    fn program_records_transactions() {
+        use crate::mock::Fault;
+        use crate::transactions::TransactionStatus;
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+
+        let mut hc12 =
+            HC12::factor_settings(module.serial(), module.set_pin(), &mut delay).unwrap();
+        hc12.run(B9600::default(), &mut delay).unwrap();
+        module.inject_fault(Fault::Error);
+        hc12.run(Fu3::default(), &mut delay).unwrap_err();
+        module.inject_fault(Fault::Ignore);
+        hc12.run(Power::P8, &mut delay).unwrap_err();
+
+        let hc12 = hc12.into_transparent_mode(&mut delay).unwrap();
+        assert!(!module.in_at_mode());
+
+        let log: heapless::Vec<_, 3> = hc12
+            .transaction_log()
+            .map(|entry| (entry.command.as_str(), entry.status))
+            .collect();
+        assert_eq!(
+            log.as_slice(),
+            [
+                ("AT+B9600", TransactionStatus::Ok),
+                ("AT+FU3", TransactionStatus::NoOK),
+                ("AT+P8", TransactionStatus::NoResponse),
+            ]
+        );
+    }
+
+    #[test]
+    fn dump_reports_configuration_and_firmware() {
+        extern crate std;
+        use crate::diagnostics::DeviceStatus;
+        use crate::linktest::PerReport;
+        use crate::modes::FuMode;
+        use std::{string::ToString, vec::Vec};
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .power(Power::MIN)
+            .channel(Channel::new(21).unwrap())
+            .program(&mut delay)
+            .unwrap();
+
+        let dump = hc12.dump(&mut delay);
+        assert_eq!(
+            dump.configuration.map(|full| full.configuration),
+            Some(Configuration::new(Channel::new(21).unwrap(), Power::MIN))
+        );
+        assert_eq!(
+            dump.reported,
+            Some(DeviceStatus {
+                baudrate_bps: 9600,
+                channel: Channel::new(21).unwrap(),
+                power: Power::MIN,
+                mode: FuMode::Fu3,
+            })
+        );
+        assert_eq!(
+            dump.firmware.as_deref(),
+            Some("www.hc01.com HC-12_V2.6\r\n")
+        );
+        assert_eq!(dump.link, None);
+        let text = dump.to_string();
+        let lines: Vec<&str> = text.lines().take(4).collect();
+        assert_eq!(
+            lines,
+            [
+                "driver: CH021 (441.4 MHz), P1 (-1 dBm), FU3 @ 9600",
+                "module: CH021 (441.4 MHz), P1 (-1 dBm), FU3 @ 9600",
+                "firmware: www.hc01.com HC-12_V2.6",
+                "link: not measured",
+            ]
+        );
+        if cfg!(feature = "transaction-log") {
+            assert!(text.ends_with(
+                "AT+RX -> OK+FU3 (Ok)\n\
+                AT+V -> www.hc01.com HC-12_V2.6 (NoOK)\n"
+            ));
+        }
+
+        // a module that ignores the read-back still gives the rest
+        module.inject_fault(Fault::Ignore);
+        let mut dump = hc12.dump(&mut delay);
+        assert_eq!(dump.reported, None);
+        assert!(dump.firmware.is_some());
+        dump.link = Some(PerReport {
+            sent: 20,
+            received: 18,
+            corrupted: 1,
+        });
+        let text = dump.to_string();
+        let lines: Vec<&str> = text.lines().take(4).collect();
+        assert_eq!(
+            lines,
+            [
+                "driver: CH021 (441.4 MHz), P1 (-1 dBm), FU3 @ 9600",
+                "module: unknown",
+                "firmware: www.hc01.com HC-12_V2.6",
+                "link: 18 of 20 probes returned, 1 corrupted",
+            ]
+        );
+    }
+
+    #[test]
+    fn observer_sees_session() {
+        extern crate std;
+        use std::{sync::Mutex, vec::Vec};
+
+        static EVENTS: Mutex<Vec<AtEvent>> = Mutex::new(Vec::new());
+        fn record(event: AtEvent) {
+            EVENTS.lock().unwrap().push(event);
+        }
+
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let device = Duo {
+            sink: Sink::new().accept_data(10 + 7),
+            src: Source::new().data(b"OK+B9600\r\n"),
+        };
+
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay)
+            .unwrap()
+            .observer(record);
+        run_command(
+            &mut hc12.device,
+            B9600::default(),
+            &mut delay,
+            hc12.session.observer,
+        )
+        .unwrap();
+        assert!(matches!(
+            run_command(
+                &mut hc12.device,
+                Power::P8,
+                &mut delay,
+                hc12.session.observer
+            ),
+            Err(Error::NoResponse)
+        ));
+        hc12.into_transparent_mode(&mut delay).unwrap();
+        assert_eq!(pins.states().as_slice(), [PinState::Low, PinState::High]);
+
+        let expected = [
+            AtEvent::CommandSent("AT+B9600".try_into().unwrap()),
+            AtEvent::ResponseReceived("OK+B9600\r\n".try_into().unwrap()),
+            AtEvent::CommandSent("AT+P8".try_into().unwrap()),
+            AtEvent::Timeout,
+            AtEvent::TransitionPerformed(Transition::IntoTransparent),
+        ];
+        assert_eq!(*EVENTS.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn hard_reset_boots_into_at_mode() {
+        let set = PinLog::new();
+        let vcc = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let mut supply = Supply::new(vcc.pin());
+
+        let serial = Duo {
+            sink: Sink::new(),
+            src: Source::new(),
+        };
+        let mut hc12 = HC12::factor_settings(serial, set.pin(), &mut delay).unwrap();
+        hc12.hard_reset(&mut supply, &mut delay).unwrap();
+
+        assert_eq!(set.states().as_slice(), [PinState::Low, PinState::Low]);
+        assert_eq!(vcc.states().as_slice(), [PinState::Low, PinState::High]);
+        // entering AT mode, then the default discharge and boot times
+        assert_eq!(delay.elapsed_ms(), 40 + 200 + 100);
+    }
+
+    #[test]
+    fn each_mode_switch_sets_the_pin_once() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let serial = Duo {
+            sink: Sink::new(),
+            src: Source::new(),
+        };
+
+        // build, then transparent, AT and transparent again
+        HC12::factor_settings(serial, pins.pin(), &mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap()
+            .into_programming_mode(&mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+
+        let (low, high) = (PinState::Low, PinState::High);
+        assert_eq!(pins.states().as_slice(), [low, high, low, high]);
+        assert_eq!(delay.calls(), 4);
+        assert_eq!(delay.elapsed_ms(), 40 + 80 + 40 + 80);
+    }
+
+    #[test]
+    fn inner_returns_serial_and_pin() {
+        let pins = PinLog::new();
+        let serial = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(b"OK+B9600\r\nOK+FU3\r\nOK+P1\r\nOK+C005\r\n"),
+        };
+        // a delay shared with other drivers, borrowed for each operation
+        let mut shared_delay = CountingDelay::new();
+
+        let hc12 = HC12::factor_settings(serial, pins.pin(), &mut shared_delay)
+            .unwrap()
+            .power(Power::MIN)
+            .channel(Channel::new(5).unwrap())
+            .program(&mut shared_delay)
+            .unwrap();
+        let (serial, mut pin) = hc12.inner();
+
+        assert!(serial.sink.data().ends_with(b"AT+C005\r\n"));
+        pin.set_high().unwrap();
+        assert_eq!(pins.states().as_slice(), [PinState::Low, PinState::High]);
+        assert!(shared_delay.calls() > 0);
+    }
+
+    #[test]
+    fn debug_reads_capture_responses_verbatim() {
+        let pins = PinLog::new();
+        let serial = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(b"ERROR\r\n"),
+        };
+        let mut hc12 =
+            HC12::factor_settings(serial, pins.pin(), &mut CountingDelay::new()).unwrap();
+        let mut buf = [0; 16];
+        let count = hc12.debug_read_raw(&mut buf).unwrap();
+        assert_eq!(&buf[..count], b"ERROR\r\n");
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut hc12 =
+            HC12::factor_settings(module.serial(), module.set_pin(), &mut delay).unwrap();
+        // a command the module rejects, sent around the builder
+        module.serial().write_all(b"AT+FU9\r\n").unwrap();
+        delay.delay_ms(100);
+        assert_eq!(hc12.debug_drain_into::<16>().unwrap(), b"ERROR\r\n");
+        assert!(hc12.debug_drain_into::<16>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn silent_module_times_out() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let result = HC12::factor_settings(Duo::default(), pins.pin(), &mut delay)
+            .unwrap()
+            .power(Power::MIN)
+            .response_timeout_ms(25)
+            .program(&mut delay);
+        assert_eq!(result.err(), Some(Error::NoResponse));
+        // entering AT mode, sending the first command, then 25 waits of 1ms
+        assert_eq!(delay.calls(), 2 + 25);
+        assert_eq!(delay.elapsed_ms(), 40 + 40 + 25);
+    }
+
+    #[test]
+    fn blocking_read_is_never_started() {
+        use crate::test_utils::{Sink, TestError};
+
+        /// Never has anything to read, and a read would wait for a byte forever
+        struct Blocking(Sink);
+
+        impl ErrorType for Blocking {
+            type Error = TestError;
+        }
+
+        impl Read for Blocking {
+            fn read(&mut self, _: &mut [u8]) -> Result<usize, Self::Error> {
+                panic!("read would block");
+            }
+        }
+
+        impl ReadReady for Blocking {
+            fn read_ready(&mut self) -> Result<bool, Self::Error> {
+                Ok(false)
+            }
+        }
+
+        impl Write for Blocking {
+            fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+                self.0.write(buf)
+            }
+
+            fn flush(&mut self) -> Result<(), Self::Error> {
+                Ok(())
+            }
+        }
+
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let result = HC12::factor_settings(Blocking(Sink::new()), pins.pin(), &mut delay)
+            .unwrap()
+            .power(Power::MIN)
+            .response_timeout_ms(25)
+            .program(&mut delay);
+        assert_eq!(result.err(), Some(Error::NoResponse));
+        assert_eq!(delay.calls(), 2 + 25);
+    }
+
+    #[test]
+    fn pin_and_device_failures_are_told_apart() {
+        use crate::test_utils::{Sink, Source, TestError};
+        use embedded_hal::digital::{ErrorKind, ErrorType};
+
+        struct BrokenPin;
+
+        impl ErrorType for BrokenPin {
+            type Error = ErrorKind;
+        }
+
+        impl OutputPin for BrokenPin {
+            fn set_low(&mut self) -> Result<(), Self::Error> {
+                Err(ErrorKind::Other)
+            }
+
+            fn set_high(&mut self) -> Result<(), Self::Error> {
+                Err(ErrorKind::Other)
+            }
+        }
+
+        let mut delay = CountingDelay::new();
+        let result = HC12::factor_settings(Duo::default(), BrokenPin, &mut delay);
+        assert_eq!(result.err(), Some(Error::PinError(ErrorKind::Other)));
+
+        // a serial port that refuses every byte
+        let device = Duo {
+            sink: Sink::new().accept_data(0),
+            src: Source::new(),
+        };
+        let pins = PinLog::new();
+        let result = HC12::factor_settings(device, pins.pin(), &mut delay)
+            .unwrap()
+            .power(Power::MIN)
+            .program(&mut delay);
+        assert_eq!(result.err(), Some(Error::DeviceError(TestError)));
+    }
+
+    #[test]
+    fn apply_diff_only_sends_the_channel() {
+        extern crate std;
+        use std::string::ToString;
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .power(Power::MIN)
+            .program(&mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+        #[cfg(feature = "transaction-log")]
+        hc12.clear_log();
+
+        let target = Configuration::new(Channel::new(5).unwrap(), Power::MIN);
+        let summary = hc12.apply_diff(target, &mut delay).unwrap();
+        assert_eq!(
+            summary.channel,
+            FieldChange {
+                old: Channel::new(1).unwrap(),
+                new: Channel::new(5).unwrap(),
+                sent: true
+            }
+        );
+        assert_eq!(
+            summary.power,
+            FieldChange {
+                old: Power::MIN,
+                new: Power::MIN,
+                sent: false
+            }
+        );
+        assert_eq!(
+            summary.to_string(),
+            "channel: 1 -> 5\npower: P1 (unchanged)\n"
+        );
+        assert_eq!(module.settings().channel, 5);
+        #[cfg(feature = "transaction-log")]
+        assert_eq!(hc12.transaction_log().count(), 1);
+
+        assert!(hc12.apply_diff(target, &mut delay).unwrap().is_empty());
+    }
+
+    #[test]
+    fn programs_through_borrowed_resources() {
+        let module = MockHc12::new();
+        let mut serial = module.serial();
+        let mut pin = module.set_pin();
+        let mut timer = module.delay();
+
+        // the serial port, pin and delay all stay owned by the caller
+        HC12::factor_settings(&mut serial, &mut pin, &mut timer)
+            .unwrap()
+            .power(Power::MIN)
+            .channel(Channel::new(12).unwrap())
+            .program(&mut timer)
+            .unwrap()
+            .into_transparent_mode(&mut timer)
+            .unwrap();
+
+        assert_eq!(module.settings().channel, 12);
+        // and can be used to build the device again
+        HC12::factor_settings(&mut serial, &mut pin, &mut timer).unwrap();
+    }
+
+    /// One delay advancing the clocks of several simulated modules
+    struct SharedTimer<'a>(&'a [&'a MockHc12]);
+
+    impl DelayNs for SharedTimer<'_> {
+        fn delay_ns(&mut self, ns: u32) {
+            for module in self.0 {
+                module.delay().delay_ns(ns);
+            }
+        }
+    }
+
+    #[test]
+    fn modules_share_one_timer() {
+        let (a, b) = (MockHc12::new(), MockHc12::new());
+        let mut timer = SharedTimer(&[&a, &b]);
+
+        let mut radio_a: TransparentHC12<_, _, Fu3, B9600> =
+            TransparentHC12::assume_factory(a.serial(), a.set_pin());
+        let radio_b: TransparentHC12<_, _, Fu3, B9600> =
+            TransparentHC12::assume_factory(b.serial(), b.set_pin());
+
+        // module B is reprogrammed step by step, while module A keeps receiving
+        let mut buffer = [0; 8];
+        let programming_b = radio_b.into_programming_mode(&mut timer).unwrap();
+        a.receive(b"ping");
+        assert_eq!(radio_a.read(&mut buffer).unwrap(), 4);
+
+        let programming_b = programming_b
+            .power(Power::MIN)
+            .channel(Channel::new(30).unwrap())
+            .program(&mut timer)
+            .unwrap();
+        a.receive(b"pong");
+        assert_eq!(radio_a.read(&mut buffer).unwrap(), 4);
+        assert_eq!(&buffer[..4], b"pong");
+
+        programming_b.into_transparent_mode(&mut timer).unwrap();
+        assert_eq!(b.settings().channel, 30);
+        assert_eq!(a.settings().channel, 1);
+        assert!(!b.in_at_mode());
+    }
+
+    #[test]
+    fn query_all_accumulates_fragmented_lines() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        // split mid-line and across lines, with a blank line, and without the colon some
+        // firmware leaves out
+        let device = Fragments::new(
+            &[
+                b"OK+B1",
+                b"9200\r\nOK+R",
+                b"C021\r\n\r\nOK+RP+11dBm\r",
+                b"\nOK+FU",
+                b"4\r\n",
+            ],
+            RESPONSE_TIMEOUT_MS / 2,
+        );
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+
+        assert_eq!(
+            hc12.query_all(&mut delay),
+            Ok(DeviceStatus {
+                baudrate_bps: 19200,
+                channel: Channel::new(21).unwrap(),
+                power: Power::P5,
+                mode: FuMode::Fu4,
+            })
+        );
+    }
+
+    #[test]
+    fn query_all_reports_missing_and_garbled_lines() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let device = Fragments::new(&[b"OK+B9600\r\nOK+RC001\r\nOK+RP:+20dBm\r\n"], 0);
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+        assert_eq!(hc12.query_all(&mut delay), Err(Error::NoResponse));
+
+        let device = Fragments::new(&[b"OK+B9600\r\nOK+RC001\r\nOK+RP:+19dBm\r\nOK+FU3\r\n"], 0);
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+        assert_eq!(hc12.query_all(&mut delay), Err(Error::UnknownPower(19)));
+
+        let device = Fragments::new(&[b"OK+B9600\r\nOK+RC001\r\nOK+RP:+2xdBm\r\nOK+FU3\r\n"], 0);
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+        assert_eq!(
+            hc12.query_all(&mut delay),
+            Err(Error::InvalidResponse(
+                "OK+RP:+2xdBm\r\n".try_into().unwrap()
+            ))
+        );
+
+        // a blank line between each answer is skipped, but not an endless run of them
+        let device = Duo {
+            sink: Sink::new(),
+            src: Source::new()
+                .data(b"\r\nOK+B9600\r\n\r\nOK+RC001\r\n\r\nOK+RP:+20dBm\r\n\r\nOK+FU3\r\n"),
+        };
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+        assert!(hc12.query_all(&mut delay).is_ok());
+
+        let device = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(&b"\r\n".repeat(100)),
+        };
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+        assert_eq!(
+            hc12.query_all(&mut delay),
+            Err(Error::InvalidResponse(Response::new()))
+        );
+        assert_eq!(hc12.device.src.remaining().len(), 2 * (100 - 5));
+    }
+
+    #[test]
+    fn query_all_reads_back_the_module() {
+        let module = MockHc12::with_settings(Settings {
+            channel: 42,
+            power: 3,
+            ..Settings::default()
+        });
+        let mut delay = module.delay();
+        let mut hc12 =
+            HC12::factor_settings(module.serial(), module.set_pin(), &mut delay).unwrap();
+        let status = hc12.query_all(&mut delay).unwrap();
+        assert_eq!(u8::from(status.channel), 42);
+        assert_eq!(status.power, Power::P3);
+        assert_eq!((status.baudrate_bps, status.mode), (9600, FuMode::Fu3));
+        // the device's own settings are left as they were
+        assert_eq!(u8::from(hc12.channel), 1);
+    }
+
+    #[test]
+    fn query_baudrate_reads_split_and_noisy_answers() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let device = Fragments::new(&[b"\xff\x00OK+B5", b"7600\r\n"], 3);
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+        assert_eq!(hc12.query_baudrate(&mut delay), Ok(57600));
+
+        let device = Fragments::new(&[b"OK+B14", b"400\r\n"], 3);
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+        assert_eq!(
+            hc12.query_baudrate(&mut delay),
+            Err(Error::UnknownBaudrate(14400))
+        );
+
+        let device = Fragments::new(&[b"OK+B96?0\r\n"], 0);
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+        assert!(matches!(
+            hc12.query_baudrate(&mut delay),
+            Err(Error::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn query_baudrate_finds_a_module_set_up_elsewhere() {
+        let module = MockHc12::with_settings(Settings {
+            baudrate_bps: 19200,
+            ..Settings::default()
+        });
+        let mut delay = module.delay();
+        let mut hc12 =
+            HC12::factor_settings(module.serial(), module.set_pin(), &mut delay).unwrap();
+        assert_eq!(hc12.query_baudrate(&mut delay), Ok(19200));
+    }
+
+    #[test]
+    fn query_channel_takes_over_the_modules_channel() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let device = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(b"OK+RC021\r\n"),
+        };
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+        let channel = Channel::new(21).unwrap();
+        assert_eq!(hc12.query_channel(&mut delay), Ok(channel));
+        assert_eq!(hc12.current_configuration().channel, channel);
+        assert_eq!(hc12.device.sink.data(), b"AT+RC\r\n");
+    }
+
+    #[test]
+    fn query_channel_rejects_garbled_and_missing_answers() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        for garbled in [&b"OK+RC0x1\r\n"[..], b"OK+RC000\r\n", b"OK+RC128\r\n"] {
+            let device = Duo {
+                sink: Sink::new(),
+                src: Source::new().data(garbled),
+            };
+            let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+            assert_eq!(
+                hc12.query_channel(&mut delay),
+                Err(Error::InvalidResponse(
+                    core::str::from_utf8(garbled).unwrap().try_into().unwrap()
+                ))
+            );
+            assert_eq!(u8::from(hc12.current_configuration().channel), 1);
+        }
+
+        let mut hc12 = HC12::factor_settings(Duo::default(), pins.pin(), &mut delay).unwrap();
+        assert_eq!(hc12.query_channel(&mut delay), Err(Error::NoResponse));
+    }
+
+    #[test]
+    fn query_power_matches_the_output() {
+        let module = MockHc12::with_settings(Settings {
+            power: 2,
+            ..Settings::default()
+        });
+        let mut delay = module.delay();
+        let mut hc12 =
+            HC12::factor_settings(module.serial(), module.set_pin(), &mut delay).unwrap();
+        assert_eq!(hc12.query_power(&mut delay), Ok(Power::P2));
+        assert_eq!(hc12.current_configuration().power, Power::P2);
+
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let device = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(b"OK+RP:+19dBm\r\n"),
+        };
+        let mut hc12 = HC12::factor_settings(device, pins.pin(), &mut delay).unwrap();
+        assert_eq!(hc12.query_power(&mut delay), Err(Error::UnknownPower(19)));
+        assert_eq!(hc12.current_configuration().power, Power::default());
+    }
+
+    fn answering(answer: &[u8]) -> Duo {
+        Duo {
+            sink: Sink::new(),
+            src: Source::new().data(answer),
+        }
+    }
+
+    #[test]
+    fn resolve_mode_follows_each_reply() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let replies: [(&[u8], FuMode); 4] = [
+            (b"OK+FU1\r\n", FuMode::Fu1),
+            (b"OK+FU2\r\n", FuMode::Fu2),
+            (b"OK+FU3\r\n", FuMode::Fu3),
+            (b"OK+FU4\r\n", FuMode::Fu4),
+        ];
+        for (reply, mode) in replies {
+            // every mode supports 1200 bps
+            let hc12 = HC12::factor_settings(answering(reply), pins.pin(), &mut delay)
+                .unwrap()
+                .b1200();
+            let resolved = hc12.resolve_mode(&mut delay).unwrap();
+            assert_eq!(resolved.mode(), mode);
+            if let ResolvedMode::Fu4(hc12) = resolved {
+                assert_eq!(hc12.programmed_mode(), 4);
+            }
+        }
+    }
+
+    #[test]
+    fn unresolved_mode_hands_the_device_back() {
+        use crate::validation::ConfigWarning;
+
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let hc12 = HC12::factor_settings(answering(b"OK+FU9\r\n"), pins.pin(), &mut delay).unwrap();
+        let unresolved = hc12.resolve_mode(&mut delay).unwrap_err();
+        assert_eq!(
+            unresolved.error,
+            Error::InvalidResponse("OK+FU9\r\n".try_into().unwrap())
+        );
+        assert_eq!(unresolved.hc12.device.sink.data(), b"AT+RF\r\n");
+
+        // FU4 does not run at 9600 bps
+        let hc12 = HC12::factor_settings(answering(b"OK+FU4\r\n"), pins.pin(), &mut delay)
+            .unwrap()
+            .channel(Channel::new(7).unwrap());
+        let unresolved = hc12.resolve_mode(&mut delay).unwrap_err();
+        assert_eq!(
+            unresolved.error,
+            Error::Validation(ConfigWarning::SpeedNotSupported {
+                mode: 4,
+                baud_bps: 9600
+            })
+        );
+        assert_eq!(u8::from(unresolved.hc12.channel), 7);
+    }
+
+    #[test]
+    fn firmware_version_reads_the_banner() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut hc12 =
+            HC12::factor_settings(module.serial(), module.set_pin(), &mut delay).unwrap();
+        let firmware = hc12.firmware_version(&mut delay).unwrap();
+        assert_eq!(firmware.banner.as_str(), "www.hc01.com HC-12_V2.6\r\n");
+        assert_eq!(firmware.version, Some((2, 6)));
+        assert!(firmware.at_least(2, 5) && !firmware.at_least(2, 7));
+
+        module.set_firmware("SI4463 RF MODULE");
+        let firmware = hc12.firmware_version(&mut delay).unwrap();
+        assert_eq!(firmware.version, None);
+        assert!(!firmware.at_least(0, 0));
+    }
+
+    #[test]
+    fn serial_format_needs_firmware_that_knows_it() {
+        use crate::capabilities::{Capability, CapabilityTable};
+        use crate::paramaters::{DataBits, Parity, StopBits};
+
+        let even = SerialFormat::new(DataBits::Eight, Parity::Even, StopBits::One).unwrap();
+        let table = CapabilityTable::<4>::new();
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut hc12 =
+            HC12::factor_settings(module.serial(), module.set_pin(), &mut delay).unwrap();
+        assert_eq!(hc12.serial_format(), SerialFormat::EIGHT_N_ONE);
+
+        module.set_firmware("www.hc01.com HC-12_V2.3");
+        hc12.query_version(&table, &mut delay).unwrap();
+        assert_eq!(
+            hc12.set_serial_format(even, &mut delay),
+            Err(Error::UnsupportedByFirmware(Capability::SerialFormat))
+        );
+        assert_eq!(hc12.serial_format(), SerialFormat::EIGHT_N_ONE);
+
+        module.set_firmware("www.hc01.com HC-12_V2.6");
+        hc12.query_version(&table, &mut delay).unwrap();
+        let line = hc12.set_serial_format(even, &mut delay).unwrap();
+        assert_eq!(line, "OK+U8E1\r\n");
+        let hc12 = hc12.into_transparent_mode(&mut delay).unwrap();
+        assert_eq!(hc12.serial_format(), even);
+    }
+
+    #[test]
+    fn ping_confirms_at_mode_on_the_first_try() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let hc12 = HC12::factor_settings_verified(answering(b"OK\r\n"), pins.pin(), 3, &mut delay)
+            .unwrap();
+        assert_eq!(hc12.device.sink.data(), b"AT\r\n");
+        assert_eq!(pins.states().as_slice(), [PinState::Low]);
+    }
+
+    #[test]
+    fn ping_is_retried_until_answered() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let device = answering(b"ERROR\r\nERROR\r\nOK\r\n");
+        let hc12 = HC12::factor_settings_verified(device, pins.pin(), 3, &mut delay).unwrap();
+        assert_eq!(hc12.device.sink.data(), b"AT\r\nAT\r\nAT\r\n");
+        assert_eq!(pins.states().as_slice(), [PinState::Low]);
+        // the 40ms entry, and two retries
+        let retry_ms = AtTimings::DATASHEET.ping_retry_ms;
+        assert!(delay.elapsed_ms() >= 40 + 2 * u64::from(retry_ms));
+
+        // a module that missed the first pulse, on the mock
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let hc12 =
+            TransparentHC12::<_, _, Fu3, B9600>::assume_factory(module.serial(), module.set_pin());
+        module.inject_fault(Fault::Ignore);
+        let mut hc12 = hc12.into_programming_mode_verified(2, &mut delay).unwrap();
+        assert!(module.in_at_mode());
+        assert_eq!(hc12.query_channel(&mut delay), Ok(Channel::default()));
+    }
+
+    #[test]
+    fn unanswered_pings_hand_the_device_back() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let not_listening =
+            HC12::factor_settings_verified(Duo::default(), pins.pin(), 3, &mut delay).unwrap_err();
+        assert_eq!(not_listening.error, Error::NoResponse);
+        assert_eq!(pins.states().as_slice(), [PinState::Low, PinState::High]);
+        let (device, _pin) = not_listening.hc12.inner();
+        assert_eq!(device.sink.data(), b"AT\r\nAT\r\nAT\r\n");
+
+        // no attempts still pings once
+        let not_listening =
+            HC12::factor_settings_verified(Duo::default(), pins.pin(), 0, &mut delay).unwrap_err();
+        assert_eq!(not_listening.hc12.device.sink.data(), b"AT\r\n");
+    }
+
+    #[test]
+    fn raw_commands_return_whatever_came_back() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .response_timeout_ms(100);
+
+        // a reply that arrives after its command gave up is discarded
+        module.set_response_latency_ms(300);
+        assert_eq!(hc12.ping(&mut delay), Err(Error::NoResponse));
+        module.advance_ms(500);
+        module.set_response_latency_ms(5);
+        let line: Response = hc12.send_raw_at("AT+RC", &mut delay).unwrap();
+        assert_eq!(line, "OK+RC001\r\n");
+
+        let line: Response = hc12.send_raw_at("AT+XYZ", &mut delay).unwrap();
+        assert_eq!(line, "ERROR\r\n");
+        let result: Result<Response, _> = hc12.send_raw_at_expect_ok("AT+XYZ", &mut delay);
+        assert_eq!(result, Err(Error::NoOK("ERROR\r\n".try_into().unwrap())));
+        let line: Response = hc12.send_raw_at_expect_ok("AT+RF", &mut delay).unwrap();
+        assert_eq!(line, "OK+FU3\r\n");
+
+        let result: Result<Response, _> = hc12.send_raw_at("AT+SOMETHINGLONGER", &mut delay);
+        assert_eq!(result, Err(Error::CommandTooLong));
+    }
+
+    #[test]
+    fn raw_command_with_nothing_to_discard() {
+        /// A UART whose reads block, as embedded-io allows, until a byte arrives
+        struct BlockingSerial<'a>(MockSerial<'a>);
+
+        impl ErrorType for BlockingSerial<'_> {
+            type Error = Infallible;
+        }
+
+        impl Read for BlockingSerial<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+                assert!(self.0.read_ready()?, "read would block");
+                self.0.read(buf)
+            }
+        }
+
+        impl ReadReady for BlockingSerial<'_> {
+            fn read_ready(&mut self) -> Result<bool, Self::Error> {
+                self.0.read_ready()
+            }
+        }
+
+        impl Write for BlockingSerial<'_> {
+            fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+                self.0.write(buf)
+            }
+
+            fn flush(&mut self) -> Result<(), Self::Error> {
+                Ok(())
+            }
+        }
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let serial = BlockingSerial(module.serial());
+        let mut hc12 = HC12::factor_settings(serial, module.set_pin(), &mut delay).unwrap();
+        let line: Response = hc12.send_raw_at("AT+RC", &mut delay).unwrap();
+        assert_eq!(line, "OK+RC001\r\n");
+    }
+
+    #[test]
+    fn raw_command_without_an_answer() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let mut hc12 = HC12::factor_settings(Duo::default(), pins.pin(), &mut delay).unwrap();
+        let line: Response = hc12.send_raw_at("AT+NEW", &mut delay).unwrap();
+        assert!(line.is_empty());
+        let result: Result<Response, _> = hc12.send_raw_at_expect_ok("AT+NEW", &mut delay);
+        assert_eq!(result, Err(Error::NoResponse));
+        assert_eq!(hc12.device.sink.data(), b"AT+NEW\r\nAT+NEW\r\n");
+    }
+
+    #[test]
+    fn echoed_settings_are_verified() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let channel = Channel::new(21).unwrap();
+        let program = |answer: &[u8], delay: &mut CountingDelay| {
+            HC12::factor_settings(answering(answer), pins.pin(), delay)
+                .unwrap()
+                .power(Power::MIN)
+                .channel(channel)
+                .program(delay)
+                .map(|hc12| hc12.current_configuration())
+        };
+
+        let matched = program(b"OK+B9600\r\nOK+FU3\r\nOK+P1\r\nOK+C021\r\n", &mut delay);
+        assert_eq!(matched.unwrap().channel, channel);
+        assert_eq!(
+            program(b"OK+B9600\r\nOK+FU3\r\nOK+P1\r\nOK+C005\r\n", &mut delay),
+            Err(Error::Mismatch {
+                expected: 21,
+                got: 5
+            })
+        );
+        assert_eq!(
+            program(b"OK+B4800\r\n", &mut delay),
+            Err(Error::Mismatch {
+                expected: 9600,
+                got: 4800
+            })
+        );
+        assert_eq!(
+            program(b"OK\r\n", &mut delay),
+            Err(Error::InvalidResponse("OK\r\n".try_into().unwrap()))
+        );
+    }
+
+    #[test]
+    fn mismatched_echo_keeps_the_old_setting() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .power(Power::MIN)
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+
+        let configuration = Configuration::new(Channel::new(21).unwrap(), Power::MIN);
+        module.inject_fault(Fault::WrongEcho);
+        assert_eq!(
+            hc12.apply_diff(configuration, &mut delay),
+            Err(ApplyError::At(Error::Mismatch {
+                expected: 21,
+                got: 20
+            }))
+        );
+        assert_eq!(hc12.configuration().channel, Channel::default());
+
+        hc12.apply_diff(configuration, &mut delay).unwrap();
+        assert_eq!(hc12.configuration(), configuration);
+    }
+
+    /// Records every wait it is asked for, in milliseconds
+    #[derive(Default)]
+    struct SpyDelay(Vec<u32, 16>);
+
+    impl SpyDelay {
+        fn take(&mut self) -> Vec<u32, 16> {
+            core::mem::take(&mut self.0)
+        }
+    }
+
+    impl DelayNs for SpyDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+
+        fn delay_ms(&mut self, ms: u32) {
+            self.0.push(ms).unwrap();
+        }
+    }
+
+    #[test]
+    fn configured_timings_are_waited() {
+        let pins = PinLog::new();
+        let mut delay = SpyDelay::default();
+        let timings = AtTimings {
+            command_settle_ms: 60,
+            mode_change_settle_ms: 250,
+            baud_change_settle_ms: 30,
+            pin_settle_ms: 70,
+            ping_retry_ms: 20,
+        };
+
+        let hc12 = HC12::factor_settings_with_timings(
+            answering(b"OK+B9600\r\nOK+FU3\r\nOK+P1\r\nOK+C001\r\n"),
+            pins.pin(),
+            timings,
+            &mut delay,
+        )
+        .unwrap();
+        assert_eq!(delay.take(), [70]);
+
+        // the speed command gets the extra baud change settle
+        let hc12 = hc12.power(Power::MIN).program(&mut delay).unwrap();
+        assert_eq!(delay.take(), [90, 60, 60, 60]);
+
+        let hc12 = hc12.into_transparent_mode(&mut delay).unwrap();
+        assert_eq!(delay.take(), [250]);
+        let hc12 = hc12.into_programming_mode(&mut delay).unwrap();
+        assert_eq!(delay.take(), [70]);
+
+        // the defaults are the waits from before timings could be set
+        let hc12 = hc12.at_timings(AtTimings::default());
+        hc12.into_transparent_mode(&mut delay).unwrap();
+        assert_eq!(delay.take(), [SETTLE_MS]);
+    }
+}
@@ -0,0 +1,326 @@
+//! Settings typed at a console, such as `C21 P5 B9600 FU3`.
+//!
+//! [`parse`] reads a line of space separated settings into a [`FullConfiguration`]. Each
+//! of `C` (channel), `P` (power level), `B` (serial speed) and `FU` (mode) must appear
+//! once, in any order and either case, so a mistyped or forgotten setting is reported
+//! rather than left at a default. Errors carry the byte offset of the token at fault, for
+//! pointing at it in the console.
+//!
+//! [`apply`] programs the channel and power of parsed settings into a transparent device,
+//! sending only what changed, and returns a [`ChangeSummary`] that can be printed back.
+//!
+//! ```
+//! use hc12_rs::paramaters::Power;
+//! use hc12_rs::provision::{parse, ParseError, Setting};
+//!
+//! let settings = parse("FU3 c21 P1 B9600").unwrap();
+//! assert_eq!(u8::from(settings.configuration.channel), 21);
+//! assert_eq!(settings.configuration.power, Power::P1);
+//!
+//! assert_eq!(parse("C21 P1 X9 FU3"), Err(ParseError::UnknownToken { at: 7 }));
+//! assert_eq!(parse("C21 P1 FU3"), Err(ParseError::Missing(Setting::Speed)));
+//! ```
+
+use crate::paramaters::{Channel, Configuration, FullConfiguration, Power};
+use crate::validation::{ConfigWarning, SERIAL_SPEEDS_BPS};
+
+#[cfg(feature = "programming")]
+use crate::{
+    changes::{ApplyError, ChangeSummary},
+    modes::ValidMode,
+    speeds::ValidSpeed,
+    AtRead, TransparentHC12,
+};
+#[cfg(feature = "programming")]
+use embedded_hal::{delay::DelayNs, digital::OutputPin};
+#[cfg(feature = "programming")]
+use embedded_io::Write;
+
+/// A setting in a provisioning line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Setting {
+    /// `C`, the channel
+    Channel,
+    /// `P`, the power level
+    Power,
+    /// `B`, the serial speed in bits per second
+    Speed,
+    /// `FU`, the mode number
+    Mode,
+}
+
+/// A provisioning line could not be parsed. `at` is the byte offset of the token at fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum ParseError {
+    /// The token is not a setting
+    UnknownToken {
+        /// Where the token starts
+        at: usize,
+    },
+    /// The value is missing, not a number, or not accepted by the module
+    BadValue {
+        /// Where the token starts
+        at: usize,
+        /// The setting
+        setting: Setting,
+    },
+    /// The setting was already given
+    Repeated {
+        /// Where the second token starts
+        at: usize,
+        /// The setting
+        setting: Setting,
+    },
+    /// The setting was not given
+    Missing(Setting),
+    /// Every setting is valid, but the mode does not support the serial speed
+    Unsupported(ConfigWarning),
+}
+
+/// Parse a line of settings, see the [module documentation](self)
+pub fn parse(line: &str) -> Result<FullConfiguration, ParseError> {
+    let (mut channel, mut power, mut speed, mut mode) = (None, None, None, None);
+
+    for (at, token) in tokens(line) {
+        let (setting, value) = if let Some(value) = strip_prefix(token, "FU") {
+            (Setting::Mode, value)
+        } else if let Some(value) = strip_prefix(token, "C") {
+            (Setting::Channel, value)
+        } else if let Some(value) = strip_prefix(token, "P") {
+            (Setting::Power, value)
+        } else if let Some(value) = strip_prefix(token, "B") {
+            (Setting::Speed, value)
+        } else {
+            return Err(ParseError::UnknownToken { at });
+        };
+
+        let bad = ParseError::BadValue { at, setting };
+        // a leading sign would otherwise be accepted by `parse`
+        if !value.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(bad);
+        }
+        let number: u32 = value.parse().map_err(|_| bad)?;
+        let small = u8::try_from(number).map_err(|_| bad);
+        let repeated = match setting {
+            Setting::Channel => {
+                let value = Channel::new(small?).map_err(|_| bad)?;
+                channel.replace(value).is_some()
+            }
+            Setting::Power => {
+                let value = Power::try_from(small?).map_err(|_| bad)?;
+                power.replace(value).is_some()
+            }
+            Setting::Speed if SERIAL_SPEEDS_BPS.contains(&number) => {
+                speed.replace(number).is_some()
+            }
+            Setting::Mode if (1..=4).contains(&number) => mode.replace(small?).is_some(),
+            Setting::Speed | Setting::Mode => return Err(bad),
+        };
+        if repeated {
+            return Err(ParseError::Repeated { at, setting });
+        }
+    }
+
+    let settings = FullConfiguration {
+        configuration: Configuration::new(
+            channel.ok_or(ParseError::Missing(Setting::Channel))?,
+            power.ok_or(ParseError::Missing(Setting::Power))?,
+        ),
+        baudrate_bps: speed.ok_or(ParseError::Missing(Setting::Speed))?,
+        mode: mode.ok_or(ParseError::Missing(Setting::Mode))?,
+    };
+    if let Err(findings) = settings
+        .configuration
+        .validate_for(settings.mode, settings.baudrate_bps)
+    {
+        if let Some(error) = findings.iter().find(|finding| finding.is_error()) {
+            return Err(ParseError::Unsupported(*error));
+        }
+    }
+    Ok(settings)
+}
+
+/// The space separated tokens of `line`, with their byte offsets
+fn tokens(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    line.split(' ')
+        .scan(0, |at, token| {
+            let start = *at;
+            *at += token.len() + 1;
+            Some((start, token))
+        })
+        .filter(|(_, token)| !token.is_empty())
+}
+
+/// `token` without `prefix`, matched ignoring ASCII case
+fn strip_prefix<'a>(token: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = token.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &token[prefix.len()..])
+}
+
+/// Parsed settings could not be applied
+#[cfg(feature = "programming")]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProvisionError<D: core::fmt::Debug, P> {
+    /// The mode or serial speed differs from the device's. These are part of the device's
+    /// type, so changing them means programming it again with
+    /// [`HC12`](crate::HC12).
+    ModeOrSpeed {
+        /// The mode number asked for
+        mode: u8,
+        /// The serial speed asked for
+        baudrate_bps: u32,
+    },
+    /// Programming the channel or power failed
+    Apply(ApplyError<D, P>),
+}
+
+/// Program the channel and power of `settings` into `hc12`, see
+/// [`apply_diff`](TransparentHC12::apply_diff). The mode and serial speed must already
+/// match.
+#[cfg(feature = "programming")]
+pub fn apply<Device, Pin, Mode, Speed>(
+    hc12: &mut TransparentHC12<Device, Pin, Mode, Speed>,
+    settings: &FullConfiguration,
+    delay: &mut impl DelayNs,
+) -> Result<ChangeSummary, ProvisionError<Device::Error, Pin::Error>>
+where
+    Device: AtRead + Write,
+    Pin: OutputPin,
+    Mode: ValidMode,
+    Speed: ValidSpeed,
+{
+    if settings.mode != Mode::NUMBER || settings.baudrate_bps != Speed::bps() {
+        return Err(ProvisionError::ModeOrSpeed {
+            mode: settings.mode,
+            baudrate_bps: settings.baudrate_bps,
+        });
+    }
+    hc12.apply_diff(settings.configuration, delay)
+        .map_err(ProvisionError::Apply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(channel: u8, power: Power, mode: u8, baudrate_bps: u32) -> FullConfiguration {
+        FullConfiguration {
+            configuration: Configuration::new(Channel::new(channel).unwrap(), power),
+            mode,
+            baudrate_bps,
+        }
+    }
+
+    #[test]
+    fn parses_in_any_order() {
+        let expected = settings(21, Power::P1, 3, 9600);
+        assert_eq!(parse("C21 P1 B9600 FU3"), Ok(expected));
+        assert_eq!(parse("  fu3 B9600  p1 C21 "), Ok(expected));
+        assert_eq!(
+            parse("FU4 B1200 C100 P1"),
+            Ok(settings(100, Power::P1, 4, 1200))
+        );
+    }
+
+    #[test]
+    fn points_at_the_bad_token() {
+        use ParseError::*;
+
+        assert_eq!(parse("C21 P1 B9600 radio"), Err(UnknownToken { at: 13 }));
+        assert_eq!(
+            parse("C0 P1 B9600 FU3"),
+            Err(BadValue {
+                at: 0,
+                setting: Setting::Channel
+            })
+        );
+        assert_eq!(
+            parse("C21 P9 B9600 FU3"),
+            Err(BadValue {
+                at: 4,
+                setting: Setting::Power
+            })
+        );
+        assert_eq!(
+            parse("C21 P1 B9601 FU3"),
+            Err(BadValue {
+                at: 7,
+                setting: Setting::Speed
+            })
+        );
+        assert_eq!(
+            parse("C21 P1 B9600 FU+3"),
+            Err(BadValue {
+                at: 13,
+                setting: Setting::Mode
+            })
+        );
+        assert_eq!(
+            parse("C21 P1 C22 B9600 FU3"),
+            Err(Repeated {
+                at: 7,
+                setting: Setting::Channel
+            })
+        );
+    }
+
+    #[test]
+    fn partial_lines_are_rejected() {
+        assert_eq!(parse(""), Err(ParseError::Missing(Setting::Channel)));
+        assert_eq!(
+            parse("C21 B9600 FU3"),
+            Err(ParseError::Missing(Setting::Power))
+        );
+        assert_eq!(
+            parse("C21 P1 B9600 FU"),
+            Err(ParseError::BadValue {
+                at: 13,
+                setting: Setting::Mode
+            })
+        );
+        assert_eq!(
+            parse("C21 P1 B9600 FU4"),
+            Err(ParseError::Unsupported(ConfigWarning::SpeedNotSupported {
+                mode: 4,
+                baud_bps: 9600
+            }))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "programming")]
+    fn applies_over_the_mock() {
+        extern crate std;
+        use crate::mock::MockHc12;
+        use crate::HC12;
+        use std::string::ToString;
+
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .power(Power::MIN)
+            .program(&mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+
+        let summary = apply(&mut hc12, &parse("C21 P1 B9600 FU3").unwrap(), &mut delay).unwrap();
+        assert_eq!(
+            summary.to_string(),
+            "channel: 1 -> 21\npower: P1 (unchanged)\n"
+        );
+        assert_eq!(module.settings().channel, 21);
+
+        assert_eq!(
+            apply(&mut hc12, &parse("C21 P1 B1200 FU4").unwrap(), &mut delay),
+            Err(ProvisionError::ModeOrSpeed {
+                mode: 4,
+                baudrate_bps: 1200
+            })
+        );
+    }
+}
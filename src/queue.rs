@@ -0,0 +1,281 @@
+use embedded_io::{Write, WriteReady};
+use heapless::Deque;
+
+use crate::storage::Ring;
+use crate::time::IntoMillis;
+
+/// The transmit queue does not have space for the frame
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct QueueFull;
+
+/// Bytes used to store the length of each queued frame
+const HEADER: usize = 2;
+
+/// A transmit queue that never blocks the caller.
+///
+/// Frames are accepted immediately into a buffer of `N` bytes (each frame uses two extra
+/// bytes of bookkeeping), kept in `Q`, see [`storage`](crate::storage), and are pushed to
+/// the device by [`pump`](QueuedWriter::pump), which should be called regularly from the
+/// main loop. `pump` only writes while the device reports it is ready, and starts frames
+/// no closer together than the configured interval, such as a mode's
+/// [`PACKET_INTERVAL_MS`](crate::modes::ValidMode::PACKET_INTERVAL_MS).
+///
+/// Times are milliseconds from any free-running clock, and may wrap around.
+pub struct QueuedWriter<D, const N: usize, Q = Deque<u8, N>> {
+    device: D,
+    interval_ms: u32,
+    queue: Q,
+    frames: usize,
+    remaining: usize,
+    last_start_ms: Option<u32>,
+}
+
+impl<D, const N: usize> QueuedWriter<D, N> {
+    /// Queue writes to a device, starting frames at least `interval` apart
+    pub fn new(device: D, interval: impl IntoMillis) -> Self {
+        Self::with_queue(device, interval, Deque::new())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<D, const N: usize> QueuedWriter<D, N, alloc::collections::VecDeque<u8>> {
+    /// [`new`](QueuedWriter::new), allocating the queue as frames are enqueued
+    pub fn new_alloc(device: D, interval: impl IntoMillis) -> Self {
+        Self::with_queue(device, interval, alloc::collections::VecDeque::new())
+    }
+}
+
+impl<D, const N: usize, Q: Ring<u8>> QueuedWriter<D, N, Q> {
+    fn with_queue(device: D, interval: impl IntoMillis, queue: Q) -> Self {
+        Self {
+            device,
+            interval_ms: interval.into_ms(),
+            queue,
+            frames: 0,
+            remaining: 0,
+            last_start_ms: None,
+        }
+    }
+
+    /// Queue a frame for transmission
+    pub fn enqueue(&mut self, frame: &[u8]) -> Result<(), QueueFull> {
+        let length = u16::try_from(frame.len()).map_err(|_| QueueFull)?;
+        if N - self.queue.len() < frame.len() + HEADER {
+            return Err(QueueFull);
+        }
+
+        for byte in length.to_le_bytes().iter().chain(frame) {
+            // space was checked above
+            self.queue.push_back(*byte).ok();
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Number of frame bytes still waiting to be written
+    pub fn pending(&self) -> usize {
+        self.queue.len() - self.frames * HEADER
+    }
+
+    /// The earliest time at which the last queued frame can be started, given the
+    /// pacing interval, or `None` if there is nothing waiting to be started. Frames are
+    /// assumed to be written as soon as they are allowed to start.
+    pub fn flush_deadline(&self, now_ms: u32) -> Option<u32> {
+        if self.frames == 0 {
+            return (self.remaining > 0).then_some(now_ms);
+        }
+
+        let first = self.next_start_ms(now_ms);
+        let later = self.interval_ms.saturating_mul(self.frames as u32 - 1);
+        Some(first.wrapping_add(later))
+    }
+
+    /// Return the device. Any queued frames are dropped.
+    pub fn inner(self) -> D {
+        self.device
+    }
+
+    fn next_start_ms(&self, now_ms: u32) -> u32 {
+        match self.last_start_ms {
+            Some(last) if now_ms.wrapping_sub(last) < self.interval_ms => {
+                last.wrapping_add(self.interval_ms)
+            }
+            _ => now_ms,
+        }
+    }
+}
+
+impl<D, const N: usize, Q: Ring<u8>> QueuedWriter<D, N, Q>
+where
+    D: Write + WriteReady,
+{
+    /// Write as much queued data as the device and the pacing allow, without blocking
+    pub fn pump(&mut self, now_ms: u32) -> Result<(), D::Error> {
+        loop {
+            if self.remaining == 0 {
+                if self.frames == 0 || self.next_start_ms(now_ms) != now_ms {
+                    return Ok(());
+                }
+
+                let mut length = [0u8; HEADER];
+                for byte in length.iter_mut() {
+                    *byte = self.queue.pop_front().unwrap_or_default();
+                }
+                self.remaining = u16::from_le_bytes(length) as usize;
+                self.frames -= 1;
+                self.last_start_ms = Some(now_ms);
+            }
+
+            if !self.device.write_ready()? {
+                return Ok(());
+            }
+
+            let (front, _) = self.queue.as_slices();
+            let chunk = &front[..front.len().min(self.remaining)];
+            let written = self.device.write(chunk)?;
+            if written == 0 {
+                return Ok(());
+            }
+            for _ in 0..written {
+                self.queue.pop_front();
+            }
+            self.remaining -= written;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use embedded_io::ErrorType;
+    use heapless::Vec;
+
+    /// Records writes, accepting at most `room` bytes before reporting busy
+    #[derive(Default)]
+    struct Port {
+        written: Vec<u8, 64>,
+        room: usize,
+    }
+
+    impl ErrorType for Port {
+        type Error = Infallible;
+    }
+
+    impl Write for Port {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let count = buf.len().min(self.room);
+            self.written.extend_from_slice(&buf[..count]).ok();
+            self.room -= count;
+            Ok(count)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl WriteReady for Port {
+        fn write_ready(&mut self) -> Result<bool, Self::Error> {
+            Ok(self.room > 0)
+        }
+    }
+
+    fn port(room: usize) -> Port {
+        Port {
+            room,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn frames_are_written_in_order() {
+        let mut writer: QueuedWriter<_, 32> = QueuedWriter::new(port(64), 0);
+        writer.enqueue(b"one").unwrap();
+        writer.enqueue(b"two").unwrap();
+        writer.enqueue(b"three").unwrap();
+        assert_eq!(writer.pending(), 11);
+
+        writer.pump(0).unwrap();
+        assert_eq!(writer.pending(), 0);
+        assert_eq!(writer.inner().written.as_slice(), b"onetwothree");
+    }
+
+    #[test]
+    fn waits_for_write_ready() {
+        let mut writer: QueuedWriter<_, 32> = QueuedWriter::new(port(4), 0);
+        writer.enqueue(b"hello").unwrap();
+
+        writer.pump(0).unwrap();
+        assert_eq!(writer.pending(), 1);
+
+        writer.device.room = 4;
+        writer.pump(1).unwrap();
+        assert_eq!(writer.pending(), 0);
+        assert_eq!(writer.inner().written.as_slice(), b"hello");
+    }
+
+    #[test]
+    fn frames_are_paced() {
+        let mut writer: QueuedWriter<_, 32> = QueuedWriter::new(port(64), 1000);
+        writer.enqueue(b"a").unwrap();
+        writer.enqueue(b"b").unwrap();
+        writer.enqueue(b"c").unwrap();
+        assert_eq!(writer.flush_deadline(100), Some(2100));
+
+        writer.pump(100).unwrap();
+        assert_eq!(writer.device.written.as_slice(), b"a");
+        assert_eq!(writer.flush_deadline(500), Some(2100));
+
+        writer.pump(1099).unwrap();
+        assert_eq!(writer.device.written.as_slice(), b"a");
+
+        writer.pump(1100).unwrap();
+        assert_eq!(writer.device.written.as_slice(), b"ab");
+
+        writer.pump(5000).unwrap();
+        assert_eq!(writer.device.written.as_slice(), b"abc");
+        assert_eq!(writer.flush_deadline(5000), None);
+    }
+
+    #[test]
+    fn pacing_survives_clock_wraparound() {
+        let mut writer: QueuedWriter<_, 32> = QueuedWriter::new(port(64), 1000);
+        writer.enqueue(b"a").unwrap();
+        writer.enqueue(b"b").unwrap();
+
+        writer.pump(u32::MAX - 499).unwrap();
+        writer.pump(499).unwrap();
+        assert_eq!(writer.device.written.as_slice(), b"a");
+
+        writer.pump(500).unwrap();
+        assert_eq!(writer.device.written.as_slice(), b"ab");
+    }
+
+    #[test]
+    fn full_queue_is_reported() {
+        let mut writer: QueuedWriter<_, 8> = QueuedWriter::new(port(64), 0);
+        writer.enqueue(b"12345").unwrap();
+        assert!(writer.enqueue(b"").is_err());
+        assert_eq!(writer.pending(), 5);
+
+        writer.pump(0).unwrap();
+        writer.enqueue(b"123456").unwrap();
+        assert!(writer.enqueue(b"7").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn allocated_queue_is_bounded_the_same() {
+        let mut writer: QueuedWriter<_, 8, alloc::collections::VecDeque<u8>> =
+            QueuedWriter::new_alloc(port(64), 0);
+        writer.enqueue(b"12345").unwrap();
+        assert!(writer.enqueue(b"").is_err());
+
+        writer.pump(0).unwrap();
+        writer.enqueue(b"123456").unwrap();
+        writer.pump(0).unwrap();
+        assert_eq!(writer.inner().written.as_slice(), b"12345123456");
+    }
+}
@@ -0,0 +1,120 @@
+//! Blocking reads that give up after a timeout, or when asked to.
+//!
+//! [`read_exact_timeout`] fills a buffer from any [`Read`] + [`ReadReady`] device, waiting
+//! at most a given time. [`read_exact_cancellable`] also calls a predicate, such as a check
+//! of a stop button, and gives up as soon as it returns true. Both poll the device, and
+//! whenever nothing is ready they check the predicate and then wait
+//! [`POLL_INTERVAL_MS`], so a cancellation takes effect within about that long.
+
+use embedded_hal::delay::DelayNs;
+use embedded_io::{Read, ReadReady};
+
+use crate::time::IntoMillis;
+
+/// How long a blocking read waits between polls of an idle device
+pub const POLL_INTERVAL_MS: u32 = 1;
+
+/// A blocking read did not fill its buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum ReadError<E> {
+    /// The serial device failed
+    Link(E),
+    /// The timeout passed, with this many bytes received
+    TimedOut {
+        /// Bytes received before the timeout
+        received: usize,
+    },
+    /// The predicate asked to stop, with this many bytes received
+    Cancelled {
+        /// Bytes received before the cancellation
+        received: usize,
+    },
+}
+
+/// Fill `buf`, waiting at most `timeout` in total for bytes to arrive
+pub fn read_exact_timeout<D>(
+    device: &mut D,
+    buf: &mut [u8],
+    delay: &mut impl DelayNs,
+    timeout: impl IntoMillis,
+) -> Result<(), ReadError<D::Error>>
+where
+    D: Read + ReadReady,
+{
+    read_exact_cancellable(device, buf, delay, timeout, || false)
+}
+
+/// Like [`read_exact_timeout`], but give up with [`ReadError::Cancelled`] once
+/// `should_cancel` returns true. It is called before each wait of [`POLL_INTERVAL_MS`].
+pub fn read_exact_cancellable<D>(
+    device: &mut D,
+    buf: &mut [u8],
+    delay: &mut impl DelayNs,
+    timeout: impl IntoMillis,
+    mut should_cancel: impl FnMut() -> bool,
+) -> Result<(), ReadError<D::Error>>
+where
+    D: Read + ReadReady,
+{
+    let timeout_ms = timeout.into_ms();
+    let mut received = 0;
+    let mut waited_ms = 0;
+    while received < buf.len() {
+        if device.read_ready().map_err(ReadError::Link)? {
+            received += device.read(&mut buf[received..]).map_err(ReadError::Link)?;
+            continue;
+        }
+        if should_cancel() {
+            return Err(ReadError::Cancelled { received });
+        }
+        if waited_ms >= timeout_ms {
+            return Err(ReadError::TimedOut { received });
+        }
+        delay.delay_ms(POLL_INTERVAL_MS);
+        waited_ms += POLL_INTERVAL_MS;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{CountingDelay, Source};
+
+    #[test]
+    fn reads_what_is_there() {
+        let mut source = Source::new().data(b"hello");
+        let mut buf = [0; 5];
+        let result = read_exact_timeout(&mut source, &mut buf, &mut CountingDelay::new(), 10);
+        assert_eq!(result, Ok(()));
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[test]
+    fn times_out_with_the_partial_count() {
+        let mut source = Source::new().data(b"he");
+        let mut delay = CountingDelay::new();
+        let mut buf = [0; 5];
+        assert_eq!(
+            read_exact_timeout(&mut source, &mut buf, &mut delay, 3000),
+            Err(ReadError::TimedOut { received: 2 })
+        );
+        assert_eq!(delay.elapsed_ms(), 3000);
+    }
+
+    #[test]
+    fn cancels_promptly() {
+        let mut source = Source::new().data(b"he");
+        let mut delay = CountingDelay::new();
+        let mut buf = [0; 5];
+        let mut polls = 0;
+        let result = read_exact_cancellable(&mut source, &mut buf, &mut delay, 5000, || {
+            polls += 1;
+            polls == 10
+        });
+        assert_eq!(result, Err(ReadError::Cancelled { received: 2 }));
+        // the tenth check came after nine waits, long before the timeout
+        assert_eq!(delay.elapsed_ms(), 9 * POLL_INTERVAL_MS as u64);
+    }
+}
@@ -0,0 +1,283 @@
+//! Changing settings in one scoped trip to AT mode.
+//!
+//! [`TransparentHC12::reprogram`] pulls the programming pin low, hands an [`AtSession`] to
+//! a closure, and returns the module to transparent mode when the closure returns, whether
+//! or not it succeeded. Settings changed through the session are kept by the device, so a
+//! channel hop is one call:
+//!
+//! ```
+//! use hc12_rs::mock::MockHc12;
+//! use hc12_rs::paramaters::{Channel, Power};
+//! use hc12_rs::HC12;
+//!
+//! let module = MockHc12::new();
+//! let mut delay = module.delay();
+//! let mut hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+//!     .unwrap()
+//!     .power(Power::MAX)
+//!     .program(&mut delay)
+//!     .unwrap()
+//!     .into_transparent_mode(&mut delay)
+//!     .unwrap();
+//!
+//! let channel = Channel::new(21).unwrap();
+//! hc12.reprogram(&mut delay, |at| at.set_channel(channel))
+//!     .unwrap();
+//! assert_eq!(*hc12.channel(), channel);
+//! assert!(!module.in_at_mode());
+//! ```
+
+use core::convert::Infallible;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use embedded_io::Write;
+
+use crate::commands::{Command, Version};
+use crate::events::{notify, AtEvent, Transition};
+use crate::paramaters::{Channel, Configuration, Power};
+use crate::{AtRead, Error, Response, TransparentHC12};
+
+/// A [`reprogram`](TransparentHC12::reprogram) did not complete
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum ReprogramError<E, P> {
+    /// The programming pin could not be pulled low. The closure was not run.
+    Enter(P),
+    /// The closure failed. The module was returned to transparent mode.
+    Session(E),
+    /// The programming pin could not be released, so the module may still be in AT mode.
+    /// `session` is the closure's error, if it failed as well.
+    Leave {
+        /// The pin error
+        pin: P,
+        /// The closure's error, if any
+        session: Option<E>,
+    },
+}
+
+/// The module in AT mode, for the duration of a
+/// [`reprogram`](TransparentHC12::reprogram) closure. Commands are recorded in the
+/// transaction log and seen by the observer as usual.
+pub struct AtSession<'a, Device, Pin, Mode, Speed> {
+    hc12: &'a mut TransparentHC12<Device, Pin, Mode, Speed>,
+    delay: &'a mut dyn DelayNs,
+}
+
+impl<Device, Pin, Mode, Speed> AtSession<'_, Device, Pin, Mode, Speed>
+where
+    Device: AtRead + Write,
+{
+    /// Switch to `channel`, if it is in the device's allowed channels, returning the
+    /// module's answer, such as `OK+C021`
+    pub fn set_channel(&mut self, channel: Channel) -> Result<Response, Error<Device::Error>> {
+        self.hc12.session.allowed.check(channel)?;
+        let line = self.run(channel)?;
+        self.hc12.channel = channel;
+        Ok(line)
+    }
+
+    /// Switch to `power`, if it is not above the build's
+    /// [`MAX_POWER`](crate::paramaters::MAX_POWER), returning the module's answer, such
+    /// as `OK+P4`
+    pub fn set_power(&mut self, power: Power) -> Result<Response, Error<Device::Error>> {
+        power
+            .within_cap()
+            .map_err(|_| Error::ExceedsBuildCap(power))?;
+        let line = self.run(power)?;
+        self.hc12.power = power;
+        Ok(line)
+    }
+
+    /// The firmware version reported by the module, such as `www.hc01.com HC-12_V2.6`
+    pub fn version(&mut self) -> Result<Response<32>, Error<Device::Error, Infallible, 32>> {
+        match self.run(Version) {
+            // the version line does not contain OK
+            Err(Error::NoOK(line)) => Ok(line),
+            result => result,
+        }
+    }
+
+    /// The channel and power, including changes made in this session
+    pub fn configuration(&self) -> Configuration {
+        Configuration::new(self.hc12.channel, self.hc12.power)
+    }
+
+    /// Run a single AT command, recording it in the transaction log
+    pub(crate) fn run<const N: usize>(
+        &mut self,
+        command: impl Command,
+    ) -> Result<Response<N>, Error<Device::Error, Infallible, N>> {
+        self.hc12.session.permits(&command)?;
+        self.hc12.exchange_in_at_mode(command, self.delay)
+    }
+}
+
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed>
+where
+    Device: AtRead + Write,
+    Pin: OutputPin,
+{
+    /// Enter AT mode, run `f` with an [`AtSession`], and return to transparent mode, see
+    /// the [module documentation](crate::reprogram). The return to transparent mode is
+    /// attempted even if `f` fails. Blocks for at least 120ms with the default
+    /// [`AtTimings`](crate::AtTimings), plus the commands sent.
+    pub fn reprogram<R, E>(
+        &mut self,
+        delay: &mut impl DelayNs,
+        f: impl FnOnce(&mut AtSession<'_, Device, Pin, Mode, Speed>) -> Result<R, E>,
+    ) -> Result<R, ReprogramError<E, Pin::Error>> {
+        self.pin.set_low().map_err(ReprogramError::Enter)?;
+        delay.delay_ms(self.session.timings.pin_settle_ms);
+        notify(self.session.observer, || {
+            AtEvent::TransitionPerformed(Transition::IntoProgramming)
+        });
+
+        let result = f(&mut AtSession {
+            hc12: self,
+            delay: &mut *delay,
+        });
+
+        if let Err(pin) = self.pin.set_high() {
+            return Err(ReprogramError::Leave {
+                pin,
+                session: result.err(),
+            });
+        }
+        delay.delay_ms(self.session.timings.mode_change_settle_ms);
+        notify(self.session.observer, || {
+            AtEvent::TransitionPerformed(Transition::IntoTransparent)
+        });
+        result.map_err(ReprogramError::Session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Fault, MockHc12, MockSetPin};
+    use crate::modes::Fu3;
+    use crate::speeds::B9600;
+    use crate::HC12;
+    use core::cell::Cell;
+    use embedded_hal::digital::{ErrorKind, ErrorType};
+
+    /// The mock's SET pin, which can be made to fail when released
+    struct StuckPin<'a> {
+        pin: MockSetPin<'a>,
+        stuck: &'a Cell<bool>,
+    }
+
+    impl ErrorType for StuckPin<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl OutputPin for StuckPin<'_> {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.pin.set_low().map_err(|never| match never {})
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            if self.stuck.get() {
+                return Err(ErrorKind::Other);
+            }
+            self.pin.set_high().map_err(|never| match never {})
+        }
+    }
+
+    fn transparent<'a>(
+        module: &'a MockHc12,
+        stuck: &'a Cell<bool>,
+    ) -> TransparentHC12<crate::mock::MockSerial<'a>, StuckPin<'a>, Fu3, B9600> {
+        let pin = StuckPin {
+            pin: module.set_pin(),
+            stuck,
+        };
+        let mut delay = module.delay();
+        HC12::factor_settings(module.serial(), pin, &mut delay)
+            .unwrap()
+            .power(Power::MAX)
+            .program(&mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap()
+    }
+
+    #[test]
+    fn closure_changes_settings() {
+        let (module, stuck) = (MockHc12::new(), Cell::new(false));
+        let mut hc12 = transparent(&module, &stuck);
+        let mut delay = module.delay();
+
+        let configuration = hc12
+            .reprogram(&mut delay, |at| {
+                let line = at.set_channel(Channel::new(21).unwrap())?;
+                assert_eq!(line, "OK+C021\r\n");
+                let line = at.set_power(Power::P1)?;
+                assert_eq!(line, "OK+P1\r\n");
+                Ok::<_, Error<_>>(at.configuration())
+            })
+            .unwrap();
+        assert_eq!(
+            configuration,
+            Configuration::new(Channel::new(21).unwrap(), Power::P1)
+        );
+        assert_eq!(hc12.configuration(), configuration);
+        assert!(!module.in_at_mode());
+
+        let version = hc12.reprogram(&mut delay, |at| at.version()).unwrap();
+        assert!(version.starts_with("www.hc01.com"));
+        assert_eq!(module.settings().channel, 21);
+    }
+
+    #[test]
+    fn failed_closure_still_leaves_at_mode() {
+        let (module, stuck) = (MockHc12::new(), Cell::new(false));
+        let mut hc12 = transparent(&module, &stuck);
+        let mut delay = module.delay();
+
+        let result = hc12.reprogram(&mut delay, |at| {
+            at.set_channel(Channel::new(5).unwrap())?;
+            module.inject_fault(Fault::Error);
+            at.set_power(Power::P1)
+        });
+        assert!(matches!(
+            result,
+            Err(ReprogramError::Session(Error::NoOK(_)))
+        ));
+        // the channel change before the failure is kept
+        assert_eq!(
+            hc12.configuration(),
+            Configuration::new(Channel::new(5).unwrap(), Power::MAX)
+        );
+        assert!(!module.in_at_mode());
+    }
+
+    #[test]
+    fn stuck_pin_reports_both_failures() {
+        let (module, stuck) = (MockHc12::new(), Cell::new(false));
+        let mut hc12 = transparent(&module, &stuck);
+        let mut delay = module.delay();
+
+        stuck.set(true);
+        let result = hc12.reprogram(&mut delay, |at| at.set_power(Power::P1));
+        assert_eq!(
+            result,
+            Err(ReprogramError::Leave {
+                pin: ErrorKind::Other,
+                session: None
+            })
+        );
+        assert!(module.in_at_mode());
+
+        module.inject_fault(Fault::Ignore);
+        let result = hc12.reprogram(&mut delay, |at| at.set_power(Power::P1));
+        assert_eq!(
+            result,
+            Err(ReprogramError::Leave {
+                pin: ErrorKind::Other,
+                session: Some(Error::NoResponse)
+            })
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! Parsing of the module's answers to AT commands.
+//!
+//! Nothing here does any I/O: every function works on bytes already read, so the same
+//! parsing used by the driver can be applied to a captured serial log. Lines may start
+//! with noise, such as the bytes a module emits while powering up, and end with `\r\n`.
+//!
+//! ```
+//! use hc12_rs::response::{self, Reply};
+//!
+//! let rx = b"OK+B9600\r\nOK+RC001\r\nOK+RP:+20dBm\r\nOK+FU3\r\n";
+//! let mut values = response::lines(rx).map(response::value);
+//! assert_eq!(values.next(), Some(Some(&b"B9600"[..])));
+//! assert_eq!(values.next(), Some(Some(&b"RC001"[..])));
+//!
+//! assert_eq!(response::parse(b"\xff\x00OK\r\n"), Reply::Ok);
+//! assert_eq!(response::parse(b"ERROR\r\n"), Reply::Error);
+//! ```
+
+use core::str::from_utf8;
+
+/// One line of an answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum Reply<'a> {
+    /// A bare `OK`, as answered to `AT`
+    Ok,
+    /// `OK+` and a value, such as `B9600` for `OK+B9600`
+    Value(&'a [u8]),
+    /// `ERROR`, for a command the module did not accept
+    Error,
+    /// Any other line, such as the version banner
+    Other(&'a [u8]),
+}
+
+/// Parse a single line, ignoring leading noise and the line terminator
+pub fn parse(line: &[u8]) -> Reply<'_> {
+    let line = trim(line);
+    match find(line, b"OK") {
+        Some(at) => match &line[at + 2..] {
+            [] => Reply::Ok,
+            [b'+', value @ ..] => Reply::Value(value),
+            _ => Reply::Other(line),
+        },
+        None if line == b"ERROR" => Reply::Error,
+        None => Reply::Other(line),
+    }
+}
+
+/// Whether the line acknowledges a command. Any line containing `OK` does, as the driver
+/// has always treated them.
+pub fn is_ok(line: &[u8]) -> bool {
+    find(line, b"OK").is_some()
+}
+
+/// The value of an `OK+` line, such as `P8` for `OK+P8`
+pub fn value(line: &[u8]) -> Option<&[u8]> {
+    match parse(line) {
+        Reply::Value(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// The line without leading noise: anything before the first printable ASCII character
+pub fn skip_noise(line: &[u8]) -> &[u8] {
+    let start = line
+        .iter()
+        .position(u8::is_ascii_graphic)
+        .unwrap_or(line.len());
+    &line[start..]
+}
+
+/// The line without leading noise or trailing whitespace, including the terminator
+pub fn trim(line: &[u8]) -> &[u8] {
+    let line = skip_noise(line);
+    let end = line
+        .iter()
+        .rposition(|byte| !byte.is_ascii_whitespace())
+        .map_or(0, |last| last + 1);
+    &line[..end]
+}
+
+/// The line as text, up to any bytes that are not valid UTF-8
+pub fn text(line: &[u8]) -> &str {
+    match from_utf8(line) {
+        Ok(text) => text,
+        // the bytes up to `valid_up_to` are valid, so this cannot fail
+        Err(error) => from_utf8(&line[..error.valid_up_to()]).unwrap(),
+    }
+}
+
+/// The length of the first complete line in `bytes`, including its `\n`, or `None` if the
+/// line has not been fully received
+pub fn line_end(bytes: &[u8]) -> Option<usize> {
+    bytes
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map(|end| end + 1)
+}
+
+/// The lines in `bytes`, such as the answer to `AT+RX`, each trimmed with [`trim`].
+/// Blank lines are skipped, and an unterminated last line is included.
+pub fn lines(bytes: &[u8]) -> Lines<'_> {
+    Lines { rest: bytes }
+}
+
+/// An iterator over the lines of an answer, see [`lines`]
+#[derive(Debug, Clone)]
+pub struct Lines<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.rest.is_empty() {
+            let end = line_end(self.rest).unwrap_or(self.rest.len());
+            let (line, rest) = self.rest.split_at(end);
+            self.rest = rest;
+
+            let line = trim(line);
+            if !line.is_empty() {
+                return Some(line);
+            }
+        }
+        None
+    }
+}
+
+/// The serial speed in an answer to `AT+RB`, such as 9600 for `OK+B9600`
+pub fn baudrate_bps(line: &[u8]) -> Option<u32> {
+    number(value(line)?.strip_prefix(b"B")?)
+}
+
+/// The number a setting command echoes after `prefix`, such as 21 for `OK+C021` and `C`
+pub fn echoed(line: &[u8], prefix: &[u8]) -> Option<u32> {
+    number(value(line)?.strip_prefix(prefix)?)
+}
+
+/// The channel number in an answer to `AT+RC`, such as 21 for `OK+RC021`
+pub fn channel(line: &[u8]) -> Option<u8> {
+    number(value(line)?.strip_prefix(b"RC")?)?.try_into().ok()
+}
+
+/// The transmit power in an answer to `AT+RP`, such as 20 for `OK+RP:+20dBm`. Some
+/// firmware leaves out the colon, as in `OK+RP+20dBm`.
+pub fn power_dbm(line: &[u8]) -> Option<i8> {
+    let dbm = value(line)?.strip_prefix(b"RP")?.strip_suffix(b"dBm")?;
+    let dbm = dbm.strip_prefix(b":").unwrap_or(dbm);
+    let (negative, digits) = match dbm {
+        [b'+', digits @ ..] => (false, digits),
+        [b'-', digits @ ..] => (true, digits),
+        digits => (false, digits),
+    };
+    let dbm = i8::try_from(number(digits)?).ok()?;
+    Some(if negative { -dbm } else { dbm })
+}
+
+/// The mode number in an answer to `AT+RF`, such as 3 for `OK+FU3`
+pub fn mode(line: &[u8]) -> Option<u8> {
+    number(value(line)?.strip_prefix(b"FU")?)?.try_into().ok()
+}
+
+/// The major and minor version in an answer to `AT+V`, such as (2, 6) for
+/// `www.hc01.com HC-12_V2.6`. The last `V` or `v` followed by `major.minor` is used, and
+/// `None` is returned if there is none.
+pub fn firmware_version(line: &[u8]) -> Option<(u8, u8)> {
+    let line = trim(line);
+    line.iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, byte)| matches!(byte, b'V' | b'v'))
+        .find_map(|(at, _)| {
+            let version = &line[at + 1..];
+            let dot = version.iter().position(|&byte| byte == b'.')?;
+            let major = number(&version[..dot])?.try_into().ok()?;
+            let minor = &version[dot + 1..];
+            let digits = minor
+                .iter()
+                .position(|byte| !byte.is_ascii_digit())
+                .unwrap_or(minor.len());
+            Some((major, number(&minor[..digits])?.try_into().ok()?))
+        })
+}
+
+/// A short run of decimal digits, and nothing else
+fn number(digits: &[u8]) -> Option<u32> {
+    if digits.is_empty() || digits.len() > 9 || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    Some(
+        digits
+            .iter()
+            .fold(0, |number, digit| number * 10 + u32::from(digit - b'0')),
+    )
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::Vec;
+
+    /// Single-line answers, as sent by the module, and how they parse
+    const CORPUS: &[(&[u8], Reply)] = &[
+        // AT
+        (b"OK\r\n", Reply::Ok),
+        // settings echoed back
+        (b"OK+B9600\r\n", Reply::Value(b"B9600")),
+        (b"OK+B115200\r\n", Reply::Value(b"B115200")),
+        (b"OK+C001\r\n", Reply::Value(b"C001")),
+        (b"OK+C127\r\n", Reply::Value(b"C127")),
+        (b"OK+P8\r\n", Reply::Value(b"P8")),
+        (b"OK+FU3\r\n", Reply::Value(b"FU3")),
+        (b"OK+SLEEP\r\n", Reply::Value(b"SLEEP")),
+        (b"OK+DEFAULT\r\n", Reply::Value(b"DEFAULT")),
+        (b"OK+U8N1\r\n", Reply::Value(b"U8N1")),
+        // queries
+        (b"OK+RC021\r\n", Reply::Value(b"RC021")),
+        (b"OK+RP:+20dBm\r\n", Reply::Value(b"RP:+20dBm")),
+        (b"OK+RP:-1dBm\r\n", Reply::Value(b"RP:-1dBm")),
+        // version banners of several firmware releases
+        (
+            b"www.hc01.com HC-12_V2.6\r\n",
+            Reply::Other(b"www.hc01.com HC-12_V2.6"),
+        ),
+        (
+            b"www.hc01.com  HC-12_V2.4\r\n",
+            Reply::Other(b"www.hc01.com  HC-12_V2.4"),
+        ),
+        (b"HC-12_V2.3\r\n", Reply::Other(b"HC-12_V2.3")),
+        // rejected commands
+        (b"ERROR\r\n", Reply::Error),
+        (b"ERROR", Reply::Error),
+        // some firmware ends lines with a bare \n, or sends nothing after the value
+        (b"OK+P4\n", Reply::Value(b"P4")),
+        (b"OK+P4", Reply::Value(b"P4")),
+        // noise from powering up or switching the SET pin, before the answer
+        (b"\x00OK\r\n", Reply::Ok),
+        (b"\xff\xfeOK+B9600\r\n", Reply::Value(b"B9600")),
+        (b"\xf8\x00OK+FU3\r\n", Reply::Value(b"FU3")),
+        (b"\x80ERROR\r\n", Reply::Error),
+        // printable bytes before OK, from a line the host came in on half-way
+        (b"xOK+C005\r\n", Reply::Value(b"C005")),
+        // a garbled line, as seen at the wrong serial speed
+        (b"\xe0\x1c\xf8?\r\n", Reply::Other(b"?")),
+        (b"\r\n", Reply::Other(b"")),
+    ];
+
+    #[test]
+    fn corpus_parses() {
+        for (line, reply) in CORPUS {
+            assert_eq!(parse(line), *reply, "{line:?}");
+            let ok = matches!(reply, Reply::Ok | Reply::Value(_));
+            assert_eq!(is_ok(line), ok, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn fragmented_corpus_parses_once_complete() {
+        for (line, reply) in CORPUS {
+            let Some(end) = line_end(line) else {
+                continue;
+            };
+            // the answer arrives in two reads, split at every position
+            for split in 0..line.len() {
+                let mut received: Vec<u8, 32> = Vec::new();
+                received.extend_from_slice(&line[..split]).unwrap();
+                assert_eq!(line_end(&received), None, "{line:?} at {split}");
+
+                received.extend_from_slice(&line[split..]).unwrap();
+                assert_eq!(line_end(&received), Some(end), "{line:?} at {split}");
+                assert_eq!(parse(&received[..end]), *reply, "{line:?} at {split}");
+            }
+        }
+    }
+
+    #[test]
+    fn rx_dump_splits_into_lines() {
+        let dumps: [&[u8]; 3] = [
+            b"OK+B9600\r\nOK+RC001\r\nOK+RP:+20dBm\r\nOK+FU3\r\n",
+            // bare line feeds, and a blank line
+            b"OK+B9600\nOK+RC001\n\nOK+RP:+20dBm\nOK+FU3\n",
+            // noise before the first line, no terminator after the last
+            b"\x00\xffOK+B9600\r\nOK+RC001\r\nOK+RP:+20dBm\r\nOK+FU3",
+        ];
+        for dump in dumps {
+            let mut values = lines(dump).map(value);
+            assert_eq!(values.next(), Some(Some(&b"B9600"[..])));
+            assert_eq!(values.next(), Some(Some(&b"RC001"[..])));
+            assert_eq!(values.next(), Some(Some(&b"RP:+20dBm"[..])));
+            assert_eq!(values.next(), Some(Some(&b"FU3"[..])));
+            assert_eq!(values.next(), None);
+        }
+    }
+
+    #[test]
+    fn query_answers_parse() {
+        assert_eq!(baudrate_bps(b"\xffOK+B115200\r\n"), Some(115_200));
+        assert_eq!(channel(b"OK+RC021\r\n"), Some(21));
+        assert_eq!(power_dbm(b"OK+RP:+20dBm\r\n"), Some(20));
+        assert_eq!(power_dbm(b"OK+RP:-1dBm\r\n"), Some(-1));
+        assert_eq!(power_dbm(b"OK+RP+20dBm\r\n"), Some(20));
+        assert_eq!(mode(b"OK+FU3\r\n"), Some(3));
+
+        // the wrong answer, or a garbled value
+        assert_eq!(channel(b"OK+B9600\r\n"), None);
+        assert_eq!(channel(b"OK+RC0x1\r\n"), None);
+        assert_eq!(channel(b"OK+RC\r\n"), None);
+        assert_eq!(channel(b"OK+RC999\r\n"), None);
+        assert_eq!(power_dbm(b"OK+RP:+20\r\n"), None);
+        assert_eq!(baudrate_bps(b"ERROR\r\n"), None);
+    }
+
+    #[test]
+    fn firmware_versions_parse() {
+        assert_eq!(
+            firmware_version(b"www.hc01.com HC-12_V2.6\r\n"),
+            Some((2, 6))
+        );
+        assert_eq!(firmware_version(b"www.hc01.com  HC-12_V2.4"), Some((2, 4)));
+        assert_eq!(firmware_version(b"www.hc01.com HC-12 v2.6"), Some((2, 6)));
+        assert_eq!(firmware_version(b"HC-12_V2.3\r\n"), Some((2, 3)));
+        assert_eq!(firmware_version(b"SI4463 V1.10 beta"), Some((1, 10)));
+
+        // no version, or not one that can be read
+        assert_eq!(firmware_version(b"www.hc01.com HC-12"), None);
+        assert_eq!(firmware_version(b"HC-12_V2"), None);
+        assert_eq!(firmware_version(b"HC-12_Vx.6"), None);
+        assert_eq!(firmware_version(b"\xe0\x1c\xf8?\r\n"), None);
+    }
+
+    #[test]
+    fn text_stops_at_invalid_utf8() {
+        assert_eq!(text(b"OK+P8\r\n"), "OK+P8\r\n");
+        assert_eq!(text(b"OK\xff+P8"), "OK");
+        assert_eq!(text(skip_noise(b"\xff\xfeOK\r\n")), "OK\r\n");
+    }
+}
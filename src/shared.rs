@@ -0,0 +1,187 @@
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use embedded_io::{Read, ReadReady, Write, WriteReady};
+
+/// A device that can be shared between the main loop and interrupt handlers.
+///
+/// Every operation runs inside a critical section, so interrupts are held off for its
+/// whole duration. Only short operations are offered: a frame is only written when the
+/// device reports it is ready, and reads only take what is already available. Long
+/// operations, such as returning to programming mode, cannot be performed through the
+/// lock; [`take`](SharedHc12::take) the device out instead, and
+/// [`install`](SharedHc12::install) it again afterwards.
+///
+/// # Example
+/// ```ignore
+/// static RADIO: SharedHc12<TransparentHC12<Uart, Pin, Fu3, B9600>> = SharedHc12::new();
+///
+/// RADIO.install(hc12);
+///
+/// #[interrupt]
+/// fn BUTTON() {
+///     RADIO.try_write_frame(b"ALERT").ok();
+/// }
+/// ```
+pub struct SharedHc12<D> {
+    device: Mutex<RefCell<Option<D>>>,
+}
+
+impl<D> SharedHc12<D> {
+    /// Create an empty handle, usable in a `static`
+    pub const fn new() -> Self {
+        Self {
+            device: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    /// Place a device in the handle, returning the previous one, if any
+    pub fn install(&self, device: D) -> Option<D> {
+        critical_section::with(|cs| self.device.borrow_ref_mut(cs).replace(device))
+    }
+
+    /// Remove the device from the handle, for operations too long to be run in a
+    /// critical section
+    pub fn take(&self) -> Option<D> {
+        critical_section::with(|cs| self.device.borrow_ref_mut(cs).take())
+    }
+
+    /// Run a closure on the device inside a critical section. Returns `None` if no
+    /// device is installed. Keep the closure short, interrupts are disabled while it runs.
+    pub fn with<R>(&self, f: impl FnOnce(&mut D) -> R) -> Option<R> {
+        critical_section::with(|cs| self.device.borrow_ref_mut(cs).as_mut().map(f))
+    }
+}
+
+impl<D> Default for SharedHc12<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D> SharedHc12<D>
+where
+    D: Write + WriteReady,
+{
+    /// Write a whole frame if the device is ready to accept data, returning whether it
+    /// was written. The frame should fit in the serial device's transmit buffer, as the
+    /// write blocks, inside the critical section, until it has been accepted.
+    pub fn try_write_frame(&self, frame: &[u8]) -> Result<bool, D::Error> {
+        self.with(|device| {
+            if device.write_ready()? {
+                device.write_all(frame)?;
+                Ok(true)
+            } else {
+                Ok(false)
+            }
+        })
+        .unwrap_or(Ok(false))
+    }
+}
+
+impl<D> SharedHc12<D>
+where
+    D: Read + ReadReady,
+{
+    /// Read whatever data is already available, without waiting for more. Returns the
+    /// number of bytes read.
+    pub fn try_read_available(&self, buf: &mut [u8]) -> Result<usize, D::Error> {
+        self.with(|device| {
+            if device.read_ready()? {
+                device.read(buf)
+            } else {
+                Ok(0)
+            }
+        })
+        .unwrap_or(Ok(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use embedded_io::ErrorType;
+    use heapless::{Deque, Vec};
+
+    /// A port with a five byte transmit buffer and some pending received data
+    #[derive(Default)]
+    struct Port {
+        tx: Vec<u8, 5>,
+        rx: Deque<u8, 8>,
+    }
+
+    impl ErrorType for Port {
+        type Error = Infallible;
+    }
+
+    impl Write for Port {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let count = buf.len().min(self.tx.capacity() - self.tx.len());
+            self.tx.extend_from_slice(&buf[..count]).ok();
+            Ok(count)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl WriteReady for Port {
+        fn write_ready(&mut self) -> Result<bool, Self::Error> {
+            Ok(!self.tx.is_full())
+        }
+    }
+
+    impl Read for Port {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let mut count = 0;
+            for slot in buf.iter_mut() {
+                let Some(byte) = self.rx.pop_front() else {
+                    break;
+                };
+                *slot = byte;
+                count += 1;
+            }
+            Ok(count)
+        }
+    }
+
+    impl ReadReady for Port {
+        fn read_ready(&mut self) -> Result<bool, Self::Error> {
+            Ok(!self.rx.is_empty())
+        }
+    }
+
+    #[test]
+    fn empty_handle_does_nothing() {
+        let shared: SharedHc12<Port> = SharedHc12::new();
+        assert!(!shared.try_write_frame(b"hello").unwrap());
+        assert_eq!(shared.try_read_available(&mut [0u8; 4]).unwrap(), 0);
+        assert!(shared.with(|_| ()).is_none());
+    }
+
+    #[test]
+    fn write_and_read_through_handle() {
+        static SHARED: SharedHc12<Port> = SharedHc12::new();
+
+        let mut port = Port::default();
+        for byte in b"pong" {
+            port.rx.push_back(*byte).unwrap();
+        }
+        SHARED.install(port);
+
+        assert!(SHARED.try_write_frame(b"ping!").unwrap());
+        // the transmit buffer is full, so the next frame is refused rather than blocking
+        assert!(!SHARED.try_write_frame(b"again").unwrap());
+
+        let mut buffer = [0u8; 8];
+        let read = SHARED.try_read_available(&mut buffer).unwrap();
+        assert_eq!(&buffer[..read], b"pong");
+        assert_eq!(SHARED.try_read_available(&mut buffer).unwrap(), 0);
+
+        let port = SHARED.take().unwrap();
+        assert_eq!(port.tx.as_slice(), b"ping!");
+        assert!(SHARED.take().is_none());
+    }
+}
@@ -0,0 +1,216 @@
+//! Leaving the module in a known, quiet state before power is removed.
+//!
+//! [`shutdown`](TransparentHC12::shutdown) flushes the serial device, enters AT mode,
+//! optionally sends `AT+SLEEP`, and leaves the programming pin at a chosen level, then
+//! returns the serial device and the pin. Each step is attempted even if an earlier one
+//! failed, and the device and pin are returned either way, with any failures in a
+//! [`ShutdownError`].
+//!
+//! [`park`](TransparentHC12::park) puts the module to sleep but keeps the device, and
+//! [`resume`](TransparentHC12::resume) wakes it again.
+
+use core::fmt::Debug;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{OutputPin, PinState};
+use embedded_io::Write;
+
+use crate::autosleep::AutoSleepError;
+use crate::commands::{exchange, Command, Sleep};
+use crate::events::{notify, AtEvent, Transition};
+use crate::{AtRead, Error, TransparentHC12};
+
+/// What [`shutdown`](TransparentHC12::shutdown) leaves the module doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct ShutdownOptions {
+    /// Send `AT+SLEEP`. The module only goes to sleep once the pin is left high.
+    pub sleep: bool,
+    /// The level the programming pin is left at. High leaves AT mode.
+    pub pin: PinState,
+}
+
+impl Default for ShutdownOptions {
+    /// Asleep, with the pin high
+    fn default() -> Self {
+        Self {
+            sleep: true,
+            pin: PinState::High,
+        }
+    }
+}
+
+/// Steps of a [`shutdown`](TransparentHC12::shutdown) failed. The device and pin are
+/// returned all the same.
+#[derive(Debug)]
+pub struct ShutdownError<Device, Pin, D: Debug, P> {
+    /// The serial device
+    pub device: Device,
+    /// The programming pin
+    pub pin: Pin,
+    /// The flush failed, so bytes may have been lost
+    pub flush: Option<D>,
+    /// `AT+SLEEP` failed. It is not sent if the pin could not be pulled low, see
+    /// [`set_pin`](Self::set_pin).
+    pub sleep: Option<Error<D>>,
+    /// The pin could not be switched, the first time it failed
+    pub set_pin: Option<P>,
+}
+
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed>
+where
+    Device: AtRead + Write,
+    Pin: OutputPin,
+{
+    /// Quiet the module and return the serial device and the programming pin, see the
+    /// [module documentation](crate::shutdown). Blocks for 40ms by default, plus the
+    /// `AT+SLEEP` exchange.
+    #[allow(clippy::type_complexity)]
+    pub fn shutdown(
+        mut self,
+        options: ShutdownOptions,
+        delay: &mut impl DelayNs,
+    ) -> Result<(Device, Pin), ShutdownError<Device, Pin, Device::Error, Pin::Error>> {
+        let flush = self.device.flush().err();
+        let mut set_pin = None;
+        let mut sleep = None;
+
+        match self.pin.set_low() {
+            Ok(()) => {
+                delay.delay_ms(self.session.timings.pin_settle_ms);
+                notify(self.session.observer, || {
+                    AtEvent::TransitionPerformed(Transition::IntoProgramming)
+                });
+                if options.sleep {
+                    sleep = self
+                        .session
+                        .permits(&Sleep)
+                        .and_then(|()| {
+                            exchange(
+                                &mut self.device,
+                                Sleep.command(),
+                                delay,
+                                self.session.observer,
+                                self.session.timings.settle_ms(&Sleep),
+                                self.session.response_timeout_ms,
+                            )
+                        })
+                        .err();
+                }
+            }
+            // without AT mode, `AT+SLEEP` is not sent
+            Err(error) => set_pin = Some(error),
+        }
+
+        if options.pin == PinState::High {
+            match self.pin.set_high() {
+                Ok(()) => notify(self.session.observer, || {
+                    AtEvent::TransitionPerformed(Transition::IntoTransparent)
+                }),
+                Err(error) => set_pin = set_pin.or(Some(error)),
+            }
+        }
+
+        if flush.is_none() && sleep.is_none() && set_pin.is_none() {
+            return Ok((self.device, self.pin));
+        }
+        Err(ShutdownError {
+            device: self.device,
+            pin: self.pin,
+            flush,
+            sleep,
+            set_pin,
+        })
+    }
+
+    /// Put the module to sleep with `AT+SLEEP`, keeping the device. Nothing is received
+    /// until [`resume`](Self::resume). Blocks for at least 120ms.
+    pub fn park(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), AutoSleepError<Device::Error, Pin::Error>> {
+        self.device.flush().map_err(AutoSleepError::Link)?;
+        self.round_trip(Sleep, delay)
+            .map_err(AutoSleepError::Pin)?
+            .map_err(AutoSleepError::At)?;
+        Ok(())
+    }
+
+    /// Wake a module put to sleep by [`park`](Self::park). Blocks for the pin and mode
+    /// change settle times of the [`AtTimings`](crate::AtTimings).
+    pub fn resume(&mut self, delay: &mut impl DelayNs) -> Result<(), Pin::Error> {
+        self.wake(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockHc12;
+    use crate::test_utils::{CountingDelay, Duo, PinLog, Sink, Source};
+    use crate::HC12;
+
+    #[test]
+    fn sleeps_and_returns_everything() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let serial = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(b"OK+SLEEP\r\n"),
+        };
+        let hc12 = HC12::factor_settings(serial, pins.pin(), &mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+
+        let (serial, _pin) = hc12
+            .shutdown(ShutdownOptions::default(), &mut delay)
+            .unwrap();
+        assert_eq!(serial.sink.data(), b"AT+SLEEP\r\n");
+        assert_eq!(
+            pins.states().as_slice(),
+            [PinState::Low, PinState::High, PinState::Low, PinState::High]
+        );
+    }
+
+    #[test]
+    fn failed_sleep_still_returns_everything() {
+        let pins = PinLog::new();
+        let mut delay = CountingDelay::new();
+        let hc12 = HC12::factor_settings(Duo::default(), pins.pin(), &mut delay)
+            .unwrap()
+            .response_timeout_ms(10)
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+
+        let options = ShutdownOptions {
+            sleep: true,
+            pin: PinState::Low,
+        };
+        let error = hc12.shutdown(options, &mut delay).unwrap_err();
+        assert!(matches!(error.sleep, Some(Error::NoResponse)));
+        assert_eq!((error.flush, error.set_pin), (None, None));
+        assert_eq!(error.device.sink.data(), b"AT+SLEEP\r\n");
+        // the pin is left low, holding the module in AT mode
+        assert_eq!(
+            pins.states().as_slice(),
+            [PinState::Low, PinState::High, PinState::Low]
+        );
+    }
+
+    #[test]
+    fn park_and_resume() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let mut hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap();
+
+        hc12.park(&mut delay).unwrap();
+        assert!(module.is_asleep());
+        hc12.resume(&mut delay).unwrap();
+        assert!(!module.is_asleep());
+        assert!(!module.in_at_mode());
+    }
+}
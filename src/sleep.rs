@@ -0,0 +1,225 @@
+//! A sleeping module, as a type of its own.
+//!
+//! [`into_sleep`](crate::HC12::into_sleep) sends `AT+SLEEP` and leaves AT mode, after
+//! which the module draws about 22µA and neither sends nor receives. The
+//! [`SleepingHC12`] it returns cannot be read from or written to, so bytes cannot be lost
+//! to a module that is not listening. [`wake`](SleepingHC12::wake) pulses the programming
+//! pin and returns the transparent device it was put to sleep from.
+//!
+//! [`park`](crate::TransparentHC12::park) sleeps without changing the type, for code that
+//! keeps the device in one place.
+//!
+//! ```
+//! use hc12_rs::mock::MockHc12;
+//! use hc12_rs::HC12;
+//! use embedded_io::Write;
+//!
+//! let module = MockHc12::new();
+//! let mut delay = module.delay();
+//! let asleep = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+//!     .unwrap()
+//!     .into_sleep(&mut delay)
+//!     .unwrap();
+//! assert!(module.is_asleep());
+//!
+//! let mut hc12 = asleep.wake(&mut delay).unwrap();
+//! hc12.write_all(b"morning").unwrap();
+//! assert_eq!(module.transmitted(), b"morning");
+//! ```
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+
+use crate::TransparentHC12;
+
+/// A module asleep after `AT+SLEEP`, see the [module documentation](self)
+pub struct SleepingHC12<Device, Pin, Mode, Speed> {
+    pub(crate) hc12: TransparentHC12<Device, Pin, Mode, Speed>,
+}
+
+impl<Device, Pin, Mode, Speed> SleepingHC12<Device, Pin, Mode, Speed> {
+    /// Return the serial device and the programming pin, leaving the module asleep
+    pub fn inner(self) -> (Device, Pin) {
+        (self.hc12.device, self.hc12.pin)
+    }
+}
+
+impl<Device, Pin: OutputPin, Mode, Speed> SleepingHC12<Device, Pin, Mode, Speed> {
+    /// Wake the module by pulling the programming pin low for the pin settle time of its
+    /// [`AtTimings`](crate::AtTimings), then releasing it. Blocks until the mode change
+    /// settle time has passed too, after which the module is ready.
+    pub fn wake(
+        mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<TransparentHC12<Device, Pin, Mode, Speed>, Pin::Error> {
+        self.hc12.wake(delay)?;
+        Ok(self.hc12)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use core::convert::Infallible;
+
+    use embedded_hal::digital;
+    use embedded_io::Write;
+    use heapless::Vec;
+
+    use super::*;
+    use crate::capabilities::{Capabilities, Capability, CapabilityTable};
+    use crate::mock::MockHc12;
+    use crate::test_utils::{Duo, Sink, Source};
+    use crate::{AtTimings, Error, HC12};
+
+    /// A pin level or a delay, in the order they happened
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Step {
+        Low,
+        High,
+        DelayMs(u32),
+    }
+
+    #[derive(Default)]
+    struct Timeline(RefCell<Vec<Step, 32>>);
+
+    impl Timeline {
+        fn push(&self, step: Step) {
+            self.0.borrow_mut().push(step).unwrap();
+        }
+
+        fn take(&self) -> Vec<Step, 32> {
+            core::mem::take(&mut *self.0.borrow_mut())
+        }
+    }
+
+    struct TimelinePin<'a>(&'a Timeline);
+
+    impl digital::ErrorType for TimelinePin<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for TimelinePin<'_> {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.0.push(Step::Low);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.0.push(Step::High);
+            Ok(())
+        }
+    }
+
+    struct TimelineDelay<'a>(&'a Timeline);
+
+    impl DelayNs for TimelineDelay<'_> {
+        fn delay_ns(&mut self, _ns: u32) {}
+
+        fn delay_ms(&mut self, ms: u32) {
+            self.0.push(Step::DelayMs(ms));
+        }
+    }
+
+    #[test]
+    fn sleeps_then_wakes_with_the_datasheet_timing() {
+        let timeline = Timeline::default();
+        let mut delay = TimelineDelay(&timeline);
+        let serial = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(b"OK+SLEEP\r\n"),
+        };
+        let hc12 = HC12::factor_settings(serial, TimelinePin(&timeline), &mut delay).unwrap();
+        timeline.take();
+
+        // the pin is only released once the module has answered
+        let asleep = hc12.into_sleep(&mut delay).unwrap();
+        let steps = timeline.take();
+        assert!(steps.ends_with(&[Step::High, Step::DelayMs(80)]));
+        assert_eq!(steps.iter().filter(|step| **step == Step::High).count(), 1);
+
+        let hc12 = asleep.wake(&mut delay).unwrap();
+        assert_eq!(
+            timeline.take(),
+            [Step::Low, Step::DelayMs(40), Step::High, Step::DelayMs(80)]
+        );
+        let (serial, _pin) = hc12.inner();
+        assert_eq!(serial.sink.data(), b"AT+SLEEP\r\n");
+    }
+
+    #[test]
+    fn wakes_with_the_configured_timings() {
+        let timeline = Timeline::default();
+        let mut delay = TimelineDelay(&timeline);
+        let serial = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(b"OK+SLEEP\r\n"),
+        };
+        let timings = AtTimings {
+            pin_settle_ms: 70,
+            mode_change_settle_ms: 250,
+            ..AtTimings::DATASHEET
+        };
+        let hc12 =
+            HC12::factor_settings_with_timings(serial, TimelinePin(&timeline), timings, &mut delay)
+                .unwrap();
+        let asleep = hc12.into_sleep(&mut delay).unwrap();
+        timeline.take();
+
+        asleep.wake(&mut delay).unwrap();
+        assert_eq!(
+            timeline.take(),
+            [Step::Low, Step::DelayMs(70), Step::High, Step::DelayMs(250)]
+        );
+    }
+
+    #[test]
+    fn refused_sleep_stays_in_at_mode() {
+        let timeline = Timeline::default();
+        let mut delay = TimelineDelay(&timeline);
+        let serial = Duo {
+            sink: Sink::new(),
+            src: Source::new().data(b"OK+B9600\r\n"),
+        };
+        let hc12 = HC12::factor_settings(serial, TimelinePin(&timeline), &mut delay).unwrap();
+        timeline.take();
+
+        let result = hc12.into_sleep(&mut delay);
+        let Err(error) = result else {
+            panic!("slept after a wrong answer");
+        };
+        assert_eq!(
+            error,
+            Error::InvalidResponse("OK+B9600\r\n".try_into().unwrap())
+        );
+        assert!(!timeline.take().contains(&Step::High));
+    }
+
+    #[test]
+    fn sleeps_on_the_mock() {
+        let module = MockHc12::new();
+        let mut delay = module.delay();
+        let asleep = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .into_sleep(&mut delay)
+            .unwrap();
+        assert!(module.is_asleep());
+        assert!(!module.in_at_mode());
+
+        let mut hc12 = asleep.wake(&mut delay).unwrap();
+        assert!(!module.is_asleep());
+        hc12.write_all(b"up").unwrap();
+        assert_eq!(module.transmitted(), b"up");
+
+        // firmware that cannot sleep is not asked to
+        module.set_firmware("CLONE V0.9");
+        let mut hc12 = hc12.into_programming_mode(&mut delay).unwrap();
+        hc12.query_version(&CapabilityTable::<4>::new(), &mut delay)
+            .unwrap();
+        assert_eq!(hc12.capabilities(), Some(Capabilities::CONSERVATIVE));
+        assert!(matches!(
+            hc12.into_sleep(&mut delay),
+            Err(Error::UnsupportedByFirmware(Capability::Sleep))
+        ));
+    }
+}
@@ -0,0 +1,152 @@
+//! Where buffers and queues keep their contents.
+//!
+//! The [`FrameReader`](crate::framing::FrameReader),
+//! [`QueuedWriter`](crate::queue::QueuedWriter) and
+//! [`TransactionLog`](crate::transactions::TransactionLog) store their contents in
+//! heapless collections by default, reserving their full capacity up front. With the
+//! `alloc` feature, each also has a `new_alloc` constructor that stores them in `alloc`
+//! collections instead, which grow as they fill. The capacity `N` is then only an upper
+//! bound, so it can be set generously without costing memory until it is used.
+//!
+//! [`Buffer`] and [`Ring`] are implemented for both, and may be implemented for other
+//! storage.
+
+use heapless::{Deque, Vec};
+
+/// A growing sequence of items
+pub trait Buffer<T> {
+    /// The number of items held
+    fn len(&self) -> usize;
+
+    /// Whether no items are held
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add an item at the end, handing it back if there is no room
+    fn push(&mut self, item: T) -> Result<(), T>;
+
+    /// Remove every item
+    fn clear(&mut self);
+
+    /// The items held
+    fn as_mut_slice(&mut self) -> &mut [T];
+}
+
+/// A first-in, first-out queue of items
+pub trait Ring<T> {
+    /// The number of items held
+    fn len(&self) -> usize;
+
+    /// Whether no items are held
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add an item at the back, handing it back if there is no room
+    fn push_back(&mut self, item: T) -> Result<(), T>;
+
+    /// Remove the item at the front
+    fn pop_front(&mut self) -> Option<T>;
+
+    /// The item at the back
+    fn back(&self) -> Option<&T>;
+
+    /// Remove every item
+    fn clear(&mut self);
+
+    /// The items held, front first, in two parts as the ring wraps around
+    fn as_slices(&self) -> (&[T], &[T]);
+}
+
+impl<T, const N: usize> Buffer<T> for Vec<T, N> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn push(&mut self, item: T) -> Result<(), T> {
+        Vec::push(self, item)
+    }
+
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        Vec::as_mut_slice(self)
+    }
+}
+
+impl<T, const N: usize> Ring<T> for Deque<T, N> {
+    fn len(&self) -> usize {
+        Deque::len(self)
+    }
+
+    fn push_back(&mut self, item: T) -> Result<(), T> {
+        Deque::push_back(self, item)
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        Deque::pop_front(self)
+    }
+
+    fn back(&self) -> Option<&T> {
+        Deque::back(self)
+    }
+
+    fn clear(&mut self) {
+        Deque::clear(self);
+    }
+
+    fn as_slices(&self) -> (&[T], &[T]) {
+        Deque::as_slices(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Buffer<T> for alloc::vec::Vec<T> {
+    fn len(&self) -> usize {
+        alloc::vec::Vec::len(self)
+    }
+
+    fn push(&mut self, item: T) -> Result<(), T> {
+        alloc::vec::Vec::push(self, item);
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        alloc::vec::Vec::clear(self);
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        alloc::vec::Vec::as_mut_slice(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Ring<T> for alloc::collections::VecDeque<T> {
+    fn len(&self) -> usize {
+        alloc::collections::VecDeque::len(self)
+    }
+
+    fn push_back(&mut self, item: T) -> Result<(), T> {
+        alloc::collections::VecDeque::push_back(self, item);
+        Ok(())
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        alloc::collections::VecDeque::pop_front(self)
+    }
+
+    fn back(&self) -> Option<&T> {
+        alloc::collections::VecDeque::back(self)
+    }
+
+    fn clear(&mut self) {
+        alloc::collections::VecDeque::clear(self);
+    }
+
+    fn as_slices(&self) -> (&[T], &[T]) {
+        alloc::collections::VecDeque::as_slices(self)
+    }
+}
@@ -0,0 +1,308 @@
+//! Noticing a module that reset to other settings.
+//!
+//! A brown-out restarts the module with its saved settings, which may be behind the
+//! driver, and nothing else notices until traffic stops.
+//! [`check_module`](TransparentHC12::check_module) briefly enters AT mode, asks the module
+//! for each setting, and reports the first that differs from the device's as
+//! [`ModuleReverted`], so the application can apply its settings again. The module is
+//! returned to transparent mode whatever the outcome.
+//!
+//! [`Supervisor`] runs the check every so often, from a [`Clock`].
+//!
+//! ```
+//! use hc12_rs::mock::{MockHc12, Settings};
+//! use hc12_rs::provision::Setting;
+//! use hc12_rs::supervise::{CheckError, ModuleReverted};
+//! use hc12_rs::paramaters::{Channel, Power};
+//! use hc12_rs::HC12;
+//!
+//! let module = MockHc12::new();
+//! let mut delay = module.delay();
+//! let mut hc12 = HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+//!     .unwrap()
+//!     .channel(Channel::new(21).unwrap())
+//!     .power(Power::MAX)
+//!     .program(&mut delay)
+//!     .unwrap()
+//!     .into_transparent_mode(&mut delay)
+//!     .unwrap();
+//! hc12.check_module(&mut delay).unwrap();
+//!
+//! module.reset(Settings::default());
+//! assert_eq!(
+//!     hc12.check_module(&mut delay),
+//!     Err(CheckError::Reverted(ModuleReverted {
+//!         field: Setting::Channel,
+//!         expected: 21,
+//!         reported: 1,
+//!     }))
+//! );
+//! ```
+
+use core::fmt::Debug;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use embedded_io::Write;
+
+use crate::commands::Query;
+use crate::modes::ValidMode;
+use crate::paramaters::{Channel, Power};
+use crate::provision::Setting;
+use crate::reprogram::{AtSession, ReprogramError};
+use crate::response;
+use crate::speeds::ValidSpeed;
+use crate::time::{Clock, IntoMillis};
+use crate::{AtRead, Error, TransparentHC12};
+
+/// A setting reported by the module differs from the device's. Channels and modes are
+/// numbers, power is the level number, and the serial speed is in bits per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct ModuleReverted {
+    /// The setting that differs
+    pub field: Setting,
+    /// The device's value
+    pub expected: u32,
+    /// The module's value
+    pub reported: u32,
+}
+
+/// A [`check_module`](TransparentHC12::check_module) did not pass
+#[derive(Debug, PartialEq, Eq)]
+pub enum CheckError<D: Debug, P> {
+    /// The module has other settings than the device
+    Reverted(ModuleReverted),
+    /// A query was not answered, or the answer could not be understood
+    At(Error<D>),
+    /// The programming pin could not be switched. If it could not be released, the
+    /// module may still be in AT mode.
+    Pin(P),
+}
+
+impl<D: Debug, P> From<ReprogramError<CheckError<D, P>, P>> for CheckError<D, P> {
+    fn from(error: ReprogramError<CheckError<D, P>, P>) -> Self {
+        match error {
+            ReprogramError::Enter(pin) | ReprogramError::Leave { pin, .. } => Self::Pin(pin),
+            ReprogramError::Session(error) => error,
+        }
+    }
+}
+
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed>
+where
+    Device: AtRead + Write,
+    Pin: OutputPin,
+    Mode: ValidMode,
+    Speed: ValidSpeed,
+{
+    /// Check that the module still has the device's settings, see the
+    /// [module documentation](crate::supervise). Blocks for at least 120ms, plus four AT
+    /// exchanges.
+    pub fn check_module(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), CheckError<Device::Error, Pin::Error>> {
+        let expected = [
+            (Setting::Channel, u32::from(u8::from(self.channel))),
+            (Setting::Power, u32::from(u8::from(&self.power))),
+            (Setting::Speed, Speed::bps()),
+            (Setting::Mode, u32::from(Mode::NUMBER)),
+        ];
+
+        self.reprogram(delay, |at| {
+            for (field, expected) in expected {
+                let reported = reported(at, field).map_err(CheckError::At)?;
+                if reported != expected {
+                    return Err(CheckError::Reverted(ModuleReverted {
+                        field,
+                        expected,
+                        reported,
+                    }));
+                }
+            }
+            Ok(())
+        })
+        .map_err(CheckError::from)
+    }
+}
+
+/// Ask the module for a setting, as a number in the units of [`ModuleReverted`]
+fn reported<Device, Pin, Mode, Speed>(
+    at: &mut AtSession<'_, Device, Pin, Mode, Speed>,
+    field: Setting,
+) -> Result<u32, Error<Device::Error>>
+where
+    Device: AtRead + Write,
+{
+    let query = match field {
+        Setting::Channel => Query::Channel,
+        Setting::Power => Query::Power,
+        Setting::Speed => Query::Baudrate,
+        Setting::Mode => Query::Mode,
+    };
+    let line = at.run(query)?;
+    let line_bytes = line.as_bytes();
+
+    let value = match field {
+        Setting::Channel => response::channel(line_bytes)
+            .and_then(|channel| Channel::new(channel).ok())
+            .map(|channel| u8::from(channel).into()),
+        // the module's power is reported even if it is above this build's cap
+        Setting::Power => response::power_dbm(line_bytes)
+            .and_then(Power::level_at)
+            .map(|power| u8::from(&power).into()),
+        Setting::Speed => response::baudrate_bps(line_bytes),
+        Setting::Mode => response::mode(line_bytes).map(u32::from),
+    };
+    value.ok_or(Error::InvalidResponse(line))
+}
+
+/// Runs [`check_module`](TransparentHC12::check_module) every so often
+#[derive(Debug)]
+pub struct Supervisor<C> {
+    clock: C,
+    interval_ms: u32,
+    next_ms: Option<u32>,
+}
+
+impl<C: Clock> Supervisor<C> {
+    /// Check every `interval`, the first time on the first poll
+    pub fn new(clock: C, interval: impl IntoMillis) -> Self {
+        Self {
+            clock,
+            interval_ms: interval.into_ms(),
+            next_ms: None,
+        }
+    }
+
+    /// Check the module if it is due, returning the outcome, or `None` if it was not due.
+    /// The next check is due an interval later, whatever the outcome.
+    #[allow(clippy::type_complexity)]
+    pub fn poll<Device, Pin, Mode, Speed>(
+        &mut self,
+        hc12: &mut TransparentHC12<Device, Pin, Mode, Speed>,
+        delay: &mut impl DelayNs,
+    ) -> Option<Result<(), CheckError<Device::Error, Pin::Error>>>
+    where
+        Device: AtRead + Write,
+        Pin: OutputPin,
+        Mode: ValidMode,
+        Speed: ValidSpeed,
+    {
+        let now_ms = self.clock.now_ms();
+        if let Some(next_ms) = self.next_ms {
+            if (now_ms.wrapping_sub(next_ms) as i32) < 0 {
+                return None;
+            }
+        }
+        self.next_ms = Some(now_ms.wrapping_add(self.interval_ms));
+        Some(hc12.check_module(delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Fault, MockHc12, Settings};
+    use crate::HC12;
+
+    fn programmed(
+        module: &MockHc12,
+    ) -> TransparentHC12<
+        crate::mock::MockSerial<'_>,
+        crate::mock::MockSetPin<'_>,
+        crate::modes::Fu3,
+        crate::speeds::B9600,
+    > {
+        let mut delay = module.delay();
+        HC12::factor_settings(module.serial(), module.set_pin(), &mut delay)
+            .unwrap()
+            .channel(Channel::new(21).unwrap())
+            .power(Power::P1)
+            .program(&mut delay)
+            .unwrap()
+            .into_transparent_mode(&mut delay)
+            .unwrap()
+    }
+
+    #[test]
+    fn reverted_channel_is_reported() {
+        let module = MockHc12::new();
+        let mut hc12 = programmed(&module);
+        let mut delay = module.delay();
+        assert_eq!(hc12.check_module(&mut delay), Ok(()));
+
+        // a brown-out, after which the module came back with older settings
+        module.reset(Settings {
+            power: 1,
+            ..Settings::default()
+        });
+        assert_eq!(
+            hc12.check_module(&mut delay),
+            Err(CheckError::Reverted(ModuleReverted {
+                field: Setting::Channel,
+                expected: 21,
+                reported: 1,
+            }))
+        );
+        // back in transparent mode, and nothing was changed
+        assert!(!module.in_at_mode());
+        assert_eq!(module.settings().channel, 1);
+
+        module.reset(Settings {
+            channel: 21,
+            ..Settings::default()
+        });
+        assert_eq!(
+            hc12.check_module(&mut delay),
+            Err(CheckError::Reverted(ModuleReverted {
+                field: Setting::Power,
+                expected: 1,
+                reported: 8,
+            }))
+        );
+    }
+
+    #[test]
+    fn unanswered_query_still_leaves_at_mode() {
+        let module = MockHc12::new();
+        let mut hc12 = programmed(&module);
+        let mut delay = module.delay();
+
+        module.inject_fault(Fault::Ignore);
+        assert_eq!(
+            hc12.check_module(&mut delay),
+            Err(CheckError::At(Error::NoResponse))
+        );
+        assert!(!module.in_at_mode());
+
+        module.inject_fault(Fault::WrongEcho);
+        assert!(matches!(
+            hc12.check_module(&mut delay),
+            Err(CheckError::At(Error::InvalidResponse(_)))
+        ));
+    }
+
+    #[test]
+    fn supervisor_checks_every_interval() {
+        let module = MockHc12::new();
+        let mut hc12 = programmed(&module);
+        let mut delay = module.delay();
+        let mut supervisor = Supervisor::new(|| module.now_ms(), 10_000);
+
+        assert_eq!(supervisor.poll(&mut hc12, &mut delay), Some(Ok(())));
+        assert_eq!(supervisor.poll(&mut hc12, &mut delay), None);
+
+        module.reset(Settings {
+            power: 1,
+            ..Settings::default()
+        });
+        module.advance_ms(10_000);
+        assert!(matches!(
+            supervisor.poll(&mut hc12, &mut delay),
+            Some(Err(CheckError::Reverted(_)))
+        ));
+        assert_eq!(supervisor.poll(&mut hc12, &mut delay), None);
+    }
+}
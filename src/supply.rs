@@ -0,0 +1,224 @@
+//! Hard resets through a pin switching the module's supply.
+//!
+//! When a module stops answering, cutting its power is often the only reliable recovery.
+//! A [`Supply`] holds the pin switching VCC (or an enable transistor), driven high to
+//! power the module; wrap it in [`InvertedPin`](crate::adapters::InvertedPin) for an
+//! active-low switch. Boards without one use [`Supply::none`], on which every power cycle
+//! fails with [`SupplyError::NotAvailable`].
+//!
+//! The SET pin is set before power returns, so the module boots in the chosen mode. A
+//! device resets with [`TransparentHC12::hard_reset`], or, while programming at 9600 bps,
+//! `HC12::hard_reset`, which boots the module into AT mode.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{OutputPin, PinState};
+
+use crate::TransparentHC12;
+
+/// How long a power cycle waits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct PowerCycle {
+    /// How long the supply stays off, for the module's capacitors to discharge
+    pub discharge_ms: u32,
+    /// How long the module takes to boot once powered
+    pub boot_ms: u32,
+}
+
+impl Default for PowerCycle {
+    /// 200ms off, and 100ms to boot
+    fn default() -> Self {
+        Self {
+            discharge_ms: 200,
+            boot_ms: 100,
+        }
+    }
+}
+
+/// A power cycle could not be performed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum SupplyError<V, S> {
+    /// There is no supply pin
+    NotAvailable,
+    /// The supply pin could not be switched
+    Vcc(V),
+    /// The SET pin could not be switched
+    Set(S),
+}
+
+/// The pin switching a module's supply, if there is one
+#[derive(Debug)]
+pub struct Supply<V> {
+    vcc: Option<V>,
+    timing: PowerCycle,
+}
+
+impl<V> Default for Supply<V> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl<V> Supply<V> {
+    /// A supply switched by `vcc`, with the default [`PowerCycle`] timing
+    pub fn new(vcc: V) -> Self {
+        Self {
+            vcc: Some(vcc),
+            timing: PowerCycle::default(),
+        }
+    }
+
+    /// A module powered permanently, which cannot be power cycled
+    pub fn none() -> Self {
+        Self {
+            vcc: None,
+            timing: PowerCycle::default(),
+        }
+    }
+
+    /// Use `timing` for power cycles
+    pub fn timing(mut self, timing: PowerCycle) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Whether there is a supply pin
+    pub fn is_available(&self) -> bool {
+        self.vcc.is_some()
+    }
+
+    /// Return the supply pin
+    pub fn inner(self) -> Option<V> {
+        self.vcc
+    }
+}
+
+impl<V: OutputPin> Supply<V> {
+    /// Switch the module off, set the SET pin to `set_state` (low for AT mode), switch
+    /// the module back on and wait for it to boot.
+    pub fn power_cycle<S: OutputPin>(
+        &mut self,
+        set: &mut S,
+        set_state: PinState,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), SupplyError<V::Error, S::Error>> {
+        let vcc = self.vcc.as_mut().ok_or(SupplyError::NotAvailable)?;
+
+        vcc.set_low().map_err(SupplyError::Vcc)?;
+        set.set_state(set_state).map_err(SupplyError::Set)?;
+        delay.delay_ms(self.timing.discharge_ms);
+        vcc.set_high().map_err(SupplyError::Vcc)?;
+        delay.delay_ms(self.timing.boot_ms);
+        Ok(())
+    }
+}
+
+impl<Device, Pin: OutputPin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed> {
+    /// Power cycle the module, leaving it in transparent mode. Its settings are kept
+    /// across the reset, so the device stays valid.
+    pub fn hard_reset<V: OutputPin>(
+        &mut self,
+        supply: &mut Supply<V>,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), SupplyError<V::Error, Pin::Error>> {
+        supply.power_cycle(&mut self.pin, PinState::High, delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::Fu3;
+    use crate::speeds::B9600;
+    use crate::test_utils::Sink;
+    use core::cell::RefCell;
+    use core::convert::Infallible;
+    use embedded_hal::digital::ErrorType;
+    use heapless::Vec;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Event {
+        Vcc(PinState),
+        Set(PinState),
+        Wait(u32),
+    }
+
+    type Log = RefCell<Vec<Event, 16>>;
+
+    /// A pin recording into a shared log
+    struct Recorder<'a> {
+        log: &'a Log,
+        event: fn(PinState) -> Event,
+    }
+
+    impl ErrorType for Recorder<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Recorder<'_> {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.log.borrow_mut().push((self.event)(PinState::Low)).ok();
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.log
+                .borrow_mut()
+                .push((self.event)(PinState::High))
+                .ok();
+            Ok(())
+        }
+    }
+
+    /// A delay recording into the same log
+    struct Waits<'a>(&'a Log);
+
+    impl DelayNs for Waits<'_> {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0.borrow_mut().push(Event::Wait(ns / 1_000_000)).ok();
+        }
+    }
+
+    fn recorder(log: &Log, event: fn(PinState) -> Event) -> Recorder<'_> {
+        Recorder { log, event }
+    }
+
+    #[test]
+    fn hard_reset_sequences_supply_and_set() {
+        let log = Log::default();
+        let mut supply = Supply::new(recorder(&log, Event::Vcc)).timing(PowerCycle {
+            discharge_ms: 150,
+            boot_ms: 60,
+        });
+        let mut hc12: TransparentHC12<_, _, Fu3, B9600> =
+            TransparentHC12::assume_factory(Sink::new(), recorder(&log, Event::Set));
+
+        hc12.hard_reset(&mut supply, &mut Waits(&log)).unwrap();
+        assert_eq!(
+            log.borrow().as_slice(),
+            [
+                Event::Vcc(PinState::Low),
+                Event::Set(PinState::High),
+                Event::Wait(150),
+                Event::Vcc(PinState::High),
+                Event::Wait(60),
+            ]
+        );
+    }
+
+    #[test]
+    fn without_supply_pin_nothing_is_touched() {
+        let log = Log::default();
+        let mut supply: Supply<Recorder> = Supply::none();
+        assert!(!supply.is_available());
+
+        let result = supply.power_cycle(
+            &mut recorder(&log, Event::Set),
+            PinState::Low,
+            &mut Waits(&log),
+        );
+        assert_eq!(result, Err(SupplyError::NotAvailable));
+        assert!(log.borrow().is_empty());
+    }
+}
@@ -0,0 +1,349 @@
+//! Fake devices for testing code built on this crate, without hardware or other
+//! dev-dependencies.
+//!
+//! - [`Source`] and [`Sink`] are a scripted serial input and a recording serial output,
+//!   and [`Duo`] combines them into one device. [`fixtures`] has responses from a real
+//!   module to script them with.
+//! - [`PinLog`] hands out [`RecordingPin`]s, and keeps the levels they were set to.
+//! - [`CountingDelay`] returns immediately, counting the time it was asked to wait.
+//!
+//! ```
+//! use embedded_hal::digital::PinState;
+//! use hc12_rs::test_utils::{fixtures, CountingDelay, Duo, PinLog, Sink, Source};
+//! # #[cfg(feature = "programming")]
+//! use hc12_rs::HC12;
+//!
+//! let pins = PinLog::new();
+//! let mut delay = CountingDelay::new();
+//! let device = Duo {
+//!     sink: Sink::new(),
+//!     src: Source::new().data(fixtures::PROGRAM_FACTORY),
+//! };
+//!
+//! # // the fixture answers P8, which a capped build refuses to send
+//! # #[cfg(all(feature = "programming", not(any(
+//! #     feature = "max-power-p1", feature = "max-power-p2", feature = "max-power-p3",
+//! #     feature = "max-power-p4", feature = "max-power-p5", feature = "max-power-p6",
+//! #     feature = "max-power-p7"
+//! # ))))]
+//! # {
+//! HC12::factor_settings(device, pins.pin(), &mut delay)
+//!     .unwrap()
+//!     .program(&mut delay)
+//!     .unwrap();
+//! assert_eq!(pins.states().as_slice(), [PinState::Low]);
+//! # }
+//! ```
+
+use core::cell::RefCell;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, PinState};
+use embedded_io::{ErrorKind, ErrorType, Read, ReadReady, Write, WriteReady};
+use heapless::Vec;
+
+/// Bytes held by a [`Source`] or [`Sink`]
+pub const CAPACITY: usize = 256;
+
+/// Levels held by a [`PinLog`]
+pub const PIN_CAPACITY: usize = 16;
+
+/// Responses of a module with factory firmware
+pub mod fixtures {
+    /// The answer to `AT`
+    pub const OK: &[u8] = b"OK\r\n";
+    /// The answer to any rejected command
+    pub const ERROR: &[u8] = b"ERROR\r\n";
+    /// The answer to `AT+V`
+    pub const VERSION: &[u8] = b"www.hc01.com HC-12_V2.6\r\n";
+    /// The answer to `AT+B9600`
+    pub const OK_B9600: &[u8] = b"OK+B9600\r\n";
+    /// The answer to `AT+FU3`
+    pub const OK_FU3: &[u8] = b"OK+FU3\r\n";
+    /// The answer to `AT+P8`
+    pub const OK_P8: &[u8] = b"OK+P8\r\n";
+    /// The answer to `AT+C001`
+    pub const OK_C001: &[u8] = b"OK+C001\r\n";
+    /// The answers to programming the factory settings, in the order `program` sends them
+    pub const PROGRAM_FACTORY: &[u8] = b"OK+B9600\r\nOK+FU3\r\nOK+P8\r\nOK+C001\r\n";
+}
+
+/// A [`Sink`] was written past the bytes it accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct TestError;
+
+impl embedded_io::Error for TestError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// A serial input returning up to [`CAPACITY`] scripted bytes
+#[derive(Debug, Default, Clone)]
+pub struct Source {
+    data: Vec<u8, CAPACITY>,
+    position: usize,
+}
+
+impl Source {
+    /// A source with nothing to read
+    pub fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Append bytes to be read. Panics if they do not fit.
+    pub fn data(mut self, data: &[u8]) -> Self {
+        self.data.extend_from_slice(data).expect("source is full");
+        self
+    }
+
+    /// Bytes not yet read
+    pub fn remaining(&self) -> &[u8] {
+        &self.data[self.position..]
+    }
+}
+
+impl ErrorType for Source {
+    type Error = TestError;
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let count = buf.len().min(self.remaining().len());
+        buf[..count].copy_from_slice(&self.data[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+impl ReadReady for Source {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.remaining().is_empty())
+    }
+}
+
+/// A serial output recording up to [`CAPACITY`] written bytes
+#[derive(Debug, Clone)]
+pub struct Sink {
+    data: Vec<u8, CAPACITY>,
+    accepted: usize,
+}
+
+impl Sink {
+    /// A sink accepting up to [`CAPACITY`] bytes
+    pub fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            accepted: CAPACITY,
+        }
+    }
+
+    /// Only accept `count` bytes, failing writes beyond them
+    pub fn accept_data(mut self, count: usize) -> Self {
+        self.accepted = count.min(CAPACITY);
+        self
+    }
+
+    /// The bytes written so far
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Take the bytes written
+    pub fn into_inner_data(self) -> Vec<u8, CAPACITY> {
+        self.data
+    }
+}
+
+impl Default for Sink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ErrorType for Sink {
+    type Error = TestError;
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let count = buf.len().min(self.accepted - self.data.len());
+        if count == 0 && !buf.is_empty() {
+            return Err(TestError);
+        }
+        // the count is within the accepted bytes, which fit
+        self.data.extend_from_slice(&buf[..count]).ok();
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl WriteReady for Sink {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.data.len() < self.accepted)
+    }
+}
+
+/// A [`Sink`] and a [`Source`] as a single device
+#[derive(Debug, Default, Clone)]
+pub struct Duo {
+    /// Where writes go
+    pub sink: Sink,
+    /// Where reads come from
+    pub src: Source,
+}
+
+impl ErrorType for Duo {
+    type Error = TestError;
+}
+
+impl Write for Duo {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.sink.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl Read for Duo {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.src.read(buf)
+    }
+}
+
+impl ReadReady for Duo {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.src.read_ready()
+    }
+}
+
+/// Keeps up to [`PIN_CAPACITY`] levels set on its [`RecordingPin`]s
+#[derive(Debug, Default)]
+pub struct PinLog {
+    states: RefCell<Vec<PinState, PIN_CAPACITY>>,
+}
+
+impl PinLog {
+    /// An empty log
+    pub fn new() -> Self {
+        Self {
+            states: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// A pin recording into this log
+    pub fn pin(&self) -> RecordingPin<'_> {
+        RecordingPin { log: self }
+    }
+
+    /// The levels set so far, oldest first
+    pub fn states(&self) -> Vec<PinState, PIN_CAPACITY> {
+        self.states.borrow().clone()
+    }
+}
+
+/// An output pin recording every level it is set to in a [`PinLog`]. Levels beyond the
+/// log's capacity are dropped.
+#[derive(Debug)]
+pub struct RecordingPin<'a> {
+    log: &'a PinLog,
+}
+
+impl digital::ErrorType for RecordingPin<'_> {
+    type Error = core::convert::Infallible;
+}
+
+impl digital::OutputPin for RecordingPin<'_> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.log.states.borrow_mut().push(PinState::Low).ok();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.log.states.borrow_mut().push(PinState::High).ok();
+        Ok(())
+    }
+}
+
+/// A delay that returns immediately, counting the time asked for
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CountingDelay {
+    elapsed_ns: u64,
+    calls: u32,
+}
+
+impl CountingDelay {
+    /// A delay that has not been used
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Milliseconds asked for so far, rounded down
+    pub fn elapsed_ms(&self) -> u64 {
+        self.elapsed_ns / 1_000_000
+    }
+
+    /// Microseconds asked for so far, rounded down
+    pub fn elapsed_us(&self) -> u64 {
+        self.elapsed_ns / 1_000
+    }
+
+    /// How many times the delay was used
+    pub fn calls(&self) -> u32 {
+        self.calls
+    }
+}
+
+impl DelayNs for CountingDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.elapsed_ns += ns as u64;
+        self.calls += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::digital::OutputPin;
+
+    #[test]
+    fn sink_rejects_beyond_accepted() {
+        let mut sink = Sink::new().accept_data(4);
+        assert_eq!(sink.write(b"AT+B9600"), Ok(4));
+        assert_eq!(sink.write(b"00"), Err(TestError));
+        assert_eq!(sink.data(), b"AT+B");
+    }
+
+    #[test]
+    fn source_reads_in_order() {
+        let mut source = Source::new().data(fixtures::OK).data(fixtures::ERROR);
+        let mut buffer = [0u8; 6];
+        assert_eq!(source.read(&mut buffer), Ok(6));
+        assert_eq!(&buffer, b"OK\r\nER");
+        assert_eq!(source.remaining(), b"ROR\r\n");
+    }
+
+    #[test]
+    fn pins_and_delays_record() {
+        let pins = PinLog::new();
+        let mut pin = pins.pin();
+        pin.set_low().unwrap();
+        pin.set_high().unwrap();
+        assert_eq!(pins.states().as_slice(), [PinState::Low, PinState::High]);
+
+        let mut delay = CountingDelay::new();
+        delay.delay_ms(40);
+        delay.delay_us(500);
+        assert_eq!(delay.elapsed_ms(), 40);
+        assert_eq!(delay.calls(), 2);
+    }
+}
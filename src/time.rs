@@ -0,0 +1,207 @@
+//! Time keeping for operations that need to know the current time, rather than to wait.
+//!
+//! Times are `u32` milliseconds from an arbitrary epoch, so they wrap around roughly every
+//! 49.7 days. All comparisons use wrapping arithmetic, which is correct as long as the
+//! durations involved are shorter than about 24.8 days (`i32::MAX` milliseconds).
+//!
+//! Lengths of time, such as timeouts and intervals, are taken as [`IntoMillis`]: a bare
+//! `u32` is milliseconds, and with the `fugit` feature a `fugit` duration of any unit is
+//! converted, so `500.micros()` cannot be mistaken for 500ms.
+
+/// A length of time, converted to whole milliseconds
+pub trait IntoMillis {
+    /// The length in milliseconds
+    fn into_ms(self) -> u32;
+}
+
+impl IntoMillis for u32 {
+    /// Already milliseconds
+    fn into_ms(self) -> u32 {
+        self
+    }
+}
+
+#[cfg(feature = "fugit")]
+impl<const NOM: u32, const DENOM: u32> IntoMillis for fugit::Duration<u32, NOM, DENOM> {
+    /// Rounded up, so a timeout is never shortened, and saturating at `u32::MAX`
+    fn into_ms(self) -> u32 {
+        let scaled = u128::from(self.ticks()) * u128::from(NOM) * 1000;
+        let ms = scaled.div_ceil(u128::from(DENOM).max(1));
+        u32::try_from(ms).unwrap_or(u32::MAX)
+    }
+}
+
+/// A free-running millisecond clock
+pub trait Clock {
+    /// The current time in milliseconds. May wrap around.
+    fn now_ms(&self) -> u32;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> u32,
+{
+    fn now_ms(&self) -> u32 {
+        self()
+    }
+}
+
+/// A [`Clock`] backed by `embassy_time::Instant`
+#[cfg(feature = "embassy-time")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EmbassyClock;
+
+#[cfg(feature = "embassy-time")]
+impl Clock for EmbassyClock {
+    fn now_ms(&self) -> u32 {
+        embassy_time::Instant::now().as_millis() as u32
+    }
+}
+
+#[cfg(feature = "std")]
+extern crate std;
+
+/// A [`Clock`] backed by `std::time::Instant`, counting from its creation
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct StdClock {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl StdClock {
+    /// Start a clock at zero
+    pub fn new() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now_ms(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+}
+
+/// A point in time after which an operation should give up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Deadline {
+    start_ms: u32,
+    duration_ms: u32,
+}
+
+impl Deadline {
+    /// A deadline `duration` from now
+    pub fn after(clock: &impl Clock, duration: impl IntoMillis) -> Self {
+        Self::starting_at(clock.now_ms(), duration.into_ms())
+    }
+
+    /// A deadline `duration_ms` milliseconds after `start_ms`
+    pub const fn starting_at(start_ms: u32, duration_ms: u32) -> Self {
+        Self {
+            start_ms,
+            duration_ms,
+        }
+    }
+
+    /// Milliseconds since the deadline was set
+    pub fn elapsed_ms(&self, clock: &impl Clock) -> u32 {
+        clock.now_ms().wrapping_sub(self.start_ms)
+    }
+
+    /// Whether the deadline has passed
+    pub fn is_expired(&self, clock: &impl Clock) -> bool {
+        self.elapsed_ms(clock) >= self.duration_ms
+    }
+
+    /// Milliseconds left until the deadline, zero once it has passed
+    pub fn remaining_ms(&self, clock: &impl Clock) -> u32 {
+        self.duration_ms.saturating_sub(self.elapsed_ms(clock))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    #[test]
+    fn closure_clock() {
+        let now = Cell::new(10);
+        let clock = || now.get();
+        assert_eq!(clock.now_ms(), 10);
+        now.set(20);
+        assert_eq!(clock.now_ms(), 20);
+    }
+
+    #[test]
+    fn deadline_expires() {
+        let now = Cell::new(1000);
+        let clock = || now.get();
+        let deadline = Deadline::after(&clock, 100);
+
+        assert!(!deadline.is_expired(&clock));
+        assert_eq!(deadline.remaining_ms(&clock), 100);
+
+        now.set(1099);
+        assert!(!deadline.is_expired(&clock));
+        assert_eq!(deadline.remaining_ms(&clock), 1);
+
+        now.set(1100);
+        assert!(deadline.is_expired(&clock));
+        assert_eq!(deadline.remaining_ms(&clock), 0);
+    }
+
+    #[test]
+    fn deadline_across_wraparound() {
+        let now = Cell::new(u32::MAX - 49);
+        let clock = || now.get();
+        let deadline = Deadline::after(&clock, 100);
+
+        now.set(u32::MAX);
+        assert!(!deadline.is_expired(&clock));
+        assert_eq!(deadline.elapsed_ms(&clock), 49);
+
+        now.set(49);
+        assert!(!deadline.is_expired(&clock));
+        assert_eq!(deadline.remaining_ms(&clock), 1);
+
+        now.set(50);
+        assert!(deadline.is_expired(&clock));
+        assert_eq!(deadline.elapsed_ms(&clock), 100);
+    }
+
+    #[test]
+    fn zero_length_deadline_is_expired() {
+        let clock = || 5;
+        assert!(Deadline::after(&clock, 0).is_expired(&clock));
+    }
+
+    #[test]
+    #[cfg(feature = "fugit")]
+    fn fugit_durations_convert() {
+        use fugit::{ExtU32, MicrosDurationU32, MillisDurationU32, SecsDurationU32};
+
+        assert_eq!(MillisDurationU32::millis(250).into_ms(), 250);
+        assert_eq!(SecsDurationU32::secs(3).into_ms(), 3000);
+        // microseconds round up to the next whole millisecond
+        assert_eq!(MicrosDurationU32::micros(500).into_ms(), 1);
+        assert_eq!(MicrosDurationU32::micros(2000).into_ms(), 2);
+        assert_eq!(MicrosDurationU32::micros(2001).into_ms(), 3);
+        assert_eq!(SecsDurationU32::secs(u32::MAX).into_ms(), u32::MAX);
+
+        let clock = || 1000;
+        let deadline = Deadline::after(&clock, 500.micros::<1, 1_000_000>());
+        assert_eq!(deadline, Deadline::starting_at(1000, 1));
+    }
+}
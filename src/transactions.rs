@@ -0,0 +1,183 @@
+use core::fmt::Debug;
+
+use heapless::{Deque, String};
+
+use crate::commands::clip;
+use crate::storage::Ring;
+use crate::{Error, Response};
+
+/// Number of transactions kept by a device
+pub const DEVICE_LOG_DEPTH: usize = 8;
+
+/// How an AT transaction ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum TransactionStatus {
+    /// The module answered OK
+    Ok,
+    /// The module answered, but not with OK
+    NoOK,
+    /// The module did not answer
+    NoResponse,
+    /// The response did not fit in the response buffer
+    Truncated,
+    /// The serial device failed
+    DeviceError,
+}
+
+/// A single AT command and its outcome
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Transaction {
+    /// The command that was sent
+    pub command: String<16>,
+    /// The response line, if one was received
+    pub response: Option<Response>,
+    /// How the transaction ended
+    pub status: TransactionStatus,
+}
+
+/// A ring buffer of the last `N` AT transactions, for post-mortem debugging, kept in `S`,
+/// see [`storage`](crate::storage). Once full, the oldest transaction is dropped to make
+/// room.
+#[derive(Debug, Default)]
+pub struct TransactionLog<const N: usize, S = Deque<Transaction, N>> {
+    entries: S,
+}
+
+impl<const N: usize> TransactionLog<N> {
+    /// An empty log
+    pub const fn new() -> Self {
+        Self {
+            entries: Deque::new(),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> TransactionLog<N, alloc::collections::VecDeque<Transaction>> {
+    /// An empty log that allocates as transactions are logged
+    pub const fn new_alloc() -> Self {
+        Self {
+            entries: alloc::collections::VecDeque::new(),
+        }
+    }
+}
+
+impl<const N: usize, S: Ring<Transaction>> TransactionLog<N, S> {
+    /// The logged transactions, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Transaction> {
+        let (front, back) = self.entries.as_slices();
+        front.iter().chain(back)
+    }
+
+    /// The most recent transaction
+    pub fn last(&self) -> Option<&Transaction> {
+        self.entries.back()
+    }
+
+    /// Forget all logged transactions
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Add a transaction, dropping the oldest if the log is full
+    pub fn push(&mut self, transaction: Transaction) {
+        if self.entries.len() >= N {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(transaction).ok();
+    }
+
+    /// Log the outcome of running a command. Responses longer than the default capacity
+    /// are clipped.
+    pub(crate) fn record<D: Debug, P, const M: usize>(
+        &mut self,
+        command: String<16>,
+        result: Result<&Response<M>, &Error<D, P, M>>,
+    ) {
+        let (response, status) = match result {
+            Ok(line) => (Some(clip(line)), TransactionStatus::Ok),
+            Err(Error::NoOK(line)) => (Some(clip(line)), TransactionStatus::NoOK),
+            Err(Error::NoResponse) => (None, TransactionStatus::NoResponse),
+            Err(Error::Truncated) => (None, TransactionStatus::Truncated),
+            Err(_) => (None, TransactionStatus::DeviceError),
+        };
+
+        self.push(Transaction {
+            command,
+            response,
+            status,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(command: &str) -> Transaction {
+        Transaction {
+            command: command.try_into().unwrap(),
+            response: None,
+            status: TransactionStatus::NoResponse,
+        }
+    }
+
+    #[test]
+    fn oldest_entries_are_evicted() {
+        let mut log: TransactionLog<3> = TransactionLog::new();
+        for command in ["AT+C001", "AT+C002", "AT+C003", "AT+C004", "AT+C005"] {
+            log.push(transaction(command));
+        }
+
+        let commands: heapless::Vec<&str, 3> =
+            log.iter().map(|entry| entry.command.as_str()).collect();
+        assert_eq!(commands.as_slice(), ["AT+C003", "AT+C004", "AT+C005"]);
+        assert_eq!(log.last().unwrap().command.as_str(), "AT+C005");
+        drop(commands);
+
+        log.clear();
+        assert_eq!(log.iter().count(), 0);
+    }
+
+    #[test]
+    fn outcomes_are_classified() {
+        let mut log: TransactionLog<4> = TransactionLog::new();
+        let ok: Result<_, Error<()>> = Ok("OK+P8\r\n".try_into().unwrap());
+        let no_ok: Result<_, Error<()>> = Err(Error::NoOK("ERROR\r\n".try_into().unwrap()));
+        let silent: Result<Response, Error<()>> = Err(Error::NoResponse);
+        let failed: Result<Response, Error<()>> = Err(Error::DeviceError(()));
+
+        for result in [ok, no_ok, silent, failed] {
+            log.record("AT+P8".try_into().unwrap(), result.as_ref());
+        }
+
+        let statuses: heapless::Vec<_, 4> = log
+            .iter()
+            .map(|entry| (entry.status, entry.response.as_deref()))
+            .collect();
+        assert_eq!(
+            statuses.as_slice(),
+            [
+                (TransactionStatus::Ok, Some("OK+P8\r\n")),
+                (TransactionStatus::NoOK, Some("ERROR\r\n")),
+                (TransactionStatus::NoResponse, None),
+                (TransactionStatus::DeviceError, None),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn allocated_log_evicts_the_same() {
+        let mut log: TransactionLog<2, alloc::collections::VecDeque<Transaction>> =
+            TransactionLog::new_alloc();
+        for command in ["AT+C001", "AT+C002", "AT+C003"] {
+            log.push(transaction(command));
+        }
+        let commands: heapless::Vec<&str, 2> =
+            log.iter().map(|entry| entry.command.as_str()).collect();
+        assert_eq!(commands.as_slice(), ["AT+C002", "AT+C003"]);
+    }
+}
@@ -0,0 +1,156 @@
+//! Long writes split into radio packets, with progress reports.
+//!
+//! A write much longer than a packet overruns the module's buffer unless it is fed in
+//! pieces no faster than they go out over the air.
+//! [`write_all_chunked`](TransparentHC12::write_all_chunked) writes
+//! [`MAX_PACKET_PAYLOAD`] bytes at a time, waiting after each for its time on air, or the
+//! mode's [`PACKET_INTERVAL_MS`](ValidMode::PACKET_INTERVAL_MS) if that is longer.
+//! [`write_all_chunked_with_progress`](TransparentHC12::write_all_chunked_with_progress)
+//! also reports a [`Progress`] after each chunk, and stops if the callback breaks.
+
+use core::ops::ControlFlow;
+
+use embedded_hal::delay::DelayNs;
+use embedded_io::Write;
+
+use crate::airtime::{self, MAX_PACKET_PAYLOAD};
+use crate::modes::ValidMode;
+use crate::speeds::ValidSpeed;
+use crate::TransparentHC12;
+
+/// How far a chunked write has got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub struct Progress {
+    /// Bytes written so far
+    pub bytes_sent: usize,
+    /// Bytes in the whole write
+    pub total: usize,
+    /// The index of the chunk just written, from zero
+    pub chunk: usize,
+}
+
+/// A chunked write did not finish
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum TransferError<E> {
+    /// The serial device failed
+    Link(E),
+    /// The progress callback stopped the write, after the chunk it was shown
+    Aborted(Progress),
+}
+
+impl<Device, Pin, Mode, Speed> TransparentHC12<Device, Pin, Mode, Speed>
+where
+    Device: Write,
+    Mode: ValidMode,
+    Speed: ValidSpeed,
+{
+    /// Write all of `buf` a packet at a time, see the [module documentation](crate::transfer)
+    pub fn write_all_chunked(
+        &mut self,
+        buf: &[u8],
+        delay: &mut impl DelayNs,
+    ) -> Result<(), Device::Error> {
+        self.write_all_chunked_with_progress(buf, delay, |_| ControlFlow::Continue(()))
+            .map_err(|error| match error {
+                TransferError::Link(error) => error,
+                TransferError::Aborted(_) => unreachable!("the callback never breaks"),
+            })
+    }
+
+    /// Like [`write_all_chunked`](Self::write_all_chunked), calling `progress` after each
+    /// chunk has been written, before waiting for it to go out. Returning
+    /// [`ControlFlow::Break`] stops the write.
+    pub fn write_all_chunked_with_progress(
+        &mut self,
+        buf: &[u8],
+        delay: &mut impl DelayNs,
+        mut progress: impl FnMut(Progress) -> ControlFlow<()>,
+    ) -> Result<(), TransferError<Device::Error>> {
+        let mut report = Progress {
+            bytes_sent: 0,
+            total: buf.len(),
+            chunk: 0,
+        };
+        for (index, chunk) in buf.chunks(MAX_PACKET_PAYLOAD).enumerate() {
+            self.write_all(chunk).map_err(TransferError::Link)?;
+            self.flush().map_err(TransferError::Link)?;
+            report.bytes_sent += chunk.len();
+            report.chunk = index;
+            if progress(report).is_break() {
+                return Err(TransferError::Aborted(report));
+            }
+
+            let on_air_us = airtime::time_on_air_us_for::<Mode, Speed>(chunk.len());
+            delay.delay_us(on_air_us.max(Mode::PACKET_INTERVAL_MS.saturating_mul(1000)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::{Fu3, Fu4};
+    use crate::speeds::{B1200, B9600};
+    use crate::test_utils::{CountingDelay, PinLog, Sink};
+    use heapless::Vec;
+
+    #[test]
+    fn reports_each_chunk() {
+        let pins = PinLog::new();
+        let mut hc12: TransparentHC12<_, _, Fu3, B9600> =
+            TransparentHC12::assume_configured(Sink::new(), pins.pin(), Default::default());
+        let data = [0x5a; 150];
+        let mut reports: Vec<Progress, 4> = Vec::new();
+
+        hc12.write_all_chunked_with_progress(&data, &mut CountingDelay::new(), |report| {
+            reports.push(report).unwrap();
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(
+            reports,
+            [(60, 0), (120, 1), (150, 2)].map(|(bytes_sent, chunk)| Progress {
+                bytes_sent,
+                total: 150,
+                chunk
+            })
+        );
+        let (sink, _) = hc12.inner();
+        assert_eq!(sink.data(), data);
+    }
+
+    #[test]
+    fn callback_aborts_mid_transfer() {
+        let pins = PinLog::new();
+        let mut hc12: TransparentHC12<_, _, Fu4, B1200> =
+            TransparentHC12::assume_configured(Sink::new(), pins.pin(), Default::default());
+        let mut delay = CountingDelay::new();
+        let mut calls = 0;
+
+        let result = hc12.write_all_chunked_with_progress(&[1; 200], &mut delay, |report| {
+            calls += 1;
+            if report.chunk == 1 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(
+            result,
+            Err(TransferError::Aborted(Progress {
+                bytes_sent: 120,
+                total: 200,
+                chunk: 1
+            }))
+        );
+        assert_eq!(calls, 2);
+        // the first chunk was followed by FU4's two second packet interval, the aborted
+        // one was not
+        assert_eq!(delay.elapsed_ms(), 2000);
+        let (sink, _) = hc12.inner();
+        assert_eq!(sink.data().len(), 120);
+    }
+}
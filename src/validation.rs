@@ -0,0 +1,209 @@
+//! Checking a configuration against the mode and serial speed it will be used with.
+//!
+//! The types of a device already rule out most invalid combinations, but a
+//! [`Configuration`] may also come from outside, such as a console or stored settings.
+//! [`Configuration::validate_for`] reports combinations the module rejects as errors, and
+//! combinations it accepts but the datasheet advises against as advisories. Each is a
+//! named [`ConfigWarning`] variant.
+
+use heapless::Vec;
+
+use crate::paramaters::{Channel, Configuration, Power};
+
+/// The serial speeds the module accepts, in bits per second
+pub const SERIAL_SPEEDS_BPS: [u32; 8] = [1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200];
+
+/// The highest channel that is reliable in FU4 at full power, see
+/// [`ConfigWarning::Fu4HighChannelAtFullPower`]
+pub const FU4_FULL_POWER_MAX_CHANNEL: u8 = 100;
+
+/// The most findings [`Configuration::validate_for`] reports
+pub const MAX_WARNINGS: usize = 4;
+
+/// Something wrong or unwise about a configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-03", derive(defmt::Format))]
+pub enum ConfigWarning {
+    /// Error: there is no mode with this number
+    UnknownMode(u8),
+    /// Error: the module does not accept this serial speed
+    UnknownSpeed(u32),
+    /// Error: the mode does not support this serial speed. FU2 runs at up to 4800 bps,
+    /// FU4 only at 1200 bps.
+    SpeedNotSupported {
+        /// The mode number
+        mode: u8,
+        /// The serial speed
+        baud_bps: u32,
+    },
+    /// Advisory: in FU4 at full power, the channels above
+    /// [`FU4_FULL_POWER_MAX_CHANNEL`] are known to be unreliable
+    Fu4HighChannelAtFullPower(Channel),
+    /// Advisory: in FU3, serial speeds above 9600 bps raise the air rate, and the
+    /// datasheet gives a much shorter range for them
+    Fu3ShortRange(u32),
+}
+
+impl ConfigWarning {
+    /// Whether the module would reject the configuration, rather than only being
+    /// advised against
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            Self::UnknownMode(_) | Self::UnknownSpeed(_) | Self::SpeedNotSupported { .. }
+        )
+    }
+}
+
+impl Configuration {
+    /// Check this configuration for use in mode number `mode`, as in `AT+FUn`, at serial
+    /// speed `baud_bps`. Returns every finding, errors first; see
+    /// [`ConfigWarning::is_error`].
+    ///
+    /// ```
+    /// use hc12_rs::paramaters::{Channel, Configuration, Power};
+    /// use hc12_rs::validation::ConfigWarning;
+    ///
+    /// let config = Configuration::new(Channel::new(120).unwrap(), Power::P8);
+    /// assert_eq!(config.validate_for(3, 9600), Ok(()));
+    ///
+    /// let findings = config.validate_for(4, 9600).unwrap_err();
+    /// assert!(findings[0].is_error());
+    /// assert!(matches!(findings[1], ConfigWarning::Fu4HighChannelAtFullPower(_)));
+    /// ```
+    pub fn validate_for(
+        &self,
+        mode: u8,
+        baud_bps: u32,
+    ) -> Result<(), Vec<ConfigWarning, MAX_WARNINGS>> {
+        let mut errors: Vec<ConfigWarning, MAX_WARNINGS> = Vec::new();
+        let mut advisories: Vec<ConfigWarning, MAX_WARNINGS> = Vec::new();
+
+        if !(1..=4).contains(&mode) {
+            errors.push(ConfigWarning::UnknownMode(mode)).ok();
+        }
+        if !SERIAL_SPEEDS_BPS.contains(&baud_bps) {
+            errors.push(ConfigWarning::UnknownSpeed(baud_bps)).ok();
+        }
+        let supported = match mode {
+            2 => baud_bps <= 4800,
+            4 => baud_bps == 1200,
+            _ => true,
+        };
+        if !supported {
+            errors
+                .push(ConfigWarning::SpeedNotSupported { mode, baud_bps })
+                .ok();
+        }
+
+        if mode == 4
+            && self.power == Power::P8
+            && u8::from(self.channel) > FU4_FULL_POWER_MAX_CHANNEL
+        {
+            advisories
+                .push(ConfigWarning::Fu4HighChannelAtFullPower(self.channel))
+                .ok();
+        }
+        if mode == 3 && baud_bps > 9600 {
+            advisories.push(ConfigWarning::Fu3ShortRange(baud_bps)).ok();
+        }
+
+        // there are at most two errors and one advisory
+        for advisory in advisories {
+            errors.push(advisory).ok();
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(channel: u8, power: Power) -> Configuration {
+        Configuration::new(Channel::new(channel).unwrap(), power)
+    }
+
+    fn findings(
+        config: Configuration,
+        mode: u8,
+        baud_bps: u32,
+    ) -> Vec<ConfigWarning, MAX_WARNINGS> {
+        config
+            .validate_for(mode, baud_bps)
+            .err()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn factory_settings_are_clean() {
+        assert_eq!(Configuration::FACTORY.validate_for(3, 9600), Ok(()));
+        for baud_bps in SERIAL_SPEEDS_BPS {
+            assert_eq!(Configuration::FACTORY.validate_for(1, baud_bps), Ok(()));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let factory = Configuration::FACTORY;
+        assert_eq!(findings(factory, 0, 9600), [ConfigWarning::UnknownMode(0)]);
+        assert_eq!(
+            findings(factory, 1, 39400),
+            [ConfigWarning::UnknownSpeed(39400)]
+        );
+        assert_eq!(
+            findings(factory, 2, 9600),
+            [ConfigWarning::SpeedNotSupported {
+                mode: 2,
+                baud_bps: 9600
+            }]
+        );
+        assert_eq!(
+            findings(factory, 4, 2400),
+            [ConfigWarning::SpeedNotSupported {
+                mode: 4,
+                baud_bps: 2400
+            }]
+        );
+        assert!(findings(factory, 4, 2400)
+            .iter()
+            .all(ConfigWarning::is_error));
+    }
+
+    #[test]
+    fn advisories() {
+        let high = config(101, Power::P8);
+        assert_eq!(
+            findings(high, 4, 1200),
+            [ConfigWarning::Fu4HighChannelAtFullPower(high.channel)]
+        );
+        // only at full power, and only above the limit
+        assert_eq!(config(101, Power::P7).validate_for(4, 1200), Ok(()));
+        assert_eq!(config(100, Power::P8).validate_for(4, 1200), Ok(()));
+
+        assert_eq!(
+            findings(Configuration::FACTORY, 3, 19200),
+            [ConfigWarning::Fu3ShortRange(19200)]
+        );
+        assert!(!ConfigWarning::Fu3ShortRange(19200).is_error());
+    }
+
+    #[test]
+    fn errors_come_before_advisories() {
+        let found = findings(config(127, Power::P8), 4, 19200);
+        assert_eq!(
+            found,
+            [
+                ConfigWarning::SpeedNotSupported {
+                    mode: 4,
+                    baud_bps: 19200
+                },
+                ConfigWarning::Fu4HighChannelAtFullPower(Channel::MAX),
+            ]
+        );
+    }
+}